@@ -0,0 +1,363 @@
+//! Album art module
+//!
+//! Finds a track's cover art and derives a colour palette from it. Art embedded in the file is
+//! preferred: an ID3v2 `APIC` frame (in a WAV file's `id3 ` chunk or at the start of an MP3) or a
+//! FLAC `PICTURE` block. Failing that, an image such as `cover.jpg` in the track's folder is used.
+//!
+//! The palette is found by median cut: the pixels are split into boxes of similar colours, and
+//! the average colour of each box becomes a palette entry, most common first.
+
+use crate::id3::{self, Tag};
+use crate::ui::theme::Color;
+use nannou::image::{self, GenericImageView, RgbaImage};
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Names (without extension, ignoring case) of cover images, in order of preference.
+const COVER_NAMES: [&str; 4] = ["cover", "folder", "front", "album"];
+
+/// Extensions of cover images.
+const COVER_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// Largest width or height artwork is kept at, in pixels.
+const MAX_SIZE: u32 = 256;
+
+/// Number of colours in a palette.
+const PALETTE_SIZE: usize = 5;
+
+/// ID3 and FLAC picture type of the front cover, preferred over other pictures.
+const FRONT_COVER: u32 = 3;
+
+/// A track's cover art, scaled down for display, and the colours it is made of.
+pub struct Artwork {
+    /// The picture, at most [`MAX_SIZE`] pixels wide and high.
+    pub image: RgbaImage,
+    /// The picture's dominant colours, most common first.
+    pub palette: Vec<Color>,
+}
+
+impl Artwork {
+    /// Finds and decodes the cover art of an audio file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the audio file.
+    ///
+    /// # Returns
+    ///
+    /// The artwork, or `None` if the file has none and there is no cover image next to it.
+    pub fn find(path: &Path) -> Option<Self> {
+        let bytes = embedded_picture(path).or_else(|| cover_file(path))?;
+        match image::load_from_memory(&bytes) {
+            Ok(mut picture) => {
+                if picture.width() > MAX_SIZE || picture.height() > MAX_SIZE {
+                    picture = picture.thumbnail(MAX_SIZE, MAX_SIZE);
+                }
+                let image = picture.to_rgba8();
+                let palette = median_cut(&image, PALETTE_SIZE);
+                Some(Artwork { image, palette })
+            }
+            Err(e) => {
+                eprintln!(
+                    "⚠️ Could not decode the artwork of '{}': {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Reads the picture embedded in an audio file, preferring the front cover.
+fn embedded_picture(path: &Path) -> Option<Vec<u8>> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    if extension == "flac" {
+        let mut file = File::open(path).ok()?;
+        return flac_picture(&mut file).ok().flatten();
+    }
+
+    let tag = Tag::read(path)?;
+    let mut first = None;
+    for frame in tag.frames_with(&["APIC", "PIC"]) {
+        match parse_apic(&frame.data, tag.version) {
+            Some((FRONT_COVER, picture)) => return Some(picture),
+            Some((_, picture)) => {
+                first.get_or_insert(picture);
+            }
+            None => {}
+        }
+    }
+    first
+}
+
+/// Reads the first cover image found in the audio file's folder.
+fn cover_file(path: &Path) -> Option<Vec<u8>> {
+    let mut covers: Vec<(usize, _)> = fs::read_dir(path.parent()?)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|candidate| {
+            let stem = candidate.file_stem()?.to_str()?.to_ascii_lowercase();
+            let extension = candidate.extension()?.to_str()?.to_ascii_lowercase();
+            let rank = COVER_NAMES.iter().position(|&name| name == stem)?;
+            COVER_EXTENSIONS
+                .contains(&extension.as_str())
+                .then_some((rank, candidate))
+        })
+        .collect();
+    covers.sort();
+    covers
+        .into_iter()
+        .find_map(|(_, cover)| fs::read(cover).ok())
+}
+
+/// Splits an `APIC` (or version 2.2 `PIC`) frame into its picture type and image data.
+fn parse_apic(frame: &[u8], version: u8) -> Option<(u32, Vec<u8>)> {
+    let (&encoding, rest) = frame.split_first()?;
+    // Version 2.2 names the image format in three characters, later versions by MIME type.
+    let rest = if version == 2 {
+        rest.get(3..)?
+    } else {
+        let end = rest.iter().position(|&byte| byte == 0)?;
+        &rest[end + 1..]
+    };
+    let (&picture_type, rest) = rest.split_first()?;
+    // The picture follows a description.
+    let (_, data) = id3::split_text(rest, encoding);
+    Some((picture_type as u32, data.to_vec()))
+}
+
+/// Reads the `PICTURE` metadata blocks of a FLAC file, preferring the front cover.
+fn flac_picture(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+    let mut marker = [0u8; 4];
+    file.read_exact(&mut marker)?;
+    if &marker != b"fLaC" {
+        return Ok(None);
+    }
+
+    let mut first = None;
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header)?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let size = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        if block_type == 6 {
+            let mut block = vec![0u8; size];
+            file.read_exact(&mut block)?;
+            match parse_flac_picture(&block) {
+                Some((FRONT_COVER, picture)) => return Ok(Some(picture)),
+                Some((_, picture)) => {
+                    first.get_or_insert(picture);
+                }
+                None => {}
+            }
+        } else {
+            file.seek(SeekFrom::Current(size as i64))?;
+        }
+        if is_last {
+            return Ok(first);
+        }
+    }
+}
+
+/// Splits a FLAC `PICTURE` block into its picture type and image data.
+fn parse_flac_picture(mut block: &[u8]) -> Option<(u32, Vec<u8>)> {
+    let picture_type = take_u32(&mut block)?;
+    let mime_length = take_u32(&mut block)? as usize;
+    take(&mut block, mime_length)?;
+    let description_length = take_u32(&mut block)? as usize;
+    // The description is followed by the width, height, colour depth and palette size.
+    take(&mut block, description_length + 16)?;
+    let data_length = take_u32(&mut block)? as usize;
+    Some((picture_type, take(&mut block, data_length)?.to_vec()))
+}
+
+/// Removes the first `length` bytes from `data` and returns them.
+fn take<'a>(data: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+    let taken = data.get(..length)?;
+    *data = &data[length..];
+    Some(taken)
+}
+
+/// Removes a big-endian `u32` from the start of `data` and returns it.
+fn take_u32(data: &mut &[u8]) -> Option<u32> {
+    take(data, 4).map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Finds the dominant colours of an image by median cut.
+///
+/// The box of pixels whose colours spread furthest along one channel is repeatedly split at its
+/// median along that channel, until there are `count` boxes or no box can be split.
+/// Transparent pixels are ignored.
+///
+/// # Returns
+///
+/// The average colour of each box, from the box with the most pixels to the one with the fewest.
+fn median_cut(image: &RgbaImage, count: usize) -> Vec<Color> {
+    let pixels: Vec<[u8; 3]> = image
+        .pixels()
+        .filter(|pixel| pixel[3] >= 128)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, pixels)| {
+                let (channel, range) = widest_channel(pixels);
+                (index, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((index, channel, range)) = widest else {
+            break;
+        };
+        if range == 0 {
+            break;
+        }
+        let mut lower = boxes.swap_remove(index);
+        lower.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = lower.split_off(lower.len() / 2);
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+
+    boxes.sort_by_key(|pixels| Reverse(pixels.len()));
+    boxes
+        .iter()
+        .map(|pixels| {
+            let mut sums = [0u64; 3];
+            for pixel in pixels {
+                for (sum, &value) in sums.iter_mut().zip(pixel) {
+                    *sum += value as u64;
+                }
+            }
+            let average = |sum: u64| sum as f32 / pixels.len() as f32 / 255.0;
+            Color::rgb(average(sums[0]), average(sums[1]), average(sums[2]))
+        })
+        .collect()
+}
+
+/// Returns the colour channel whose values spread furthest in a box, and how far they spread.
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = pixels.iter().map(|pixel| pixel[channel]);
+            let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, range)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::Rgba;
+    use std::process;
+
+    /// Builds a FLAC `PICTURE` block body.
+    fn picture_block(picture_type: u32, data: &[u8]) -> Vec<u8> {
+        let mut block = picture_type.to_be_bytes().to_vec();
+        block.extend(10u32.to_be_bytes());
+        block.extend(b"image/jpeg");
+        block.extend(5u32.to_be_bytes());
+        block.extend(b"Cover");
+        // Width, height, colour depth and palette size.
+        block.extend([0; 16]);
+        block.extend((data.len() as u32).to_be_bytes());
+        block.extend(data);
+        block
+    }
+
+    /// Builds a metadata block header and body.
+    fn metadata_block(block_type: u8, is_last: bool, body: &[u8]) -> Vec<u8> {
+        let mut block = vec![block_type | if is_last { 0x80 } else { 0 }];
+        block.extend(&(body.len() as u32).to_be_bytes()[1..]);
+        block.extend(body);
+        block
+    }
+
+    fn flac_picture_of(bytes: &[u8]) -> Option<Vec<u8>> {
+        let path =
+            std::env::temp_dir().join(format!("music_visualizer-artwork-{}.flac", process::id()));
+        fs::write(&path, bytes).unwrap();
+        let picture = flac_picture(&mut File::open(&path).unwrap());
+        fs::remove_file(&path).ok();
+        picture.unwrap()
+    }
+
+    #[test]
+    fn flac_files_prefer_the_front_cover() {
+        let mut flac = b"fLaC".to_vec();
+        flac.extend(metadata_block(0, false, &[0; 34]));
+        flac.extend(metadata_block(6, false, &picture_block(4, b"back")));
+        flac.extend(metadata_block(
+            6,
+            false,
+            &picture_block(FRONT_COVER, b"front"),
+        ));
+        flac.extend(metadata_block(1, true, &[0; 8]));
+        assert_eq!(flac_picture_of(&flac).as_deref(), Some(&b"front"[..]));
+
+        // Any picture is better than none.
+        let mut flac = b"fLaC".to_vec();
+        flac.extend(metadata_block(6, true, &picture_block(4, b"back")));
+        assert_eq!(flac_picture_of(&flac).as_deref(), Some(&b"back"[..]));
+
+        assert_eq!(flac_picture_of(b"RIFF0000WAVE"), None);
+    }
+
+    #[test]
+    fn apic_frames_split_into_type_and_picture() {
+        let mut apic = vec![0];
+        apic.extend(b"image/png\0");
+        apic.push(FRONT_COVER as u8);
+        apic.extend(b"Front\0");
+        apic.extend(b"\x89PNG");
+        assert_eq!(
+            parse_apic(&apic, 3),
+            Some((FRONT_COVER, b"\x89PNG".to_vec()))
+        );
+
+        // Version 2.2 names the format in three characters; UTF-16 descriptions end in two
+        // null bytes.
+        let mut pic = vec![1];
+        pic.extend(b"PNG");
+        pic.push(0);
+        pic.extend(b"\xff\xfeA\0\0\0");
+        pic.extend(b"\x89PNG");
+        assert_eq!(parse_apic(&pic, 2), Some((0, b"\x89PNG".to_vec())));
+    }
+
+    #[test]
+    fn a_two_colour_image_gives_a_two_colour_palette() {
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        // Three quarters red, a quarter blue, and a transparent green row that is ignored.
+        let image = RgbaImage::from_fn(8, 9, |x, y| match (x, y) {
+            (_, 8) => Rgba([0, 255, 0, 0]),
+            (0..6, _) => red,
+            _ => blue,
+        });
+
+        let palette = median_cut(&image, PALETTE_SIZE);
+        let (red, blue) = (Color::rgb(1.0, 0.0, 0.0), Color::rgb(0.0, 0.0, 1.0));
+        assert_eq!(palette[0], red);
+        assert!(palette.contains(&blue));
+        assert!(
+            palette
+                .iter()
+                .all(|&colour| colour == red || colour == blue)
+        );
+        assert!(median_cut(&RgbaImage::new(4, 4), PALETTE_SIZE).is_empty());
+    }
+}
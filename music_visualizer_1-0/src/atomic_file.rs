@@ -0,0 +1,53 @@
+//! Atomic file module
+//!
+//! Writes files that must never be left half-written, such as the cache manifest, the session,
+//! the settings and the EQ presets, which are rewritten while the player runs and could be cut
+//! off by a crash or a kill.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Extension given to files while they are being written.
+const TEMP_EXTENSION: &str = "tmp";
+
+/// Number of temporary files named so far, so writes of the same file never share one.
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes a file by writing a temporary file next to it and renaming it into place, creating the
+/// parent folder if needed.
+///
+/// The data is flushed to disk before the rename, so `path` holds either its old contents or the
+/// complete new ones, even if the program stops partway. Each write uses its own temporary file,
+/// so concurrent writes of the same file do not cut each other off; the last rename wins.
+///
+/// # Arguments
+///
+/// * `path` - The file to write.
+/// * `bytes` - Its new contents.
+pub fn write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(
+        ".{}-{}.{}",
+        process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed),
+        TEMP_EXTENSION
+    ));
+    let temp = path.with_file_name(temp_name);
+    let result = fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()
+    });
+    match result.and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            fs::remove_file(&temp).ok();
+            Err(e)
+        }
+    }
+}
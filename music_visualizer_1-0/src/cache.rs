@@ -0,0 +1,633 @@
+//! Audio cache module
+//!
+//! Keeps prepared (trimmed and resampled) audio in the `music_cache` folder so the expensive
+//! processing is only done once per file and output rate.
+//!
+//! Each cached file is named after a [`CacheKey`]: a hash of the source file's contents, its
+//! modification time, the target sample rate and the output format. Editing or replacing a source
+//! file therefore changes the key, and two files with the same title never collide.
+//!
+//! A manifest (`music_cache/manifest.json`) records the size, checksum and last use of every
+//! cached file:
+//! - Files are checked against the manifest before use; a damaged file is deleted and rebuilt.
+//! - Files are written to a temporary name and renamed into place, so a crash mid-write never
+//!   leaves a partial file under a valid name.
+//! - When the folder grows past its size limit, the least recently used files are evicted.
+//! - Files the manifest does not know about (such as leftovers of an interrupted write or caches
+//!   from older versions) are removed when the cache is opened.
+
+use crate::atomic_file;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Folder (relative to the working directory) holding the cached audio.
+pub const CACHE_DIR: &str = "music_cache";
+
+/// Name of the manifest file inside the cache folder.
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Starting value of the 64-bit FNV-1a hash.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Multiplier of the 64-bit FNV-1a hash.
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Identifies one prepared version of a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    /// Path of the source file, as the manifest records it.
+    source: String,
+    /// Hash of the source file's contents.
+    content_hash: u64,
+    /// Modification time of the source file, in seconds since the Unix epoch.
+    modified: u64,
+    /// Sample rate of the prepared audio.
+    sample_rate: u32,
+    /// Describes how the audio was prepared and stored (e.g. sample format and resampler).
+    format: String,
+}
+
+impl CacheKey {
+    /// Returns the name of the cached file for this key.
+    fn file_name(&self) -> String {
+        format!(
+            "{:016x}-{}-{}Hz-{}.wav",
+            self.content_hash, self.modified, self.sample_rate, self.format
+        )
+    }
+}
+
+/// What the manifest knows about one cached file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Size of the file in bytes.
+    size: u64,
+    /// FNV-1a hash of the file's contents.
+    checksum: u64,
+    /// When the file was last read or written, in milliseconds since the Unix epoch.
+    last_used: u64,
+}
+
+/// The hash of a source file, remembered so unchanged files are not read again to build a key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SourceRecord {
+    size: u64,
+    modified: u64,
+    content_hash: u64,
+    /// Names of the cached files prepared from or used for this source.
+    #[serde(default)]
+    entries: Vec<String>,
+}
+
+/// Contents of the manifest file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Manifest {
+    /// Cached files by file name.
+    entries: HashMap<String, CacheEntry>,
+    /// Source file hashes by path.
+    sources: HashMap<String, SourceRecord>,
+}
+
+/// Size-limited cache of prepared audio files.
+pub struct AudioCache {
+    dir: PathBuf,
+    /// Largest total size of the cached files, in bytes.
+    limit: u64,
+    manifest: Manifest,
+}
+
+impl AudioCache {
+    // ============================================================================
+    // Public Methods
+    // ============================================================================
+
+    /// Opens the cache in `dir`, dropping anything the manifest cannot vouch for.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The cache folder; it is created when the first file is stored.
+    /// * `limit` - Largest total size of the cached files, in bytes.
+    pub fn open(dir: impl Into<PathBuf>, limit: u64) -> Self {
+        let dir = dir.into();
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = match fs::read_to_string(&manifest_path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!(
+                    "⚠️ Invalid cache manifest '{}': {}. Starting a new cache.",
+                    manifest_path.display(),
+                    e
+                );
+                Manifest::default()
+            }),
+            Err(_) => Manifest::default(),
+        };
+
+        let mut cache = AudioCache {
+            dir,
+            limit,
+            manifest,
+        };
+        cache.sweep();
+        cache.evict(None);
+        cache
+    }
+
+    /// Builds the key of a source file prepared at `sample_rate` in `format`.
+    ///
+    /// The source is only read (to hash its contents) if its size or modification time changed
+    /// since it was last hashed. The cache is only locked to look up and record the hash, so other
+    /// loads are not held up while a file is read.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The shared cache.
+    /// * `source` - Path of the source audio file.
+    /// * `sample_rate` - Sample rate of the prepared audio.
+    /// * `format` - Describes how the audio is prepared and stored.
+    ///
+    /// # Returns
+    ///
+    /// The key, or an error if the source file cannot be read.
+    pub fn key(
+        cache: &Mutex<Self>,
+        source: &Path,
+        sample_rate: u32,
+        format: &str,
+    ) -> io::Result<CacheKey> {
+        let metadata = fs::metadata(source)?;
+        let size = metadata.len();
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |age| age.as_secs());
+
+        let source_key = source.to_string_lossy().into_owned();
+        let known = cache
+            .lock()
+            .unwrap()
+            .manifest
+            .sources
+            .get(&source_key)
+            .filter(|record| record.size == size && record.modified == modified)
+            .map(|record| record.content_hash);
+        let content_hash = match known {
+            Some(content_hash) => content_hash,
+            None => {
+                let content_hash = fnv1a(&fs::read(source)?);
+                let mut cache = cache.lock().unwrap();
+                // Files prepared from the old contents stay listed, so invalidating the source
+                // still drops them.
+                let entries = cache
+                    .manifest
+                    .sources
+                    .remove(&source_key)
+                    .map(|record| record.entries)
+                    .unwrap_or_default();
+                cache.manifest.sources.insert(
+                    source_key.clone(),
+                    SourceRecord {
+                        size,
+                        modified,
+                        content_hash,
+                        entries,
+                    },
+                );
+                cache.save_manifest();
+                content_hash
+            }
+        };
+
+        Ok(CacheKey {
+            source: source_key,
+            content_hash,
+            modified,
+            sample_rate,
+            format: format.to_string(),
+        })
+    }
+
+    /// Returns the contents of a cached file, if it exists and is intact.
+    ///
+    /// A file that does not match its size or checksum is deleted. The file is read and checked
+    /// without holding the lock.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The shared cache.
+    /// * `key` - Key of the wanted file.
+    pub fn get(cache: &Mutex<Self>, key: &CacheKey) -> Option<Vec<u8>> {
+        let name = key.file_name();
+        let (path, entry) = {
+            let cache = cache.lock().unwrap();
+            (
+                cache.dir.join(&name),
+                cache.manifest.entries.get(&name)?.clone(),
+            )
+        };
+
+        let intact = match fs::read(&path) {
+            Ok(bytes) if bytes.len() as u64 == entry.size && fnv1a(&bytes) == entry.checksum => {
+                Some(bytes)
+            }
+            Ok(_) => {
+                eprintln!(
+                    "⚠️ Cached file '{}' is damaged; rebuilding it.",
+                    path.display()
+                );
+                None
+            }
+            Err(e) => {
+                eprintln!("⚠️ Could not read cached file '{}': {}", path.display(), e);
+                None
+            }
+        };
+
+        let mut cache = cache.lock().unwrap();
+        match intact {
+            Some(bytes) => {
+                if let Some(entry) = cache.manifest.entries.get_mut(&name) {
+                    entry.last_used = now_millis();
+                }
+                cache.note_entry(key, &name);
+                cache.save_manifest();
+                Some(bytes)
+            }
+            None => {
+                cache.remove(&name);
+                cache.save_manifest();
+                None
+            }
+        }
+    }
+
+    /// Stores a file in the cache, evicting the least recently used files if over the limit.
+    ///
+    /// The file is written without holding the lock; the cache is only locked to record it.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The shared cache.
+    /// * `key` - Key of the file.
+    /// * `bytes` - Contents of the file.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the file is in place, or the error that stopped it from being written.
+    pub fn put(cache: &Mutex<Self>, key: &CacheKey, bytes: &[u8]) -> io::Result<()> {
+        let name = key.file_name();
+        let path = cache.lock().unwrap().dir.join(&name);
+        atomic_file::write(&path, bytes)?;
+        let checksum = fnv1a(bytes);
+
+        let mut cache = cache.lock().unwrap();
+        cache.manifest.entries.insert(
+            name.clone(),
+            CacheEntry {
+                size: bytes.len() as u64,
+                checksum,
+                last_used: now_millis(),
+            },
+        );
+        cache.note_entry(key, &name);
+        cache.evict(Some(&name));
+        cache.save_manifest();
+        Ok(())
+    }
+
+    /// Drops the prepared versions of a source file that was modified, renamed or deleted.
+    ///
+    /// Their keys could no longer be built, so they would otherwise only go once evicted. A
+    /// cached file another source with the same contents also uses is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Path of the source audio file, as it was.
+    pub fn invalidate(&mut self, source: &Path) {
+        let source_key = source.to_string_lossy().into_owned();
+        let Some(record) = self.manifest.sources.remove(&source_key) else {
+            return;
+        };
+        let stale: Vec<String> = record
+            .entries
+            .into_iter()
+            .filter(|name| {
+                !self
+                    .manifest
+                    .sources
+                    .values()
+                    .any(|other| other.entries.contains(name))
+            })
+            .collect();
+        for name in &stale {
+            self.remove(name);
+        }
+        if !stale.is_empty() {
+            println!(
+                "🧹 Dropped {} cached version(s) of '{}'.",
+                stale.len(),
+                source.display()
+            );
+        }
+        self.save_manifest();
+    }
+
+    // ============================================================================
+    // Private Helper Methods
+    // ============================================================================
+
+    /// Removes files the manifest does not list, manifest entries whose file is gone, and
+    /// remembered hashes of source files that no longer exist.
+    fn sweep(&mut self) {
+        let mut changed = false;
+        self.manifest.entries.retain(|name, _| {
+            let exists = self.dir.join(name).is_file();
+            changed |= !exists;
+            exists
+        });
+        self.manifest.sources.retain(|path, _| {
+            let exists = Path::new(path).is_file();
+            changed |= !exists;
+            exists
+        });
+        for record in self.manifest.sources.values_mut() {
+            record
+                .entries
+                .retain(|name| self.manifest.entries.contains_key(name));
+        }
+
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name == MANIFEST_FILE || self.manifest.entries.contains_key(&name) {
+                    continue;
+                }
+                if entry.path().is_file() && fs::remove_file(entry.path()).is_ok() {
+                    println!("🧹 Removed stale cache file '{}'.", name);
+                }
+            }
+        }
+
+        if changed {
+            self.save_manifest();
+        }
+    }
+
+    /// Evicts the least recently used files until the cache fits its size limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - A file that must not be evicted, such as the one just stored.
+    fn evict(&mut self, keep: Option<&str>) {
+        let mut total: u64 = self.manifest.entries.values().map(|entry| entry.size).sum();
+        if total <= self.limit {
+            return;
+        }
+
+        let mut by_age: Vec<(String, u64, u64)> = self
+            .manifest
+            .entries
+            .iter()
+            .filter(|(name, _)| Some(name.as_str()) != keep)
+            .map(|(name, entry)| (name.clone(), entry.last_used, entry.size))
+            .collect();
+        by_age.sort_by_key(|(_, last_used, _)| *last_used);
+
+        for (name, _, size) in by_age {
+            if total <= self.limit {
+                break;
+            }
+            self.remove(&name);
+            total -= size;
+            println!("🧹 Evicted '{}' from the cache.", name);
+        }
+        self.save_manifest();
+    }
+
+    /// Records that a cached file belongs to the source of a key.
+    fn note_entry(&mut self, key: &CacheKey, name: &str) {
+        if let Some(record) = self.manifest.sources.get_mut(&key.source)
+            && !record.entries.iter().any(|entry| entry == name)
+        {
+            record.entries.push(name.to_string());
+        }
+    }
+
+    /// Deletes a cached file and its manifest entry.
+    fn remove(&mut self, name: &str) {
+        self.manifest.entries.remove(name);
+        for record in self.manifest.sources.values_mut() {
+            record.entries.retain(|entry| entry != name);
+        }
+        let path = self.dir.join(name);
+        if path.exists()
+            && let Err(e) = fs::remove_file(&path)
+        {
+            eprintln!(
+                "⚠️ Could not remove cached file '{}': {}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    /// Writes the manifest atomically, creating the cache folder if needed.
+    fn save_manifest(&self) {
+        let path = self.dir.join(MANIFEST_FILE);
+        let result = serde_json::to_string_pretty(&self.manifest)
+            .map_err(|e| e.to_string())
+            .and_then(|json| atomic_file::write(&path, json.as_bytes()).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!(
+                "⚠️ Could not save cache manifest to '{}': {}",
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Returns the current time in milliseconds since the Unix epoch.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |age| age.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Modification time given to every source file.
+    const SOURCE_MODIFIED: SystemTime = UNIX_EPOCH;
+
+    /// A folder in the temporary directory that is removed when dropped.
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new() -> Self {
+            static CREATED: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "music_visualizer-cache-{}-{}",
+                process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempFolder(path)
+        }
+
+        /// Writes a source file below the folder and returns its path.
+        ///
+        /// Every source gets the same modification time, so files with the same contents get
+        /// the same key however long the writes take.
+        fn source(&self, name: &str, contents: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, contents).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SOURCE_MODIFIED))
+                .unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    /// Opens a cache in `folder` holding at most `limit` bytes.
+    fn open(folder: &TempFolder, limit: u64) -> Mutex<AudioCache> {
+        Mutex::new(AudioCache::open(folder.0.join("cache"), limit))
+    }
+
+    fn key(cache: &Mutex<AudioCache>, source: &Path) -> CacheKey {
+        AudioCache::key(cache, source, 48000, "s16").unwrap()
+    }
+
+    /// Lets the clock move on, so the next use is more recent.
+    fn tick() {
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    #[test]
+    fn stored_files_read_back_until_damaged() {
+        let folder = TempFolder::new();
+        let cache = open(&folder, 1 << 20);
+        let key = key(&cache, &folder.source("song.wav", b"source"));
+
+        AudioCache::put(&cache, &key, b"prepared").unwrap();
+        assert_eq!(AudioCache::get(&cache, &key).unwrap(), b"prepared");
+
+        let path = folder.0.join("cache").join(key.file_name());
+        fs::write(&path, b"prepares").unwrap();
+        assert_eq!(AudioCache::get(&cache, &key), None);
+        assert!(!path.exists());
+        assert_eq!(AudioCache::get(&cache, &key), None);
+    }
+
+    #[test]
+    fn the_least_recently_used_files_are_evicted() {
+        let folder = TempFolder::new();
+        let cache = open(&folder, 20);
+        let keys: Vec<CacheKey> = ["a", "b", "c"]
+            .iter()
+            .map(|name| key(&cache, &folder.source(name, name.as_bytes())))
+            .collect();
+
+        AudioCache::put(&cache, &keys[0], &[0; 8]).unwrap();
+        tick();
+        AudioCache::put(&cache, &keys[1], &[1; 8]).unwrap();
+        tick();
+        // Reading the first file makes the second the oldest.
+        AudioCache::get(&cache, &keys[0]).unwrap();
+        tick();
+        AudioCache::put(&cache, &keys[2], &[2; 8]).unwrap();
+
+        assert!(AudioCache::get(&cache, &keys[0]).is_some());
+        assert_eq!(AudioCache::get(&cache, &keys[1]), None);
+        assert!(AudioCache::get(&cache, &keys[2]).is_some());
+    }
+
+    #[test]
+    fn reopening_drops_unknown_files() {
+        let folder = TempFolder::new();
+        let key = {
+            let cache = open(&folder, 1 << 20);
+            let key = key(&cache, &folder.source("song.wav", b"source"));
+            AudioCache::put(&cache, &key, b"prepared").unwrap();
+            key
+        };
+        let stray = folder.0.join("cache").join("song.wav.1-0.tmp");
+        fs::write(&stray, b"half").unwrap();
+
+        let cache = open(&folder, 1 << 20);
+
+        assert!(!stray.exists());
+        assert_eq!(AudioCache::get(&cache, &key).unwrap(), b"prepared");
+    }
+
+    #[test]
+    fn invalidating_keeps_files_shared_with_another_source() {
+        let folder = TempFolder::new();
+        let cache = open(&folder, 1 << 20);
+        let original = folder.source("song.wav", b"same");
+        let copy = folder.source("copy.wav", b"same");
+        let other = folder.source("other.wav", b"other");
+        let (original_key, copy_key, other_key) = (
+            key(&cache, &original),
+            key(&cache, &copy),
+            key(&cache, &other),
+        );
+        AudioCache::put(&cache, &original_key, b"prepared").unwrap();
+        AudioCache::get(&cache, &copy_key).unwrap();
+        AudioCache::put(&cache, &other_key, b"other prepared").unwrap();
+
+        cache.lock().unwrap().invalidate(&original);
+        assert!(AudioCache::get(&cache, &copy_key).is_some());
+
+        cache.lock().unwrap().invalidate(&copy);
+        assert_eq!(AudioCache::get(&cache, &original_key), None);
+        assert!(AudioCache::get(&cache, &other_key).is_some());
+    }
+
+    #[test]
+    fn concurrent_writes_of_one_file_all_succeed() {
+        let folder = TempFolder::new();
+        let cache = Arc::new(open(&folder, 1 << 20));
+        let key = key(&cache, &folder.source("song.wav", b"source"));
+        let bytes = vec![7; 1 << 16];
+
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let (cache, key, bytes) = (Arc::clone(&cache), key.clone(), bytes.clone());
+                thread::spawn(move || AudioCache::put(&cache, &key, &bytes))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+
+        assert_eq!(AudioCache::get(&cache, &key).unwrap(), bytes);
+    }
+}
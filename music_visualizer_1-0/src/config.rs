@@ -0,0 +1,83 @@
+//! Configuration module
+//!
+//! Loads and saves user settings as JSON in `config/config.json`. Missing files or fields fall
+//! back to defaults, so older config files keep working as new settings are added.
+
+use crate::atomic_file;
+use crate::mixer::TransitionMode;
+use crate::resample::ResampleQuality;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Path (relative to the working directory) of the settings file.
+pub const CONFIG_PATH: &str = "config/config.json";
+
+/// User settings persisted between runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// How queued tracks follow each other.
+    pub transition: TransitionMode,
+    /// Crossfade length in seconds for the crossfade and smart modes.
+    pub crossfade_seconds: f32,
+    /// Name of the equalizer preset applied at startup.
+    pub eq_preset: String,
+    /// Algorithm used when a song's sample rate differs from the output stream's.
+    pub resample_quality: ResampleQuality,
+    /// Largest total size of the audio cache, in megabytes.
+    pub cache_limit_mb: u64,
+    /// Keys per action name, replacing the default bindings of the listed actions.
+    pub key_bindings: HashMap<String, Vec<String>>,
+    /// Name of the colour theme applied at startup.
+    pub theme: String,
+    /// Whether a song restored from the last session waits for PLAY, even if it was playing.
+    pub resume_paused: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            transition: TransitionMode::Gapless,
+            crossfade_seconds: 6.0,
+            eq_preset: "Flat".to_string(),
+            resample_quality: ResampleQuality::default(),
+            cache_limit_mb: 2048,
+            key_bindings: HashMap::new(),
+            theme: "Dark".to_string(),
+            resume_paused: true,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the settings file, falling back to defaults if it is missing or invalid.
+    pub fn load() -> Self {
+        match fs::read_to_string(CONFIG_PATH) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!(
+                    "⚠️ Invalid config '{}': {}. Using defaults.",
+                    CONFIG_PATH, e
+                );
+                Config::default()
+            }),
+            Err(_) => Config::default(),
+        }
+    }
+
+    /// Writes the settings file, creating the config folder if needed.
+    ///
+    /// The file is replaced atomically, so a crash while saving keeps the previous settings.
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                atomic_file::write(Path::new(CONFIG_PATH), json.as_bytes())
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            eprintln!("⚠️ Could not save config to '{}': {}", CONFIG_PATH, e);
+        }
+    }
+}
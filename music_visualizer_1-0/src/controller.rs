@@ -5,10 +5,34 @@
 //! - Menu (user interface)
 //! - Song (audio playback)
 //!
-//! Handles layout, updates, and rendering of the complete application.
+//! Handles layout, updates, and rendering of the complete application. The layout follows the
+//! window as it is resized, and the sidebar can be resized by dragging the divider or collapsed
+//! with the handle on it. The mouse wheel scrolls the song list in the sidebar.
+//!
+//! Key presses are looked up in the [`KeyMap`] and carried out by the menu, except for those that
+//! concern the layout or the view.
+//!
+//! Files dropped on the view are played at once; files dropped on the menu are queued.
+//!
+//! The session (what is playing, the visualizer and the window) is saved every
+//! [`SESSION_SAVE_INTERVAL`] and on exit, and restored at startup.
 
+use crate::keymap::{Action, KeyMap, Screen};
+use crate::session::{Session, WindowGeometry};
+use crate::ui::layout::Layout;
+use crate::ui::theme::{self, TextSize};
 use crate::{menu::Menu, view::View};
+use nannou::event::ModifiersState;
 use nannou::prelude::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Distance scrolled per notch of the mouse wheel, in points
+const SCROLL_LINE_HEIGHT: f32 = 40.0;
+
+/// How often the session is saved while the app runs, so little is lost if it is killed
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(30);
 
 /// Main application controller that orchestrates all components
 ///
@@ -21,8 +45,22 @@ pub struct Controller {
     view: View,
     /// Manages the user interface and controls
     menu: Menu,
-    /// Stores the main window dimensions
-    window_rect: Rect,
+    /// Splits the window between the view and the menu sidebar
+    layout: Layout,
+    /// Whether the divider is being dragged
+    dragging_divider: bool,
+    /// Whether the left mouse button was down during the previous update
+    was_mouse_pressed: bool,
+    /// Maps key presses to actions
+    keymap: KeyMap,
+    /// Keys currently held down, to tell presses from the keyboard's repeats
+    keys_held: HashSet<Key>,
+    /// Files dropped since the last update, and whether each landed on the view
+    dropped_files: Vec<(PathBuf, bool)>,
+    /// Where the window is, for the session
+    window: Option<WindowGeometry>,
+    /// When the session was last saved
+    session_saved_at: Instant,
 }
 
 impl Controller {
@@ -30,47 +68,190 @@ impl Controller {
     ///
     /// # Arguments
     /// * `win_rect` - The dimensions of the application window
+    /// * `scale_factor` - Physical pixels per point of the window
+    /// * `session` - The last session to pick up from, if there is one
     ///
     /// # Layout
-    /// - Menu takes up 200px on the right side
+    /// - Menu takes up a sidebar on the right side (200 points to start with)
     /// - View occupies remaining space on the left
     /// - A divider line separates the two sections
-    pub fn new(win_rect: Rect) -> Self {
-        let menu_width = 200.0;
-        let menu_rect = Rect::from_x_y_w_h(
-            win_rect.right() - menu_width / 2.0,
-            win_rect.y(),
-            menu_width,
-            win_rect.h(),
-        );
-
-        let view_rect = Rect::from_x_y_w_h(
-            win_rect.left() + (win_rect.w() - menu_width) / 2.0,
-            win_rect.y(),
-            win_rect.w() - menu_width,
-            win_rect.h(),
-        );
+    pub fn new(win_rect: Rect, scale_factor: f32, session: Option<&Session>) -> Self {
+        let layout = Layout::new(win_rect, scale_factor);
+        let mut menu = Menu::new(layout.menu_rect());
+        let keymap = KeyMap::new(&menu.config().key_bindings);
+        let mut view = View::new(layout.view_rect());
+        if let Some(session) = session {
+            menu.restore_session(session);
+            view.set_visualizer(session.visualizer);
+        }
 
         Controller {
-            view: View::new(view_rect),
-            menu: Menu::new(menu_rect),
-            window_rect: win_rect,
+            view,
+            menu,
+            layout,
+            dragging_divider: false,
+            was_mouse_pressed: false,
+            keymap,
+            keys_held: HashSet::new(),
+            dropped_files: Vec::new(),
+            window: session.and_then(|session| session.window),
+            session_saved_at: Instant::now(),
+        }
+    }
+
+    /// Notes where the window is after it was moved or resized, for the session
+    ///
+    /// # Arguments
+    /// * `geometry` - The window's position and size
+    pub fn window_changed(&mut self, geometry: WindowGeometry) {
+        self.window = Some(geometry);
+    }
+
+    /// Saves what is playing, the visualizer and the window, to be restored on the next start
+    pub fn save_session(&mut self) {
+        Session {
+            visualizer: self.view.visualizer(),
+            window: self.window,
+            ..self.menu.session()
+        }
+        .save();
+        self.session_saved_at = Instant::now();
+    }
+
+    /// Recomputes the layout after the window was resized or its scale factor changed
+    ///
+    /// # Arguments
+    /// * `win_rect` - The new dimensions of the application window
+    /// * `scale_factor` - Physical pixels per point of the window
+    pub fn resize(&mut self, win_rect: Rect, scale_factor: f32) {
+        if self.layout.resize(win_rect, scale_factor) {
+            self.apply_layout();
+        }
+    }
+
+    /// Scrolls whatever is under the mouse in response to the mouse wheel or a touchpad
+    ///
+    /// # Arguments
+    /// * `mouse` - The current mouse position
+    /// * `delta` - How far the wheel turned (in lines) or the touchpad moved (in pixels)
+    /// * `scale_factor` - Physical pixels per point of the window
+    pub fn scroll(&mut self, mouse: Point2, delta: MouseScrollDelta, scale_factor: f32) {
+        let distance = match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines * SCROLL_LINE_HEIGHT,
+            MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / scale_factor,
+        };
+        if !self.layout.is_collapsed() {
+            self.menu.scroll(mouse, distance);
         }
     }
 
+    /// Carries out the action bound to a key press
+    ///
+    /// While the search box has focus, keys go to it instead, except for those bound to actions
+    /// that work while typing (such as moving through the list). Holding a key only repeats
+    /// actions meant to repeat, and song list actions are ignored while the sidebar is collapsed.
+    ///
+    /// # Arguments
+    /// * `key` - The pressed key
+    /// * `mods` - The modifier keys held at the time
+    pub fn key_pressed(&mut self, key: Key, mods: ModifiersState) {
+        // A key that is already held is being repeated by the keyboard.
+        let is_repeat = !self.keys_held.insert(key);
+        let collapsed = self.layout.is_collapsed();
+        let action = self.keymap.action(key, mods, self.menu.screen());
+
+        if self.menu.is_typing() && !collapsed && !action.is_some_and(Action::works_while_typing) {
+            self.menu.edit_text(key, mods);
+            return;
+        }
+        let Some(action) = action else {
+            return;
+        };
+        if is_repeat && !action.repeats() {
+            return;
+        }
+
+        match action {
+            Action::ToggleSidebar => {
+                self.layout.toggle_sidebar();
+                self.apply_layout();
+            }
+            Action::SwitchVisualizer => self.view.next_visualizer(),
+            Action::DebugDump => {
+                self.menu.perform(action);
+                self.keymap.print();
+            }
+            _ if collapsed && action.screen() == Screen::Selection => {}
+            _ => self.menu.perform(action),
+        }
+    }
+
+    /// Notes that a key was let go, so its next press is not taken for a repeat
+    ///
+    /// # Arguments
+    /// * `key` - The released key
+    pub fn key_released(&mut self, key: Key) {
+        self.keys_held.remove(&key);
+    }
+
+    /// Passes a typed character to the menu while the sidebar is shown
+    ///
+    /// # Arguments
+    /// * `c` - The typed character
+    pub fn received_character(&mut self, c: char) {
+        if !self.layout.is_collapsed() {
+            self.menu.received_character(c);
+        }
+    }
+
+    /// Notes a file or folder dropped on the window
+    ///
+    /// Dropping several files delivers them one at a time, so they are collected and handed to
+    /// the menu together on the next update.
+    ///
+    /// # Arguments
+    /// * `path` - The dropped file or folder
+    /// * `mouse` - Where it was dropped
+    pub fn file_dropped(&mut self, path: PathBuf, mouse: Point2) {
+        let on_view = self.layout.is_collapsed() || !self.layout.menu_rect().contains(mouse);
+        self.dropped_files.push((path, on_view));
+    }
+
     /// Updates all application components
     ///
     /// Called once per frame to:
-    /// 1. Update menu state based on user input
-    /// 2. Update song playback based on menu state
-    /// 3. Update view based on playback state
+    /// 1. Play or queue the files dropped since the last update
+    /// 2. Update menu state based on user input
+    /// 3. Update the player's output based on menu state
+    /// 4. Update view based on playback and loading state and the song's artwork and lyrics
+    /// 5. Save the session now and then
     ///
     /// # Arguments
     /// * `app` - Reference to the Nannou application for input handling
     pub fn update(&mut self, app: &App) {
+        if !self.dropped_files.is_empty() {
+            let (on_view, on_menu): (Vec<_>, Vec<_>) = self
+                .dropped_files
+                .drain(..)
+                .partition(|(_, on_view)| *on_view);
+            let paths = |files: Vec<(PathBuf, bool)>| -> Vec<PathBuf> {
+                files.into_iter().map(|(path, _)| path).collect()
+            };
+            self.menu.open_files(&paths(on_view), true);
+            self.menu.open_files(&paths(on_menu), false);
+        }
+        self.update_sidebar(app);
         self.menu.update(app);
-        self.menu.song.update(self.menu.is_playing());
-        self.view.update(self.menu.song.is_playing());
+        self.menu.player.set_playing(self.menu.is_playing());
+        self.view.update(
+            self.menu.player.is_playing(),
+            self.menu.load_status(),
+            self.menu.artwork(),
+            self.menu.lyrics(),
+        );
+        if self.session_saved_at.elapsed() >= SESSION_SAVE_INTERVAL {
+            self.save_session();
+        }
     }
 
     /// Renders all application components
@@ -86,27 +267,69 @@ impl Controller {
     /// * `frame` - The target frame for rendering
     pub fn view(&self, app: &App, frame: Frame) {
         let draw = app.draw();
+        let theme = theme::current();
 
         // Clear the background
-        draw.background().color(BLACK);
+        draw.background().color(theme.background);
 
         // Draw view and menu
         self.view.draw(&draw); //TODO: Rename (maybe canvas)
-        self.menu.draw(&draw);
+        if !self.layout.is_collapsed() {
+            self.menu.draw(&draw);
+        }
 
-        // Draw divider line between view and menu
+        // Draw divider line between view and menu, one physical pixel wide
+        let divider_x = self.layout.divider_x();
+        let window_rect = app.window_rect();
         draw.line()
-            .start(pt2(
-                self.window_rect.right() - 200.0,
-                self.window_rect.top(),
-            ))
-            .end(pt2(
-                self.window_rect.right() - 200.0,
-                self.window_rect.bottom(),
-            ))
-            .color(BLACK)
-            .weight(1.0);
+            .start(pt2(divider_x, window_rect.top()))
+            .end(pt2(divider_x, window_rect.bottom()))
+            .color(theme.background)
+            .weight(self.layout.pixel());
+
+        // Draw the handle that collapses and expands the sidebar
+        let toggle = self.layout.toggle_rect();
+        draw.rect()
+            .xy(toggle.xy())
+            .wh(toggle.wh())
+            .color(theme.background);
+        draw.text(if self.layout.is_collapsed() { "<" } else { ">" })
+            .xy(toggle.xy())
+            .color(theme.text)
+            .font_size(theme.font_size(TextSize::Small));
 
         draw.to_frame(app, &frame).unwrap();
     }
+
+    /// Handles dragging the divider and collapsing or expanding the sidebar with the mouse
+    ///
+    /// # Arguments
+    /// * `app` - Reference to the Nannou application for input handling
+    fn update_sidebar(&mut self, app: &App) {
+        let mouse = app.mouse.position();
+        let is_mouse_pressed = app.mouse.buttons.left().is_down();
+        let just_pressed = is_mouse_pressed && !self.was_mouse_pressed;
+
+        if just_pressed && self.layout.toggle_rect().contains(mouse) {
+            self.layout.toggle_sidebar();
+            self.apply_layout();
+        } else if just_pressed && self.layout.is_near_divider(mouse) {
+            self.dragging_divider = true;
+        }
+
+        if !is_mouse_pressed {
+            self.dragging_divider = false;
+        } else if self.dragging_divider {
+            self.layout.drag_divider_to(mouse.x);
+            self.apply_layout();
+        }
+
+        self.was_mouse_pressed = is_mouse_pressed;
+    }
+
+    /// Hands the layout's current rects to the view and the menu
+    fn apply_layout(&mut self) {
+        self.view.set_rect(self.layout.view_rect());
+        self.menu.set_rect(self.layout.menu_rect());
+    }
 }
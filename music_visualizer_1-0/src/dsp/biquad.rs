@@ -0,0 +1,272 @@
+//! Biquad filter module
+//!
+//! Second-order IIR filters using the RBJ "Audio EQ Cookbook" formulas, run in transposed
+//! direct form II with separate state per channel.
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// The response shape of a biquad filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FilterKind {
+    /// Boost or cut around a centre frequency.
+    #[default]
+    Peaking,
+    /// Boost or cut everything below the corner frequency.
+    LowShelf,
+    /// Boost or cut everything above the corner frequency.
+    HighShelf,
+    /// Remove everything above the cutoff frequency.
+    LowPass,
+    /// Remove everything below the cutoff frequency.
+    HighPass,
+}
+
+impl FilterKind {
+    /// Encodes the kind for storage in an atomic.
+    pub fn to_u8(self) -> u8 {
+        match self {
+            FilterKind::Peaking => 0,
+            FilterKind::LowShelf => 1,
+            FilterKind::HighShelf => 2,
+            FilterKind::LowPass => 3,
+            FilterKind::HighPass => 4,
+        }
+    }
+
+    /// Decodes a kind stored with [`to_u8`](Self::to_u8).
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => FilterKind::LowShelf,
+            2 => FilterKind::HighShelf,
+            3 => FilterKind::LowPass,
+            4 => FilterKind::HighPass,
+            _ => FilterKind::Peaking,
+        }
+    }
+}
+
+/// Normalised biquad coefficients (`a0` divided out).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    /// Coefficients that pass the signal through unchanged.
+    pub const IDENTITY: Coefficients = Coefficients {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    /// Computes the coefficients for a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The filter shape.
+    /// * `frequency` - Centre, corner or cutoff frequency in Hz.
+    /// * `q` - Quality factor (bandwidth); for shelves this is the shelf slope.
+    /// * `gain_db` - Boost/cut in decibels (ignored for low- and high-pass).
+    /// * `sample_rate` - Stream sample rate in Hz.
+    pub fn new(kind: FilterKind, frequency: f32, q: f32, gain_db: f32, sample_rate: f32) -> Self {
+        // Keep the frequency safely below Nyquist so the filter stays stable.
+        let frequency = frequency.clamp(10.0, sample_rate * 0.49);
+        let q = q.max(0.05);
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * q);
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            FilterKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos_w0,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos_w0,
+                1.0 - alpha / a,
+            ),
+            FilterKind::LowShelf => {
+                let sqrt_a = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a),
+                    2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a),
+                    (a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a,
+                    -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    (a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a,
+                )
+            }
+            FilterKind::HighShelf => {
+                let sqrt_a = 2.0 * a.sqrt() * alpha;
+                (
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 + sqrt_a),
+                    -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                    a * ((a + 1.0) + (a - 1.0) * cos_w0 - sqrt_a),
+                    (a + 1.0) - (a - 1.0) * cos_w0 + sqrt_a,
+                    2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                    (a + 1.0) - (a - 1.0) * cos_w0 - sqrt_a,
+                )
+            }
+            FilterKind::LowPass => (
+                (1.0 - cos_w0) / 2.0,
+                1.0 - cos_w0,
+                (1.0 - cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            FilterKind::HighPass => (
+                (1.0 + cos_w0) / 2.0,
+                -(1.0 + cos_w0),
+                (1.0 + cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+        };
+
+        Coefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// A biquad filter with independent state for each channel.
+pub struct Biquad {
+    coefficients: Coefficients,
+    /// Transposed direct form II state `(z1, z2)` per channel.
+    state: Vec<(f32, f32)>,
+}
+
+impl Biquad {
+    /// Creates a pass-through filter for `channels` channels.
+    pub fn new(channels: usize) -> Self {
+        Biquad {
+            coefficients: Coefficients::IDENTITY,
+            state: vec![(0.0, 0.0); channels.max(1)],
+        }
+    }
+
+    /// Replaces the coefficients, keeping the filter state.
+    pub fn set_coefficients(&mut self, coefficients: Coefficients) {
+        self.coefficients = coefficients;
+    }
+
+    /// Clears the filter memory.
+    pub fn reset(&mut self) {
+        self.state.fill((0.0, 0.0));
+    }
+
+    /// Filters one sample of the given channel.
+    #[inline]
+    pub fn process_sample(&mut self, channel: usize, input: f32) -> f32 {
+        let c = &self.coefficients;
+        let (z1, z2) = &mut self.state[channel];
+        let output = c.b0 * input + *z1;
+        *z1 = c.b1 * input - c.a1 * output + *z2;
+        *z2 = c.b2 * input - c.a2 * output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    /// Returns the filter's gain at `frequency`, in dB.
+    fn response_db(c: &Coefficients, frequency: f32) -> f32 {
+        let w = 2.0 * PI * frequency / SAMPLE_RATE;
+        // H(z) = (b0 + b1 z^-1 + b2 z^-2) / (1 + a1 z^-1 + a2 z^-2) at z = e^jw.
+        let magnitude = |c0: f32, c1: f32, c2: f32| {
+            let re = c0 + c1 * w.cos() + c2 * (2.0 * w).cos();
+            let im = -c1 * w.sin() - c2 * (2.0 * w).sin();
+            re.hypot(im)
+        };
+        20.0 * (magnitude(c.b0, c.b1, c.b2) / magnitude(1.0, c.a1, c.a2)).log10()
+    }
+
+    #[test]
+    fn peaking_filters_reach_their_gain_at_the_centre() {
+        for gain_db in [-12.0, -3.0, 6.0, 12.0] {
+            for frequency in [31.0, 1000.0, 16000.0] {
+                let c =
+                    Coefficients::new(FilterKind::Peaking, frequency, 1.41, gain_db, SAMPLE_RATE);
+                assert!((response_db(&c, frequency) - gain_db).abs() < 0.05);
+            }
+            // Four octaves away the band has almost no effect.
+            let c = Coefficients::new(FilterKind::Peaking, 1000.0, 1.41, gain_db, SAMPLE_RATE);
+            assert!(response_db(&c, 62.0).abs() < 0.5);
+            assert!(response_db(&c, 16000.0).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn shelves_reach_their_gain_past_the_corner() {
+        let low = Coefficients::new(FilterKind::LowShelf, 1000.0, 0.707, 9.0, SAMPLE_RATE);
+        assert!((response_db(&low, 20.0) - 9.0).abs() < 0.1);
+        assert!((response_db(&low, 1000.0) - 4.5).abs() < 0.1);
+        assert!(response_db(&low, 20000.0).abs() < 0.1);
+
+        let high = Coefficients::new(FilterKind::HighShelf, 1000.0, 0.707, -9.0, SAMPLE_RATE);
+        assert!((response_db(&high, 20000.0) + 9.0).abs() < 0.1);
+        assert!((response_db(&high, 1000.0) + 4.5).abs() < 0.1);
+        assert!(response_db(&high, 20.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn passes_are_three_db_down_at_the_cutoff() {
+        let low = Coefficients::new(FilterKind::LowPass, 2000.0, 0.707, 0.0, SAMPLE_RATE);
+        assert!((response_db(&low, 2000.0) + 3.0).abs() < 0.1);
+        let high = Coefficients::new(FilterKind::HighPass, 2000.0, 0.707, 0.0, SAMPLE_RATE);
+        assert!((response_db(&high, 2000.0) + 3.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn zero_db_peaking_filters_are_bit_transparent() {
+        let mut filter = Biquad::new(2);
+        filter.set_coefficients(Coefficients::new(
+            FilterKind::Peaking,
+            1000.0,
+            1.41,
+            0.0,
+            SAMPLE_RATE,
+        ));
+        for i in 0..4800 {
+            let input = (i as f32 * 0.37).sin() * 0.8;
+            assert_eq!(
+                filter.process_sample(i % 2, input).to_bits(),
+                input.to_bits()
+            );
+        }
+    }
+
+    #[test]
+    fn resetting_clears_the_filter_memory() {
+        let mut filter = Biquad::new(1);
+        filter.set_coefficients(Coefficients::new(
+            FilterKind::LowPass,
+            200.0,
+            0.707,
+            0.0,
+            SAMPLE_RATE,
+        ));
+        filter.process_sample(0, 1.0);
+        assert_ne!(filter.process_sample(0, 0.0), 0.0);
+        filter.reset();
+        assert_eq!(filter.process_sample(0, 0.0), 0.0);
+    }
+}
@@ -0,0 +1,602 @@
+//! Equalizer module
+//!
+//! Provides the two EQ stages of the effect chain:
+//! - [`GraphicEq`]: ten fixed octave bands from 31 Hz to 16 kHz
+//! - [`ParametricEq`]: up to [`MAX_PARAMETRIC_BANDS`] freely placed biquad bands
+//!
+//! Both read their settings from a shared [`EqParams`], which the UI updates. Settings can be
+//! stored as named [`EqPreset`]s in `config/eq_presets.json`.
+
+use super::biquad::{Biquad, Coefficients, FilterKind};
+use super::{AtomicF32, DspNode, smoothing_factor};
+use crate::atomic_file;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Centre frequencies of the graphic EQ bands, in Hz.
+pub const GRAPHIC_BANDS: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Largest boost or cut the graphic EQ allows, in dB.
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+/// Number of parametric bands available.
+pub const MAX_PARAMETRIC_BANDS: usize = 6;
+
+/// Path (relative to the working directory) of the user's saved presets.
+pub const PRESETS_PATH: &str = "config/eq_presets.json";
+
+/// Q of the graphic EQ's peaking filters (about one octave wide).
+const GRAPHIC_Q: f32 = 1.41;
+
+/// Time constant for parameter smoothing, in seconds.
+const SMOOTHING_SECONDS: f32 = 0.05;
+
+// ============================================================================
+// Presets
+// ============================================================================
+
+/// One band of the parametric EQ as stored in a preset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParametricBand {
+    /// Filter shape.
+    pub kind: FilterKind,
+    /// Centre, corner or cutoff frequency in Hz.
+    pub frequency: f32,
+    /// Boost/cut in dB (ignored for low- and high-pass).
+    pub gain_db: f32,
+    /// Quality factor.
+    pub q: f32,
+}
+
+/// A named set of EQ settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqPreset {
+    /// Name shown in the EQ panel.
+    pub name: String,
+    /// Gain of each graphic band in dB.
+    pub graphic: [f32; 10],
+    /// Active parametric bands (at most [`MAX_PARAMETRIC_BANDS`]).
+    #[serde(default)]
+    pub parametric: Vec<ParametricBand>,
+}
+
+impl EqPreset {
+    /// Returns a flat graphic EQ with the given name and no parametric bands.
+    fn flat(name: &str) -> Self {
+        EqPreset {
+            name: name.to_string(),
+            graphic: [0.0; 10],
+            parametric: Vec::new(),
+        }
+    }
+
+    /// Returns the presets that ship with the player.
+    pub fn builtin() -> Vec<EqPreset> {
+        vec![
+            EqPreset::flat("Flat"),
+            EqPreset {
+                name: "Bass Boost".to_string(),
+                graphic: [6.0, 5.0, 3.5, 1.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                parametric: Vec::new(),
+            },
+            EqPreset {
+                name: "Treble Boost".to_string(),
+                graphic: [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.5, 3.0, 4.5, 6.0],
+                parametric: Vec::new(),
+            },
+            EqPreset {
+                name: "Vocal".to_string(),
+                graphic: [-3.0, -2.0, -1.0, 0.0, 2.0, 3.5, 3.5, 2.0, 0.0, -1.0],
+                parametric: Vec::new(),
+            },
+            EqPreset {
+                name: "Loudness".to_string(),
+                graphic: [5.0, 3.5, 1.5, 0.0, -1.0, -1.0, 0.0, 1.5, 3.5, 5.0],
+                parametric: Vec::new(),
+            },
+            EqPreset {
+                name: "Rumble Filter".to_string(),
+                graphic: [0.0; 10],
+                parametric: vec![ParametricBand {
+                    kind: FilterKind::HighPass,
+                    frequency: 30.0,
+                    gain_db: 0.0,
+                    q: 0.707,
+                }],
+            },
+        ]
+    }
+
+    /// Loads the built-in presets followed by the user's saved presets.
+    ///
+    /// A saved preset replaces a built-in one with the same name.
+    pub fn load_all() -> Vec<EqPreset> {
+        let mut presets = Self::builtin();
+        for preset in Self::load_user() {
+            match presets.iter_mut().find(|p| p.name == preset.name) {
+                Some(existing) => *existing = preset,
+                None => presets.push(preset),
+            }
+        }
+        presets
+    }
+
+    /// Saves `preset` to the user presets file, replacing any preset with the same name.
+    pub fn save_user(preset: &EqPreset) {
+        let mut presets = Self::load_user();
+        match presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset.clone(),
+            None => presets.push(preset.clone()),
+        }
+
+        let result = serde_json::to_string_pretty(&presets)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                atomic_file::write(Path::new(PRESETS_PATH), json.as_bytes())
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            eprintln!("⚠️ Could not save EQ presets to '{}': {}", PRESETS_PATH, e);
+        }
+    }
+
+    /// Reads the user presets file, returning an empty list if it is missing or invalid.
+    fn load_user() -> Vec<EqPreset> {
+        match fs::read_to_string(PRESETS_PATH) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("⚠️ Invalid EQ presets '{}': {}", PRESETS_PATH, e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// ============================================================================
+// Shared Parameters
+// ============================================================================
+
+/// Target settings of one parametric band, shared with the audio thread.
+#[derive(Debug, Default)]
+struct BandParams {
+    enabled: AtomicBool,
+    kind: AtomicU8,
+    frequency: AtomicF32,
+    gain_db: AtomicF32,
+    q: AtomicF32,
+}
+
+/// EQ settings shared between the UI and the audio thread.
+///
+/// The UI writes target values; the EQ nodes read them once per buffer and glide towards them.
+#[derive(Debug, Default)]
+pub struct EqParams {
+    graphic: [AtomicF32; 10],
+    parametric: [BandParams; MAX_PARAMETRIC_BANDS],
+}
+
+impl EqParams {
+    /// Returns the target gain of a graphic band in dB.
+    pub fn graphic_gain(&self, band: usize) -> f32 {
+        self.graphic[band].load()
+    }
+
+    /// Sets the target gain of a graphic band, clamped to ±[`MAX_GAIN_DB`].
+    pub fn set_graphic_gain(&self, band: usize, gain_db: f32) {
+        self.graphic[band].store(gain_db.clamp(-MAX_GAIN_DB, MAX_GAIN_DB));
+    }
+
+    /// Returns whether the settings leave the audio unchanged: every graphic band at 0 dB and no
+    /// parametric band enabled.
+    pub fn is_flat(&self) -> bool {
+        self.graphic.iter().all(|gain| gain.load().abs() <= 1e-3)
+            && !self
+                .parametric
+                .iter()
+                .any(|params| params.enabled.load(Ordering::Relaxed))
+    }
+
+    /// Applies every setting of a preset.
+    pub fn apply(&self, preset: &EqPreset) {
+        for (band, gain) in preset.graphic.iter().enumerate() {
+            self.set_graphic_gain(band, *gain);
+        }
+        for (index, params) in self.parametric.iter().enumerate() {
+            match preset.parametric.get(index) {
+                Some(band) => {
+                    params.kind.store(band.kind.to_u8(), Ordering::Relaxed);
+                    params.frequency.store(band.frequency);
+                    params.gain_db.store(band.gain_db);
+                    params.q.store(band.q);
+                    params.enabled.store(true, Ordering::Relaxed);
+                }
+                None => params.enabled.store(false, Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Captures the current settings as a preset.
+    pub fn snapshot(&self, name: &str) -> EqPreset {
+        let mut graphic = [0.0; 10];
+        for (band, gain) in graphic.iter_mut().enumerate() {
+            *gain = self.graphic_gain(band);
+        }
+        let parametric = self
+            .parametric
+            .iter()
+            .filter(|params| params.enabled.load(Ordering::Relaxed))
+            .map(|params| ParametricBand {
+                kind: FilterKind::from_u8(params.kind.load(Ordering::Relaxed)),
+                frequency: params.frequency.load(),
+                gain_db: params.gain_db.load(),
+                q: params.q.load(),
+            })
+            .collect();
+        EqPreset {
+            name: name.to_string(),
+            graphic,
+            parametric,
+        }
+    }
+}
+
+// ============================================================================
+// Graphic EQ
+// ============================================================================
+
+/// Ten-band graphic equalizer built from peaking filters.
+pub struct GraphicEq {
+    params: Arc<EqParams>,
+    filters: Vec<Biquad>,
+    /// Smoothed gain of each band, in dB.
+    gains: [f32; 10],
+    /// Whether each band was filtering in the last buffer.
+    active: [bool; 10],
+    sample_rate: f32,
+}
+
+impl GraphicEq {
+    /// Creates a graphic EQ for a stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Shared settings written by the UI.
+    /// * `sample_rate` - Stream sample rate in Hz.
+    /// * `channels` - Number of interleaved channels.
+    pub fn new(params: Arc<EqParams>, sample_rate: u32, channels: usize) -> Self {
+        GraphicEq {
+            params,
+            filters: (0..GRAPHIC_BANDS.len())
+                .map(|_| Biquad::new(channels))
+                .collect(),
+            gains: [0.0; 10],
+            active: [false; 10],
+            sample_rate: sample_rate as f32,
+        }
+    }
+}
+
+impl DspNode for GraphicEq {
+    fn process(&mut self, buffer: &mut [f32], channels: usize) {
+        let frames = buffer.len() / channels.max(1);
+        let alpha = smoothing_factor(frames, self.sample_rate, SMOOTHING_SECONDS);
+
+        for (band, filter) in self.filters.iter_mut().enumerate() {
+            let target = self.params.graphic_gain(band);
+            let gain = &mut self.gains[band];
+            if (target - *gain).abs() > 1e-4 {
+                *gain += (target - *gain) * alpha;
+                filter.set_coefficients(Coefficients::new(
+                    FilterKind::Peaking,
+                    GRAPHIC_BANDS[band],
+                    GRAPHIC_Q,
+                    *gain,
+                    self.sample_rate,
+                ));
+            }
+        }
+
+        // Flat bands are exact pass-throughs, so skip them. A skipped band's memory is stale, so
+        // it starts from rest when it becomes audible again, as a flat band's would be.
+        let active: [bool; 10] = std::array::from_fn(|band| self.gains[band].abs() > 1e-3);
+        for (band, filter) in self.filters.iter_mut().enumerate() {
+            if active[band] && !self.active[band] {
+                filter.reset();
+            }
+        }
+        self.active = active;
+        for frame in buffer.chunks_mut(channels) {
+            for (ch, sample) in frame.iter_mut().enumerate() {
+                for (band, filter) in self.filters.iter_mut().enumerate() {
+                    if active[band] {
+                        *sample = filter.process_sample(ch, *sample);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Parametric EQ
+// ============================================================================
+
+/// Smoothed state of one parametric band.
+struct Band {
+    filter: Biquad,
+    kind: FilterKind,
+    frequency: f32,
+    gain_db: f32,
+    q: f32,
+    /// Wet/dry amount, ramped so bands fade in and out instead of switching abruptly.
+    mix: f32,
+}
+
+/// Parametric equalizer with freely configurable biquad bands.
+pub struct ParametricEq {
+    params: Arc<EqParams>,
+    bands: Vec<Band>,
+    sample_rate: f32,
+}
+
+impl ParametricEq {
+    /// Creates a parametric EQ for a stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Shared settings written by the UI.
+    /// * `sample_rate` - Stream sample rate in Hz.
+    /// * `channels` - Number of interleaved channels.
+    pub fn new(params: Arc<EqParams>, sample_rate: u32, channels: usize) -> Self {
+        ParametricEq {
+            params,
+            bands: (0..MAX_PARAMETRIC_BANDS)
+                .map(|_| Band {
+                    filter: Biquad::new(channels),
+                    kind: FilterKind::Peaking,
+                    frequency: 1000.0,
+                    gain_db: 0.0,
+                    q: 0.707,
+                    mix: 0.0,
+                })
+                .collect(),
+            sample_rate: sample_rate as f32,
+        }
+    }
+}
+
+impl DspNode for ParametricEq {
+    fn process(&mut self, buffer: &mut [f32], channels: usize) {
+        let frames = buffer.len() / channels.max(1);
+        if frames == 0 {
+            return;
+        }
+        let alpha = smoothing_factor(frames, self.sample_rate, SMOOTHING_SECONDS);
+
+        for (band, params) in self.bands.iter_mut().zip(&self.params.parametric) {
+            let enabled = params.enabled.load(Ordering::Relaxed);
+            let kind = FilterKind::from_u8(params.kind.load(Ordering::Relaxed));
+
+            // A change of filter shape fades the band out, swaps the shape, then fades it back in.
+            let target_mix = if kind != band.kind {
+                if band.mix < 1e-3 {
+                    band.kind = kind;
+                    band.filter.reset();
+                }
+                0.0
+            } else if enabled {
+                1.0
+            } else {
+                0.0
+            };
+
+            if !enabled && band.mix < 1e-3 {
+                band.mix = 0.0;
+                continue;
+            }
+
+            // A silent band jumps straight to its settings; an audible one glides there.
+            // Frequency glides in the log domain so sweeps sound even across octaves.
+            let glide = if band.mix == 0.0 { 1.0 } else { alpha };
+            let target_frequency = params.frequency.load().max(10.0);
+            let frequency =
+                (band.frequency.ln() + (target_frequency.ln() - band.frequency.ln()) * glide).exp();
+            let gain_db = band.gain_db + (params.gain_db.load() - band.gain_db) * glide;
+            let q = band.q + (params.q.load().max(0.05) - band.q) * glide;
+            if (frequency - band.frequency).abs() > 1e-3
+                || (gain_db - band.gain_db).abs() > 1e-4
+                || (q - band.q).abs() > 1e-4
+                || band.mix == 0.0
+            {
+                band.frequency = frequency;
+                band.gain_db = gain_db;
+                band.q = q;
+                band.filter.set_coefficients(Coefficients::new(
+                    band.kind,
+                    frequency,
+                    q,
+                    gain_db,
+                    self.sample_rate,
+                ));
+            }
+
+            // Ramp the wet/dry mix linearly across the buffer.
+            let start_mix = band.mix;
+            let end_mix = start_mix + (target_mix - start_mix) * alpha;
+            let step = (end_mix - start_mix) / frames as f32;
+            for (i, frame) in buffer.chunks_mut(channels).enumerate() {
+                let mix = start_mix + step * i as f32;
+                for (ch, sample) in frame.iter_mut().enumerate() {
+                    let wet = band.filter.process_sample(ch, *sample);
+                    *sample += (wet - *sample) * mix;
+                }
+            }
+            band.mix = if (end_mix - target_mix).abs() < 1e-3 {
+                target_mix
+            } else {
+                end_mix
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Frames per processed buffer.
+    const BUFFER: usize = 256;
+
+    /// Index of the 1 kHz graphic band.
+    const BAND_1K: usize = 5;
+
+    /// A mono sine at 1 kHz, continuing from `frame`.
+    fn sine(frame: &mut usize) -> Vec<f32> {
+        let buffer = (0..BUFFER)
+            .map(|i| (2.0 * PI * 1000.0 * (*frame + i) as f32 / SAMPLE_RATE as f32).sin() * 0.25)
+            .collect();
+        *frame += BUFFER;
+        buffer
+    }
+
+    fn peak_db(buffer: &[f32]) -> f32 {
+        let peak = buffer
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        20.0 * (peak / 0.25).log10()
+    }
+
+    fn largest_step(buffer: &[f32]) -> f32 {
+        buffer
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn flat_bands_are_bit_transparent() {
+        let params = Arc::new(EqParams::default());
+        let mut graphic = GraphicEq::new(Arc::clone(&params), SAMPLE_RATE, 2);
+        let mut parametric = ParametricEq::new(params, SAMPLE_RATE, 2);
+        let input: Vec<f32> = (0..BUFFER * 2)
+            .map(|i| (i as f32 * 0.61).sin() * 0.9)
+            .collect();
+
+        let mut buffer = input.clone();
+        graphic.process(&mut buffer, 2);
+        parametric.process(&mut buffer, 2);
+        assert_eq!(buffer, input);
+    }
+
+    #[test]
+    fn a_gain_step_ramps_smoothly() {
+        let params = Arc::new(EqParams::default());
+        let mut eq = GraphicEq::new(Arc::clone(&params), SAMPLE_RATE, 1);
+        let alpha = smoothing_factor(BUFFER, SAMPLE_RATE as f32, SMOOTHING_SECONDS);
+        let mut frame = 0;
+        for _ in 0..4 {
+            eq.process(&mut sine(&mut frame), 1);
+        }
+
+        params.set_graphic_gain(BAND_1K, MAX_GAIN_DB);
+        let mut previous = 0.0;
+        let mut output = Vec::new();
+        for _ in 0..100 {
+            let mut buffer = sine(&mut frame);
+            eq.process(&mut buffer, 1);
+            let level = peak_db(&buffer);
+            // Each buffer moves at most the smoothing step towards the target, plus the filter's
+            // own settling.
+            assert!(
+                level - previous <= MAX_GAIN_DB * alpha + 0.5,
+                "{} after {}",
+                level,
+                previous
+            );
+            previous = level;
+            output.extend(buffer);
+        }
+        assert!((previous - MAX_GAIN_DB).abs() < 0.2);
+
+        // Nothing moves faster than the boosted sine itself does.
+        let boosted = 0.25 * 10f32.powf(MAX_GAIN_DB / 20.0);
+        assert!(largest_step(&output) <= boosted * 2.0 * PI * 1000.0 / SAMPLE_RATE as f32 * 1.05);
+    }
+
+    #[test]
+    fn a_band_turned_back_on_starts_from_rest() {
+        let params = Arc::new(EqParams::default());
+        let mut eq = GraphicEq::new(Arc::clone(&params), SAMPLE_RATE, 1);
+        let mut frame = 0;
+
+        params.set_graphic_gain(BAND_1K, MAX_GAIN_DB);
+        for _ in 0..20 {
+            eq.process(&mut sine(&mut frame), 1);
+        }
+        params.set_graphic_gain(BAND_1K, 0.0);
+        let mut buffers = 0;
+        while eq.active[BAND_1K] {
+            eq.process(&mut sine(&mut frame), 1);
+            buffers += 1;
+            assert!(buffers < 1000, "the band never turned off");
+        }
+
+        // With the band off its memory still holds the sine; turned back on, silence must stay
+        // silent.
+        params.set_graphic_gain(BAND_1K, MAX_GAIN_DB);
+        let mut silence = vec![0.0; BUFFER];
+        eq.process(&mut silence, 1);
+        assert!(eq.active[BAND_1K]);
+        assert!(silence.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn a_parametric_band_fades_in() {
+        let params = Arc::new(EqParams::default());
+        let mut eq = ParametricEq::new(Arc::clone(&params), SAMPLE_RATE, 1);
+        params.apply(&EqPreset {
+            name: "Boost".to_string(),
+            graphic: [0.0; 10],
+            parametric: vec![ParametricBand {
+                kind: FilterKind::Peaking,
+                frequency: 1000.0,
+                gain_db: MAX_GAIN_DB,
+                q: 1.0,
+            }],
+        });
+
+        let mut frame = 0;
+        let mut levels = Vec::new();
+        for _ in 0..100 {
+            let mut buffer = sine(&mut frame);
+            eq.process(&mut buffer, 1);
+            levels.push(peak_db(&buffer));
+        }
+        assert!(levels[0] < 3.0);
+        assert!((levels[99] - MAX_GAIN_DB).abs() < 0.2);
+    }
+
+    #[test]
+    fn flatness_follows_the_settings() {
+        let params = EqParams::default();
+        assert!(params.is_flat());
+        params.set_graphic_gain(0, 3.0);
+        assert!(!params.is_flat());
+        params.set_graphic_gain(0, 0.0);
+        params.apply(
+            &EqPreset::builtin()
+                .into_iter()
+                .find(|p| p.name == "Rumble Filter")
+                .unwrap(),
+        );
+        assert!(!params.is_flat());
+    }
+}
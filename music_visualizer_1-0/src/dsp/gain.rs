@@ -0,0 +1,46 @@
+//! Volume module
+//!
+//! The last stage of the effect chain: scales the output by the volume set in the UI. The gain
+//! ramps linearly across each buffer towards its target, so volume steps never click.
+
+use super::{AtomicF32, DspNode};
+use std::sync::Arc;
+
+/// Output volume stage.
+pub struct Gain {
+    /// Linear volume (0.0 to 1.0) written by the UI.
+    volume: Arc<AtomicF32>,
+    /// Gain applied at the end of the last buffer.
+    current: f32,
+}
+
+impl Gain {
+    /// Creates a volume stage starting at the shared volume.
+    ///
+    /// # Arguments
+    ///
+    /// * `volume` - Shared linear volume written by the UI.
+    pub fn new(volume: Arc<AtomicF32>) -> Self {
+        let current = volume.load();
+        Gain { volume, current }
+    }
+}
+
+impl DspNode for Gain {
+    fn process(&mut self, buffer: &mut [f32], channels: usize) {
+        let target = self.volume.load();
+        if target == 1.0 && self.current == 1.0 {
+            return;
+        }
+
+        let frames = (buffer.len() / channels.max(1)).max(1);
+        let step = (target - self.current) / frames as f32;
+        for frame in buffer.chunks_mut(channels) {
+            self.current += step;
+            for sample in frame {
+                *sample *= self.current;
+            }
+        }
+        self.current = target;
+    }
+}
@@ -0,0 +1,79 @@
+//! Digital signal processing module
+//!
+//! Effects applied to the mixed output in the audio callback. Every effect implements
+//! [`DspNode`] and is run in order by a [`DspChain`]. Speed and pitch changes are applied
+//! earlier, per source, by the [`stretch`] module.
+//!
+//! Nodes are built before the stream starts and never allocate while processing. Parameters
+//! are shared with the UI through atomics and smoothed by each node, so changes never block the
+//! audio thread or cause clicks.
+
+pub mod biquad;
+pub mod eq;
+pub mod gain;
+pub mod stretch;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// An audio effect that processes interleaved buffers in place.
+pub trait DspNode: Send {
+    /// Processes one interleaved buffer in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - Interleaved samples to process.
+    /// * `channels` - Number of interleaved channels.
+    fn process(&mut self, buffer: &mut [f32], channels: usize);
+}
+
+/// An ordered list of effects applied one after another.
+#[derive(Default)]
+pub struct DspChain {
+    nodes: Vec<Box<dyn DspNode>>,
+}
+
+impl DspChain {
+    /// Appends an effect to the end of the chain.
+    pub fn push(&mut self, node: Box<dyn DspNode>) {
+        self.nodes.push(node);
+    }
+
+    /// Runs every effect over the buffer, in order.
+    pub fn process(&mut self, buffer: &mut [f32], channels: usize) {
+        for node in &mut self.nodes {
+            node.process(buffer, channels);
+        }
+    }
+}
+
+/// An `f32` that can be shared between threads without locking.
+#[derive(Debug, Default)]
+pub struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    /// Creates a new atomic holding `value`.
+    pub fn new(value: f32) -> Self {
+        AtomicF32(AtomicU32::new(value.to_bits()))
+    }
+
+    /// Reads the current value.
+    pub fn load(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Replaces the current value.
+    pub fn store(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Returns the per-buffer smoothing factor for a one-pole ramp with the given time constant.
+///
+/// # Arguments
+///
+/// * `frames` - Number of frames in the buffer.
+/// * `sample_rate` - Stream sample rate.
+/// * `time_constant` - Time (in seconds) to cover about 63% of a parameter change.
+pub fn smoothing_factor(frames: usize, sample_rate: f32, time_constant: f32) -> f32 {
+    1.0 - (-(frames as f32) / (time_constant * sample_rate)).exp()
+}
@@ -0,0 +1,483 @@
+//! Time-stretching module
+//!
+//! Changes playback speed and pitch independently while a song plays:
+//! - [`PlaybackRate`]: the speed, pitch and mode settings shared with the UI
+//! - [`TimeStretcher`]: the per-source engine run in the output callback
+//!
+//! The engine has two stages. A WSOLA (waveform-similarity overlap-add) stage changes the tempo
+//! without touching the pitch by overlapping short windows of the song, each shifted slightly so
+//! it lines up with the previous one. A `rubato` polynomial resampler then changes the pitch by
+//! resampling, which also changes the tempo; the WSOLA stage compensates for that. In varispeed
+//! mode the WSOLA stage passes audio through unchanged and only the resampler runs, so pitch
+//! follows speed like a turntable.
+
+use super::AtomicF32;
+use rubato::{FastFixedOut, PolynomialDegree, Resampler};
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Slowest allowed playback speed.
+pub const MIN_SPEED: f32 = 0.5;
+
+/// Fastest allowed playback speed.
+pub const MAX_SPEED: f32 = 2.0;
+
+/// Largest pitch shift in either direction, in semitones.
+pub const MAX_PITCH_SEMITONES: f32 = 12.0;
+
+/// Length of the WSOLA hop in seconds; windows are twice as long.
+const HOP_SECONDS: f32 = 0.015;
+
+/// Largest resampling ratio the pitch stage must reach (two octaves of speed and pitch combined).
+const MAX_PITCH_RATIO: f64 = 4.0;
+
+/// Only every n-th sample is compared when searching for the best-aligned window.
+const SEARCH_STRIDE: usize = 4;
+
+/// How speed changes affect pitch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedMode {
+    /// Keep the pitch while changing the speed.
+    #[default]
+    Stretch,
+    /// Let the pitch follow the speed, like changing a turntable's speed.
+    Varispeed,
+}
+
+/// Speed and pitch settings shared between the UI and the audio thread.
+#[derive(Debug)]
+pub struct PlaybackRate {
+    speed: AtomicF32,
+    semitones: AtomicF32,
+    varispeed: AtomicBool,
+}
+
+impl Default for PlaybackRate {
+    fn default() -> Self {
+        PlaybackRate {
+            speed: AtomicF32::new(1.0),
+            semitones: AtomicF32::new(0.0),
+            varispeed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl PlaybackRate {
+    /// Returns the playback speed (1.0 is normal speed).
+    pub fn speed(&self) -> f32 {
+        self.speed.load()
+    }
+
+    /// Sets the playback speed, clamped to [`MIN_SPEED`]..=[`MAX_SPEED`].
+    ///
+    /// The speed is rounded to hundredths so stepping up and down returns exactly to 1.0.
+    pub fn set_speed(&self, speed: f32) {
+        let speed = (speed * 100.0).round() / 100.0;
+        self.speed.store(speed.clamp(MIN_SPEED, MAX_SPEED));
+    }
+
+    /// Returns the pitch shift in semitones.
+    pub fn semitones(&self) -> f32 {
+        self.semitones.load()
+    }
+
+    /// Sets the pitch shift, clamped to ±[`MAX_PITCH_SEMITONES`].
+    pub fn set_semitones(&self, semitones: f32) {
+        self.semitones
+            .store(semitones.clamp(-MAX_PITCH_SEMITONES, MAX_PITCH_SEMITONES));
+    }
+
+    /// Returns how speed changes affect pitch.
+    pub fn mode(&self) -> SpeedMode {
+        if self.varispeed.load(Ordering::Relaxed) {
+            SpeedMode::Varispeed
+        } else {
+            SpeedMode::Stretch
+        }
+    }
+
+    /// Sets how speed changes affect pitch.
+    pub fn set_mode(&self, mode: SpeedMode) {
+        self.varispeed
+            .store(mode == SpeedMode::Varispeed, Ordering::Relaxed);
+    }
+
+    /// Returns the tempo factor of the WSOLA stage and the pitch factor of the resampler.
+    ///
+    /// Their product is always the playback speed, so the song moves forward at that rate.
+    pub fn factors(&self) -> (f32, f32) {
+        let speed = self.speed();
+        let mut pitch = 2f32.powf(self.semitones() / 12.0);
+        if self.mode() == SpeedMode::Varispeed {
+            pitch *= speed;
+        }
+        (speed / pitch, pitch)
+    }
+
+    /// Returns whether audio plays unmodified.
+    pub fn is_neutral(&self) -> bool {
+        self.speed() == 1.0 && self.semitones() == 0.0
+    }
+}
+
+/// Real-time time-stretcher and pitch-shifter for one interleaved source.
+///
+/// All buffers are allocated up front, so [`next_frame`](Self::next_frame) is safe to call from
+/// the output callback.
+pub struct TimeStretcher {
+    channels: usize,
+    /// Synthesis hop in frames; windows are `2 * hop` frames long.
+    hop: usize,
+    /// How far (in frames) a window may move to line up with the previous one.
+    tolerance: usize,
+    window: Vec<f32>,
+    /// Interleaved overlap-add accumulator, `2 * hop` frames long.
+    overlap: Vec<f32>,
+    /// Nominal start of the next window in the song, in frames.
+    analysis: f64,
+    /// Where the previous window would have continued naturally, if it was placed.
+    natural: Option<isize>,
+    resampler: FastFixedOut<f32>,
+    /// Current output/input ratio of the resampler.
+    ratio: f64,
+    /// Planar WSOLA output waiting to be resampled.
+    pending: Vec<Vec<f32>>,
+    pending_len: usize,
+    /// Planar resampler output being played.
+    output: Vec<Vec<f32>>,
+    output_len: usize,
+    output_pos: usize,
+}
+
+impl TimeStretcher {
+    /// Creates a stretcher for a source.
+    ///
+    /// # Arguments
+    ///
+    /// * `channels` - Number of interleaved channels in the source.
+    /// * `sample_rate` - Sample rate of the source in Hz.
+    pub fn new(channels: usize, sample_rate: u32) -> Self {
+        let channels = channels.max(1);
+        let hop = ((sample_rate as f32 * HOP_SECONDS) as usize).max(64);
+        let length = hop * 2;
+        // A periodic Hann window sums to exactly one at 50% overlap.
+        let window = (0..length)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / length as f32).cos())
+            .collect();
+        let resampler =
+            FastFixedOut::new(1.0, MAX_PITCH_RATIO, PolynomialDegree::Cubic, hop, channels)
+                .expect("Failed to create pitch resampler");
+        let pending_capacity = resampler.input_frames_max() + hop;
+        let output_capacity = resampler.output_frames_max();
+
+        TimeStretcher {
+            channels,
+            hop,
+            tolerance: hop / 2,
+            window,
+            overlap: vec![0.0; length * channels],
+            analysis: 0.0,
+            natural: None,
+            resampler,
+            ratio: 1.0,
+            pending: vec![vec![0.0; pending_capacity]; channels],
+            pending_len: 0,
+            output: vec![vec![0.0; output_capacity]; channels],
+            output_len: 0,
+            output_pos: 0,
+        }
+    }
+
+    /// Drops all buffered audio and continues from `frame` of the song.
+    pub fn seek(&mut self, frame: usize) {
+        self.analysis = frame as f64;
+        self.natural = None;
+        self.overlap.fill(0.0);
+        self.resampler.reset();
+        self.ratio = 1.0;
+        self.pending_len = 0;
+        self.output_len = 0;
+        self.output_pos = 0;
+    }
+
+    /// Moves the read position back by `frames` without dropping buffered audio.
+    ///
+    /// Used to loop: the next windows are aligned with the audio already playing, so the jump
+    /// is smoothed by the usual overlap-add instead of clicking.
+    pub fn jump_back(&mut self, frames: usize) {
+        self.analysis = (self.analysis - frames as f64).max(0.0);
+        self.natural = self.natural.map(|natural| natural - frames as isize);
+    }
+
+    /// Returns how far into the song (in frames) the stretcher has read.
+    pub fn position(&self) -> usize {
+        self.analysis.max(0.0) as usize
+    }
+
+    /// Produces the next output frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The song's interleaved samples.
+    /// * `tempo` - Tempo factor of the WSOLA stage (see [`PlaybackRate::factors`]).
+    /// * `pitch` - Pitch factor of the resampler.
+    /// * `frame` - Receives one sample per channel.
+    pub fn next_frame(&mut self, samples: &[f32], tempo: f32, pitch: f32, frame: &mut [f32]) {
+        if self.output_pos >= self.output_len {
+            self.refill(samples, tempo as f64, pitch as f64);
+        }
+        for (ch, sample) in frame.iter_mut().enumerate().take(self.channels) {
+            *sample = if self.output_pos < self.output_len {
+                self.output[ch][self.output_pos]
+            } else {
+                0.0
+            };
+        }
+        self.output_pos += 1;
+    }
+
+    /// Runs both stages until the resampler has produced a new block of output.
+    fn refill(&mut self, samples: &[f32], tempo: f64, pitch: f64) {
+        let ratio = 1.0 / pitch;
+        if (ratio - self.ratio).abs() > 1e-9
+            && self.resampler.set_resample_ratio(ratio, true).is_ok()
+        {
+            self.ratio = ratio;
+        }
+
+        while self.pending_len < self.resampler.input_frames_next() {
+            self.overlap_add_step(samples, tempo);
+        }
+
+        self.output_pos = 0;
+        match self
+            .resampler
+            .process_into_buffer(&self.pending, &mut self.output, None)
+        {
+            Ok((consumed, produced)) => {
+                for channel in &mut self.pending {
+                    channel.copy_within(consumed..self.pending_len, 0);
+                }
+                self.pending_len -= consumed;
+                self.output_len = produced;
+            }
+            Err(_) => {
+                // Play silence for one block rather than stall the callback.
+                self.pending_len = 0;
+                self.output_len = self.hop;
+                for channel in &mut self.output {
+                    channel[..self.hop].fill(0.0);
+                }
+            }
+        }
+    }
+
+    /// Places one window and appends a hop of finished audio to the resampler's input.
+    fn overlap_add_step(&mut self, samples: &[f32], tempo: f64) {
+        let channels = self.channels;
+        let nominal = self.analysis.round() as isize;
+        // At normal tempo consecutive windows already line up exactly.
+        let start = match self.natural {
+            Some(natural) if (tempo - 1.0).abs() > 1e-6 => {
+                self.best_alignment(samples, natural, nominal)
+            }
+            _ => nominal,
+        };
+
+        for (i, weight) in self.window.iter().enumerate() {
+            for ch in 0..channels {
+                self.overlap[i * channels + ch] +=
+                    weight * sample_at(samples, channels, start + i as isize, ch);
+            }
+        }
+
+        for i in 0..self.hop {
+            for (ch, channel) in self.pending.iter_mut().enumerate() {
+                channel[self.pending_len + i] = self.overlap[i * channels + ch];
+            }
+        }
+        self.pending_len += self.hop;
+
+        let hop_samples = self.hop * channels;
+        self.overlap.copy_within(hop_samples.., 0);
+        let length = self.overlap.len();
+        self.overlap[length - hop_samples..].fill(0.0);
+
+        self.natural = Some(start + self.hop as isize);
+        self.analysis += self.hop as f64 * tempo;
+    }
+
+    /// Finds the window start near `nominal` whose first half best matches the audio that
+    /// naturally follows the previous window (maximum cross-correlation of the channel sums).
+    fn best_alignment(&self, samples: &[f32], natural: isize, nominal: isize) -> isize {
+        let channels = self.channels;
+        let mono = |frame: isize| -> f32 {
+            (0..channels)
+                .map(|ch| sample_at(samples, channels, frame, ch))
+                .sum()
+        };
+        let tolerance = self.tolerance as isize;
+
+        let mut best = nominal;
+        let mut best_score = f32::NEG_INFINITY;
+        for candidate in (nominal - tolerance)..=(nominal + tolerance) {
+            let score: f32 = (0..self.hop)
+                .step_by(SEARCH_STRIDE)
+                .map(|i| mono(natural + i as isize) * mono(candidate + i as isize))
+                .sum();
+            if score > best_score {
+                best_score = score;
+                best = candidate;
+            }
+        }
+        best
+    }
+}
+
+/// Returns one sample of interleaved audio, or silence outside the song.
+fn sample_at(samples: &[f32], channels: usize, frame: isize, channel: usize) -> f32 {
+    if frame < 0 {
+        return 0.0;
+    }
+    samples
+        .get(frame as usize * channels + channel)
+        .copied()
+        .unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    fn sine(frames: usize, frequency: f32) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * PI * frequency * i as f32 / SAMPLE_RATE as f32).sin() * 0.5)
+            .collect()
+    }
+
+    /// Runs a mono song through a stretcher until it has read all of it, returning the output
+    /// and the read position after each output frame.
+    fn stretch(samples: &[f32], rate: &PlaybackRate) -> (Vec<f32>, Vec<usize>) {
+        let mut stretcher = TimeStretcher::new(1, SAMPLE_RATE);
+        let (tempo, pitch) = rate.factors();
+        let mut frame = [0.0];
+        let (mut output, mut positions) = (Vec::new(), Vec::new());
+        while stretcher.position() < samples.len() {
+            stretcher.next_frame(samples, tempo, pitch, &mut frame);
+            output.push(frame[0]);
+            positions.push(stretcher.position());
+        }
+        (output, positions)
+    }
+
+    /// Returns how many song frames are read per output frame through the middle of a song.
+    ///
+    /// The stretcher reads ahead of what it outputs and moves in whole blocks, so this compares
+    /// the first output frames of two blocks away from the ends.
+    fn measured_speed(positions: &[usize], frames: usize) -> f32 {
+        let reached = |frame| {
+            positions
+                .iter()
+                .position(|&position| position >= frame)
+                .unwrap()
+        };
+        let (first, last) = (reached(frames / 4), reached(frames * 3 / 4));
+        (positions[last] - positions[first]) as f32 / (last - first) as f32
+    }
+
+    /// Estimates the frequency of a sine from its rising zero crossings, skipping the edges
+    /// where the stretcher fills and drains.
+    fn frequency(samples: &[f32]) -> f32 {
+        let skip = SAMPLE_RATE as usize / 10;
+        let middle = &samples[skip..samples.len() - skip];
+        let crossings: Vec<usize> = middle
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[0] < 0.0 && pair[1] >= 0.0)
+            .map(|(i, _)| i)
+            .collect();
+        let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+        (crossings.len() - 1) as f32 * SAMPLE_RATE as f32 / (last - first) as f32
+    }
+
+    fn rate(speed: f32, semitones: f32, mode: SpeedMode) -> PlaybackRate {
+        let rate = PlaybackRate::default();
+        rate.set_speed(speed);
+        rate.set_semitones(semitones);
+        rate.set_mode(mode);
+        rate
+    }
+
+    #[test]
+    fn stretching_changes_the_length_but_not_the_pitch() {
+        let input = sine(SAMPLE_RATE as usize, 440.0);
+        for speed in [0.5, 2.0] {
+            let (output, positions) = stretch(&input, &rate(speed, 0.0, SpeedMode::Stretch));
+            let measured = measured_speed(&positions, input.len());
+            assert!(
+                (measured - speed).abs() < speed * 0.01,
+                "{}x at {}x",
+                measured,
+                speed
+            );
+            assert!((frequency(&output) - 440.0).abs() < 5.0, "at {}x", speed);
+        }
+    }
+
+    #[test]
+    fn varispeed_moves_the_pitch_with_the_speed() {
+        let input = sine(SAMPLE_RATE as usize, 440.0);
+        let (output, _) = stretch(&input, &rate(2.0, 0.0, SpeedMode::Varispeed));
+        assert!((frequency(&output) - 880.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn an_octave_up_or_down_doubles_or_halves_the_frequency() {
+        let input = sine(SAMPLE_RATE as usize, 440.0);
+        for (semitones, expected) in [(12.0, 880.0), (-12.0, 220.0)] {
+            let (output, positions) = stretch(&input, &rate(1.0, semitones, SpeedMode::Stretch));
+            let measured = measured_speed(&positions, input.len());
+            assert!(
+                (measured - 1.0).abs() < 0.01,
+                "{}x at {} semitones",
+                measured,
+                semitones
+            );
+            let measured = frequency(&output);
+            assert!(
+                (measured - expected).abs() < expected * 0.01,
+                "{} Hz",
+                measured
+            );
+        }
+    }
+
+    #[test]
+    fn settings_are_clamped() {
+        let rate = PlaybackRate::default();
+        assert!(rate.is_neutral());
+        rate.set_speed(0.1);
+        assert_eq!(rate.speed(), MIN_SPEED);
+        rate.set_speed(5.0);
+        assert_eq!(rate.speed(), MAX_SPEED);
+        rate.set_speed(1.234);
+        assert_eq!(rate.speed(), 1.23);
+        rate.set_semitones(-20.0);
+        assert_eq!(rate.semitones(), -MAX_PITCH_SEMITONES);
+        rate.set_semitones(20.0);
+        assert_eq!(rate.semitones(), MAX_PITCH_SEMITONES);
+        assert!(!rate.is_neutral());
+    }
+
+    #[test]
+    fn the_factors_multiply_to_the_speed() {
+        for mode in [SpeedMode::Stretch, SpeedMode::Varispeed] {
+            for (speed, semitones) in [(1.0, 0.0), (0.5, 12.0), (2.0, -7.0), (1.5, 3.0)] {
+                let (tempo, pitch) = rate(speed, semitones, mode).factors();
+                assert!((tempo * pitch - speed).abs() < 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! ID3v2 tag module
+//!
+//! Reads the ID3v2 tags (versions 2.2 to 2.4) that carry artwork, lyrics, genre and tempo. WAV
+//! files keep the tag in an `id3 ` chunk, MP3 files at their start. Compressed and encrypted
+//! frames are skipped.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// A frame of an ID3v2 tag.
+pub struct Frame {
+    /// Frame ID: four characters, or three in version 2.2 tags (e.g. `PIC` for `APIC`).
+    pub id: String,
+    /// The frame's content, with unsynchronisation undone.
+    pub data: Vec<u8>,
+}
+
+/// The frames of an ID3v2 tag.
+pub struct Tag {
+    /// Major version: 2, 3 or 4.
+    pub version: u8,
+    frames: Vec<Frame>,
+}
+
+impl Tag {
+    /// Reads the ID3v2 tag of a WAV or MP3 file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the audio file.
+    ///
+    /// # Returns
+    ///
+    /// The tag, or `None` if the file has none or cannot be read.
+    pub fn read(path: &Path) -> Option<Tag> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        let mut file = File::open(path).ok()?;
+        let bytes = match extension.as_str() {
+            "wav" => riff_id3_chunk(&mut file),
+            "mp3" => leading_tag(&mut file),
+            _ => return None,
+        };
+        Tag::parse(&bytes.ok()??)
+    }
+
+    /// Returns the frames with any of the given IDs, in the order they appear in the tag.
+    pub fn frames_with<'a>(&'a self, ids: &'a [&str]) -> impl Iterator<Item = &'a Frame> + 'a {
+        self.frames
+            .iter()
+            .filter(move |frame| ids.contains(&frame.id.as_str()))
+    }
+
+    /// Returns the value of the first text frame (such as `TBPM`) with any of the given IDs.
+    ///
+    /// # Returns
+    ///
+    /// The frame's first value, or `None` if there is no such frame or it is empty.
+    pub fn text(&self, ids: &[&str]) -> Option<String> {
+        let frame = self.frames_with(ids).next()?;
+        let (&encoding, data) = frame.data.split_first()?;
+        // Version 2.4 separates several values with null characters.
+        let (text, _) = split_text(data, encoding);
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+
+    /// Parses a tag, starting with its `ID3` header.
+    fn parse(tag: &[u8]) -> Option<Tag> {
+        if tag.len() < 10 || &tag[0..3] != b"ID3" {
+            return None;
+        }
+        let version = tag[3];
+        let flags = tag[5];
+        let size = synchsafe(&tag[6..10]) as usize;
+        let mut body = tag[10..].get(..size).unwrap_or(&tag[10..]).to_vec();
+        // Before version 2.4 unsynchronisation applies to the whole tag.
+        if flags & 0x80 != 0 && version < 4 {
+            body = remove_unsynchronisation(&body);
+        }
+
+        let mut rest = &body[..];
+        if flags & 0x40 != 0 && version >= 3 {
+            let extended = rest.get(0..4)?;
+            let extended_size = if version == 3 {
+                u32::from_be_bytes(extended.try_into().unwrap()) as usize + 4
+            } else {
+                synchsafe(extended) as usize
+            };
+            rest = rest.get(extended_size..)?;
+        }
+
+        let (id_length, header_length) = if version == 2 { (3, 6) } else { (4, 10) };
+        let mut frames = Vec::new();
+        // Padding (zero bytes) ends the frames.
+        while rest.len() >= header_length && rest[0] != 0 {
+            let id = String::from_utf8_lossy(&rest[..id_length]).into_owned();
+            let size_bytes = &rest[id_length..id_length * 2];
+            let size = match version {
+                2 => u32::from_be_bytes([0, size_bytes[0], size_bytes[1], size_bytes[2]]),
+                3 => u32::from_be_bytes(size_bytes.try_into().unwrap()),
+                _ => synchsafe(size_bytes),
+            } as usize;
+            let Some(data) = rest.get(header_length..header_length + size) else {
+                break;
+            };
+            let format_flags = if version >= 3 { rest[9] } else { 0 };
+            if let Some(data) = frame_data(data, version, format_flags) {
+                frames.push(Frame { id, data });
+            }
+            rest = &rest[header_length + size..];
+        }
+        Some(Tag { version, frames })
+    }
+}
+
+/// Splits text ending in a null character (two bytes wide in UTF-16) off the start of `data`.
+///
+/// # Arguments
+///
+/// * `data` - Bytes starting with the text.
+/// * `encoding` - The frame's text encoding byte.
+///
+/// # Returns
+///
+/// The decoded text and the bytes after its terminator. Without a terminator, all of `data` is
+/// taken as the text.
+pub fn split_text(data: &[u8], encoding: u8) -> (String, &[u8]) {
+    let terminator = if is_utf16(encoding) {
+        (0..data.len().saturating_sub(1))
+            .step_by(2)
+            .find(|&i| data[i] == 0 && data[i + 1] == 0)
+            .map(|i| (i, i + 2))
+    } else {
+        data.iter().position(|&byte| byte == 0).map(|i| (i, i + 1))
+    };
+    let (end, next) = terminator.unwrap_or((data.len(), data.len()));
+    (decode_text(&data[..end], encoding), &data[next..])
+}
+
+/// Decodes text in one of the ID3 encodings: Latin-1 (0), UTF-16 with a byte order mark (1),
+/// UTF-16BE (2) or UTF-8 (3).
+pub fn decode_text(bytes: &[u8], encoding: u8) -> String {
+    let text = match encoding {
+        0 => bytes.iter().map(|&byte| byte as char).collect(),
+        1 | 2 => {
+            let (big_endian, bytes) = match bytes {
+                [0xfe, 0xff, rest @ ..] => (true, rest),
+                [0xff, 0xfe, rest @ ..] => (false, rest),
+                _ => (encoding == 2, bytes),
+            };
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| {
+                    let pair = [pair[0], pair[1]];
+                    if big_endian {
+                        u16::from_be_bytes(pair)
+                    } else {
+                        u16::from_le_bytes(pair)
+                    }
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+/// Returns whether a text encoding byte stands for UTF-16, whose characters are two bytes wide.
+fn is_utf16(encoding: u8) -> bool {
+    encoding == 1 || encoding == 2
+}
+
+/// Reads the body of a WAV file's `id3 ` chunk, which holds a complete ID3v2 tag.
+fn riff_id3_chunk(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+
+    let mut chunk_header = [0u8; 8];
+    while file.read_exact(&mut chunk_header).is_ok() {
+        let id = &chunk_header[0..4];
+        let size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+        if id.eq_ignore_ascii_case(b"id3 ") {
+            return read_body(file, size, Vec::new()).map(Some);
+        }
+        // RIFF chunks are padded to an even number of bytes.
+        file.seek(SeekFrom::Current((size + (size & 1)) as i64))?;
+    }
+    Ok(None)
+}
+
+/// Reads the ID3v2 tag at the start of an MP3 file, header included.
+fn leading_tag(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+    let mut header = [0u8; 10];
+    file.read_exact(&mut header)?;
+    if &header[0..3] != b"ID3" {
+        return Ok(None);
+    }
+    read_body(file, synchsafe(&header[6..10]) as u64, header.to_vec()).map(Some)
+}
+
+/// Reads the `size` bytes that follow, after what `buffer` already holds.
+///
+/// The size is read from the file, so the buffer only grows as far as the data actually goes;
+/// a corrupt size cannot make it allocate more than the file holds.
+///
+/// # Returns
+///
+/// The buffer, or an `UnexpectedEof` error if the file ends first.
+fn read_body(file: &mut File, size: u64, mut buffer: Vec<u8>) -> io::Result<Vec<u8>> {
+    let start = buffer.len();
+    file.take(size).read_to_end(&mut buffer)?;
+    if ((buffer.len() - start) as u64) < size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buffer)
+}
+
+/// Undoes a frame's format flags.
+///
+/// # Returns
+///
+/// The frame's data, or `None` if it is compressed or encrypted.
+fn frame_data(data: &[u8], version: u8, format_flags: u8) -> Option<Vec<u8>> {
+    match version {
+        3 if format_flags & 0xc0 != 0 => None,
+        // A group identifier byte precedes the data of grouped frames.
+        3 if format_flags & 0x20 != 0 => data.get(1..).map(<[u8]>::to_vec),
+        4 if format_flags & 0x0c != 0 => None,
+        4 => {
+            // A group identifier byte, then a data length indicator, may precede the data.
+            let data = if format_flags & 0x40 != 0 {
+                data.get(1..)?
+            } else {
+                data
+            };
+            let data = if format_flags & 0x01 != 0 {
+                data.get(4..)?
+            } else {
+                data
+            };
+            Some(if format_flags & 0x02 != 0 {
+                remove_unsynchronisation(data)
+            } else {
+                data.to_vec()
+            })
+        }
+        _ => Some(data.to_vec()),
+    }
+}
+
+/// Decodes a synchsafe integer: four bytes of which only the low seven bits count.
+fn synchsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .take(4)
+        .fold(0, |value, &byte| (value << 7) | (byte & 0x7f) as u32)
+}
+
+/// Drops the zero byte that unsynchronisation inserts after every `0xff`.
+fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    for (i, &byte) in data.iter().enumerate() {
+        if byte == 0 && i > 0 && data[i - 1] == 0xff {
+            continue;
+        }
+        decoded.push(byte);
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process;
+
+    fn synchsafe_bytes(value: u32) -> [u8; 4] {
+        [
+            (value >> 21) as u8 & 0x7f,
+            (value >> 14) as u8 & 0x7f,
+            (value >> 7) as u8 & 0x7f,
+            value as u8 & 0x7f,
+        ]
+    }
+
+    /// Builds a tag from its header flags and body.
+    fn tag(version: u8, flags: u8, body: &[u8]) -> Vec<u8> {
+        let mut tag = b"ID3".to_vec();
+        tag.extend([version, 0, flags]);
+        tag.extend(synchsafe_bytes(body.len() as u32));
+        tag.extend(body);
+        tag
+    }
+
+    /// Builds a frame with the given format flags (the second flag byte).
+    fn frame(version: u8, id: &str, format_flags: u8, data: &[u8]) -> Vec<u8> {
+        let mut frame = id.as_bytes().to_vec();
+        match version {
+            2 => frame.extend(&(data.len() as u32).to_be_bytes()[1..]),
+            3 => frame.extend((data.len() as u32).to_be_bytes()),
+            _ => frame.extend(synchsafe_bytes(data.len() as u32)),
+        }
+        if version >= 3 {
+            frame.extend([0, format_flags]);
+        }
+        frame.extend(data);
+        frame
+    }
+
+    fn text_data(text: &str) -> Vec<u8> {
+        let mut data = vec![0];
+        data.extend(text.as_bytes());
+        data
+    }
+
+    fn frame_ids(tag: &Tag) -> Vec<&str> {
+        tag.frames.iter().map(|frame| frame.id.as_str()).collect()
+    }
+
+    #[test]
+    fn version_2_2_frames_have_three_character_ids() {
+        let mut body = frame(2, "TT2", 0, &text_data("Title"));
+        body.extend(frame(2, "TBP", 0, &text_data("128")));
+        body.extend([0; 16]);
+
+        let tag = Tag::parse(&tag(2, 0, &body)).unwrap();
+        assert_eq!(tag.version, 2);
+        assert_eq!(frame_ids(&tag), ["TT2", "TBP"]);
+        assert_eq!(tag.text(&["TBPM", "TBP"]).as_deref(), Some("128"));
+    }
+
+    #[test]
+    fn version_2_3_tags_undo_unsynchronisation_and_skip_the_extended_header() {
+        let mut body = vec![0, 0, 0, 6, 0, 0, 0, 0, 0, 0];
+        body.extend(frame(3, "APIC", 0, &[0xff, 0xe0, 0x01]));
+        // Compressed frames are skipped; grouped frames lose their group byte.
+        body.extend(frame(3, "TCON", 0x80, &text_data("Zipped")));
+        body.extend(frame(
+            3,
+            "TBPM",
+            0x20,
+            &[&[7][..], &text_data("90")].concat(),
+        ));
+        let unsynchronised: Vec<u8> = body
+            .iter()
+            .flat_map(|&byte| {
+                if byte == 0xff {
+                    vec![0xff, 0]
+                } else {
+                    vec![byte]
+                }
+            })
+            .collect();
+
+        let tag = Tag::parse(&tag(3, 0xc0, &unsynchronised)).unwrap();
+        assert_eq!(frame_ids(&tag), ["APIC", "TBPM"]);
+        assert_eq!(tag.frames[0].data, [0xff, 0xe0, 0x01]);
+        assert_eq!(tag.text(&["TBPM"]).as_deref(), Some("90"));
+    }
+
+    #[test]
+    fn version_2_4_frames_undo_their_own_format_flags() {
+        // The extended header's synchsafe size counts itself.
+        let mut body = vec![0, 0, 0, 6, 1, 0];
+        body.extend(frame(4, "TCON", 0, b"\x03Rock\0Pop"));
+        let mut unsynchronised = synchsafe_bytes(3).to_vec();
+        unsynchronised.extend([0xff, 0x00, 0xe0, 0x02]);
+        body.extend(frame(4, "APIC", 0x03, &unsynchronised));
+        let mut grouped = vec![9];
+        grouped.extend(synchsafe_bytes(4));
+        grouped.extend(text_data("140"));
+        body.extend(frame(4, "TBPM", 0x41, &grouped));
+        body.extend(frame(4, "TIT2", 0x04, &text_data("Encrypted")));
+        body.extend([0; 8]);
+
+        let tag = Tag::parse(&tag(4, 0x40, &body)).unwrap();
+        assert_eq!(frame_ids(&tag), ["TCON", "APIC", "TBPM"]);
+        assert_eq!(tag.text(&["TCON"]).as_deref(), Some("Rock"));
+        assert_eq!(tag.frames[1].data, [0xff, 0xe0, 0x02]);
+        assert_eq!(tag.text(&["TBPM"]).as_deref(), Some("140"));
+    }
+
+    #[test]
+    fn truncated_tags_keep_the_complete_frames() {
+        let mut body = frame(3, "TBPM", 0, &text_data("100"));
+        body.extend(frame(3, "TIT2", 0, &text_data("Cut off")));
+        let mut bytes = tag(3, 0, &body);
+        bytes.truncate(bytes.len() - 3);
+
+        let tag = Tag::parse(&bytes).unwrap();
+        assert_eq!(frame_ids(&tag), ["TBPM"]);
+        assert!(Tag::parse(b"ID3\x03").is_none());
+        assert!(Tag::parse(b"RIFF\0\0\0\0\0\0\0\0").is_none());
+    }
+
+    #[test]
+    fn text_comes_in_every_encoding() {
+        assert_eq!(decode_text(b"Caf\xe9", 0), "Café");
+        assert_eq!(decode_text(b"\xff\xfeC\0a\0f\0\xe9\0", 1), "Café");
+        assert_eq!(decode_text(b"\0C\0a\0f\0\xe9", 2), "Café");
+        assert_eq!(decode_text("Café\0".as_bytes(), 3), "Café");
+
+        let (text, rest) = split_text(b"\xff\xfeA\0\0\0rest", 1);
+        assert_eq!((text.as_str(), rest), ("A", &b"rest"[..]));
+        let (text, rest) = split_text(b"no terminator", 0);
+        assert_eq!((text.as_str(), rest), ("no terminator", &b""[..]));
+    }
+
+    #[test]
+    fn tags_are_found_in_wav_and_mp3_files() {
+        let tag = tag(3, 0, &frame(3, "TBPM", 0, &text_data("75")));
+        let folder = std::env::temp_dir().join(format!("music_visualizer-id3-{}", process::id()));
+        fs::create_dir_all(&folder).unwrap();
+
+        let mp3 = folder.join("song.mp3");
+        fs::write(&mp3, [&tag[..], &[0xff, 0xfb, 0x90, 0x00]].concat()).unwrap();
+        // An odd-sized chunk before the tag is padded to an even length.
+        let mut wav = b"RIFF\0\0\0\0WAVE".to_vec();
+        wav.extend(b"junk\x03\0\0\0abc\0");
+        wav.extend(b"id3 ");
+        wav.extend((tag.len() as u32).to_le_bytes());
+        wav.extend(&tag);
+        let wav_path = folder.join("song.wav");
+        fs::write(&wav_path, wav).unwrap();
+
+        let bpm = |path: &Path| Tag::read(path).and_then(|tag| tag.text(&["TBPM"]));
+        let found = (bpm(&mp3), bpm(&wav_path));
+        fs::remove_dir_all(&folder).ok();
+        assert_eq!(found, (Some("75".to_string()), Some("75".to_string())));
+    }
+}
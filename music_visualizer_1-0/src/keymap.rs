@@ -0,0 +1,671 @@
+//! Key bindings module
+//!
+//! Maps keys and chords (a key with Ctrl, Shift or Alt held) to the [`Action`]s they trigger.
+//! Each action belongs to a [`Screen`], so the same key can mean different things on the song
+//! selection screen and while a song plays (the arrow keys move through the song list on one and
+//! seek or change the volume on the other).
+//!
+//! The defaults can be overridden per action in the `key_bindings` section of the config file,
+//! for example `"key_bindings": { "play_pause": ["Space", "K"], "debug_dump": [] }`. An empty list
+//! unbinds the action; actions that are not listed keep their default keys.
+
+use crate::store::MAX_RATING;
+use nannou::event::ModifiersState;
+use nannou::prelude::Key;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// The screen an action applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    /// The library and playlist list.
+    Selection,
+    /// A song is loaded.
+    Playback,
+    /// Either screen.
+    Any,
+}
+
+/// Something the user can trigger from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// Plays or pauses the song.
+    PlayPause,
+    /// Skips to the next track in the queue.
+    NextTrack,
+    /// Goes back to the previous track in the queue.
+    PreviousTrack,
+    /// Jumps forward within the song.
+    SeekForward,
+    /// Jumps backward within the song.
+    SeekBackward,
+    /// Raises the output volume.
+    VolumeUp,
+    /// Lowers the output volume.
+    VolumeDown,
+    /// Opens or closes the EQ panel.
+    ToggleEq,
+    /// Returns to the song selection screen (or cancels a load).
+    Back,
+    /// Sets the loop start at the playback position.
+    SetLoopStart,
+    /// Sets the loop end at the playback position.
+    SetLoopEnd,
+    /// Removes the loop.
+    ClearLoop,
+    /// Adds a bookmark at the playback position.
+    AddBookmark,
+    /// Removes the bookmark nearest to the playback position.
+    RemoveBookmark,
+    /// Jumps to a bookmark (numbered from 1).
+    JumpToBookmark(u8),
+    /// Rates the playing track with a number of stars (0 clears the rating).
+    Rate(u8),
+    /// Moves the keyboard selection in the song list up one row.
+    SelectPrevious,
+    /// Moves the keyboard selection in the song list down one row.
+    SelectNext,
+    /// Moves the keyboard selection up one page.
+    SelectPageUp,
+    /// Moves the keyboard selection down one page.
+    SelectPageDown,
+    /// Selects the first row of the song list.
+    SelectFirst,
+    /// Selects the last row of the song list.
+    SelectLast,
+    /// Plays the selected song or imports the selected playlist.
+    PlaySelected,
+    /// Moves the keyboard focus to the search box.
+    FocusSearch,
+    /// Collapses or expands the sidebar.
+    ToggleSidebar,
+    /// Switches to the next visualizer.
+    SwitchVisualizer,
+    /// Switches to the next colour theme.
+    NextTheme,
+    /// Prints debug information about the song and the audio device.
+    DebugDump,
+}
+
+/// Number of bookmarks reachable with [`Action::JumpToBookmark`].
+const BOOKMARK_KEYS: u8 = 9;
+
+impl Action {
+    /// Returns every action, in the order they are listed in the config file.
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::PlayPause,
+            Action::NextTrack,
+            Action::PreviousTrack,
+            Action::SeekForward,
+            Action::SeekBackward,
+            Action::VolumeUp,
+            Action::VolumeDown,
+            Action::ToggleEq,
+            Action::Back,
+            Action::SetLoopStart,
+            Action::SetLoopEnd,
+            Action::ClearLoop,
+            Action::AddBookmark,
+            Action::RemoveBookmark,
+        ];
+        actions.extend((1..=BOOKMARK_KEYS).map(Action::JumpToBookmark));
+        actions.extend((0..=MAX_RATING).map(Action::Rate));
+        actions.extend([
+            Action::SelectPrevious,
+            Action::SelectNext,
+            Action::SelectPageUp,
+            Action::SelectPageDown,
+            Action::SelectFirst,
+            Action::SelectLast,
+            Action::PlaySelected,
+            Action::FocusSearch,
+            Action::ToggleSidebar,
+            Action::SwitchVisualizer,
+            Action::NextTheme,
+            Action::DebugDump,
+        ]);
+        actions
+    }
+
+    /// Returns the name of the action in the config file.
+    pub fn name(self) -> String {
+        let name = match self {
+            Action::PlayPause => "play_pause",
+            Action::NextTrack => "next_track",
+            Action::PreviousTrack => "previous_track",
+            Action::SeekForward => "seek_forward",
+            Action::SeekBackward => "seek_backward",
+            Action::VolumeUp => "volume_up",
+            Action::VolumeDown => "volume_down",
+            Action::ToggleEq => "toggle_eq",
+            Action::Back => "back",
+            Action::SetLoopStart => "set_loop_start",
+            Action::SetLoopEnd => "set_loop_end",
+            Action::ClearLoop => "clear_loop",
+            Action::AddBookmark => "add_bookmark",
+            Action::RemoveBookmark => "remove_bookmark",
+            Action::JumpToBookmark(number) => return format!("jump_to_bookmark_{}", number),
+            Action::Rate(stars) => return format!("rate_{}", stars),
+            Action::SelectPrevious => "select_previous",
+            Action::SelectNext => "select_next",
+            Action::SelectPageUp => "select_page_up",
+            Action::SelectPageDown => "select_page_down",
+            Action::SelectFirst => "select_first",
+            Action::SelectLast => "select_last",
+            Action::PlaySelected => "play_selected",
+            Action::FocusSearch => "focus_search",
+            Action::ToggleSidebar => "toggle_sidebar",
+            Action::SwitchVisualizer => "switch_visualizer",
+            Action::NextTheme => "next_theme",
+            Action::DebugDump => "debug_dump",
+        };
+        name.to_string()
+    }
+
+    /// Returns the screen on which the action's keys are active.
+    pub fn screen(self) -> Screen {
+        match self {
+            Action::SelectPrevious
+            | Action::SelectNext
+            | Action::SelectPageUp
+            | Action::SelectPageDown
+            | Action::SelectFirst
+            | Action::SelectLast
+            | Action::PlaySelected
+            | Action::FocusSearch => Screen::Selection,
+            Action::ToggleSidebar
+            | Action::SwitchVisualizer
+            | Action::NextTheme
+            | Action::DebugDump
+            | Action::Back => Screen::Any,
+            _ => Screen::Playback,
+        }
+    }
+
+    /// Returns whether holding the key repeats the action.
+    ///
+    /// Toggles fire once per press; stepping actions such as seeking follow the keyboard's
+    /// repeat rate.
+    pub fn repeats(self) -> bool {
+        matches!(
+            self,
+            Action::SeekForward
+                | Action::SeekBackward
+                | Action::VolumeUp
+                | Action::VolumeDown
+                | Action::SelectPrevious
+                | Action::SelectNext
+                | Action::SelectPageUp
+                | Action::SelectPageDown
+        )
+    }
+
+    /// Returns whether the action still works while typing in the search box.
+    ///
+    /// These are the keys a text field has no use for, so the list can be browsed and played
+    /// without leaving the search box.
+    pub fn works_while_typing(self) -> bool {
+        matches!(
+            self,
+            Action::SelectPrevious
+                | Action::SelectNext
+                | Action::SelectPageUp
+                | Action::SelectPageDown
+                | Action::PlaySelected
+        )
+    }
+
+    /// Returns the keys bound to the action unless the config file says otherwise.
+    fn default_chords(self) -> Vec<KeyChord> {
+        let key = KeyChord::key;
+        match self {
+            Action::PlayPause => vec![key(Key::Space), key(Key::PlayPause)],
+            Action::NextTrack => vec![key(Key::N), key(Key::NextTrack)],
+            Action::PreviousTrack => vec![key(Key::P), key(Key::PrevTrack)],
+            Action::SeekForward => vec![key(Key::Right)],
+            Action::SeekBackward => vec![key(Key::Left)],
+            Action::VolumeUp => vec![key(Key::Up), key(Key::VolumeUp)],
+            Action::VolumeDown => vec![key(Key::Down), key(Key::VolumeDown)],
+            Action::ToggleEq => vec![key(Key::E)],
+            Action::Back => vec![key(Key::Escape)],
+            Action::SetLoopStart => vec![key(Key::LBracket)],
+            Action::SetLoopEnd => vec![key(Key::RBracket)],
+            Action::ClearLoop => vec![key(Key::Backslash)],
+            Action::AddBookmark => vec![key(Key::M)],
+            Action::RemoveBookmark => vec![KeyChord {
+                shift: true,
+                ..key(Key::M)
+            }],
+            Action::JumpToBookmark(number) => NUMBER_KEYS
+                .get(number as usize)
+                .map(|&digit| vec![key(digit)])
+                .unwrap_or_default(),
+            Action::Rate(stars) => NUMBER_KEYS
+                .get(stars as usize)
+                .map(|&digit| {
+                    vec![KeyChord {
+                        ctrl: true,
+                        ..key(digit)
+                    }]
+                })
+                .unwrap_or_default(),
+            Action::SelectPrevious => vec![key(Key::Up)],
+            Action::SelectNext => vec![key(Key::Down)],
+            Action::SelectPageUp => vec![key(Key::PageUp)],
+            Action::SelectPageDown => vec![key(Key::PageDown)],
+            Action::SelectFirst => vec![key(Key::Home)],
+            Action::SelectLast => vec![key(Key::End)],
+            Action::PlaySelected => vec![key(Key::Return), key(Key::NumpadEnter)],
+            Action::FocusSearch => vec![
+                KeyChord {
+                    ctrl: true,
+                    ..key(Key::F)
+                },
+                key(Key::Slash),
+            ],
+            Action::ToggleSidebar => vec![key(Key::Tab)],
+            Action::SwitchVisualizer => vec![key(Key::V)],
+            Action::NextTheme => vec![key(Key::T)],
+            Action::DebugDump => vec![key(Key::D)],
+        }
+    }
+}
+
+/// The digit keys, indexed by the digit they type.
+const NUMBER_KEYS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+/// Keys that can be named in the config file, besides letters and digits.
+const NAMED_KEYS: [Key; 48] = [
+    Key::Escape,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::Insert,
+    Key::Home,
+    Key::Delete,
+    Key::End,
+    Key::PageDown,
+    Key::PageUp,
+    Key::Left,
+    Key::Up,
+    Key::Right,
+    Key::Down,
+    Key::Back,
+    Key::Return,
+    Key::Space,
+    Key::Tab,
+    Key::NumpadAdd,
+    Key::NumpadSubtract,
+    Key::NumpadEnter,
+    Key::Apostrophe,
+    Key::Backslash,
+    Key::Comma,
+    Key::Equals,
+    Key::Grave,
+    Key::LBracket,
+    Key::Minus,
+    Key::Period,
+    Key::Plus,
+    Key::RBracket,
+    Key::Semicolon,
+    Key::Slash,
+    Key::PlayPause,
+    Key::NextTrack,
+    Key::PrevTrack,
+    Key::MediaStop,
+    Key::VolumeUp,
+    Key::VolumeDown,
+];
+
+/// The letter keys, in alphabetical order.
+const LETTER_KEYS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+
+/// A key together with the modifiers that must be held with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: Key,
+    /// Ctrl (or Cmd on macOS).
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    /// Returns the chord for a key pressed without modifiers.
+    pub fn key(key: Key) -> Self {
+        KeyChord {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    /// Returns the chord for a key pressed with the given modifiers held.
+    pub fn pressed(key: Key, mods: ModifiersState) -> Self {
+        KeyChord {
+            key,
+            ctrl: mods.ctrl() || mods.logo(),
+            shift: mods.shift(),
+            alt: mods.alt(),
+        }
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl"),
+            (self.shift, "Shift"),
+            (self.alt, "Alt"),
+        ] {
+            if held {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match NUMBER_KEYS.iter().position(|&digit| digit == self.key) {
+            Some(digit) => write!(f, "{}", digit),
+            None => write!(f, "{:?}", self.key),
+        }
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    /// Parses chords such as `"Space"`, `"Shift+M"` or `"Ctrl+F"`.
+    ///
+    /// Key names are those of [`Key`] (`"PageUp"`, `"LBracket"`, ...), plus `"Enter"`,
+    /// `"Backspace"` and `"Esc"`; digits may be written as `"1"` and the Plus key as `"+"`
+    /// (`"Ctrl++"`). Case is ignored.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let trimmed = text.trim();
+        // A trailing "+" after the separator is the key itself.
+        let (modifiers, key_name) = match trimmed.strip_suffix("++") {
+            Some(modifiers) => (Some(modifiers), "+"),
+            None if trimmed == "+" => (None, "+"),
+            None => match trimmed.rsplit_once('+') {
+                Some((modifiers, key_name)) => (Some(modifiers), key_name.trim()),
+                None => (None, trimmed),
+            },
+        };
+        if key_name.is_empty() {
+            return Err(format!("'{}' names no key", text));
+        }
+
+        let mut chord = KeyChord::key(parse_key(key_name)?);
+        for modifier in modifiers
+            .into_iter()
+            .flat_map(|m| m.split('+'))
+            .map(str::trim)
+        {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" | "cmd" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" | "option" => chord.alt = true,
+                _ => return Err(format!("unknown modifier '{}' in '{}'", modifier, text)),
+            }
+        }
+        Ok(chord)
+    }
+}
+
+/// Parses a key name as written in the config file.
+fn parse_key(name: &str) -> Result<Key, String> {
+    let lower = name.to_ascii_lowercase();
+    let alias = match lower.as_str() {
+        "enter" => Some(Key::Return),
+        "backspace" => Some(Key::Back),
+        "esc" => Some(Key::Escape),
+        "+" => Some(Key::Plus),
+        _ => None,
+    };
+    alias
+        .or_else(|| {
+            lower
+                .parse::<usize>()
+                .ok()
+                .and_then(|digit| NUMBER_KEYS.get(digit).copied())
+        })
+        .or_else(|| {
+            NUMBER_KEYS
+                .iter()
+                .chain(&LETTER_KEYS)
+                .chain(&NAMED_KEYS)
+                .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+                .copied()
+        })
+        .ok_or_else(|| format!("unknown key '{}'", name))
+}
+
+/// The active key bindings.
+pub struct KeyMap {
+    bindings: HashMap<KeyChord, Vec<Action>>,
+}
+
+impl KeyMap {
+    // ============================================================================
+    // Public Methods
+    // ============================================================================
+
+    /// Builds the key bindings from the defaults and the user's overrides.
+    ///
+    /// Overrides naming an unknown action or an unparsable key are reported and skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `overrides` - Keys per action name, from the config file.
+    pub fn new(overrides: &HashMap<String, Vec<String>>) -> Self {
+        let actions = Action::all();
+        for name in overrides.keys() {
+            if !actions.iter().any(|action| action.name() == *name) {
+                eprintln!("⚠️ Unknown action '{}' in key bindings.", name);
+            }
+        }
+
+        let mut bindings: HashMap<KeyChord, Vec<Action>> = HashMap::new();
+        for action in actions {
+            let chords = match overrides.get(&action.name()) {
+                Some(keys) => keys
+                    .iter()
+                    .filter_map(|text| match text.parse::<KeyChord>() {
+                        Ok(chord) => Some(chord),
+                        Err(e) => {
+                            eprintln!("⚠️ Invalid key for '{}': {}", action.name(), e);
+                            None
+                        }
+                    })
+                    .collect(),
+                None => action.default_chords(),
+            };
+            for chord in chords {
+                bindings.entry(chord).or_default().push(action);
+            }
+        }
+        KeyMap { bindings }
+    }
+
+    /// Returns the action a key press triggers on a screen, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The pressed key.
+    /// * `mods` - The modifier keys held at the time.
+    /// * `screen` - The screen shown ([`Screen::Selection`] or [`Screen::Playback`]).
+    pub fn action(&self, key: Key, mods: ModifiersState, screen: Screen) -> Option<Action> {
+        self.bindings
+            .get(&KeyChord::pressed(key, mods))?
+            .iter()
+            .copied()
+            .find(|action| action.screen() == screen || action.screen() == Screen::Any)
+    }
+
+    /// Prints every binding, grouped by action.
+    pub fn print(&self) {
+        println!("\n⌨️ Key bindings:");
+        for action in Action::all() {
+            let mut chords: Vec<String> = self
+                .bindings
+                .iter()
+                .filter(|(_, actions)| actions.contains(&action))
+                .map(|(chord, _)| chord.to_string())
+                .collect();
+            chords.sort();
+            if !chords.is_empty() {
+                println!("  {:<20} {}", action.name(), chords.join(", "));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> KeyChord {
+        text.parse().unwrap()
+    }
+
+    fn overrides(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(action, keys)| {
+                let keys = keys.iter().map(|key| key.to_string()).collect();
+                (action.to_string(), keys)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn key_names_ignore_case_and_accept_aliases() {
+        assert_eq!(chord("space"), KeyChord::key(Key::Space));
+        assert_eq!(chord("PAGEUP"), KeyChord::key(Key::PageUp));
+        assert_eq!(chord("Enter"), KeyChord::key(Key::Return));
+        assert_eq!(chord("backspace"), KeyChord::key(Key::Back));
+        assert_eq!(chord("Esc"), KeyChord::key(Key::Escape));
+        assert_eq!(chord("m"), KeyChord::key(Key::M));
+    }
+
+    #[test]
+    fn digits_name_the_number_keys() {
+        assert_eq!(chord("0"), KeyChord::key(Key::Key0));
+        assert_eq!(chord("7"), KeyChord::key(Key::Key7));
+        assert_eq!(chord("Key7"), KeyChord::key(Key::Key7));
+        assert!("10".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn modifiers_are_parsed_in_any_order() {
+        let expected = KeyChord {
+            ctrl: true,
+            shift: true,
+            ..KeyChord::key(Key::S)
+        };
+        assert_eq!(chord("Ctrl+Shift+S"), expected);
+        assert_eq!(chord(" shift + control + s "), expected);
+        assert_eq!(chord("Cmd+Shift+S"), expected);
+        assert!(chord("Option+Left").alt);
+    }
+
+    #[test]
+    fn a_trailing_plus_is_the_plus_key() {
+        assert_eq!(chord("+"), KeyChord::key(Key::Plus));
+        assert_eq!(chord("Plus"), KeyChord::key(Key::Plus));
+        let ctrl_plus = KeyChord {
+            ctrl: true,
+            ..KeyChord::key(Key::Plus)
+        };
+        assert_eq!(chord("Ctrl++"), ctrl_plus);
+        assert_eq!(chord("Ctrl+Plus"), ctrl_plus);
+        assert_eq!(chord(&ctrl_plus.to_string()), ctrl_plus);
+    }
+
+    #[test]
+    fn malformed_chords_are_rejected() {
+        for text in ["", "Ctrl+", "++", "Hyper+A", "Ctrl+Banana", "F13"] {
+            assert!(text.parse::<KeyChord>().is_err(), "'{}' parsed", text);
+        }
+    }
+
+    #[test]
+    fn chords_survive_a_display_round_trip() {
+        for action in Action::all() {
+            for default in action.default_chords() {
+                assert_eq!(chord(&default.to_string()), default);
+            }
+        }
+    }
+
+    #[test]
+    fn overrides_replace_only_the_listed_actions() {
+        let keymap = KeyMap::new(&overrides(&[
+            ("play_pause", &["K", "Ctrl++", "Nonsense"]),
+            ("debug_dump", &[]),
+            ("no_such_action", &["X"]),
+        ]));
+        let none = ModifiersState::empty();
+        let play = Screen::Playback;
+
+        assert_eq!(keymap.action(Key::K, none, play), Some(Action::PlayPause));
+        assert_eq!(
+            keymap.action(Key::Plus, ModifiersState::CTRL, play),
+            Some(Action::PlayPause)
+        );
+        assert_eq!(keymap.action(Key::Space, none, play), None);
+        assert_eq!(keymap.action(Key::D, none, play), None);
+        assert_eq!(keymap.action(Key::X, none, play), None);
+        assert_eq!(
+            keymap.action(Key::V, none, play),
+            Some(Action::SwitchVisualizer)
+        );
+    }
+}
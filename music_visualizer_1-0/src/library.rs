@@ -0,0 +1,320 @@
+//! Music library module
+//!
+//! Scans the `music_library` folder for playable audio files and exposes them as [`Track`]s.
+//! Basic metadata (title, artist, album, genre and duration) is read straight from the WAV headers
+//! so that playlists and the song list can refer to songs by more than their file name. Genre
+//! and tempo are also taken from an ID3 tag in the file. After the first scan, the library can be
+//! kept up to date one file at a time.
+
+use crate::id3::Tag;
+use crate::song::Song;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Folder (relative to the working directory) that holds the user's music.
+pub const LIBRARY_DIR: &str = "music_library";
+
+/// File extensions the player is able to decode.
+const AUDIO_EXTENSIONS: [&str; 1] = ["wav"];
+
+/// Encoder delay and padding, in frames, to trim for gapless playback.
+///
+/// Lossy encoders add priming samples at the start and padding at the end of a stream. Files
+/// decoded from such sources can carry the amounts in an iTunes-style `iTunSMPB` comment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GaplessInfo {
+    /// Frames of encoder delay at the start of the audio.
+    pub delay: usize,
+    /// Frames of padding at the end of the audio.
+    pub padding: usize,
+}
+
+impl GaplessInfo {
+    /// Parses an `iTunSMPB` value (`" 00000000 00000840 000001CA 0000000000A8A0F6 ..."`).
+    ///
+    /// # Returns
+    ///
+    /// The delay and padding, or `None` if the value is malformed or both are zero.
+    fn from_itunsmpb(value: &str) -> Option<Self> {
+        let mut fields = value.split_whitespace().skip(1);
+        let delay = usize::from_str_radix(fields.next()?, 16).ok()?;
+        let padding = usize::from_str_radix(fields.next()?, 16).ok()?;
+        (delay > 0 || padding > 0).then_some(GaplessInfo { delay, padding })
+    }
+}
+
+/// A single playable audio file in the library.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Track {
+    /// Canonical path to the audio file.
+    pub path: PathBuf,
+    /// The file name of the track (e.g. "example-song.wav").
+    pub file_name: String,
+    /// Display title, taken from the file's tags or derived from the file name.
+    pub title: String,
+    /// Performing artist, if tagged.
+    pub artist: Option<String>,
+    /// Album name, if tagged.
+    pub album: Option<String>,
+    /// Genre, if tagged.
+    pub genre: Option<String>,
+    /// Tempo in beats per minute, if tagged.
+    pub bpm: Option<f32>,
+    /// Duration in seconds, if the header could be read.
+    pub duration: Option<f64>,
+    /// Native sample rate in Hz, if the header could be read.
+    pub sample_rate: Option<u32>,
+    /// Number of channels, if the header could be read.
+    pub channels: Option<usize>,
+    /// Encoder delay/padding to trim, if the file provides it.
+    pub gapless: Option<GaplessInfo>,
+}
+
+impl Track {
+    /// Builds a `Track` from an audio file on disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the audio file.
+    ///
+    /// # Returns
+    ///
+    /// An [`io::Result`] containing the track, or an error if the path cannot be resolved.
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let path = fs::canonicalize(path)?;
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let tags = read_info_tags(&path).unwrap_or_default();
+        let id3 = Tag::read(&path);
+        let id3_text = |ids: &[&str]| id3.as_ref().and_then(|tag| tag.text(ids));
+        let header = hound::WavReader::open(&path)
+            .ok()
+            .map(|reader| (reader.spec(), reader.duration()));
+
+        Ok(Track {
+            title: tags
+                .title
+                .unwrap_or_else(|| Song::get_title_from_file(&file_name)),
+            artist: tags.artist,
+            album: tags.album,
+            genre: tags.genre.or_else(|| id3_text(&["TCON", "TCO"])),
+            bpm: id3_text(&["TBPM", "TBP"]).and_then(|bpm| bpm.parse::<f32>().ok()),
+            gapless: tags.comment.as_deref().and_then(parse_gapless_comment),
+            duration: header.map(|(spec, frames)| frames as f64 / spec.sample_rate as f64),
+            sample_rate: header.map(|(spec, _)| spec.sample_rate),
+            channels: header.map(|(spec, _)| spec.channels as usize),
+            file_name,
+            path,
+        })
+    }
+}
+
+/// The collection of tracks found in the music library folder.
+#[derive(Debug, Default)]
+pub struct Library {
+    tracks: Vec<Track>,
+}
+
+impl Library {
+    /// Scans a directory for audio files.
+    ///
+    /// Files with unsupported extensions are skipped. Tracks are sorted by file name so the
+    /// order is stable between scans.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir_path` - The directory to scan.
+    ///
+    /// # Returns
+    ///
+    /// An [`io::Result`] containing the library, or an error if the directory cannot be read.
+    pub fn scan(dir_path: &str) -> io::Result<Self> {
+        let mut tracks = Vec::new();
+        for entry in fs::read_dir(dir_path)? {
+            let path = entry?.path();
+            if path.is_file() && is_audio_file(&path) {
+                match Track::from_path(&path) {
+                    Ok(track) => tracks.push(track),
+                    Err(e) => eprintln!("Skipping '{}': {}", path.display(), e),
+                }
+            }
+        }
+        tracks.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        Ok(Library { tracks })
+    }
+
+    /// Returns all tracks in the library.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Adds a track, or replaces the track with the same path, keeping the tracks sorted by file
+    /// name.
+    ///
+    /// # Returns
+    ///
+    /// `true` if a track was replaced.
+    pub fn insert(&mut self, track: Track) -> bool {
+        let replaced = self.remove(&track.path).is_some();
+        let index = self
+            .tracks
+            .partition_point(|t| t.file_name <= track.file_name);
+        self.tracks.insert(index, track);
+        replaced
+    }
+
+    /// Removes the track with a path, which need not exist on disk any more.
+    ///
+    /// # Returns
+    ///
+    /// The removed track, or `None` if no track has that path.
+    pub fn remove(&mut self, path: &Path) -> Option<Track> {
+        let index = self.tracks.iter().position(|t| t.path == path)?;
+        Some(self.tracks.remove(index))
+    }
+
+    /// Finds a track by its (canonical) path.
+    pub fn find_by_path(&self, path: &Path) -> Option<&Track> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.tracks.iter().find(|t| t.path == path)
+    }
+
+    /// Finds a track by file name, ignoring case.
+    pub fn find_by_file_name(&self, file_name: &str) -> Option<&Track> {
+        self.tracks
+            .iter()
+            .find(|t| t.file_name.eq_ignore_ascii_case(file_name))
+    }
+
+    /// Finds a track by title (and artist, when one is given), ignoring case.
+    pub fn find_by_title(&self, title: &str, artist: Option<&str>) -> Option<&Track> {
+        self.tracks.iter().find(|t| {
+            t.title.eq_ignore_ascii_case(title)
+                && match (artist, &t.artist) {
+                    (Some(wanted), Some(actual)) => wanted.eq_ignore_ascii_case(actual),
+                    _ => true,
+                }
+        })
+    }
+}
+
+/// Returns whether the path has an extension the player can decode.
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| AUDIO_EXTENSIONS.iter().any(|a| a.eq_ignore_ascii_case(ext)))
+        .unwrap_or(false)
+}
+
+/// Lists the audio files in a folder and its subfolders, in order of their paths.
+///
+/// # Arguments
+///
+/// * `dir_path` - The folder.
+///
+/// # Returns
+///
+/// The paths of the audio files. Subfolders that cannot be read are skipped.
+pub fn audio_files_in(dir_path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir_path) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .flat_map(|path| {
+            if path.is_dir() {
+                audio_files_in(&path)
+            } else if is_audio_file(&path) {
+                vec![path]
+            } else {
+                Vec::new()
+            }
+        })
+        .collect()
+}
+
+/// Metadata read from a WAV file's `LIST`/`INFO` chunk.
+#[derive(Debug, Default)]
+struct InfoTags {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    genre: Option<String>,
+    comment: Option<String>,
+}
+
+/// Extracts gapless info from a comment of the form `iTunSMPB=<value>` or `iTunSMPB: <value>`.
+fn parse_gapless_comment(comment: &str) -> Option<GaplessInfo> {
+    let value = comment.trim().strip_prefix("iTunSMPB")?;
+    GaplessInfo::from_itunsmpb(value.trim_start_matches([':', '=']))
+}
+
+/// Reads the RIFF `LIST`/`INFO` tags (`INAM`, `IART`, `IPRD`, `IGNR`, `ICMT`) from a WAV file.
+///
+/// The audio data itself is skipped over, so this is cheap even for large files.
+fn read_info_tags(path: &Path) -> io::Result<InfoTags> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok(InfoTags::default());
+    }
+
+    let mut tags = InfoTags::default();
+    let mut chunk_header = [0u8; 8];
+    while file.read_exact(&mut chunk_header).is_ok() {
+        let id = &chunk_header[0..4];
+        let size = u32::from_le_bytes(chunk_header[4..8].try_into().unwrap()) as u64;
+        // RIFF chunks are padded to an even number of bytes.
+        let padded = size + (size & 1);
+
+        if id == b"LIST" {
+            // The buffer grows only as far as the data goes, so a corrupt size cannot make it
+            // allocate more than the file holds.
+            let mut body = Vec::new();
+            file.by_ref().take(size).read_to_end(&mut body)?;
+            if (body.len() as u64) < size {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if body.starts_with(b"INFO") {
+                parse_info_list(&body[4..], &mut tags);
+            }
+            file.seek(SeekFrom::Current((padded - size) as i64))?;
+        } else {
+            file.seek(SeekFrom::Current(padded as i64))?;
+        }
+    }
+    Ok(tags)
+}
+
+/// Parses the sub-chunks of an `INFO` list into `tags`.
+fn parse_info_list(mut body: &[u8], tags: &mut InfoTags) {
+    while body.len() >= 8 {
+        let id = &body[0..4];
+        let size = u32::from_le_bytes(body[4..8].try_into().unwrap()) as usize;
+        let end = (8 + size).min(body.len());
+        let value = String::from_utf8_lossy(&body[8..end])
+            .trim_end_matches('\0')
+            .trim()
+            .to_string();
+
+        if !value.is_empty() {
+            match id {
+                b"INAM" => tags.title = Some(value),
+                b"IART" => tags.artist = Some(value),
+                b"IPRD" => tags.album = Some(value),
+                b"IGNR" => tags.genre = Some(value),
+                b"ICMT" => tags.comment = Some(value),
+                _ => {}
+            }
+        }
+        body = &body[(end + (size & 1)).min(body.len())..];
+    }
+}
@@ -0,0 +1,148 @@
+//! Background loading module
+//!
+//! Decoding and resampling a song can take seconds, so it runs on a worker thread instead of in
+//! the UI update. A [`LoadJob`] owns one such load: the worker sends its progress back over a
+//! channel, the menu polls the job every frame, and dropping the job cancels the load.
+
+use crate::cache::AudioCache;
+use crate::library::Track;
+use crate::resample::ResampleQuality;
+use crate::song::{Song, SongError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The step a load is working on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStage {
+    /// Fingerprinting the file and looking for a cached copy.
+    Checking,
+    /// Reading and decoding the file.
+    Decoding,
+    /// Converting the audio to the output rate.
+    Resampling,
+    /// Writing the prepared audio to the cache.
+    Caching,
+}
+
+impl LoadStage {
+    /// Returns the text shown while the stage runs.
+    pub fn label(self) -> &'static str {
+        match self {
+            LoadStage::Checking => "Checking cache",
+            LoadStage::Decoding => "Decoding",
+            LoadStage::Resampling => "Resampling",
+            LoadStage::Caching => "Saving to cache",
+        }
+    }
+}
+
+/// Messages sent from the worker to its [`LoadJob`].
+enum LoadEvent {
+    /// The worker reached `fraction` (0.0 to 1.0) of a stage.
+    Progress(LoadStage, f32),
+    /// The song is ready.
+    Finished(Song),
+    /// The song could not be loaded.
+    Failed(SongError),
+}
+
+/// A song being prepared on a worker thread.
+///
+/// Dropping the job cancels the load; the worker stops at its next progress report.
+pub struct LoadJob {
+    track: Track,
+    events: Receiver<LoadEvent>,
+    cancelled: Arc<AtomicBool>,
+    stage: LoadStage,
+    fraction: f32,
+}
+
+impl LoadJob {
+    /// Starts preparing a track on a new worker thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track to load.
+    /// * `target` - The sample rate and channel count of the output stream the song will be
+    ///   played on.
+    /// * `quality` - The resampling algorithm to use if the file's rate differs.
+    /// * `cache` - The cache of prepared audio.
+    pub fn spawn(
+        track: Track,
+        target: (u32, usize),
+        quality: ResampleQuality,
+        cache: Arc<Mutex<AudioCache>>,
+    ) -> Self {
+        let (sender, events) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let worker_track = track.clone();
+        let worker_cancelled = Arc::clone(&cancelled);
+        let spawned = thread::Builder::new()
+            .name("song-loader".to_string())
+            .spawn(move || {
+                let report = |stage: LoadStage, fraction: f32| {
+                    // A closed channel means the job was dropped, so stop either way.
+                    sender.send(LoadEvent::Progress(stage, fraction)).is_ok()
+                        && !worker_cancelled.load(Ordering::Relaxed)
+                };
+                let event = match Song::from_track(&worker_track, target, quality, &cache, &report)
+                {
+                    Ok(song) => LoadEvent::Finished(song),
+                    Err(SongError::Cancelled) => return,
+                    Err(e) => LoadEvent::Failed(e),
+                };
+                sender.send(event).ok();
+            });
+        if let Err(e) = spawned {
+            eprintln!("Failed to start loading '{}': {}", track.file_name, e);
+        }
+
+        LoadJob {
+            track,
+            events,
+            cancelled,
+            stage: LoadStage::Checking,
+            fraction: 0.0,
+        }
+    }
+
+    /// Returns the track being loaded.
+    pub fn track(&self) -> &Track {
+        &self.track
+    }
+
+    /// Returns the current stage and how far through it (0.0 to 1.0) the worker is.
+    pub fn progress(&self) -> (LoadStage, f32) {
+        (self.stage, self.fraction)
+    }
+
+    /// Applies the worker's progress reports and returns the song once it is ready.
+    ///
+    /// # Returns
+    ///
+    /// The loaded song, the error that stopped it from loading, or [`SongError::Cancelled`] if
+    /// the worker stopped without either; `None` while the load is still running.
+    pub fn poll(&mut self) -> Option<Result<Song, SongError>> {
+        loop {
+            match self.events.try_recv() {
+                Ok(LoadEvent::Progress(stage, fraction)) => {
+                    self.stage = stage;
+                    self.fraction = fraction;
+                }
+                Ok(LoadEvent::Finished(song)) => return Some(Ok(song)),
+                Ok(LoadEvent::Failed(e)) => return Some(Err(e)),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return Some(Err(SongError::Cancelled)),
+            }
+        }
+    }
+}
+
+impl Drop for LoadJob {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
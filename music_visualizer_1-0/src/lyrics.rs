@@ -0,0 +1,355 @@
+//! Lyrics module
+//!
+//! Loads a track's lyrics from an `.lrc` file with the same name next to the audio file, or
+//! failing that from the file's ID3 tag: synchronised lyrics (`SYLT`) or plain lyrics (`USLT`).
+//!
+//! An LRC line can carry several `[mm:ss.xx]` time tags, so a repeated chorus is written once,
+//! and an `[offset:±ms]` header shifts every line (a positive offset shows lines earlier). Word
+//! timings of enhanced LRC (`<mm:ss.xx>`) are dropped.
+
+use crate::id3::{self, Tag};
+use std::fs;
+use std::path::Path;
+
+/// A line of lyrics and when it starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    /// Start of the line, in seconds from the start of the song (0 for unsynchronised lyrics).
+    pub time: f64,
+    pub text: String,
+}
+
+/// The lyrics of a song.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lyrics {
+    /// The lines, in the order they are sung.
+    pub lines: Vec<LyricLine>,
+    /// Whether the lines have start times; plain lyrics only have an order.
+    pub synced: bool,
+}
+
+impl Lyrics {
+    /// Finds the lyrics of an audio file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the audio file.
+    ///
+    /// # Returns
+    ///
+    /// The lyrics, or `None` if there is no `.lrc` file and the file has no lyrics in its tag.
+    pub fn find(path: &Path) -> Option<Self> {
+        let from_file = ["lrc", "LRC"]
+            .iter()
+            .find_map(|extension| fs::read_to_string(path.with_extension(extension)).ok())
+            .and_then(|contents| Lyrics::parse_lrc(&contents));
+        from_file.or_else(|| Lyrics::embedded(path))
+    }
+
+    /// Parses the contents of an LRC file.
+    ///
+    /// Lines without time tags are ignored unless no line has one, in which case the text is
+    /// taken as plain lyrics.
+    ///
+    /// # Returns
+    ///
+    /// The lyrics, or `None` if the file holds no lyrics.
+    pub fn parse_lrc(contents: &str) -> Option<Self> {
+        let mut offset = 0.0;
+        let mut timed = Vec::new();
+        let mut plain = Vec::new();
+        for line in contents.lines() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            let mut has_tags = false;
+            while let Some((tag, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+                has_tags = true;
+                if let Some(time) = parse_timestamp(tag) {
+                    times.push(time);
+                } else if let Some(value) = tag.strip_prefix("offset:") {
+                    offset = value.trim().parse::<f64>().unwrap_or(0.0) / 1000.0;
+                }
+                rest = after;
+            }
+
+            let text = strip_word_times(rest);
+            if !has_tags && !text.is_empty() {
+                plain.push(text.clone());
+            }
+            timed.extend(times.into_iter().map(|time| LyricLine {
+                time,
+                text: text.clone(),
+            }));
+        }
+
+        if timed.is_empty() {
+            return Lyrics::unsynced(plain);
+        }
+        for line in &mut timed {
+            line.time = (line.time - offset).max(0.0);
+        }
+        timed.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Some(Lyrics {
+            lines: timed,
+            synced: true,
+        })
+    }
+
+    /// Returns the index of the line being sung at a playback position.
+    ///
+    /// # Arguments
+    ///
+    /// * `seconds` - Position in the song, in seconds.
+    ///
+    /// # Returns
+    ///
+    /// `None` before the first line starts, and always for unsynchronised lyrics.
+    pub fn line_at(&self, seconds: f64) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+        self.lines
+            .partition_point(|line| line.time <= seconds)
+            .checked_sub(1)
+    }
+
+    /// Reads the lyrics from the audio file's ID3 tag, preferring synchronised lyrics.
+    fn embedded(path: &Path) -> Option<Self> {
+        let tag = Tag::read(path)?;
+        tag.frames_with(&["SYLT", "SLT"])
+            .find_map(|frame| parse_sylt(&frame.data))
+            .or_else(|| {
+                tag.frames_with(&["USLT", "ULT"])
+                    .find_map(|frame| parse_uslt(&frame.data))
+            })
+    }
+
+    /// Builds plain lyrics from lines of text.
+    ///
+    /// # Returns
+    ///
+    /// The lyrics, or `None` if there are no lines.
+    fn unsynced(lines: Vec<String>) -> Option<Self> {
+        (!lines.is_empty()).then(|| Lyrics {
+            lines: lines
+                .into_iter()
+                .map(|text| LyricLine { time: 0.0, text })
+                .collect(),
+            synced: false,
+        })
+    }
+}
+
+/// Parses an LRC time tag: `mm:ss`, `mm:ss.xx` or `mm:ss:xx`.
+///
+/// # Returns
+///
+/// The time in seconds, or `None` if the tag is not a time (such as an `[ar:...]` header).
+fn parse_timestamp(tag: &str) -> Option<f64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.trim().parse::<u32>().ok()?;
+    // Some files separate the hundredths with a colon.
+    let seconds = seconds.trim().replacen(':', ".", 1).parse::<f64>().ok()?;
+    (seconds >= 0.0).then_some(minutes as f64 * 60.0 + seconds)
+}
+
+/// Removes enhanced LRC word timings (`<mm:ss.xx>`) from a line's text.
+fn strip_word_times(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(length) = rest[start..].find('>') else {
+            break;
+        };
+        let inner = &rest[start + 1..start + length];
+        stripped.push_str(&rest[..start]);
+        if parse_timestamp(inner).is_none() {
+            stripped.push_str(&rest[start..=start + length]);
+        }
+        rest = &rest[start + length + 1..];
+    }
+    stripped.push_str(rest);
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parses a `SYLT` frame with millisecond timestamps.
+///
+/// Entries are often single syllables; an entry starting with a line break begins a new line.
+/// If no entry does, every entry is a line of its own.
+///
+/// # Returns
+///
+/// The lyrics, or `None` if the frame is malformed, empty or timed in MPEG frames.
+fn parse_sylt(frame: &[u8]) -> Option<Lyrics> {
+    let (&encoding, rest) = frame.split_first()?;
+    // Language (3 bytes), timestamp format and content type.
+    let header = rest.get(..5)?;
+    // MPEG frame timestamps would need the frame rate of the original MP3.
+    if header[3] != 2 {
+        return None;
+    }
+    let (_, mut rest) = id3::split_text(&rest[5..], encoding);
+
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        let (text, after) = id3::split_text(rest, encoding);
+        let Some(time) = after.get(..4) else {
+            break;
+        };
+        entries.push((u32::from_be_bytes(time.try_into().unwrap()), text));
+        rest = &after[4..];
+    }
+
+    let breaks_lines = entries
+        .iter()
+        .any(|(_, text)| text.starts_with(['\n', '\r']));
+    let mut lines: Vec<LyricLine> = Vec::new();
+    for (milliseconds, text) in entries {
+        match lines.last_mut() {
+            Some(line) if breaks_lines && !text.starts_with(['\n', '\r']) => {
+                line.text.push_str(&text)
+            }
+            _ => lines.push(LyricLine {
+                time: milliseconds as f64 / 1000.0,
+                text: text.trim_start().to_string(),
+            }),
+        }
+    }
+    for line in &mut lines {
+        line.text = line.text.trim().to_string();
+    }
+    lines.sort_by(|a, b| a.time.total_cmp(&b.time));
+    (!lines.is_empty()).then_some(Lyrics {
+        lines,
+        synced: true,
+    })
+}
+
+/// Parses a `USLT` frame into plain lyrics.
+fn parse_uslt(frame: &[u8]) -> Option<Lyrics> {
+    let (&encoding, rest) = frame.split_first()?;
+    // Language (3 bytes), then a description before the lyrics.
+    let (_, text) = id3::split_text(rest.get(3..)?, encoding);
+    let text = id3::decode_text(text, encoding);
+    Lyrics::unsynced(
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(time: f64, text: &str) -> LyricLine {
+        LyricLine {
+            time,
+            text: text.to_string(),
+        }
+    }
+
+    /// Builds a Latin-1 `SYLT` frame timed in milliseconds.
+    fn sylt_frame(entries: &[(&str, u32)]) -> Vec<u8> {
+        let mut frame = vec![0];
+        frame.extend(b"eng");
+        frame.extend([2, 1]);
+        frame.push(0);
+        for (text, milliseconds) in entries {
+            frame.extend(text.as_bytes());
+            frame.push(0);
+            frame.extend(milliseconds.to_be_bytes());
+        }
+        frame
+    }
+
+    #[test]
+    fn timestamps_take_dots_or_colons() {
+        assert_eq!(parse_timestamp("01:02.50"), Some(62.5));
+        assert_eq!(parse_timestamp("01:02:50"), Some(62.5));
+        assert_eq!(parse_timestamp("00:07"), Some(7.0));
+        assert_eq!(parse_timestamp("ar:New Order"), None);
+    }
+
+    #[test]
+    fn a_line_can_have_several_times() {
+        let lyrics = Lyrics::parse_lrc(
+            "[ar:Someone]\n[00:10.00]Verse\n[00:05.00][00:20.00]Chorus\n\n[00:15:00]Bridge",
+        )
+        .unwrap();
+        assert!(lyrics.synced);
+        assert_eq!(
+            lyrics.lines,
+            [
+                line(5.0, "Chorus"),
+                line(10.0, "Verse"),
+                line(15.0, "Bridge"),
+                line(20.0, "Chorus"),
+            ]
+        );
+        assert_eq!(lyrics.line_at(4.0), None);
+        assert_eq!(lyrics.line_at(12.0), Some(1));
+    }
+
+    #[test]
+    fn offsets_shift_every_line() {
+        let earlier = Lyrics::parse_lrc("[offset:+500]\n[00:01.00]One\n[00:00.20]Zero").unwrap();
+        assert_eq!(earlier.lines, [line(0.0, "Zero"), line(0.5, "One")]);
+
+        let later = Lyrics::parse_lrc("[offset:-1000]\n[00:01.00]One").unwrap();
+        assert_eq!(later.lines, [line(2.0, "One")]);
+    }
+
+    #[test]
+    fn word_times_are_dropped() {
+        assert_eq!(
+            strip_word_times("<00:01.00>Hello <00:01.50>world <not a time>"),
+            "Hello world <not a time>"
+        );
+        let lyrics = Lyrics::parse_lrc("[00:01.00]<00:01.00>Hello <00:01.50>world").unwrap();
+        assert_eq!(lyrics.lines, [line(1.0, "Hello world")]);
+    }
+
+    #[test]
+    fn untimed_files_are_plain_lyrics() {
+        let lyrics = Lyrics::parse_lrc("First\n\nSecond\n").unwrap();
+        assert!(!lyrics.synced);
+        assert_eq!(lyrics.lines, [line(0.0, "First"), line(0.0, "Second")]);
+        assert_eq!(lyrics.line_at(10.0), None);
+        assert_eq!(Lyrics::parse_lrc("[ar:Nobody]\n"), None);
+    }
+
+    #[test]
+    fn sylt_syllables_join_into_lines() {
+        let frame = sylt_frame(&[("\nHel", 1000), ("lo", 1200), ("\nWorld", 3000)]);
+        let lyrics = parse_sylt(&frame).unwrap();
+        assert!(lyrics.synced);
+        assert_eq!(lyrics.lines, [line(1.0, "Hello"), line(3.0, "World")]);
+
+        // Without line breaks, every entry is a line.
+        let frame = sylt_frame(&[("Two", 2000), ("One", 500)]);
+        let lyrics = parse_sylt(&frame).unwrap();
+        assert_eq!(lyrics.lines, [line(0.5, "One"), line(2.0, "Two")]);
+
+        // Timestamps in MPEG frames are not supported.
+        let mut frame = sylt_frame(&[("One", 500)]);
+        frame[4] = 1;
+        assert_eq!(parse_sylt(&frame), None);
+    }
+
+    #[test]
+    fn uslt_is_plain_lyrics() {
+        let mut frame = vec![0];
+        frame.extend(b"eng");
+        frame.extend(b"Description\0");
+        frame.extend(b"First line\r\n\r\n  Second line  \n");
+        let lyrics = parse_uslt(&frame).unwrap();
+        assert!(!lyrics.synced);
+        assert_eq!(
+            lyrics.lines,
+            [line(0.0, "First line"), line(0.0, "Second line")]
+        );
+    }
+}
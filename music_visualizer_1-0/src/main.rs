@@ -14,28 +14,77 @@
 // - rename song and edit song.rs to be stronger and a better model
 // - use idvf file types to load .wav files
 
+/// Module finding album art and deriving colour palettes from it
+mod artwork;
+/// Module writing files atomically through a temporary file
+mod atomic_file;
+/// Module caching prepared audio with integrity checks and LRU eviction
+mod cache;
+/// Module loading and saving user settings
+mod config;
 /// Module containing the controller logic for managing application state
 mod controller;
+/// Module applying the DSP effect chain (equalizers and volume) to the output
+mod dsp;
+/// Module reading ID3v2 tags embedded in audio files
+mod id3;
+/// Module mapping keys and chords to actions
+mod keymap;
+/// Module scanning the music library for playable tracks
+mod library;
+/// Module preparing songs on a worker thread with progress and cancellation
+mod loader;
+/// Module loading synced and plain lyrics from LRC files and ID3 tags
+mod lyrics;
 /// Module containing the menu UI and interaction logic
 mod menu;
-/// Module handling audio playback and song management
+/// Module mixing and crossfading sources in the output callback
+mod mixer;
+/// Module publishing playback to the desktop over MPRIS on D-Bus
+#[cfg(target_os = "linux")]
+mod mpris;
+/// Module owning the audio output stream and gapless playback engine
+mod player;
+/// Module importing and exporting M3U, PLS and XSPF playlists
+mod playlist;
+/// Module managing the play queue, shuffle and repeat modes
+mod queue;
+/// Module converting audio between sample rates
+mod resample;
+/// Module filtering the song list with fuzzy search
+mod search;
+/// Module saving and restoring the session between runs
+mod session;
+/// Module evaluating rule-based smart playlists against the library
+mod smart_playlist;
+/// Module handling song loading and management
 mod song;
+/// Module persisting per-track data such as bookmarks
+mod store;
 /// Module responsible for visual rendering
 mod view;
+/// Module watching the music library folder for changed files
+mod watcher;
 
 mod ui;
 
 use controller::Controller;
+use session::{Session, WindowGeometry};
 
 /// Main entry point for the application
 ///
 /// Initializes and runs the Nannou application with:
-/// - `model` for initialization
+/// - `model` for initialization (which also opens the window)
 /// - `update` for the main loop
 /// - `view` for rendering
-/// - A simple window for display
+/// - `resized` for keeping the layout in step with the window
+/// - `mouse_wheel` for scrolling
+/// - `key_pressed`, `key_released` and `received_character` for the keyboard
+/// - `dropped_file` for files and folders dragged onto the window
+/// - `moved` for remembering where the window is
+/// - `exit` for saving the session
 fn main() {
-    nannou::app(model).update(update).simple_window(view).run();
+    nannou::app(model).update(update).exit(exit).run();
 }
 
 /// The main application state container
@@ -57,14 +106,157 @@ struct Model {
 /// # Returns
 /// Fully initialized `Model` containing all application state
 fn model(app: &nannou::App) -> Model {
-    let window = app.main_window();
-    let win_rect = window.rect();
+    let session = Session::load();
+    let geometry = session.as_ref().and_then(|session| session.window);
+
+    let mut builder = app.new_window();
+    if let Some(geometry) = geometry {
+        builder = builder.size(geometry.width as u32, geometry.height as u32);
+    }
+    let window_id = builder
+        .view(view)
+        .resized(resized)
+        .mouse_wheel(mouse_wheel)
+        .key_pressed(key_pressed)
+        .key_released(key_released)
+        .received_character(received_character)
+        .dropped_file(dropped_file)
+        .moved(moved)
+        .build()
+        .expect("Failed to open the main window");
+    let window = app.window(window_id).expect("Main window closed");
+    if let Some(geometry) = geometry {
+        window.set_outer_position_pixels(geometry.x, geometry.y);
+    }
 
     Model {
-        controller: Controller::new(win_rect),
+        controller: Controller::new(window.rect(), window.scale_factor(), session.as_ref()),
     }
 }
 
+/// Exit handler
+///
+/// Saves the session so the next start picks up where this one left off.
+///
+/// # Arguments
+/// * `_app` - Reference to the Nannou application (unused)
+/// * `model` - The application model, handed back for cleanup
+fn exit(_app: &nannou::App, mut model: Model) {
+    model.controller.save_session();
+}
+
+/// Reads the main window's position and size for the session
+///
+/// # Returns
+/// `None` if the platform does not report window positions
+fn window_geometry(app: &nannou::App) -> Option<WindowGeometry> {
+    let window = app.main_window();
+    let (x, y) = window.outer_position_pixels().ok()?;
+    let (width, height) = window.inner_size_points();
+    Some(WindowGeometry {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+/// Window move handler
+///
+/// # Arguments
+/// * `app` - Reference to the Nannou application
+/// * `model` - Mutable reference to the application model
+/// * `_position` - The new position (read again from the window in pixels)
+fn moved(app: &nannou::App, model: &mut Model, _position: nannou::prelude::Vec2) {
+    if let Some(geometry) = window_geometry(app) {
+        model.controller.window_changed(geometry);
+    }
+}
+
+/// Window resize handler
+///
+/// Called whenever the window changes size (including when it moves to a display with a
+/// different scale factor) so the layout can be recomputed.
+///
+/// # Arguments
+/// * `app` - Reference to the Nannou application
+/// * `model` - Mutable reference to the application model
+/// * `_size` - The new window size in points (read again from the window with its scale factor)
+fn resized(app: &nannou::App, model: &mut Model, _size: nannou::prelude::Vec2) {
+    let window = app.main_window();
+    model
+        .controller
+        .resize(window.rect(), window.scale_factor());
+    if let Some(geometry) = window_geometry(app) {
+        model.controller.window_changed(geometry);
+    }
+}
+
+/// Mouse wheel and touchpad scroll handler
+///
+/// # Arguments
+/// * `app` - Reference to the Nannou application
+/// * `model` - Mutable reference to the application model
+/// * `delta` - How far the wheel turned or the touchpad moved
+/// * `_phase` - The touch phase of a touchpad gesture (unused)
+fn mouse_wheel(
+    app: &nannou::App,
+    model: &mut Model,
+    delta: nannou::event::MouseScrollDelta,
+    _phase: nannou::event::TouchPhase,
+) {
+    let scale_factor = app.main_window().scale_factor();
+    model
+        .controller
+        .scroll(app.mouse.position(), delta, scale_factor);
+}
+
+/// Key press handler
+///
+/// Called for every key press, including the keyboard's repeats while a key is held.
+///
+/// # Arguments
+/// * `app` - Reference to the Nannou application
+/// * `model` - Mutable reference to the application model
+/// * `key` - The pressed key
+fn key_pressed(app: &nannou::App, model: &mut Model, key: nannou::prelude::Key) {
+    model.controller.key_pressed(key, app.keys.mods);
+}
+
+/// Key release handler
+///
+/// # Arguments
+/// * `_app` - Reference to the Nannou application (unused)
+/// * `model` - Mutable reference to the application model
+/// * `key` - The released key
+fn key_released(_app: &nannou::App, model: &mut Model, key: nannou::prelude::Key) {
+    model.controller.key_released(key);
+}
+
+/// Character input handler
+///
+/// Called with each character typed, as produced by the keyboard layout.
+///
+/// # Arguments
+/// * `_app` - Reference to the Nannou application (unused)
+/// * `model` - Mutable reference to the application model
+/// * `c` - The typed character
+fn received_character(_app: &nannou::App, model: &mut Model, c: char) {
+    model.controller.received_character(c);
+}
+
+/// File drop handler
+///
+/// Called once for each file or folder dropped on the window.
+///
+/// # Arguments
+/// * `app` - Reference to the Nannou application
+/// * `model` - Mutable reference to the application model
+/// * `path` - The dropped file or folder
+fn dropped_file(app: &nannou::App, model: &mut Model, path: std::path::PathBuf) {
+    model.controller.file_dropped(path, app.mouse.position());
+}
+
 /// Main application update loop
 ///
 /// Called once per frame to update application state.
@@ -1,19 +1,162 @@
 //! User interface menu module
 //!
 //! Handles the interactive control panel for the application, including:
-//! - Play/pause button
+//! - The playing song's title and cover art
+//! - Play/pause, next/previous, shuffle, repeat and mix-mode buttons
+//! - Speed, pitch and varispeed controls
+//! - A progress bar for seeking and setting A–B loop points, and per-track bookmarks
+//! - An equalizer panel with band sliders and presets
+//! - Song and playlist selection from a scrollable list
 //! - Menu layout and rendering
-//! - Mouse interaction handling
+//! - Mouse interaction handling and keyboard actions
 //!
-//! The menu provides visual feedback and translates user input into playback commands.
+//! The menu is composed of [`crate::ui`] widgets, which report clicks and drags as [`Message`]s;
+//! keyboard actions are translated into the same messages, so both end up in one place.
 
-use crate::song::Song;
-use crate::ui::button::Button;
-use crate::ui::color::*;
+use crate::artwork::Artwork;
+use crate::cache::{AudioCache, CACHE_DIR};
+use crate::config::Config;
+use crate::dsp::eq::{EqPreset, GRAPHIC_BANDS, MAX_GAIN_DB};
+use crate::dsp::stretch::{PlaybackRate, SpeedMode};
+use crate::keymap::{Action, Screen};
+use crate::library::{LIBRARY_DIR, Library, Track};
+use crate::loader::LoadJob;
+use crate::lyrics::Lyrics;
+use crate::mixer::TransitionMode;
+#[cfg(target_os = "linux")]
+use crate::mpris::{MprisCommand, MprisServer, PlaybackState};
+use crate::player::Player;
+use crate::playlist::{self, PLAYLIST_DIR};
+use crate::queue::{PlayQueue, RepeatMode};
+use crate::search::{self, SearchIndex};
+use crate::session::{Resume, Session};
+use crate::smart_playlist::SmartPlaylists;
+use crate::song::{Song, SongError};
+use crate::store::{Bookmark, LibraryStore, MAX_RATING};
+use crate::ui::button::{Button, ButtonStyle};
+use crate::ui::label::Label;
+use crate::ui::list::{ListRow, ScrollList};
+use crate::ui::picture::Picture;
+use crate::ui::progress::{Marker, ProgressBar};
+use crate::ui::slider::{Orientation, Slider};
+use crate::ui::text_input::TextInput;
+use crate::ui::theme::{self, TextSize, Theme};
+use crate::ui::toggle::Toggle;
+use crate::ui::widget::{Pointer, Widget, update_all};
+use crate::watcher::LibraryWatcher;
+use nannou::event::ModifiersState;
+use nannou::image::DynamicImage;
 use nannou::prelude::*;
-use std::fs;
-use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Amount the speed buttons change the playback speed by.
+const SPEED_STEP: f32 = 0.05;
+
+/// How far the seek keys jump, in seconds.
+const SEEK_SECONDS: f32 = 5.0;
+
+/// Amount the volume keys change the output volume by.
+const VOLUME_STEP: f32 = 0.05;
+
+/// Height of a row in the song list, in points.
+const SONG_ROW_HEIGHT: f32 = 50.0;
+
+/// Distance between the tops of consecutive rows in the song list, in points.
+const SONG_ROW_PITCH: f32 = 60.0;
+
+/// Height of the menu's buttons, in points.
+const BUTTON_HEIGHT: f32 = 50.0;
+
+/// Number of plays listed by the debug dump.
+const RECENT_PLAYS: usize = 10;
+
+/// Width and height of the cover art beside the song title, in points.
+const COVER_SIZE: f32 = 48.0;
+
+/// What the menu's widgets and keyboard actions ask it to do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Message {
+    /// Toggle between playing and paused.
+    PlayPause,
+    /// Cancel the load in progress, or stop and return to the song list.
+    Back,
+    Next,
+    Previous,
+    ToggleShuffle,
+    CycleRepeat,
+    CycleTransition,
+    SpeedDown,
+    SpeedUp,
+    ResetSpeed,
+    PitchDown,
+    PitchUp,
+    ResetPitch,
+    ToggleVarispeed,
+    /// Set the output volume (0.0 to 1.0).
+    SetVolume(f32),
+    OpenEq,
+    CloseEq,
+    NextEqPreset,
+    SaveEqPreset,
+    /// Set a graphic EQ band's gain, in dB.
+    SetEqGain(usize, f32),
+    /// Seek to a fraction of the song.
+    Seek(f32),
+    /// Set the next A–B loop point at a fraction of the song.
+    MarkLoop(f32),
+    /// Play a library track, queueing the whole library.
+    PlayTrack(usize),
+    /// Play the tracks a smart playlist currently matches.
+    PlaySmartPlaylist(usize),
+    /// Import a playlist from the playlists folder and play it.
+    ImportPlaylist(usize),
+    ExportLibrary,
+}
+
+/// What a row of the song list stands for, by index into its own list.
+enum SongListEntry {
+    Track(usize),
+    SmartPlaylist(usize),
+    Playlist(usize),
+}
+
+/// The widgets of the playback screen.
+struct PlaybackControls {
+    /// The song's cover art, left of the title.
+    cover: Picture,
+    now_playing: Label,
+    /// The area of the title when there is no cover art.
+    title_row: Rect,
+    progress: ProgressBar<Message>,
+    /// Elapsed and total time, below the progress bar.
+    time: Label,
+    play: Button<Message>,
+    previous: Button<Message>,
+    next: Button<Message>,
+    shuffle: Toggle<Message>,
+    repeat: Button<Message>,
+    transition: Button<Message>,
+    /// The step down, reset and step up buttons for the speed.
+    speed: [Button<Message>; 3],
+    /// The step down, reset and step up buttons for the pitch.
+    pitch: [Button<Message>; 3],
+    varispeed: Toggle<Message>,
+    volume: Slider<Message>,
+    back: Button<Message>,
+    eq: Button<Message>,
+}
+
+/// The widgets of the EQ panel.
+struct EqPanel {
+    preset: Button<Message>,
+    save: Button<Message>,
+    done: Button<Message>,
+    /// One slider per graphic EQ band.
+    bands: Vec<Slider<Message>>,
+    /// The area the band sliders share.
+    slider_area: Rect,
+}
 
 /// Represents the interactive control menu.
 ///
@@ -23,11 +166,68 @@ use std::path::Path;
 pub struct Menu {
     is_playing: bool,
     menu_rect: Rect,
-    buttons: Vec<Button>,
+    /// Title of the selection, loading and EQ screens.
+    heading: Label,
+    playback: PlaybackControls,
+    eq_panel: EqPanel,
+    /// Exports the library as a playlist, below the song list.
+    export_button: Button<Message>,
     was_mouse_pressed: bool,
     /// The currently selected song, if any.
     pub song: Song,
-    song_buttons_created: bool,
+    /// The playback engine that owns the output stream.
+    pub player: Player,
+    /// Speed and pitch settings shared by every song loaded from the queue.
+    playback_rate: Arc<PlaybackRate>,
+    /// The next queued track, decoded ahead of time for gapless playback.
+    preloaded: Option<(Track, Song)>,
+    song_list_created: bool,
+    /// Scrollable list of the tracks and playlists matching the search.
+    song_list: ScrollList,
+    /// Search box above the song list.
+    search_box: TextInput,
+    /// Index over the tracks followed by the playlists, for filtering the song list.
+    search_index: SearchIndex,
+    /// The index entry shown in each row of the song list, best match first.
+    search_results: Vec<usize>,
+    /// Set when the search box is focused from the keyboard, so the character of the key that
+    /// focused it (such as `/`) is not typed into it.
+    skip_typed_chars: bool,
+    library: Library,
+    /// The smart playlists, evaluated again whenever the library, plays or ratings change.
+    smart_playlists: SmartPlaylists,
+    playlists: Vec<PathBuf>,
+    queue: PlayQueue,
+    config: Config,
+    /// Whether the EQ panel replaces the playback controls.
+    eq_open: bool,
+    eq_presets: Vec<EqPreset>,
+    eq_preset_index: usize,
+    /// Set when the sliders no longer match the selected preset.
+    eq_modified: bool,
+    /// Built-in and user themes, reloaded whenever the theme is switched
+    themes: Vec<Theme>,
+    theme_index: usize,
+    /// The artwork shown as the cover, to notice when the song's artwork changes
+    cover_art: Option<Arc<Artwork>>,
+    /// Persistent per-track data such as bookmarks.
+    store: LibraryStore,
+    /// Track and position (in seconds) to seek to once the song restored from the last session
+    /// has loaded.
+    resume_at: Option<(PathBuf, f64)>,
+    /// Reports files added to or changed in the music library folder, if it can be watched.
+    watcher: Option<LibraryWatcher>,
+    /// Publishes playback to the desktop and takes its media keys, if there is a session bus.
+    #[cfg(target_os = "linux")]
+    mpris: Option<MprisServer>,
+    /// Prepared audio, so songs are only resampled once per output rate.
+    cache: Arc<Mutex<AudioCache>>,
+    /// The track being prepared for playback, if any.
+    loading: Option<LoadJob>,
+    /// Title of the track whose last load failed, shown until another load starts.
+    load_error: Option<String>,
+    /// The next queued track being prepared in the background, if any.
+    preloading: Option<LoadJob>,
 }
 
 impl Menu {
@@ -37,8 +237,8 @@ impl Menu {
 
     /// Creates a new `Menu` instance.
     ///
-    /// The menu is initialized with default playback buttons (play and back) created via
-    /// [`default_buttons()`] and no song selected.
+    /// The menu is initialized with the widgets of every screen, laid out by
+    /// [`layout_widgets()`], and no song selected.
     ///
     /// # Arguments
     ///
@@ -48,57 +248,142 @@ impl Menu {
     ///
     /// A new `Menu` instance.
     pub fn new(menu_rect: Rect) -> Self {
-        Self {
+        let config = Config::load();
+        let mut player = Player::new();
+        player.set_transition(config.transition, config.crossfade_seconds);
+
+        let eq_presets = EqPreset::load_all();
+        let eq_preset_index = eq_presets
+            .iter()
+            .position(|preset| preset.name == config.eq_preset)
+            .unwrap_or(0);
+        if let Some(preset) = eq_presets.get(eq_preset_index) {
+            player.eq().apply(preset);
+        }
+
+        let themes = Theme::load_all();
+        let theme_index = themes
+            .iter()
+            .position(|theme| theme.name == config.theme)
+            .unwrap_or(0);
+        if let Some(theme) = themes.get(theme_index) {
+            theme::set_current(theme.clone());
+        }
+
+        let cache = AudioCache::open(CACHE_DIR, config.cache_limit_mb * 1024 * 1024);
+
+        let mut menu = Self {
             is_playing: false,
             song: Song::empty(),
+            player,
+            playback_rate: Arc::default(),
+            preloaded: None,
             menu_rect,
-            buttons: Self::default_buttons(menu_rect),
+            heading: Label::new("", TextSize::Heading, Rect::from_w_h(0.0, 0.0)),
+            playback: PlaybackControls::new(),
+            eq_panel: EqPanel::new(),
+            export_button: Button::new(
+                "EXPORT LIBRARY",
+                Message::ExportLibrary,
+                Rect::from_w_h(0.0, 0.0),
+            )
+            .with_style(ButtonStyle::Subdued),
             was_mouse_pressed: false,
-            song_buttons_created: false,
-        }
+            song_list_created: false,
+            song_list: ScrollList::new(
+                Self::song_list_area(menu_rect),
+                SONG_ROW_HEIGHT,
+                SONG_ROW_PITCH,
+            ),
+            search_box: TextInput::new(
+                "Search title, artist, album",
+                Self::search_box_area(menu_rect),
+            ),
+            search_index: SearchIndex::new(Vec::<String>::new()),
+            search_results: Vec::new(),
+            skip_typed_chars: false,
+            library: Library::default(),
+            smart_playlists: SmartPlaylists::default(),
+            playlists: Vec::new(),
+            queue: PlayQueue::default(),
+            config,
+            eq_open: false,
+            eq_presets,
+            eq_preset_index,
+            eq_modified: false,
+            themes,
+            theme_index,
+            cover_art: None,
+            store: LibraryStore::open(),
+            resume_at: None,
+            watcher: LibraryWatcher::start(LIBRARY_DIR),
+            #[cfg(target_os = "linux")]
+            mpris: MprisServer::start(),
+            cache: Arc::new(Mutex::new(cache)),
+            loading: None,
+            load_error: None,
+            preloading: None,
+        };
+        menu.layout_widgets();
+        menu
     }
 
     /// Updates the menu state based on user interaction.
     ///
-    /// This method processes input from the application to update button visibility,
-    /// create song selection buttons if needed, update the play button label, and process mouse
-    /// click events on visible buttons.
+    /// This method fills the song list if needed, shows the current screen's widgets and brings
+    /// their labels up to date, and passes the mouse to the visible widgets, carrying out what
+    /// they ask for.
     ///
     /// # Arguments
     ///
     /// * `app` - A reference to the nannou [`App`] which provides access to input states.
     pub fn update(&mut self, app: &App) {
-        let mouse = app.mouse.position();
-        let is_mouse_pressed = app.mouse.buttons.pressed().next().is_some();
+        let pointer = Pointer {
+            position: app.mouse.position(),
+            is_down: app.mouse.buttons.pressed().next().is_some(),
+            was_down: self.was_mouse_pressed,
+            is_secondary: app.mouse.buttons.right().is_down(),
+        };
+
+        // Characters typed from now on are meant for the search box.
+        self.skip_typed_chars = false;
 
-        // 🔍 Press 'D' to print debug information about the song's audio configuration.
-        if app.keys.down.contains(&Key::D) {
-            println!("\n🧪 [DEBUG] Dumping supported audio configs...\n");
-            self.song.debug_info();
+        // Fill the song list if needed, and keep it in step with the library folder.
+        if self.song.is_empty() && !self.song_list_created {
+            self.create_song_list();
+            self.song_list_created = true;
         }
+        self.apply_library_changes();
 
-        // Update button visibility based on the current screen.
-        self.update_button_visibility();
+        // Show the current screen's widgets, with labels matching the playback state.
+        self.update_widgets();
 
-        // Create song selection buttons if needed.
-        if self.song.is_empty() && !self.song_buttons_created {
-            self.create_song_buttons();
-            self.song_buttons_created = true;
+        // Pass the mouse to the widgets and carry out what a click or drag asks for.
+        if let Some(message) = self.process_pointer(&pointer) {
+            self.handle(message);
         }
 
-        // Update the label for the play button.
-        self.update_play_button_label();
+        // Start songs whose background load has finished.
+        self.poll_loads();
 
-        // Process mouse click events for visible buttons.
-        self.process_mouse_click_events(mouse, is_mouse_pressed);
+        // Move on through the queue once the current song has played to the end.
+        self.advance_if_finished();
 
-        self.was_mouse_pressed = is_mouse_pressed;
+        // Carry out what the desktop asked for, and tell it what is playing.
+        #[cfg(target_os = "linux")]
+        self.update_mpris();
+
+        // Show the cover art of the song that is now playing.
+        self.update_cover(app);
+
+        self.was_mouse_pressed = pointer.is_down;
     }
 
     /// Draws the menu interface.
     ///
     /// This method clears the background with a dark gray color and renders either the
-    /// song selection controls or the playback controls depending on whether a song is loaded.
+    /// song selection controls, the loading screen, the playback controls or the EQ panel
+    /// depending on whether a song is loaded or loading and the panel is open.
     ///
     /// # Arguments
     ///
@@ -107,15 +392,201 @@ impl Menu {
         draw.rect()
             .xy(self.menu_rect.xy())
             .wh(self.menu_rect.wh())
-            .color(*DARK_GRAY_F32);
+            .color(theme::current().panel);
 
-        if self.song.is_empty() {
+        if let Some(job) = self.loading.as_ref().filter(|_| self.song.is_empty()) {
+            self.draw_loading_screen(draw, job);
+        } else if self.song.is_empty() {
             self.draw_song_select_controls(draw);
+        } else if self.eq_open {
+            self.draw_eq_panel(draw);
         } else {
             self.draw_playback_controls(draw);
         }
     }
 
+    /// Moves the menu to a new area, repositioning every widget.
+    ///
+    /// # Arguments
+    ///
+    /// * `menu_rect` - The new area of the menu, for example after the window was resized.
+    pub fn set_rect(&mut self, menu_rect: Rect) {
+        self.menu_rect = menu_rect;
+        self.layout_widgets();
+    }
+
+    /// Returns the user settings loaded at startup.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns whether keys are being typed into the search box.
+    pub fn is_typing(&self) -> bool {
+        self.song.is_empty() && self.search_box.is_focused()
+    }
+
+    /// Passes a typed character to the search box, filtering the song list if it changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - The character, as produced by the keyboard layout.
+    pub fn received_character(&mut self, c: char) {
+        if self.is_typing() && !self.skip_typed_chars && self.search_box.insert_char(c) {
+            self.refresh_search();
+        }
+    }
+
+    /// Passes an editing key to the search box, filtering the song list if the text changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The pressed key (or one of its repeats while held).
+    /// * `mods` - The modifier keys held at the time.
+    pub fn edit_text(&mut self, key: Key, mods: ModifiersState) {
+        if self.is_typing() && self.search_box.key_pressed(key, mods) {
+            self.refresh_search();
+        }
+    }
+
+    /// Returns the screen shown, which decides what the keys do.
+    pub fn screen(&self) -> Screen {
+        if self.song.is_empty() {
+            Screen::Selection
+        } else {
+            Screen::Playback
+        }
+    }
+
+    /// Carries out an action triggered from the keyboard.
+    ///
+    /// Actions that have a widget send the same [`Message`] as clicking it. Sidebar and
+    /// visualizer actions are handled by the controller and ignored here.
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - The action bound to the pressed key.
+    pub fn perform(&mut self, action: Action) {
+        // The song list is hidden while the first song loads.
+        if action.screen() == Screen::Selection && self.loading.is_some() {
+            return;
+        }
+        let position = self.player.position().unwrap_or(0);
+
+        match action {
+            Action::PlayPause => self.handle(Message::PlayPause),
+            Action::NextTrack => self.handle(Message::Next),
+            Action::PreviousTrack => self.handle(Message::Previous),
+            Action::ToggleEq if self.eq_open => self.handle(Message::CloseEq),
+            Action::ToggleEq => self.handle(Message::OpenEq),
+            Action::Back if self.loading.is_some() || !self.song.is_empty() => {
+                self.handle(Message::Back)
+            }
+            Action::SeekForward | Action::SeekBackward => {
+                let step = (SEEK_SECONDS * self.song.sample_rate() as f32) as usize;
+                let frame = if action == Action::SeekForward {
+                    (position + step).min(self.song.frames())
+                } else {
+                    position.saturating_sub(step)
+                };
+                self.player.seek(frame);
+            }
+            Action::VolumeUp | Action::VolumeDown => {
+                let step = if action == Action::VolumeUp {
+                    VOLUME_STEP
+                } else {
+                    -VOLUME_STEP
+                };
+                self.player.set_volume(self.player.volume() + step);
+                println!("🔊 Volume {:.0}%", self.player.volume() * 100.0);
+            }
+            Action::SetLoopStart => {
+                self.song.set_loop_start(position);
+                self.player.set_loop(self.song.loop_range());
+            }
+            Action::SetLoopEnd => {
+                self.song.set_loop_end(position);
+                self.player.set_loop(self.song.loop_range());
+            }
+            Action::ClearLoop => {
+                self.song.clear_loop();
+                self.player.set_loop(self.song.loop_range());
+            }
+            Action::AddBookmark => self.add_bookmark(position),
+            Action::RemoveBookmark => {
+                if let Some(track) = self.queue.current_track() {
+                    let seconds = position as f64 / self.song.sample_rate() as f64;
+                    self.store.remove_nearest_bookmark(&track.path, seconds);
+                }
+            }
+            Action::JumpToBookmark(number) => {
+                self.jump_to_bookmark((number as usize).saturating_sub(1))
+            }
+            Action::SelectPrevious => self.song_list.move_selection(-1),
+            Action::SelectNext => self.song_list.move_selection(1),
+            Action::SelectPageUp => {
+                let rows = self.song_list.page_rows() as isize;
+                self.song_list.move_selection(-rows);
+            }
+            Action::SelectPageDown => {
+                let rows = self.song_list.page_rows() as isize;
+                self.song_list.move_selection(rows);
+            }
+            Action::SelectFirst => self.song_list.select(Some(0)),
+            Action::SelectLast => self.song_list.select(Some(usize::MAX)),
+            Action::PlaySelected => {
+                let message = self
+                    .song_list
+                    .selected()
+                    .and_then(|row| self.song_list_message(row));
+                if let Some(message) = message {
+                    self.handle(message);
+                }
+            }
+            Action::FocusSearch => {
+                self.search_box.set_focus(true);
+                self.skip_typed_chars = true;
+            }
+            Action::NextTheme => self.next_theme(),
+            Action::Rate(stars) => self.rate_current_track(stars),
+            Action::DebugDump => {
+                println!("\n🧪 [DEBUG] Dumping supported audio configs...\n");
+                self.song.debug_info();
+                self.player.debug_info(&self.song);
+                self.store.print_recent_plays(RECENT_PLAYS);
+            }
+            Action::Back | Action::ToggleSidebar | Action::SwitchVisualizer => {}
+        }
+    }
+
+    /// Returns a description of the load in progress and how far along it is (0.0 to 1.0).
+    ///
+    /// # Returns
+    ///
+    /// `None` if no song is being loaded for playback.
+    pub fn load_status(&self) -> Option<(String, f32)> {
+        self.loading.as_ref().map(|job| {
+            let (stage, fraction) = job.progress();
+            (format!("{} {}", stage.label(), job.track().title), fraction)
+        })
+    }
+
+    /// Returns the cover art of the current song, if it has any.
+    pub fn artwork(&self) -> Option<Arc<Artwork>> {
+        self.song.artwork.clone()
+    }
+
+    /// Returns the current song's lyrics with the playback position and the song's length.
+    ///
+    /// # Returns
+    ///
+    /// The lyrics, position and length (both in seconds), or `None` if the song has no lyrics.
+    pub fn lyrics(&self) -> Option<(Arc<Lyrics>, f64, f64)> {
+        let lyrics = self.song.lyrics.clone()?;
+        let sample_rate = self.song.sample_rate() as f64;
+        let position = self.player.position().unwrap_or(0) as f64 / sample_rate;
+        Some((lyrics, position, self.song.frames() as f64 / sample_rate))
+    }
+
     /// Returns whether a song is currently playing.
     ///
     /// # Returns
@@ -125,267 +596,1484 @@ impl Menu {
         self.is_playing
     }
 
+    /// Scrolls the song list while the selection screen is shown.
+    ///
+    /// # Arguments
+    ///
+    /// * `mouse` - The current mouse position; the list only scrolls while the mouse is over
+    ///   the menu.
+    /// * `distance` - Distance in points; positive values scroll up, like the mouse wheel.
+    pub fn scroll(&mut self, mouse: Vec2, distance: f32) {
+        if self.song.is_empty() && self.menu_rect.contains(mouse) {
+            self.song_list.scroll_by(-distance);
+        }
+    }
+
+    /// Returns the playback part of the session: the queue, the song and position, and the
+    /// volume, shuffle and repeat settings.
+    ///
+    /// A song still loading is saved to start from its beginning.
+    pub fn session(&self) -> Session {
+        let position = if self.loading.is_some() {
+            Some(0.0)
+        } else if self.song.is_empty() {
+            None
+        } else {
+            let frame = self.player.position().unwrap_or(0);
+            Some(frame as f64 / self.song.sample_rate().max(1) as f64)
+        };
+        Session::capture(&self.queue, position, self.is_playing, self.player.volume())
+    }
+
+    /// Restores the queue and settings of the last session and starts loading its song.
+    ///
+    /// The song continues where it was, paused if the config asks for that or it was paused.
+    /// See [`Session::restore_queue`] for tracks whose files are gone.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The last session.
+    pub fn restore_session(&mut self, session: &Session) {
+        self.player.set_volume(session.volume);
+        self.queue.set_shuffle(session.shuffle);
+        self.queue.set_repeat(session.repeat);
+
+        let restored = session.restore_queue();
+        let Some(track) = self
+            .queue
+            .set_tracks(restored.tracks, restored.start)
+            .cloned()
+        else {
+            return;
+        };
+        let resume_playing = session.playing && !self.config.resume_paused;
+        match restored.resume {
+            Resume::At(seconds) => {
+                println!(
+                    "⏯️ Resuming '{}' at {}.",
+                    track.title,
+                    Self::format_time(seconds)
+                );
+                self.resume_at = Some((track.path.clone(), seconds));
+                self.is_playing = resume_playing;
+            }
+            Resume::FromStart => {
+                println!(
+                    "⏯️ The last song is gone; continuing with '{}'.",
+                    track.title
+                );
+                self.is_playing = resume_playing;
+            }
+            Resume::Stopped => {
+                println!("⏹️ Restored the last queue, stopped at '{}'.", track.title);
+                self.is_playing = false;
+            }
+        }
+        self.load_track(&track);
+    }
+
+    /// Plays or queues files dropped on the window, even from outside the music library.
+    ///
+    /// Folders and playlists stand for their tracks, as [`playlist::tracks_at`] reads them.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The dropped files and folders, in the order they were dropped.
+    /// * `play_now` - Whether to replace the queue and play the first track, even if playback
+    ///   was paused; otherwise the tracks are added to the end of the queue, the first one
+    ///   loading if nothing is, and playback stays playing or paused as it was.
+    pub fn open_files(&mut self, paths: &[PathBuf], play_now: bool) {
+        let tracks: Vec<Track> = paths
+            .iter()
+            .flat_map(|path| playlist::tracks_at(path, &self.library))
+            .collect();
+        if tracks.is_empty() {
+            return;
+        }
+
+        let count = tracks.len();
+        if play_now || (self.song.is_empty() && self.loading.is_none()) {
+            println!("▶️ Playing {} dropped track(s).", count);
+            if play_now {
+                self.is_playing = true;
+            }
+            if let Some(track) = self.queue.set_tracks(tracks, 0).cloned() {
+                self.load_track(&track);
+            }
+        } else {
+            println!("➕ Queued {} dropped track(s).", count);
+            self.queue.append(tracks);
+            // The queue may have run out, in which case the first new track is next.
+            self.preload_next();
+        }
+    }
+
     // ============================================================================
     // Private Helper Methods
     // ============================================================================
 
-    /// Creates the default playback buttons (play and back).
+    /// Positions every widget within the menu.
+    fn layout_widgets(&mut self) {
+        let menu_rect = self.menu_rect;
+        self.heading.set_rect(Rect::from_x_y_w_h(
+            menu_rect.x(),
+            menu_rect.top() - 30.0,
+            menu_rect.w() * 0.9,
+            30.0,
+        ));
+        self.playback.layout(menu_rect);
+        self.eq_panel.layout(menu_rect);
+        self.search_box.set_rect(Self::search_box_area(menu_rect));
+        self.song_list.set_rect(Self::song_list_area(menu_rect));
+        self.export_button.set_rect(Rect::from_x_y_w_h(
+            menu_rect.x(),
+            menu_rect.bottom() + 40.0,
+            menu_rect.w() * 0.7,
+            40.0,
+        ));
+    }
+
+    /// Shows the widgets of the current screen and brings them up to date with the playback
+    /// state.
+    ///
+    /// If no song is selected (song selection screen), the search box, song list and export
+    /// button are shown. When a song is loaded, the playback controls are shown instead, or the
+    /// EQ panel while it is open. While the first song loads, only the back button is shown so
+    /// the load can be cancelled.
+    fn update_widgets(&mut self) {
+        let selecting = self.song.is_empty();
+        let waiting = selecting && self.loading.is_some();
+        let playing = !selecting && !self.eq_open;
+        let eq_open = !selecting && self.eq_open;
+
+        let heading = match &self.load_error {
+            _ if waiting => "LOADING".to_string(),
+            Some(title) if selecting => format!("COULD NOT LOAD {}", title.to_uppercase()),
+            None if selecting => "SELECT A SONG".to_string(),
+            _ => "EQUALIZER".to_string(),
+        };
+        self.heading.set_text(&heading);
+        self.heading.set_visible(!playing);
+
+        let browsing = selecting && !waiting;
+        self.search_box.set_visible(browsing);
+        self.song_list.set_visible(browsing);
+        self.export_button.set_visible(browsing);
+        self.export_button
+            .set_enabled(!self.library.tracks().is_empty());
+
+        self.playback.set_visible(playing);
+        self.playback.back.set_visible(playing || waiting);
+        self.eq_panel.set_visible(eq_open);
+
+        if playing {
+            self.update_playback_controls();
+        } else if eq_open {
+            self.update_eq_panel();
+        }
+    }
+
+    /// Updates the playback controls to match the playback state.
+    ///
+    /// If a song is playing, the play button shows `"PAUSE"` on green. Otherwise, it shows
+    /// `"PLAY"` on red. The shuffle, repeat, mix, speed, pitch and varispeed controls show the
+    /// current modes, lighter while they change how the song plays, and the progress bar shows
+    /// the playing position and the A–B loop.
+    fn update_playback_controls(&mut self) {
+        let controls = &mut self.playback;
+
+        controls
+            .play
+            .set_label(if self.is_playing { "PAUSE" } else { "PLAY" });
+        controls.play.style = if self.is_playing {
+            ButtonStyle::Playing
+        } else {
+            ButtonStyle::Paused
+        };
+
+        controls.shuffle.set_on(self.queue.shuffle());
+        controls
+            .varispeed
+            .set_on(self.song.speed_mode() == SpeedMode::Varispeed);
+        controls.repeat.set_label(self.queue.repeat().label());
+        controls
+            .transition
+            .set_label(self.config.transition.label());
+        controls.speed[1].set_label(&format!("{:.2}x", self.song.speed()));
+        controls.pitch[1].set_label(&format!("{:+.0} st", self.song.pitch_semitones()));
+        let modes = [
+            (&mut controls.repeat, self.queue.repeat() != RepeatMode::Off),
+            (
+                &mut controls.transition,
+                self.config.transition != TransitionMode::Gapless,
+            ),
+            (&mut controls.speed[1], self.song.speed() != 1.0),
+            (&mut controls.pitch[1], self.song.pitch_semitones() != 0.0),
+        ];
+        for (button, active) in modes {
+            button.style = if active {
+                ButtonStyle::Normal
+            } else {
+                ButtonStyle::Subdued
+            };
+        }
+        controls.volume.set_value(self.player.volume());
+
+        controls
+            .now_playing
+            .set_text(&format!("Now Playing: {}", self.song.title));
+        let frames = self.song.frames().max(1) as f32;
+        let fraction = |frame: usize| frame as f32 / frames;
+        let position = self.player.position().unwrap_or(0);
+        controls.progress.set_value(fraction(position));
+        controls.progress.set_span(
+            self.song
+                .loop_range()
+                .map(|(start, end)| (fraction(start), fraction(end))),
+        );
+        let (loop_start, loop_end) = self.song.loop_points();
+        let marker_color = theme::current().marker;
+        controls.progress.set_markers(
+            [loop_start, loop_end]
+                .into_iter()
+                .flatten()
+                .map(|frame| Marker {
+                    fraction: fraction(frame),
+                    color: marker_color,
+                })
+                .collect(),
+        );
+        let sample_rate = self.song.sample_rate() as f64;
+        let stats = self
+            .queue
+            .current_track()
+            .map(|track| self.store.stats(&track.path))
+            .unwrap_or_default();
+        controls.time.set_text(&format!(
+            "{} / {}  ·  {}  ·  {}",
+            Self::format_time(position as f64 / sample_rate),
+            Self::format_time(frames as f64 / sample_rate),
+            match stats.rating {
+                0 => "Unrated".to_string(),
+                stars => format!("Rated {}/{}", stars, MAX_RATING),
+            },
+            match stats.play_count {
+                1 => "1 play".to_string(),
+                count => format!("{} plays", count),
+            }
+        ));
+    }
+
+    /// Shows the current song's cover art, uploading it as a texture when the song's artwork
+    /// changes.
     ///
-    /// This helper function builds and returns a vector containing the play and back buttons.
+    /// # Arguments
+    ///
+    /// * `app` - The nannou [`App`], whose window the texture is created for.
+    fn update_cover(&mut self, app: &App) {
+        let unchanged = match (&self.song.artwork, &self.cover_art) {
+            (Some(artwork), Some(shown)) => Arc::ptr_eq(artwork, shown),
+            (artwork, shown) => artwork.is_none() && shown.is_none(),
+        };
+        if unchanged {
+            return;
+        }
+        self.cover_art = self.song.artwork.clone();
+        let texture = self.cover_art.as_ref().map(|artwork| {
+            wgpu::Texture::from_image(app, &DynamicImage::ImageRgba8(artwork.image.clone()))
+        });
+        self.playback.cover.set_texture(texture);
+        self.playback.fit_title();
+    }
+
+    /// Updates the EQ panel to match the equalizer.
+    ///
+    /// The sliders follow the band gains, and the preset button shows the selected EQ preset
+    /// (marked with `*` once the sliders have been moved).
+    fn update_eq_panel(&mut self) {
+        for (band, slider) in self.eq_panel.bands.iter_mut().enumerate() {
+            slider.set_value(self.player.eq().graphic_gain(band));
+        }
+        let preset_label = match self.eq_presets.get(self.eq_preset_index) {
+            Some(preset) if self.eq_modified => format!("{} *", preset.name),
+            Some(preset) => preset.name.clone(),
+            None => "PRESET".to_string(),
+        };
+        self.eq_panel.preset.set_label(&preset_label);
+        self.eq_panel
+            .preset
+            .set_enabled(!self.eq_presets.is_empty());
+    }
+
+    /// Passes the mouse to the widgets; hidden widgets ignore it.
     ///
     /// # Arguments
     ///
-    /// * `menu_rect` - The rectangle in which to position the buttons.
+    /// * `pointer` - The mouse as of this update.
     ///
     /// # Returns
     ///
-    /// A `Vec<Button>` containing the play button and the back button.
-    fn default_buttons(menu_rect: Rect) -> Vec<Button> {
-        vec![
-            Button::new(
-                "PLAY",
-                "play_button",
-                Rect::from_x_y_w_h(
-                    menu_rect.x(),
-                    menu_rect.y() + menu_rect.h() * 0.3,
-                    menu_rect.w() * 0.8,
-                    50.0,
-                ),
-            ),
-            Button::new(
-                "BACK",
-                "back_button",
-                Rect::from_x_y_w_h(
-                    menu_rect.x(),
-                    menu_rect.y() - menu_rect.h() * 0.3,
-                    menu_rect.w() * 0.8,
-                    50.0,
-                ),
-            ),
-        ]
+    /// What a clicked or dragged widget (or song list row) asks for, if anything.
+    fn process_pointer(&mut self, pointer: &Pointer) -> Option<Message> {
+        self.search_box.update(pointer);
+        let row = self.song_list.update(pointer);
+
+        let mut widgets = self.playback.widgets_mut();
+        widgets.extend(self.eq_panel.widgets_mut());
+        widgets.push(&mut self.export_button);
+        update_all(widgets, pointer).or_else(|| self.song_list_message(row?))
     }
 
-    /// Updates the visibility of buttons based on the current screen mode.
+    /// Carries out what a widget or song list row asks for.
     ///
-    /// If no song is selected (song selection screen), only song buttons (tags starting with `"song_"`)
-    /// are made visible, and playback buttons (`"play_button"` and `"back_button"`) are hidden.
-    /// When a song is loaded (playback screen), the reverse occurs.
-    fn update_button_visibility(&mut self) {
-        if self.song.is_empty() {
-            // Song selection screen: hide playback buttons, show song selection buttons.
-            for button in &mut self.buttons {
-                if button.tag == "play_button" || button.tag == "back_button" {
-                    button.is_visible = false;
-                } else if button.tag.starts_with("song_") {
-                    button.is_visible = true;
+    /// Clicks, drags and keyboard actions all end up here:
+    /// - Toggling the play state for the play button.
+    /// - Resetting the song and playback state for the back button.
+    /// - Skipping through the queue for the next and previous buttons.
+    /// - Toggling shuffle and cycling the repeat and mix modes.
+    /// - Stepping or resetting the playback speed and pitch, and toggling varispeed.
+    /// - Setting the volume with its slider.
+    /// - Opening and closing the EQ panel, cycling EQ presets, saving the current settings and
+    ///   setting band gains with the sliders.
+    /// - Seeking and setting loop points with the progress bar.
+    /// - Loading a new song when a song in the song list is clicked.
+    /// - Importing a playlist when a playlist in the song list is clicked.
+    /// - Exporting the library as a playlist when the export button is pressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - What to do.
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::PlayPause => {
+                self.is_playing = !self.is_playing;
+            }
+            Message::Back if self.loading.is_some() => {
+                // Cancel the load and stay on the current screen.
+                self.loading = None;
+            }
+            Message::Back => {
+                self.eq_open = false;
+                self.song = Song::empty();
+                self.preloaded = None;
+                self.preloading = None;
+                self.player.unload();
+                self.is_playing = false;
+                self.song_list_created = false;
+            }
+            Message::Next => {
+                let next = self.queue.next(false).cloned();
+                match next {
+                    Some(track) => self.load_track(&track),
+                    None => self.stop_at_end(),
                 }
             }
-        } else {
-            // Playback screen: show playback buttons, hide song selection buttons.
-            for button in &mut self.buttons {
-                if button.tag == "play_button" || button.tag == "back_button" {
-                    button.is_visible = true;
-                } else if button.tag.starts_with("song_") {
-                    button.is_visible = false;
+            Message::Previous => {
+                if let Some(track) = self.queue.previous().cloned() {
+                    self.load_track(&track);
+                }
+            }
+            Message::ToggleShuffle => {
+                let shuffle = !self.queue.shuffle();
+                self.queue.set_shuffle(shuffle);
+                self.preload_next();
+            }
+            Message::CycleRepeat => {
+                let repeat = self.queue.repeat().cycle();
+                self.queue.set_repeat(repeat);
+                self.preload_next();
+            }
+            Message::CycleTransition => {
+                self.config.transition = self.config.transition.cycle();
+                self.player
+                    .set_transition(self.config.transition, self.config.crossfade_seconds);
+                self.config.save();
+            }
+            Message::SpeedDown => self.song.set_speed(self.song.speed() - SPEED_STEP),
+            Message::SpeedUp => self.song.set_speed(self.song.speed() + SPEED_STEP),
+            Message::ResetSpeed => self.song.set_speed(1.0),
+            Message::PitchDown => self
+                .song
+                .set_pitch_semitones(self.song.pitch_semitones() - 1.0),
+            Message::PitchUp => self
+                .song
+                .set_pitch_semitones(self.song.pitch_semitones() + 1.0),
+            Message::ResetPitch => self.song.set_pitch_semitones(0.0),
+            Message::ToggleVarispeed => {
+                let mode = match self.song.speed_mode() {
+                    SpeedMode::Stretch => SpeedMode::Varispeed,
+                    SpeedMode::Varispeed => SpeedMode::Stretch,
+                };
+                self.song.set_speed_mode(mode);
+            }
+            Message::SetVolume(volume) => self.player.set_volume(volume),
+            Message::OpenEq => self.eq_open = true,
+            Message::CloseEq => self.eq_open = false,
+            Message::NextEqPreset => {
+                if !self.eq_presets.is_empty() {
+                    self.eq_preset_index = (self.eq_preset_index + 1) % self.eq_presets.len();
+                    self.select_eq_preset(self.eq_preset_index);
+                }
+            }
+            Message::SaveEqPreset => self.save_eq_preset(),
+            Message::SetEqGain(band, gain) => {
+                self.player.eq().set_graphic_gain(band, gain);
+                self.eq_modified = true;
+            }
+            Message::Seek(fraction) => {
+                let frame = self.frame_at(fraction);
+                self.player.seek(frame);
+            }
+            Message::MarkLoop(fraction) => {
+                let frame = self.frame_at(fraction);
+                self.mark_loop(frame);
+            }
+            Message::PlayTrack(index) => {
+                // Queue the whole library so next/previous walk through it.
+                let tracks = self.library.tracks().to_vec();
+                if let Some(track) = self.queue.set_tracks(tracks, index).cloned() {
+                    self.load_track(&track);
+                }
+            }
+            Message::PlaySmartPlaylist(index) => self.play_smart_playlist(index),
+            Message::ImportPlaylist(index) => {
+                if let Some(path) = self.playlists.get(index).cloned() {
+                    self.import_playlist(&path);
                 }
             }
+            Message::ExportLibrary => self.export_library(),
         }
     }
 
-    /// Updates the play button's label based on the current playback state.
+    /// Starts loading a library track; the menu switches to it once it is ready.
     ///
-    /// If a song is playing, the button label is set to `"PAUSE"`. Otherwise, it is set to `"PLAY"`.
-    fn update_play_button_label(&mut self) {
-        let playing = self.is_playing;
-        if let Some(play_button) = self.get_button_mut("play_button") {
-            play_button.set_label(if playing { "PAUSE" } else { "PLAY" });
+    /// A track that is already preloaded starts at once, and a background load of it is reused.
+    /// Any other load in progress is cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track to load.
+    fn load_track(&mut self, track: &Track) {
+        self.load_error = None;
+        if let Some((preloaded, song)) = self.preloaded.take()
+            && preloaded == *track
+        {
+            self.loading = None;
+            self.start_song(song);
+            return;
         }
+        self.loading = match self.preloading.take() {
+            Some(job) if job.track() == track => Some(job),
+            _ => Some(self.spawn_load(track)),
+        };
     }
 
-    /// Processes mouse click events for visible buttons.
+    /// Starts preparing a track on a worker thread.
     ///
-    /// This method checks for a new mouse press and, if a button is pressed, handles it by:
-    /// - Toggling the play state when the play button is pressed.
-    /// - Resetting the song and playback state for the back button.
-    /// - Loading a new song when a song selection button is pressed.
-    ///
-    /// # Arguments
-    ///
-    /// * `mouse` - The current mouse position.
-    /// * `is_mouse_pressed` - A boolean indicating whether a mouse button is pressed.
-    fn process_mouse_click_events(&mut self, mouse: Vec2, is_mouse_pressed: bool) {
-        if is_mouse_pressed && !self.was_mouse_pressed {
-            for button in &self.buttons {
-                if button.is_visible && button.contains(mouse) {
-                    match button.tag.as_str() {
-                        "play_button" => {
-                            self.is_playing = !self.is_playing;
-                        }
-                        "back_button" => {
-                            self.song = Song::empty();
-                            self.is_playing = false;
-                            self.song_buttons_created = false;
-                        }
-                        _ if button.tag.starts_with("song_") => {
-                            self.song =
-                                Song::from_file(Song::get_file_from_title(&button.label).as_str());
-                            // Remove song selection buttons once a song is chosen.
-                            self.buttons
-                                .retain(|b| b.tag == "play_button" || b.tag == "back_button");
-                            self.song_buttons_created = false;
-                        }
-                        _ => {}
+    /// The track is prepared at its own rate if the device supports it, so it plays bit-perfect;
+    /// otherwise it is resampled to the device's default rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track to prepare.
+    fn spawn_load(&self, track: &Track) -> LoadJob {
+        let target = self.player.format_for(
+            track.sample_rate.unwrap_or_default(),
+            track.channels.unwrap_or_default(),
+        );
+        LoadJob::spawn(
+            track.clone(),
+            target,
+            self.config.resample_quality,
+            Arc::clone(&self.cache),
+        )
+    }
+
+    /// Plays a loaded song and switches to the playback screen.
+    ///
+    /// # Arguments
+    ///
+    /// * `song` - The song to play.
+    fn start_song(&mut self, song: Song) {
+        self.song = song;
+        self.song.set_playback_rate(Arc::clone(&self.playback_rate));
+        if !self.player.match_format(&self.song) {
+            eprintln!(
+                "⚠️ '{}' is at {} Hz but the output runs at {} Hz.",
+                self.song.title,
+                self.song.sample_rate(),
+                self.player.sample_rate()
+            );
+        }
+        self.player.load(&self.song);
+        if let Some((path, seconds)) = self.resume_at.take()
+            && self
+                .queue
+                .current_track()
+                .is_some_and(|track| track.path == path)
+        {
+            self.player
+                .seek((seconds * self.song.sample_rate() as f64) as usize);
+        }
+        self.preload_next();
+        // The list is refilled on return.
+        self.search_box.set_focus(false);
+        self.song_list_created = false;
+    }
+
+    /// Collects the songs finished by the worker threads.
+    ///
+    /// A finished load starts playing; a finished preload is queued on the player.
+    fn poll_loads(&mut self) {
+        if let Some(result) = self.loading.as_mut().and_then(LoadJob::poll)
+            && let Some(job) = self.loading.take()
+        {
+            match result {
+                Ok(song) => self.start_song(song),
+                Err(SongError::Cancelled) => {}
+                Err(e) => {
+                    eprintln!("❌ Could not load '{}': {}", job.track().file_name, e);
+                    self.load_error = Some(job.track().title.clone());
+                }
+            }
+        }
+
+        if let Some(result) = self.preloading.as_mut().and_then(LoadJob::poll)
+            && let Some(job) = self.preloading.take()
+        {
+            match result {
+                Ok(mut song) => {
+                    song.set_playback_rate(Arc::clone(&self.playback_rate));
+                    self.preloaded = Some((job.track().clone(), song));
+                    self.queue_preloaded();
+                }
+                Err(SongError::Cancelled) => {}
+                Err(e) => eprintln!("❌ Could not load '{}': {}", job.track().file_name, e),
+            }
+        }
+    }
+
+    /// Queues the preloaded song on the player, if the stream can play it as it is open.
+    ///
+    /// A song in another format is kept and started once the current one ends instead.
+    fn queue_preloaded(&mut self) {
+        let song = self
+            .preloaded
+            .as_ref()
+            .map(|(_, song)| song)
+            .filter(|song| self.player.can_queue(song));
+        self.player.preload(song);
+    }
+
+    /// Prepares the track that follows the current one and queues it on the player.
+    ///
+    /// The prepared song is kept if the queue's next track has not changed since the last call;
+    /// otherwise it is loaded in the background and queued once ready.
+    fn preload_next(&mut self) {
+        if self.song.is_empty() {
+            return;
+        }
+        self.queue.prepare_next();
+        let next = self.queue.peek_next().cloned();
+
+        if self.queue.repeat() == RepeatMode::One {
+            // Loop the current song's buffer without decoding it again.
+            self.preloaded = None;
+            self.preloading = None;
+            self.player.preload(Some(&self.song));
+            return;
+        }
+
+        match next {
+            Some(track) => {
+                let is_cached =
+                    matches!(&self.preloaded, Some((preloaded, _)) if *preloaded == track);
+                if is_cached {
+                    self.preloading = None;
+                } else {
+                    self.preloaded = None;
+                    let is_loading = matches!(&self.preloading, Some(job) if *job.track() == track);
+                    if !is_loading {
+                        self.preloading = Some(self.spawn_load(&track));
                     }
-                    break;
                 }
+                self.queue_preloaded();
+            }
+            None => {
+                self.preloaded = None;
+                self.preloading = None;
+                self.player.preload(None);
+            }
+        }
+    }
+
+    /// Follows the player through the queue.
+    ///
+    /// Gapless switches made by the player are mirrored in the queue and the displayed song, and
+    /// the following track is preloaded. When the player runs out of audio, a preloaded song that
+    /// could not be queued on the stream starts; if the following track is still loading, playback
+    /// resumes once it is ready, and otherwise the end of the queue has been reached and playback
+    /// stops.
+    fn advance_if_finished(&mut self) {
+        for _ in 0..self.player.take_advanced() {
+            self.record_finished_track();
+            self.queue.next(true);
+            if let Some((_, song)) = self.preloaded.take() {
+                self.song = song;
+            }
+            self.preload_next();
+        }
+
+        if !self.song.is_empty() && self.player.is_finished() {
+            // A preloaded song in another format was not queued on the stream; start it now.
+            match self.preloaded.take() {
+                Some((_, song)) => {
+                    self.record_finished_track();
+                    self.queue.next(true);
+                    self.start_song(song);
+                }
+                None if self.preloading.is_some() => {}
+                None => {
+                    self.record_finished_track();
+                    self.stop_at_end();
+                }
+            }
+        }
+    }
+
+    /// Counts the playing track as played to the end and adds it to the listening history.
+    fn record_finished_track(&mut self) {
+        if let Some(track) = self.queue.current_track() {
+            self.store.record_play(track);
+            self.refresh_smart_playlists();
+        }
+    }
+
+    /// Rates the playing track.
+    ///
+    /// # Arguments
+    ///
+    /// * `stars` - Number of stars, or 0 to clear the rating.
+    fn rate_current_track(&mut self, stars: u8) {
+        let Some(track) = self.queue.current_track() else {
+            return;
+        };
+        self.store.set_rating(track, stars);
+        println!("⭐ Rated '{}' {}/{}.", track.title, stars, MAX_RATING);
+        self.refresh_smart_playlists();
+    }
+
+    /// Applies an EQ preset and remembers it as the startup preset.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the preset in the loaded preset list.
+    fn select_eq_preset(&mut self, index: usize) {
+        let Some(preset) = self.eq_presets.get(index) else {
+            return;
+        };
+        self.player.eq().apply(preset);
+        self.eq_preset_index = index;
+        self.eq_modified = false;
+        self.config.eq_preset = preset.name.clone();
+        self.config.save();
+    }
+
+    /// Switches to the theme after the current one and remembers it in the config.
+    ///
+    /// The theme files are read again first, so a theme being edited can be reloaded by
+    /// switching away from it and back.
+    fn next_theme(&mut self) {
+        let current = &self.themes[self.theme_index].name;
+        let themes = Theme::load_all();
+        let index = themes
+            .iter()
+            .position(|theme| &theme.name == current)
+            .map_or(0, |index| (index + 1) % themes.len());
+        self.themes = themes;
+        self.theme_index = index;
+
+        let theme = &self.themes[index];
+        theme::set_current(theme.clone());
+        self.config.theme = theme.name.clone();
+        self.config.save();
+        println!("🎨 Theme: {}", theme.name);
+    }
+
+    /// Saves the current EQ settings as the "Custom" preset and selects it.
+    fn save_eq_preset(&mut self) {
+        let preset = self.player.eq().snapshot("Custom");
+        EqPreset::save_user(&preset);
+        println!("🎚️ Saved EQ preset '{}'.", preset.name);
+
+        let index = match self.eq_presets.iter().position(|p| p.name == preset.name) {
+            Some(index) => {
+                self.eq_presets[index] = preset;
+                index
+            }
+            None => {
+                self.eq_presets.push(preset);
+                self.eq_presets.len() - 1
             }
+        };
+        self.select_eq_preset(index);
+    }
+
+    /// Returns the frame at a fraction of the song.
+    fn frame_at(&self, fraction: f32) -> usize {
+        (fraction as f64 * self.song.frames() as f64) as usize
+    }
+
+    /// Sets the loop start (A), then the loop end (B); a third call starts a new loop.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Position of the loop point, in frames.
+    fn mark_loop(&mut self, frame: usize) {
+        match self.song.loop_points() {
+            (None, _) => self.song.set_loop_start(frame),
+            (Some(_), None) => self.song.set_loop_end(frame),
+            (Some(_), Some(_)) => {
+                self.song.clear_loop();
+                self.song.set_loop_start(frame);
+            }
+        }
+        self.player.set_loop(self.song.loop_range());
+    }
+
+    /// Returns the bookmarks of the playing track.
+    fn current_bookmarks(&self) -> &[Bookmark] {
+        match self.queue.current_track() {
+            Some(track) => self.store.bookmarks(&track.path),
+            None => &[],
+        }
+    }
+
+    /// Adds a numbered bookmark to the playing track.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Position of the bookmark, in frames.
+    fn add_bookmark(&mut self, frame: usize) {
+        let Some(track) = self.queue.current_track() else {
+            return;
+        };
+        let seconds = frame as f64 / self.song.sample_rate() as f64;
+        let name = self.store.add_numbered_bookmark(track, seconds);
+        println!(
+            "🔖 Added '{}' at {} in '{}'.",
+            name,
+            Self::format_time(seconds),
+            self.song.title
+        );
+    }
+
+    /// Seeks to one of the playing track's bookmarks.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the bookmark, in order of position.
+    fn jump_to_bookmark(&mut self, index: usize) {
+        if let Some(bookmark) = self.current_bookmarks().get(index) {
+            let frame = (bookmark.seconds * self.song.sample_rate() as f64) as usize;
+            self.player.seek(frame);
+        }
+    }
+
+    /// Formats a duration as `m:ss`.
+    fn format_time(seconds: f64) -> String {
+        let seconds = seconds.max(0.0) as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+
+    /// Stops playback at the end of the queue, leaving the last song ready to play again.
+    fn stop_at_end(&mut self) {
+        self.is_playing = false;
+        self.player.rewind();
+    }
+
+    /// Imports a playlist file, queues its tracks and starts the first one.
+    ///
+    /// Entries that could not be matched to the library are reported on stderr.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the playlist file.
+    fn import_playlist(&mut self, path: &Path) {
+        let tracks = playlist::import_tracks(path, &self.library);
+        if let Some(track) = self.queue.set_tracks(tracks, 0).cloned() {
+            self.load_track(&track);
+        }
+    }
+
+    /// Plays the tracks a smart playlist matched when it was last evaluated.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the smart playlist.
+    fn play_smart_playlist(&mut self, index: usize) {
+        let Some(tracks) = self.smart_playlists.tracks_to_play(index) else {
+            return;
+        };
+        if let Some(track) = self.queue.set_tracks(tracks, 0).cloned() {
+            self.load_track(&track);
+        }
+    }
+
+    /// Exports every library track to `playlists/library.m3u8`.
+    fn export_library(&self) {
+        let path = Path::new(PLAYLIST_DIR).join("library.m3u8");
+        match playlist::export(&path, "Library", self.library.tracks()) {
+            Ok(()) => println!("✅ Exported library to '{}'.", path.display()),
+            Err(e) => eprintln!("Failed to export library: {}", e),
         }
     }
 
-    /// Draws the playback controls, including the play/pause and back buttons, as well as the
-    /// currently playing song's title.
+    /// Draws the playback controls, including the currently playing song's title, the progress
+    /// bar with the track's bookmarks, the play/pause, back, next/previous, shuffle, repeat, mix,
+    /// speed, pitch, varispeed and EQ buttons, and the volume slider.
     ///
     /// # Arguments
     ///
     /// * `draw` - A reference to the nannou [`Draw`] context used for rendering.
     fn draw_playback_controls(&self, draw: &Draw) {
-        if let Some(play_button) = self.get_button("play_button") {
-            let button_color = if self.is_playing {
-                *GREEN_F32
-            } else {
-                *RED_F32
-            };
+        self.playback.draw(draw);
+        self.draw_bookmarks(draw);
 
-            play_button.draw(draw, button_color, *BLACK_F32, None);
+        // The current song keeps playing while the next one chosen loads.
+        if let Some(job) = &self.loading {
+            let theme = theme::current();
+            let y = self.menu_rect.bottom() + 30.0;
+            draw.text(&format!("Loading: {}", job.track().title))
+                .xy(pt2(self.menu_rect.x(), y + 14.0))
+                .color(theme.accent)
+                .font_size(theme.font_size(TextSize::Detail));
+            self.draw_load_bar(draw, job, y);
         }
+    }
 
-        if let Some(back_button) = self.get_button("back_button") {
-            back_button.draw(draw, *BLUE_F32, *BLACK_F32, None);
-        }
+    /// Draws the screen shown while the first song loads, with its title, the current step,
+    /// a progress bar and the back button to cancel.
+    ///
+    /// # Arguments
+    ///
+    /// * `draw` - A reference to the nannou [`Draw`] context used for rendering.
+    /// * `job` - The load in progress.
+    fn draw_loading_screen(&self, draw: &Draw, job: &LoadJob) {
+        let theme = theme::current();
+        self.heading.draw(draw);
+        draw.text(&job.track().title)
+            .xy(pt2(self.menu_rect.x(), self.menu_rect.top() - 60.0))
+            .color(theme.text)
+            .font_size(theme.font_size(TextSize::Body));
 
-        if !self.song.is_empty() {
-            draw.text(&format!("Now Playing: {}", self.song.title))
-                .xy(pt2(self.menu_rect.x(), self.menu_rect.top() - 60.0))
-                .color(*WHITE_F32)
-                .font_size(20);
+        let y = self.menu_rect.y();
+        let (stage, _) = job.progress();
+        draw.text(stage.label())
+            .xy(pt2(self.menu_rect.x(), y + 18.0))
+            .color(theme.accent)
+            .font_size(theme.font_size(TextSize::Small));
+        self.draw_load_bar(draw, job, y);
+
+        self.playback.back.draw(draw);
+    }
+
+    /// Draws a load's progress through its current step as a bar across the menu.
+    ///
+    /// # Arguments
+    ///
+    /// * `draw` - A reference to the nannou [`Draw`] context used for rendering.
+    /// * `job` - The load in progress.
+    /// * `y` - Vertical centre of the bar.
+    fn draw_load_bar(&self, draw: &Draw, job: &LoadJob, y: f32) {
+        let (_, fraction) = job.progress();
+        let mut bar = ProgressBar::<Message>::new(Rect::from_x_y_w_h(
+            self.menu_rect.x(),
+            y,
+            self.menu_rect.w() * 0.8,
+            6.0,
+        ));
+        bar.set_value(fraction);
+        bar.draw(draw);
+    }
+
+    /// Draws the playing track's numbered bookmarks as ticks above the progress bar.
+    ///
+    /// # Arguments
+    ///
+    /// * `draw` - A reference to the nannou [`Draw`] context used for rendering.
+    fn draw_bookmarks(&self, draw: &Draw) {
+        let theme = theme::current();
+        let progress = &self.playback.progress;
+        let top = progress.state().rect.top();
+        let seconds = self.song.frames().max(1) as f64 / self.song.sample_rate() as f64;
+        for (index, bookmark) in self.current_bookmarks().iter().enumerate() {
+            let x = progress.x_at((bookmark.seconds / seconds) as f32);
+            draw.line()
+                .start(pt2(x, top))
+                .end(pt2(x, top + 4.0))
+                .color(theme.text)
+                .weight(1.5);
+            draw.text(&(index + 1).to_string())
+                .xy(pt2(x, top + 10.0))
+                .color(theme.text)
+                .font_size(theme.font_size(TextSize::Caption));
         }
     }
 
+    /// Draws the EQ panel: the preset, save and done buttons and one slider per graphic band.
+    ///
+    /// # Arguments
+    ///
+    /// * `draw` - A reference to the nannou [`Draw`] context used for rendering.
+    fn draw_eq_panel(&self, draw: &Draw) {
+        self.heading.draw(draw);
+        self.eq_panel.draw(draw);
+    }
+
     /// Draws the song selection controls.
     ///
-    /// This function renders a title ("SELECT A SONG") and all visible song selection buttons.
+    /// This function renders a title ("SELECT A SONG"), the search box, the visible rows of the
+    /// song list and the export button.
     ///
     /// # Arguments
     ///
     /// * `draw` - A reference to the nannou [`Draw`] context for rendering.
     fn draw_song_select_controls(&self, draw: &Draw) {
-        draw.text("SELECT A SONG")
-            .xy(pt2(self.menu_rect.x(), self.menu_rect.top() - 30.0))
-            .color(*WHITE_F32)
-            .font_size(24);
+        self.heading.draw(draw);
+        self.search_box.draw(draw);
+
+        // Only the rows scrolled into view are drawn, with the characters matching the search
+        // highlighted.
+        let query = self.search_box.text();
+        self.song_list.draw_rows(draw, |row| {
+            let (label, style) = match self.song_list_entry(self.search_results[row]) {
+                SongListEntry::Track(index) => (
+                    self.library.tracks()[index].title.clone(),
+                    ButtonStyle::Normal,
+                ),
+                SongListEntry::SmartPlaylist(index) => {
+                    let label = self
+                        .smart_playlists
+                        .get(index)
+                        .map(|(playlist, tracks)| format!("✦ {} · {}", playlist.name, tracks.len()))
+                        .unwrap_or_default();
+                    (label, ButtonStyle::Subdued)
+                }
+                SongListEntry::Playlist(index) => {
+                    let name = Self::playlist_name(&self.playlists[index]);
+                    (format!("♫ {}", name), ButtonStyle::Subdued)
+                }
+            };
+            ListRow {
+                highlights: search::highlights(query, &label),
+                label,
+                style,
+            }
+        });
+
+        self.export_button.draw(draw);
+    }
 
-        // Render only the song selection buttons that are marked visible.
-        for button in &self.buttons {
-            if button.tag.starts_with("song_") && button.is_visible {
-                button.draw(draw, *SLATE_F32, *WHITE_F32, Some(*LIGHT_BLUE_F32));
+    /// Fills the song list by scanning the music library and the playlists folder.
+    ///
+    /// The list holds each track in the library followed by each smart playlist and each
+    /// playlist.
+    fn create_song_list(&mut self) {
+        match Library::scan(LIBRARY_DIR) {
+            Ok(library) => self.library = library,
+            Err(e) => {
+                eprintln!("Failed to scan music library: {}", e);
+                return;
             }
         }
+        self.store.sync_tracks(self.library.tracks());
+        self.smart_playlists =
+            SmartPlaylists::load(PLAYLIST_DIR, self.library.tracks(), &self.store);
+        self.playlists = playlist::list_playlists(PLAYLIST_DIR);
+        self.rebuild_search_index();
+        self.refresh_search();
+    }
+
+    /// Updates the library with the changes the watcher saw in the music library folder.
+    ///
+    /// The smart playlists and the song list are refreshed without scrolling it.
+    fn apply_library_changes(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if !watcher.update_library(&mut self.library, &mut self.store, &self.cache) {
+            return;
+        }
+        self.refresh_smart_playlists();
+        self.rebuild_search_index();
+        self.search_results = self.search_index.search(self.search_box.text());
+        self.song_list.set_len(self.search_results.len());
+    }
+
+    /// Rebuilds the index the song list is searched with from the library and the playlists.
+    fn rebuild_search_index(&mut self) {
+        // Tracks are found by title, artist and album, playlists by name.
+        let track_texts = self.library.tracks().iter().map(|track| {
+            [
+                Some(track.title.as_str()),
+                track.artist.as_deref(),
+                track.album.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+        });
+        let smart_playlist_texts = self
+            .smart_playlists
+            .playlists()
+            .iter()
+            .map(|playlist| playlist.name.clone());
+        let playlist_texts = self
+            .playlists
+            .iter()
+            .map(|path| Self::playlist_name(path).to_string());
+        self.search_index = SearchIndex::new(
+            track_texts
+                .chain(smart_playlist_texts)
+                .chain(playlist_texts),
+        );
+    }
+
+    /// Evaluates the smart playlists again, after the library, a play count or a rating changed.
+    fn refresh_smart_playlists(&mut self) {
+        self.smart_playlists
+            .refresh(self.library.tracks(), &self.store);
     }
 
-    /// Retrieves a mutable reference to a button by its tag.
+    /// Carries out the commands received over MPRIS, then publishes the playback state.
     ///
-    /// Only buttons marked as visible are considered.
+    /// The state is held while a song loads, so the desktop goes straight from one song to the
+    /// next.
+    #[cfg(target_os = "linux")]
+    fn update_mpris(&mut self) {
+        let Some(commands) = self.mpris.as_mut().map(MprisServer::poll) else {
+            return;
+        };
+        for command in commands {
+            if let Some(command) = command.resolve(&self.playback_state()) {
+                self.handle_mpris_command(command);
+            }
+        }
+        if self.loading.is_none() {
+            let state = self.playback_state();
+            if let Some(mpris) = &mut self.mpris {
+                mpris.update(state);
+            }
+        }
+    }
+
+    /// Carries out a command received over MPRIS, once [resolved](MprisCommand::resolve).
     ///
     /// # Arguments
     ///
-    /// * `tag` - The identifier tag for the button.
+    /// * `command` - The command.
+    #[cfg(target_os = "linux")]
+    fn handle_mpris_command(&mut self, command: MprisCommand) {
+        match command {
+            MprisCommand::PlayPause => self.handle(Message::PlayPause),
+            MprisCommand::Stop => self.handle(Message::Back),
+            MprisCommand::Next => self.handle(Message::Next),
+            MprisCommand::Previous => self.handle(Message::Previous),
+            MprisCommand::SetPosition(seconds) => {
+                let frame = (seconds * self.song.sample_rate() as f64) as usize;
+                self.player.seek(frame.min(self.song.frames()));
+            }
+            MprisCommand::SetRate(rate) => self.playback_rate.set_speed(rate as f32),
+            MprisCommand::SetVolume(volume) => self.handle(Message::SetVolume(volume as f32)),
+            MprisCommand::SetShuffle(_) => self.handle(Message::ToggleShuffle),
+            MprisCommand::SetRepeat(repeat) => {
+                self.queue.set_repeat(repeat);
+                self.preload_next();
+            }
+            MprisCommand::Open(path) => self.open_files(std::slice::from_ref(&path), true),
+            MprisCommand::Play | MprisCommand::Pause | MprisCommand::Seek(_) => {}
+        }
+    }
+
+    /// Describes what the player is doing, for MPRIS.
+    #[cfg(target_os = "linux")]
+    fn playback_state(&self) -> PlaybackState {
+        PlaybackState::capture(
+            &self.song,
+            &self.queue,
+            &self.player,
+            &self.playback_rate,
+            self.is_playing,
+        )
+    }
+
+    /// Returns the area of the song list: below the search box and above the export button.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// An `Option` containing a mutable reference to the matching button if found.
-    fn get_button_mut(&mut self, tag: &str) -> Option<&mut Button> {
-        self.buttons
-            .iter_mut()
-            .find(|b| b.tag == tag && b.is_visible)
+    /// * `menu_rect` - The area of the menu.
+    fn song_list_area(menu_rect: Rect) -> Rect {
+        let top = menu_rect.top() - 100.0;
+        let bottom = menu_rect.bottom() + 70.0;
+        Rect::from_x_y_w_h(
+            menu_rect.x(),
+            (top + bottom) / 2.0,
+            menu_rect.w() * 0.85,
+            (top - bottom).max(0.0),
+        )
     }
 
-    /// Retrieves an immutable reference to a button by its tag.
-    ///
-    /// Only buttons marked as visible are considered.
+    /// Returns what clicking a song list row asks for: playing its track or importing its
+    /// playlist.
     ///
     /// # Arguments
     ///
-    /// * `tag` - The identifier tag for the button.
+    /// * `row` - The row of the song list.
+    fn song_list_message(&self, row: usize) -> Option<Message> {
+        let entry = *self.search_results.get(row)?;
+        Some(match self.song_list_entry(entry) {
+            SongListEntry::Track(index) => Message::PlayTrack(index),
+            SongListEntry::SmartPlaylist(index) => Message::PlaySmartPlaylist(index),
+            SongListEntry::Playlist(index) => Message::ImportPlaylist(index),
+        })
+    }
+
+    /// Returns what an entry of the search index stands for.
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// An `Option` containing an immutable reference to the matching button if found.
-    fn get_button(&self, tag: &str) -> Option<&Button> {
-        self.buttons.iter().find(|b| b.tag == tag && b.is_visible)
+    /// * `entry` - Index into the tracks, followed by the smart playlists and the playlists.
+    fn song_list_entry(&self, entry: usize) -> SongListEntry {
+        let tracks = self.library.tracks().len();
+        let smart_playlists = self.smart_playlists.count();
+        if entry < tracks {
+            SongListEntry::Track(entry)
+        } else if entry < tracks + smart_playlists {
+            SongListEntry::SmartPlaylist(entry - tracks)
+        } else {
+            SongListEntry::Playlist(entry - tracks - smart_playlists)
+        }
     }
 
-    /// Retrieves a list of song names from the specified directory.
+    /// Filters the song list by the search box's text and scrolls back to the top.
     ///
-    /// The file names are converted to song titles using [`Song::get_title_from_file`].
+    /// While searching, the best match is selected so Enter plays it.
+    fn refresh_search(&mut self) {
+        self.search_results = self.search_index.search(self.search_box.text());
+        self.song_list.set_len(self.search_results.len());
+        self.song_list.scroll_to_top();
+        let searching = !self.search_box.text().trim().is_empty();
+        self.song_list.select(searching.then_some(0));
+    }
+
+    /// Returns the name a playlist is listed under: its file name without the extension.
+    fn playlist_name(path: &Path) -> &str {
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+    }
+
+    /// Returns the area of the search box: between the heading and the song list.
     ///
     /// # Arguments
     ///
-    /// * `dir_path` - The path to the directory containing the song files.
+    /// * `menu_rect` - The area of the menu.
+    fn search_box_area(menu_rect: Rect) -> Rect {
+        Rect::from_x_y_w_h(
+            menu_rect.x(),
+            menu_rect.top() - 72.0,
+            menu_rect.w() * 0.85,
+            32.0,
+        )
+    }
+}
+
+impl PlaybackControls {
+    /// Creates the playback screen's widgets; [`PlaybackControls::layout`] positions them.
+    fn new() -> Self {
+        let unplaced = Rect::from_w_h(0.0, 0.0);
+        let steps = |down, reset, up| {
+            [
+                Button::new("-", down, unplaced),
+                Button::new("", reset, unplaced),
+                Button::new("+", up, unplaced),
+            ]
+        };
+        PlaybackControls {
+            cover: Picture::new(unplaced),
+            now_playing: Label::new("", TextSize::Title, unplaced),
+            title_row: unplaced,
+            progress: ProgressBar::new(unplaced)
+                .on_seek(Message::Seek)
+                .on_mark(Message::MarkLoop),
+            time: Label::new("", TextSize::Detail, unplaced),
+            play: Button::new("PLAY", Message::PlayPause, unplaced),
+            previous: Button::new("PREV", Message::Previous, unplaced),
+            next: Button::new("NEXT", Message::Next, unplaced),
+            shuffle: Toggle::new("SHUFFLE", Message::ToggleShuffle, unplaced),
+            repeat: Button::new("", Message::CycleRepeat, unplaced),
+            transition: Button::new("", Message::CycleTransition, unplaced),
+            speed: steps(Message::SpeedDown, Message::ResetSpeed, Message::SpeedUp),
+            pitch: steps(Message::PitchDown, Message::ResetPitch, Message::PitchUp),
+            varispeed: Toggle::new("VARISPEED", Message::ToggleVarispeed, unplaced),
+            volume: Slider::new(
+                Orientation::Horizontal,
+                0.0..=1.0,
+                unplaced,
+                Message::SetVolume,
+            )
+            .with_caption("VOLUME"),
+            back: Button::new("BACK", Message::Back, unplaced).with_style(ButtonStyle::Exit),
+            eq: Button::new("EQ", Message::OpenEq, unplaced),
+        }
+    }
+
+    /// Positions the widgets within the menu.
     ///
-    /// # Returns
+    /// The cover art, title and progress bar sit at the top, the buttons in rows down the middle,
+    /// and the volume slider below them.
     ///
-    /// An [`io::Result`] containing a vector of song names, or an error if the directory cannot be read.
-    fn get_song_names(&self, dir_path: &str) -> io::Result<Vec<String>> {
-        let path = Path::new(dir_path);
-        let mut file_names = Vec::new();
-
-        if path.is_dir() {
-            for entry in fs::read_dir(path)? {
-                let entry = entry?;
-                let file_name = entry.file_name();
-                if let Some(name) = file_name.to_str() {
-                    file_names.push(name.to_owned());
-                }
-            }
+    /// # Arguments
+    ///
+    /// * `menu_rect` - The area of the menu.
+    fn layout(&mut self, menu_rect: Rect) {
+        let x = menu_rect.x();
+        let w = menu_rect.w();
+        self.title_row = Rect::from_x_y_w_h(x, menu_rect.top() - 60.0, w * 0.9, COVER_SIZE);
+        self.cover.set_rect(Rect::from_x_y_w_h(
+            self.title_row.left() + COVER_SIZE / 2.0,
+            self.title_row.y(),
+            COVER_SIZE,
+            COVER_SIZE,
+        ));
+        self.fit_title();
+        let progress = Rect::from_x_y_w_h(x, menu_rect.top() - 95.0, w * 0.8, 14.0);
+        self.progress.set_rect(progress);
+        self.time.set_rect(Rect::from_x_y_w_h(
+            x,
+            progress.bottom() - 12.0,
+            w * 0.8,
+            14.0,
+        ));
+
+        self.play.set_rect(full_row(menu_rect, 0.32));
+        self.previous.set_rect(half_row(menu_rect, 0.24, -1.0));
+        self.next.set_rect(half_row(menu_rect, 0.24, 1.0));
+        self.shuffle.set_rect(full_row(menu_rect, 0.16));
+        self.repeat.set_rect(full_row(menu_rect, 0.08));
+        self.transition.set_rect(full_row(menu_rect, 0.0));
+
+        // Speed and pitch rows: a step down, the current value (click to reset) and a step up.
+        for (buttons, offset) in [(&mut self.speed, -0.08), (&mut self.pitch, -0.16)] {
+            let y = full_row(menu_rect, offset).y();
+            buttons[0].set_rect(Rect::from_x_y_w_h(x - w * 0.3, y, w * 0.2, BUTTON_HEIGHT));
+            buttons[1].set_rect(Rect::from_x_y_w_h(x, y, w * 0.36, BUTTON_HEIGHT));
+            buttons[2].set_rect(Rect::from_x_y_w_h(x + w * 0.3, y, w * 0.2, BUTTON_HEIGHT));
         }
 
-        for name in &mut file_names {
-            *name = Song::get_title_from_file(name);
+        self.varispeed.set_rect(full_row(menu_rect, -0.24));
+        self.back.set_rect(half_row(menu_rect, -0.32, -1.0));
+        self.eq.set_rect(half_row(menu_rect, -0.32, 1.0));
+        self.volume.set_rect(Rect::from_x_y_w_h(
+            x,
+            menu_rect.y() - menu_rect.h() * 0.4,
+            w * 0.8,
+            16.0,
+        ));
+    }
+
+    /// Gives the title the whole row, or the part right of the cover art when there is some.
+    fn fit_title(&mut self) {
+        let row = self.title_row;
+        let title = if self.cover.has_texture() {
+            let left = self.cover.state().rect.right() + 10.0;
+            Rect::from_corners(pt2(left, row.bottom()), pt2(row.right(), row.top()))
+        } else {
+            row
+        };
+        self.now_playing.set_rect(title);
+    }
+
+    /// Returns the widgets that respond to the mouse.
+    fn widgets_mut(&mut self) -> Vec<&mut dyn Widget<Message = Message>> {
+        let mut widgets: Vec<&mut dyn Widget<Message = Message>> = vec![
+            &mut self.progress,
+            &mut self.play,
+            &mut self.previous,
+            &mut self.next,
+            &mut self.shuffle,
+            &mut self.repeat,
+            &mut self.transition,
+            &mut self.varispeed,
+            &mut self.volume,
+            &mut self.back,
+            &mut self.eq,
+        ];
+        for button in self.speed.iter_mut().chain(&mut self.pitch) {
+            widgets.push(button);
         }
+        widgets
+    }
 
-        Ok(file_names)
+    /// Shows or hides every widget.
+    fn set_visible(&mut self, visible: bool) {
+        self.cover.set_visible(visible);
+        self.now_playing.set_visible(visible);
+        self.time.set_visible(visible);
+        for widget in self.widgets_mut() {
+            widget.set_visible(visible);
+        }
     }
 
-    /// Creates song selection buttons dynamically by scanning the music library.
+    /// Draws every visible widget.
+    fn draw(&self, draw: &Draw) {
+        self.cover.draw(draw);
+        self.now_playing.draw(draw);
+        self.progress.draw(draw);
+        self.time.draw(draw);
+        let widgets: [&dyn Widget<Message = Message>; 10] = [
+            &self.play,
+            &self.previous,
+            &self.next,
+            &self.shuffle,
+            &self.repeat,
+            &self.transition,
+            &self.varispeed,
+            &self.volume,
+            &self.back,
+            &self.eq,
+        ];
+        for widget in widgets {
+            widget.draw(draw);
+        }
+        for button in self.speed.iter().chain(&self.pitch) {
+            button.draw(draw);
+        }
+    }
+}
+
+impl EqPanel {
+    /// Creates the EQ panel's widgets; [`EqPanel::layout`] positions them.
+    fn new() -> Self {
+        let unplaced = Rect::from_w_h(0.0, 0.0);
+        let bands = GRAPHIC_BANDS
+            .iter()
+            .enumerate()
+            .map(|(band, frequency)| {
+                let caption = if *frequency >= 1000.0 {
+                    format!("{}k", frequency / 1000.0)
+                } else {
+                    format!("{}", frequency)
+                };
+                Slider::new(
+                    Orientation::Vertical,
+                    -MAX_GAIN_DB..=MAX_GAIN_DB,
+                    unplaced,
+                    move |gain| Message::SetEqGain(band, gain),
+                )
+                .with_caption(&caption)
+            })
+            .collect();
+        EqPanel {
+            preset: Button::new("PRESET", Message::NextEqPreset, unplaced),
+            save: Button::new("SAVE", Message::SaveEqPreset, unplaced)
+                .with_style(ButtonStyle::Subdued),
+            done: Button::new("DONE", Message::CloseEq, unplaced).with_style(ButtonStyle::Exit),
+            bands,
+            slider_area: unplaced,
+        }
+    }
+
+    /// Positions the widgets within the menu, with the band sliders side by side in the middle.
+    ///
+    /// # Arguments
     ///
-    /// Buttons are created for each song file found in the directory and are appended to the menu's
-    /// button list, replacing any previously created song selection buttons.
-    fn create_song_buttons(&mut self) {
-        match self.get_song_names("music_library") {
-            Ok(song_names) => {
-                // Retain only the playback buttons; song selection buttons will be recreated.
-                self.buttons
-                    .retain(|b| b.tag == "play_button" || b.tag == "back_button");
+    /// * `menu_rect` - The area of the menu.
+    fn layout(&mut self, menu_rect: Rect) {
+        self.preset.set_rect(full_row(menu_rect, 0.32));
+        self.save.set_rect(half_row(menu_rect, -0.32, -1.0));
+        self.done.set_rect(half_row(menu_rect, -0.32, 1.0));
 
-                let button_width = self.menu_rect.w() * 0.7;
-                let button_height = 50.0;
-                let vertical_spacing = 60.0;
-                let start_y = self.menu_rect.top() - 80.0;
+        let top = menu_rect.y() + menu_rect.h() * 0.2;
+        let bottom = menu_rect.y() - menu_rect.h() * 0.15;
+        let area = Rect::from_x_y_w_h(
+            menu_rect.x(),
+            (top + bottom) / 2.0,
+            menu_rect.w() * 0.9,
+            top - bottom,
+        );
+        let column_width = area.w() / self.bands.len() as f32;
+        for (band, slider) in self.bands.iter_mut().enumerate() {
+            let x = area.left() + column_width * (band as f32 + 0.5);
+            slider.set_rect(Rect::from_x_y_w_h(x, area.y(), column_width, area.h()));
+        }
+        self.slider_area = area;
+    }
 
-                for (index, name) in song_names.iter().enumerate() {
-                    let tag = format!("song_{}", index);
-                    let button_rect = Rect::from_x_y_w_h(
-                        self.menu_rect.x(),
-                        start_y - (vertical_spacing * index as f32),
-                        button_width,
-                        button_height,
-                    );
-                    self.buttons.push(Button::new(name, &tag, button_rect));
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to retrieve song names: {}", e);
-            }
+    /// Returns the widgets that respond to the mouse.
+    fn widgets_mut(&mut self) -> Vec<&mut dyn Widget<Message = Message>> {
+        let mut widgets: Vec<&mut dyn Widget<Message = Message>> =
+            vec![&mut self.preset, &mut self.save, &mut self.done];
+        for slider in &mut self.bands {
+            widgets.push(slider);
+        }
+        widgets
+    }
+
+    /// Shows or hides every widget.
+    fn set_visible(&mut self, visible: bool) {
+        for widget in self.widgets_mut() {
+            widget.set_visible(visible);
         }
     }
+
+    /// Draws the buttons, the 0 dB line and the band sliders.
+    fn draw(&self, draw: &Draw) {
+        self.preset.draw(draw);
+        self.save.draw(draw);
+        self.done.draw(draw);
+
+        let area = self.slider_area;
+        draw.line()
+            .start(pt2(area.left(), area.y()))
+            .end(pt2(area.right(), area.y()))
+            .color(theme::current().button.normal)
+            .weight(1.0);
+        for slider in &self.bands {
+            slider.draw(draw);
+        }
+    }
+}
+
+/// Returns the area of a button spanning most of the menu's width.
+///
+/// # Arguments
+///
+/// * `menu_rect` - The area of the menu.
+/// * `offset` - Height of the button's centre above the menu's centre, as a share of its height.
+fn full_row(menu_rect: Rect, offset: f32) -> Rect {
+    Rect::from_x_y_w_h(
+        menu_rect.x(),
+        menu_rect.y() + menu_rect.h() * offset,
+        menu_rect.w() * 0.8,
+        BUTTON_HEIGHT,
+    )
+}
+
+/// Returns the area of one of two buttons sharing a row.
+///
+/// # Arguments
+///
+/// * `menu_rect` - The area of the menu.
+/// * `offset` - Height of the button's centre above the menu's centre, as a share of its height.
+/// * `side` - `-1.0` for the left button and `1.0` for the right one.
+fn half_row(menu_rect: Rect, offset: f32, side: f32) -> Rect {
+    Rect::from_x_y_w_h(
+        menu_rect.x() + side * menu_rect.w() * 0.21,
+        menu_rect.y() + menu_rect.h() * offset,
+        menu_rect.w() * 0.38,
+        BUTTON_HEIGHT,
+    )
 }
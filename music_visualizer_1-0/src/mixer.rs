@@ -0,0 +1,910 @@
+//! Mixer module
+//!
+//! Runs inside the output callback and decides, frame by frame, what is heard:
+//! - The current source on its own
+//! - A switch to the next source, either back-to-back (gapless) or with a crossfade
+//! - During a crossfade, the sum of the outgoing and incoming sources with equal-power gains
+//!
+//! In smart mode the fade is placed using each song's detected silence, so it starts where the
+//! music actually ends rather than at the end of the file.
+//!
+//! Sources played at a changed speed or pitch are read through a [`TimeStretcher`]. A source
+//! can also loop between two points, with a short crossfade at the seam.
+//!
+//! The callback never waits on the UI thread: changes arrive as [`MixerCommand`]s on a
+//! single-producer ring buffer, finished sources go back on another, and the position and end
+//! state are published through atomics.
+
+use crate::dsp::stretch::{PlaybackRate, TimeStretcher};
+use crate::song::Song;
+use rtrb::{Consumer, Producer, PushError, RingBuffer};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::FRAC_PI_2;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Length of the crossfade at an A–B loop's seam, in seconds.
+const LOOP_SEAM_SECONDS: f32 = 0.01;
+
+/// Number of commands that can wait for the output callback.
+const COMMAND_CAPACITY: usize = 64;
+
+/// Number of finished sources that can wait for the UI thread to free them.
+const RETIRED_CAPACITY: usize = 16;
+
+/// How the mixer moves from one queued track to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TransitionMode {
+    /// Start the next track on the sample after the current one ends.
+    #[default]
+    Gapless,
+    /// Fade the tracks over each other for the configured length before the end of the file.
+    Crossfade,
+    /// Crossfade, but skip trailing and leading silence so the fade starts where the music ends.
+    Smart,
+}
+
+impl TransitionMode {
+    /// Returns the next mode in the cycle Gapless → Crossfade → Smart → Gapless.
+    pub fn cycle(self) -> Self {
+        match self {
+            TransitionMode::Gapless => TransitionMode::Crossfade,
+            TransitionMode::Crossfade => TransitionMode::Smart,
+            TransitionMode::Smart => TransitionMode::Gapless,
+        }
+    }
+
+    /// Returns a short label for buttons.
+    pub fn label(self) -> &'static str {
+        match self {
+            TransitionMode::Gapless => "MIX: GAPLESS",
+            TransitionMode::Crossfade => "MIX: CROSSFADE",
+            TransitionMode::Smart => "MIX: SMART",
+        }
+    }
+}
+
+/// A decoded song as seen by the output callback.
+pub struct Source {
+    samples: Arc<Vec<f32>>,
+    channels: usize,
+    /// Index of the next frame to output.
+    frame: usize,
+    /// First frame above the silence threshold.
+    content_start: usize,
+    /// One past the last frame above the silence threshold.
+    content_end: usize,
+    /// Speed and pitch settings of the song.
+    rate: Arc<PlaybackRate>,
+    stretcher: TimeStretcher,
+    /// Whether the previous frame came from the stretcher.
+    stretching: bool,
+    /// One frame of stretcher output.
+    scratch: Vec<f32>,
+    /// Start and end frame of the A–B loop, if one is set.
+    loop_range: Option<(usize, usize)>,
+    /// Length of the crossfade at the loop seam, in frames.
+    seam_frames: usize,
+    /// Whether playback has jumped back to the loop start since the last seek.
+    looped: bool,
+}
+
+/// How the loop seam shapes the frame being played.
+enum Seam {
+    /// Blend towards the given frame before the loop start, by the given amount (0 to 1).
+    Blend(usize, f32),
+    /// Scale by the given gain, for loops starting too close to the beginning of the song to
+    /// blend with the audio before them.
+    Fade(f32),
+}
+
+impl Source {
+    /// Wraps a song's shared audio data.
+    pub fn new(song: &Song) -> Self {
+        let (content_start, content_end) = song.content_bounds();
+        let channels = song.channels().max(1);
+        Source {
+            samples: song.audio_data(),
+            channels,
+            frame: 0,
+            content_start,
+            content_end,
+            rate: song.playback_rate(),
+            stretcher: TimeStretcher::new(channels, song.sample_rate()),
+            stretching: false,
+            scratch: vec![0.0; channels],
+            loop_range: song.loop_range(),
+            seam_frames: (song.sample_rate() as f32 * LOOP_SEAM_SECONDS) as usize,
+            looped: false,
+        }
+    }
+
+    /// Moves to the given frame.
+    pub fn seek(&mut self, frame: usize) {
+        self.frame = frame.min(self.frames());
+        self.stretching = false;
+        self.looped = false;
+    }
+
+    /// Returns the index of the next frame to output.
+    pub fn position(&self) -> usize {
+        self.frame
+    }
+
+    /// Sets or clears the A–B loop.
+    pub fn set_loop(&mut self, loop_range: Option<(usize, usize)>) {
+        self.loop_range = loop_range;
+        self.looped = false;
+    }
+
+    /// Returns how many song frames are consumed per output frame.
+    fn speed(&self) -> f32 {
+        self.rate.speed()
+    }
+
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.frame >= self.frames()
+    }
+
+    /// Mixes one output frame into `out`, scaled by `gain`, mapping the source's channels onto
+    /// the output's.
+    ///
+    /// Mono sources are copied to every output channel; extra source channels are dropped.
+    /// Unless the song plays at normal speed and pitch, frames come from the stretcher.
+    fn mix_frame(&mut self, out: &mut [f32], gain: f32) {
+        // Wrap before checking for the end, so a loop ending on the last frame keeps looping.
+        if let Some((start, end)) = self.loop_range
+            && self.frame >= end
+        {
+            self.frame = start;
+            self.stretching = false;
+            self.looped = true;
+        }
+        if self.is_exhausted() {
+            return;
+        }
+
+        if self.rate.is_neutral() {
+            self.stretching = false;
+            let base = self.frame * self.channels;
+            let seam = self.loop_seam();
+            for (ch, sample) in out.iter_mut().enumerate() {
+                let source_ch = ch.min(self.channels - 1);
+                let value = match seam {
+                    // Blend towards the audio just before the loop start, so the jump lands on
+                    // exactly what was fading in.
+                    Some(Seam::Blend(other, t)) => {
+                        self.samples[base + source_ch] * (t * FRAC_PI_2).cos()
+                            + self.samples[other * self.channels + source_ch]
+                                * (t * FRAC_PI_2).sin()
+                    }
+                    Some(Seam::Fade(seam_gain)) => self.samples[base + source_ch] * seam_gain,
+                    None => self.samples[base + source_ch],
+                };
+                *sample += value * gain;
+            }
+            self.frame += 1;
+            return;
+        }
+
+        if !self.stretching {
+            self.stretcher.seek(self.frame);
+            self.stretching = true;
+        }
+        let (tempo, pitch) = self.rate.factors();
+        self.stretcher
+            .next_frame(&self.samples, tempo, pitch, &mut self.scratch);
+        for (ch, sample) in out.iter_mut().enumerate() {
+            let source_ch = ch.min(self.channels - 1);
+            *sample += self.scratch[source_ch] * gain;
+        }
+        if let Some((start, end)) = self.loop_range {
+            while self.stretcher.position() >= end {
+                self.stretcher.jump_back(end - start);
+            }
+        }
+        self.frame = self.stretcher.position();
+    }
+
+    /// Returns how the current frame is shaped when it lies in the crossfade around the loop
+    /// seam.
+    ///
+    /// The seam normally blends the end of the loop into the audio just before its start. When
+    /// too little audio comes before the start, the end fades out and the start fades back in
+    /// with equal-power curves instead.
+    fn loop_seam(&self) -> Option<Seam> {
+        let (start, end) = self.loop_range?;
+        let length = self.seam_frames.min((end - start) / 2);
+        if length == 0 {
+            return None;
+        }
+        let seam_start = end - length;
+        if (seam_start..end).contains(&self.frame) {
+            let offset = self.frame - seam_start;
+            let t = offset as f32 / length as f32;
+            return Some(if length <= start {
+                Seam::Blend(start - length + offset, t)
+            } else {
+                Seam::Fade((t * FRAC_PI_2).cos())
+            });
+        }
+        if self.looped && length > start && (start..start + length).contains(&self.frame) {
+            let t = (self.frame - start) as f32 / length as f32;
+            return Some(Seam::Fade((t * FRAC_PI_2).sin()));
+        }
+        None
+    }
+}
+
+/// A crossfade in progress.
+struct Fade {
+    outgoing: Source,
+    /// Frames of the fade already rendered.
+    position: usize,
+    /// Total length of the fade in frames.
+    length: usize,
+}
+
+/// A change to the mixer, sent from the UI thread and applied at the start of the next buffer.
+pub enum MixerCommand {
+    /// Make the source current, discarding the queued source and any crossfade.
+    Load(Source),
+    /// Queue the source to follow the current one, or clear the queued source.
+    Preload(Option<Source>),
+    /// Remove the current and queued sources.
+    Unload,
+    /// Start or pause output.
+    SetPlaying(bool),
+    /// Move the current source to the given frame, ending any crossfade.
+    Seek(usize),
+    /// Set or clear the A–B loop of the current source.
+    SetLoop(Option<(usize, usize)>),
+    /// Set the transition mode and the crossfade length in frames.
+    SetTransition(TransitionMode, usize),
+}
+
+/// Sources a single command can replace: the current, the queued and a fading source.
+const MAX_RETIRED_PER_COMMAND: usize = 3;
+
+/// Marks the absence of a current source in [`MixerStatus::position`].
+const NO_POSITION: usize = usize::MAX;
+
+/// What the mixer reports back to the UI thread, published after every buffer.
+struct MixerStatus {
+    /// Frame the current source plays next, or [`NO_POSITION`].
+    position: AtomicUsize,
+    /// Whether the current source has ended with nothing queued after it.
+    finished: AtomicBool,
+    /// Switches to the next source not yet reported to the UI.
+    advanced: AtomicUsize,
+    /// Number of commands applied so far.
+    applied: AtomicUsize,
+}
+
+/// The two-source mixer driven by the output callback.
+///
+/// The mixer is owned by the callback and never locked. The UI thread controls it through a
+/// [`MixerHandle`], and everything passed between the two goes through wait-free queues and
+/// atomics.
+pub struct Mixer {
+    /// Whether audio should be produced at all.
+    playing: bool,
+    /// The source currently playing (the incoming one during a fade).
+    current: Option<Source>,
+    /// The source queued to follow the current one.
+    next: Option<Source>,
+    /// Set once the current source is exhausted with nothing queued after it.
+    finished: bool,
+    /// How to move between sources.
+    transition: TransitionMode,
+    /// Crossfade length in frames.
+    fade_frames: usize,
+    fade: Option<Fade>,
+    commands: Consumer<MixerCommand>,
+    /// Sources the mixer has finished with. Freeing them would block the output callback, so
+    /// they are handed back to the UI thread instead.
+    retired: Producer<Source>,
+    status: Arc<MixerStatus>,
+    /// Number of commands applied so far.
+    applied: usize,
+}
+
+impl Mixer {
+    /// Creates a stopped mixer with nothing loaded, and the handle that controls it.
+    pub fn new() -> (Self, MixerHandle) {
+        let (command_tx, command_rx) = RingBuffer::new(COMMAND_CAPACITY);
+        let (retired_tx, retired_rx) = RingBuffer::new(RETIRED_CAPACITY);
+        let status = Arc::new(MixerStatus {
+            position: AtomicUsize::new(NO_POSITION),
+            finished: AtomicBool::new(false),
+            advanced: AtomicUsize::new(0),
+            applied: AtomicUsize::new(0),
+        });
+        let mixer = Mixer {
+            playing: false,
+            current: None,
+            next: None,
+            finished: false,
+            transition: TransitionMode::default(),
+            fade_frames: 0,
+            fade: None,
+            commands: command_rx,
+            retired: retired_tx,
+            status: Arc::clone(&status),
+            applied: 0,
+        };
+        let handle = MixerHandle {
+            commands: command_tx,
+            retired: retired_rx,
+            status,
+            pending: VecDeque::new(),
+            sent: 0,
+            position_sync: 0,
+            finished_sync: 0,
+            expected_position: None,
+        };
+        (mixer, handle)
+    }
+
+    /// Applies the commands sent since the last buffer and publishes the result, without
+    /// rendering any audio.
+    ///
+    /// Used while no output stream is running, so the UI still sees its commands take effect.
+    pub fn update(&mut self) {
+        self.apply_commands();
+        self.publish();
+    }
+
+    /// Fills an interleaved output buffer.
+    ///
+    /// Pending commands are applied first. The next source takes over either the moment the
+    /// current one ends (gapless) or at the fade point, after which both are summed until the
+    /// fade completes.
+    pub fn render(&mut self, data: &mut [f32], channels: usize) {
+        self.apply_commands();
+
+        for frame in data.chunks_mut(channels) {
+            frame.fill(0.0);
+            if !self.playing {
+                continue;
+            }
+
+            if self.should_switch() {
+                self.switch_to_next();
+            }
+
+            match &mut self.fade {
+                Some(fade) => {
+                    // A finished fade waiting for room among the retired sources plays only the
+                    // incoming source.
+                    let gain_in = if fade.position < fade.length {
+                        // Equal-power curves keep the perceived loudness constant through the fade.
+                        let t = fade.position as f32 / fade.length as f32;
+                        let (gain_in, gain_out) = ((t * FRAC_PI_2).sin(), (t * FRAC_PI_2).cos());
+                        fade.outgoing.mix_frame(frame, gain_out);
+                        fade.position += 1;
+                        gain_in
+                    } else {
+                        1.0
+                    };
+                    if let Some(current) = &mut self.current {
+                        current.mix_frame(frame, gain_in);
+                    }
+                    if fade.position >= fade.length
+                        && self.can_retire()
+                        && let Some(fade) = self.fade.take()
+                    {
+                        self.retire(Some(fade.outgoing));
+                    }
+                }
+                None => {
+                    if let Some(current) = &mut self.current {
+                        current.mix_frame(frame, 1.0);
+                    }
+                }
+            }
+        }
+
+        self.publish();
+    }
+
+    /// Applies the commands waiting in the queue, in order.
+    ///
+    /// A command is left in the queue while the retired sources lack room for the sources it
+    /// could replace; the UI thread takes them every frame.
+    fn apply_commands(&mut self) {
+        while self.retired.slots() >= MAX_RETIRED_PER_COMMAND {
+            let Ok(command) = self.commands.pop() else {
+                break;
+            };
+            self.apply(command);
+            self.applied += 1;
+        }
+    }
+
+    /// Applies one command.
+    fn apply(&mut self, command: MixerCommand) {
+        match command {
+            MixerCommand::Load(source) => {
+                self.finished = false;
+                let replaced = self.current.replace(source);
+                self.retire(replaced);
+                let next = self.next.take();
+                self.retire(next);
+                self.cancel_fade();
+            }
+            MixerCommand::Preload(source) => {
+                // A source queued after the current one already ran out starts straight away.
+                if self.finished && source.is_some() {
+                    self.finished = false;
+                }
+                let replaced = std::mem::replace(&mut self.next, source);
+                self.retire(replaced);
+            }
+            MixerCommand::Unload => {
+                self.finished = false;
+                let current = self.current.take();
+                self.retire(current);
+                let next = self.next.take();
+                self.retire(next);
+                self.cancel_fade();
+            }
+            MixerCommand::SetPlaying(playing) => self.playing = playing,
+            MixerCommand::Seek(frame) => {
+                if let Some(current) = &mut self.current {
+                    current.seek(frame);
+                }
+                self.finished = false;
+                self.cancel_fade();
+            }
+            MixerCommand::SetLoop(loop_range) => {
+                if let Some(current) = &mut self.current {
+                    current.set_loop(loop_range);
+                }
+            }
+            MixerCommand::SetTransition(mode, fade_frames) => {
+                self.transition = mode;
+                self.fade_frames = fade_frames;
+            }
+        }
+    }
+
+    /// Publishes the mixer's state for the UI thread.
+    fn publish(&self) {
+        let position = self.current.as_ref().map_or(NO_POSITION, Source::position);
+        self.status.position.store(position, Ordering::Relaxed);
+        self.status.finished.store(self.finished, Ordering::Relaxed);
+        // Released last, so a UI that sees a command applied also sees its effect.
+        self.status.applied.store(self.applied, Ordering::Release);
+    }
+
+    /// Ends any crossfade in progress, e.g. when the user skips to another song.
+    fn cancel_fade(&mut self) {
+        let outgoing = self.fade.take().map(|fade| fade.outgoing);
+        self.retire(outgoing);
+    }
+
+    /// Hands a source the mixer has finished with to the UI thread to free.
+    ///
+    /// Callers check for room first, so the source is never freed here.
+    fn retire(&mut self, source: Option<Source>) {
+        if let Some(source) = source {
+            let _ = self.retired.push(source);
+        }
+    }
+
+    /// Returns whether the retired sources have room for another.
+    ///
+    /// A source that cannot be retired yet is kept until the UI has taken the others, which it
+    /// does every frame.
+    fn can_retire(&self) -> bool {
+        !self.retired.is_full()
+    }
+
+    /// Returns whether the current source has reached the point where the next should start.
+    ///
+    /// The switch waits while a fade is in progress, or while the source it replaces could not
+    /// be retired.
+    fn should_switch(&mut self) -> bool {
+        if self.fade.is_some() {
+            return false;
+        }
+        let Some(current) = &self.current else {
+            return false;
+        };
+        // A looping source never reaches its end.
+        if current.loop_range.is_some() {
+            return false;
+        }
+
+        if self.next.is_none() {
+            if current.is_exhausted() {
+                self.finished = true;
+            }
+            return false;
+        }
+        if !self.can_retire() {
+            return false;
+        }
+
+        let end = match self.transition {
+            TransitionMode::Gapless => return current.is_exhausted(),
+            TransitionMode::Crossfade => current.frames(),
+            TransitionMode::Smart => current.content_end,
+        };
+        // The fade is measured in output frames; convert it to song frames at the current speed.
+        let fade_frames = (self.fade_frames as f32 * current.speed()) as usize;
+        current.frame >= end.saturating_sub(fade_frames) || current.is_exhausted()
+    }
+
+    /// Makes the next source current, starting a crossfade unless in gapless mode.
+    fn switch_to_next(&mut self) {
+        let Some(mut next) = self.next.take() else {
+            return;
+        };
+        let outgoing = self.current.take();
+
+        if self.transition != TransitionMode::Gapless
+            && self.fade_frames > 0
+            && let Some(outgoing) = outgoing
+        {
+            if self.transition == TransitionMode::Smart {
+                next.frame = next.content_start;
+            }
+            // Never fade for longer than the outgoing source has audio left.
+            let remaining = match self.transition {
+                TransitionMode::Smart => outgoing.content_end,
+                _ => outgoing.frames(),
+            }
+            .saturating_sub(outgoing.frame);
+            let remaining = (remaining as f32 / outgoing.speed()) as usize;
+            let length = self.fade_frames.min(remaining);
+            if length > 0 {
+                self.fade = Some(Fade {
+                    outgoing,
+                    position: 0,
+                    length,
+                });
+            } else {
+                self.retire(Some(outgoing));
+            }
+        } else {
+            self.retire(outgoing);
+        }
+
+        self.current = Some(next);
+        self.status.advanced.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The UI thread's side of the [`Mixer`].
+///
+/// Commands take effect at the start of the output callback's next buffer. Until then, the
+/// position and end state the UI reads are the ones its last command will produce, so it never
+/// acts on the state of a source it has already replaced.
+pub struct MixerHandle {
+    commands: Producer<MixerCommand>,
+    retired: Consumer<Source>,
+    status: Arc<MixerStatus>,
+    /// Commands that did not fit in the queue, sent before any newer command.
+    pending: VecDeque<MixerCommand>,
+    /// Number of commands sent so far.
+    sent: usize,
+    /// Number of commands that must be applied before the published position is current.
+    position_sync: usize,
+    /// Number of commands that must be applied before the published end state is current.
+    finished_sync: usize,
+    /// The position the last load, seek or unload leaves the current source at.
+    expected_position: Option<usize>,
+}
+
+impl MixerHandle {
+    /// Sends a command to the mixer.
+    ///
+    /// If the queue is full, the command is kept and sent on a later call, after the commands
+    /// kept before it.
+    pub fn send(&mut self, command: MixerCommand) {
+        match &command {
+            MixerCommand::Load(_) => {
+                self.expected_position = Some(0);
+                self.position_sync = self.sent + 1;
+                self.finished_sync = self.sent + 1;
+            }
+            MixerCommand::Seek(frame) => {
+                self.expected_position = self.position().map(|_| *frame);
+                self.position_sync = self.sent + 1;
+                self.finished_sync = self.sent + 1;
+            }
+            MixerCommand::Unload => {
+                self.expected_position = None;
+                self.position_sync = self.sent + 1;
+                self.finished_sync = self.sent + 1;
+            }
+            MixerCommand::Preload(Some(_)) => self.finished_sync = self.sent + 1,
+            _ => {}
+        }
+        self.sent += 1;
+        self.pending.push_back(command);
+        self.flush();
+    }
+
+    /// Sends the commands kept while the queue was full.
+    pub fn flush(&mut self) {
+        while let Some(command) = self.pending.pop_front() {
+            if let Err(PushError::Full(command)) = self.commands.push(command) {
+                self.pending.push_front(command);
+                break;
+            }
+        }
+    }
+
+    /// Returns the frame the current source will play next, if a source is loaded.
+    pub fn position(&self) -> Option<usize> {
+        if self.applied() < self.position_sync {
+            return self.expected_position;
+        }
+        match self.status.position.load(Ordering::Relaxed) {
+            NO_POSITION => None,
+            position => Some(position),
+        }
+    }
+
+    /// Returns whether the current source has ended with nothing queued after it.
+    pub fn is_finished(&self) -> bool {
+        self.applied() >= self.finished_sync && self.status.finished.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of switches to the next source since the last call, resetting the
+    /// count.
+    pub fn take_advanced(&mut self) -> usize {
+        self.status.advanced.swap(0, Ordering::Relaxed)
+    }
+
+    /// Frees the sources the mixer has finished with.
+    pub fn free_retired(&mut self) {
+        while self.retired.pop().is_ok() {}
+    }
+
+    fn applied(&self) -> usize {
+        self.status.applied.load(Ordering::Acquire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// Builds a mono source with the whole of `samples` counted as content.
+    fn source(samples: Vec<f32>) -> Source {
+        let frames = samples.len();
+        Source {
+            samples: Arc::new(samples),
+            channels: 1,
+            frame: 0,
+            content_start: 0,
+            content_end: frames,
+            rate: Arc::new(PlaybackRate::default()),
+            stretcher: TimeStretcher::new(1, SAMPLE_RATE),
+            stretching: false,
+            scratch: vec![0.0],
+            loop_range: None,
+            seam_frames: (SAMPLE_RATE as f32 * LOOP_SEAM_SECONDS) as usize,
+            looped: false,
+        }
+    }
+
+    fn sine(frames: usize, frequency: f32) -> Vec<f32> {
+        (0..frames)
+            .map(|i| (2.0 * PI * frequency * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    /// Starts a mixer playing `source`.
+    fn playing(source: Source) -> (Mixer, MixerHandle) {
+        let (mixer, mut handle) = Mixer::new();
+        handle.send(MixerCommand::Load(source));
+        handle.send(MixerCommand::SetPlaying(true));
+        (mixer, handle)
+    }
+
+    /// Renders `frames` mono frames.
+    fn render(mixer: &mut Mixer, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.0; frames];
+        mixer.render(&mut out, 1);
+        out
+    }
+
+    fn largest_step(samples: &[f32]) -> f32 {
+        samples
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn a_loop_ending_on_the_last_frame_keeps_looping() {
+        let mut looping = source((0..100).map(|i| i as f32).collect());
+        looping.seam_frames = 0;
+        looping.set_loop(Some((50, 100)));
+        let (mut mixer, handle) = playing(looping);
+
+        let out = render(&mut mixer, 300);
+        let expected: Vec<f32> = (0..100)
+            .chain((0..4).flat_map(|_| 50..100))
+            .map(|i| i as f32)
+            .collect();
+        assert_eq!(out, expected);
+        assert!(!handle.is_finished());
+        assert!((50..=100).contains(&handle.position().unwrap()));
+    }
+
+    #[test]
+    fn a_stretched_loop_ending_on_the_last_frame_keeps_looping() {
+        let frames = SAMPLE_RATE as usize;
+        let mut looping = source(sine(frames, 440.0));
+        looping.rate.set_speed(1.5);
+        looping.set_loop(Some((frames / 2, frames)));
+        looping.seek(frames / 2);
+        let (mut mixer, handle) = playing(looping);
+
+        for _ in 0..20 {
+            let out = render(&mut mixer, 1024);
+            let position = handle.position().unwrap();
+            assert!((frames / 2..frames).contains(&position), "at {}", position);
+            assert!(out.iter().any(|sample| sample.abs() > 0.5));
+        }
+        assert!(!handle.is_finished());
+    }
+
+    #[test]
+    fn the_loop_seam_blends_into_the_audio_before_the_start() {
+        let seamless = |seam_frames| {
+            let mut looping = source(sine(4000, 440.0));
+            looping.seam_frames = seam_frames;
+            looping.set_loop(Some((1000, 1523)));
+            let (mut mixer, _handle) = playing(looping);
+            largest_step(&render(&mut mixer, 3000))
+        };
+
+        // A 440 Hz sine moves at most about 0.35 per frame at 8 kHz.
+        assert!(seamless(0) > 0.9);
+        assert!(seamless(80) < 0.5);
+    }
+
+    #[test]
+    fn a_loop_from_the_start_fades_out_and_back_in() {
+        let mut looping = source(vec![1.0; 200]);
+        looping.seam_frames = 10;
+        looping.set_loop(Some((0, 100)));
+        let (mut mixer, _handle) = playing(looping);
+
+        let out = render(&mut mixer, 300);
+        let gain = |t: f32| (t * FRAC_PI_2).sin();
+        // The first pass starts at full volume.
+        assert!(out[..90].iter().all(|&sample| sample == 1.0));
+        for k in 0..10 {
+            let t = k as f32 / 10.0;
+            assert!((out[90 + k] - gain(1.0 - t)).abs() < 1e-6);
+            assert!((out[100 + k] - gain(t)).abs() < 1e-6);
+            assert!((out[190 + k] - gain(1.0 - t)).abs() < 1e-6);
+            assert!((out[200 + k] - gain(t)).abs() < 1e-6);
+        }
+        assert!(out[110..190].iter().all(|&sample| sample == 1.0));
+        assert!(largest_step(&out) <= 0.16);
+    }
+
+    /// Starts a mixer playing `first` with `second` queued, using the given transition.
+    fn queued(
+        first: Source,
+        second: Source,
+        mode: TransitionMode,
+        fade: usize,
+    ) -> (Mixer, MixerHandle) {
+        let (mixer, mut handle) = playing(first);
+        handle.send(MixerCommand::Preload(Some(second)));
+        handle.send(MixerCommand::SetTransition(mode, fade));
+        (mixer, handle)
+    }
+
+    #[test]
+    fn gapless_switches_on_the_next_frame() {
+        let first = source(vec![1.0; 100]);
+        let second = source(vec![10.0; 100]);
+        let (mut mixer, mut handle) = playing(first);
+        handle.send(MixerCommand::Preload(Some(second)));
+
+        let out = render(&mut mixer, 250);
+        assert!(out[..100].iter().all(|&sample| sample == 1.0));
+        assert!(out[100..200].iter().all(|&sample| sample == 10.0));
+        assert!(out[200..].iter().all(|&sample| sample == 0.0));
+        assert_eq!(handle.take_advanced(), 1);
+        assert!(handle.is_finished());
+    }
+
+    #[test]
+    fn crossfades_use_equal_power_gains() {
+        // Rendering each source alone against silence gives its gain at every frame.
+        let gains = |first: f32, second: f32| {
+            let (mut mixer, _handle) = queued(
+                source(vec![first; 100]),
+                source(vec![second; 100]),
+                TransitionMode::Crossfade,
+                20,
+            );
+            render(&mut mixer, 200)
+        };
+        let (outgoing, incoming) = (gains(1.0, 0.0), gains(0.0, 1.0));
+
+        assert!(outgoing[..80].iter().all(|&gain| gain == 1.0));
+        assert!(incoming[..80].iter().all(|&gain| gain == 0.0));
+        for k in 0..20 {
+            let t = k as f32 / 20.0;
+            assert!((outgoing[80 + k] - (t * FRAC_PI_2).cos()).abs() < 1e-6);
+            assert!((incoming[80 + k] - (t * FRAC_PI_2).sin()).abs() < 1e-6);
+            let power = outgoing[80 + k].powi(2) + incoming[80 + k].powi(2);
+            assert!((power - 1.0).abs() < 1e-5);
+        }
+        // The fade ends with the outgoing source, and the incoming one plays on alone.
+        assert!(outgoing[100..].iter().all(|&gain| gain == 0.0));
+        assert!(incoming[100..180].iter().all(|&gain| gain == 1.0));
+        assert!(incoming[180..].iter().all(|&gain| gain == 0.0));
+    }
+
+    #[test]
+    fn smart_fades_start_where_the_content_ends() {
+        let mut first = source(vec![1.0; 100]);
+        first.content_end = 60;
+        let mut second = source((0..100).map(|i| i as f32).collect());
+        second.content_start = 10;
+        let (mut mixer, mut handle) = queued(first, second, TransitionMode::Smart, 20);
+
+        let out = render(&mut mixer, 40);
+        assert!(out.iter().all(|&sample| sample == 1.0));
+        assert_eq!(handle.take_advanced(), 0);
+
+        // The incoming source skips its leading silence.
+        let out = render(&mut mixer, 1);
+        assert_eq!(out, [1.0]);
+        assert_eq!(handle.take_advanced(), 1);
+        assert_eq!(handle.position(), Some(11));
+
+        // The fade covers the rest of the outgoing content.
+        let out = render(&mut mixer, 20);
+        let expected = ((1..20).map(|k| {
+            let t = k as f32 / 20.0;
+            (t * FRAC_PI_2).cos() + (10 + k) as f32 * (t * FRAC_PI_2).sin()
+        }))
+        .chain([30.0]);
+        for (sample, expected) in out.iter().zip(expected) {
+            assert!(
+                (sample - expected).abs() < 1e-4,
+                "{} != {}",
+                sample,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn switches_are_counted_until_taken() {
+        let (mut mixer, mut handle) = playing(source(vec![1.0; 10]));
+        for _ in 0..2 {
+            handle.send(MixerCommand::Preload(Some(source(vec![1.0; 10]))));
+            render(&mut mixer, 15);
+            handle.free_retired();
+        }
+        render(&mut mixer, 10);
+
+        assert_eq!(handle.take_advanced(), 2);
+        assert_eq!(handle.take_advanced(), 0);
+        assert!(handle.is_finished());
+    }
+}
@@ -0,0 +1,1161 @@
+//! MPRIS module
+//!
+//! Publishes the player on the D-Bus session bus through MPRIS (the Media Player Remote
+//! Interfacing Specification), so media keys, desktop applets and tools such as `playerctl` can
+//! see what is playing and control it. Two interfaces are served at `/org/mpris/MediaPlayer2`:
+//! `org.mpris.MediaPlayer2`, which describes the application, and `org.mpris.MediaPlayer2.Player`,
+//! which holds the transport controls and the playing track.
+//!
+//! The bus is served from a thread of its own. Calls from the desktop are passed on as
+//! [`MprisCommand`]s, collected once per frame with [`MprisServer::poll`]; in return, the menu
+//! hands over the [`PlaybackState`] every frame and [`MprisServer::update`] signals what changed.
+
+use crate::artwork::Artwork;
+use crate::dsp::stretch::{MAX_SPEED, MIN_SPEED, PlaybackRate};
+use crate::player::Player;
+use crate::playlist;
+use crate::queue::{PlayQueue, RepeatMode};
+use crate::song::Song;
+use async_io::block_on;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Instant;
+use zbus::blocking::Connection;
+use zbus::blocking::connection::Builder;
+use zbus::blocking::object_server::InterfaceRef;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, Value};
+use zbus::{fdo, interface};
+
+/// Well-known bus name of the player. If another instance holds it, `.instance<pid>` is added.
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.music_visualizer";
+
+/// Object path both interfaces are served at, as the specification requires.
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// Name of the player shown by desktop applets.
+const IDENTITY: &str = "Music Visualizer";
+
+/// Track id meaning "no track", reserved by the specification.
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// How far, in seconds, the position may stray from where playback should have taken it before
+/// the difference counts as a seek.
+const SEEK_TOLERANCE: f64 = 0.5;
+
+/// Whether the player is playing, as MPRIS reports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    /// No song is loaded.
+    #[default]
+    Stopped,
+}
+
+impl PlaybackStatus {
+    /// Returns the status as the `PlaybackStatus` property spells it.
+    fn as_str(self) -> &'static str {
+        match self {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        }
+    }
+}
+
+/// The current track, as described to the desktop.
+#[derive(Clone)]
+pub struct NowPlaying {
+    pub path: PathBuf,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Length in seconds.
+    pub length: f64,
+    pub artwork: Option<Arc<Artwork>>,
+}
+
+impl NowPlaying {
+    /// Returns whether two descriptions differ in what the `Metadata` property shows.
+    ///
+    /// Artwork is compared by identity, as a song's artwork is decoded once when it loads.
+    fn differs_from(&self, other: &NowPlaying) -> bool {
+        let same_artwork = match (&self.artwork, &other.artwork) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        self.path != other.path
+            || self.title != other.title
+            || self.artist != other.artist
+            || self.album != other.album
+            || self.length != other.length
+            || !same_artwork
+    }
+
+    /// Returns the track's MPRIS track id, which stays the same for as long as the player runs.
+    fn track_id(&self) -> ObjectPath<'static> {
+        let mut hasher = DefaultHasher::new();
+        self.path.hash(&mut hasher);
+        // The specification reserves paths below `/org/mpris` for itself.
+        ObjectPath::from_string_unchecked(format!(
+            "/music_visualizer/track/{:016x}",
+            hasher.finish()
+        ))
+    }
+}
+
+/// What the player is doing, handed to [`MprisServer::update`] every frame.
+#[derive(Clone)]
+pub struct PlaybackState {
+    pub status: PlaybackStatus,
+    /// The loaded song, or `None` if there is none.
+    pub track: Option<NowPlaying>,
+    /// Position in the song, in seconds.
+    pub position: f64,
+    /// Playback speed, 1.0 being normal.
+    pub rate: f64,
+    /// Output volume, from 0.0 (silent) to 1.0 (full).
+    pub volume: f64,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+}
+
+impl Default for PlaybackState {
+    fn default() -> Self {
+        PlaybackState {
+            status: PlaybackStatus::Stopped,
+            track: None,
+            position: 0.0,
+            rate: 1.0,
+            volume: 1.0,
+            shuffle: false,
+            repeat: RepeatMode::Off,
+            can_go_next: false,
+            can_go_previous: false,
+        }
+    }
+}
+
+impl PlaybackState {
+    /// Describes what the player is doing.
+    ///
+    /// # Arguments
+    ///
+    /// * `song` - The loaded song, empty if there is none.
+    /// * `queue` - The play queue, whose current track is the song.
+    /// * `player` - The playback engine.
+    /// * `rate` - The playback speed setting.
+    /// * `playing` - Whether the song is playing rather than paused.
+    pub fn capture(
+        song: &Song,
+        queue: &PlayQueue,
+        player: &Player,
+        rate: &PlaybackRate,
+        playing: bool,
+    ) -> Self {
+        let sample_rate = song.sample_rate().max(1) as f64;
+        let track = queue
+            .current_track()
+            .filter(|_| !song.is_empty())
+            .map(|track| NowPlaying {
+                path: track.path.clone(),
+                title: track.title.clone(),
+                artist: track.artist.clone(),
+                album: track.album.clone(),
+                length: song.frames() as f64 / sample_rate,
+                artwork: song.artwork.clone(),
+            });
+        let status = match (&track, playing) {
+            (None, _) => PlaybackStatus::Stopped,
+            (Some(_), true) => PlaybackStatus::Playing,
+            (Some(_), false) => PlaybackStatus::Paused,
+        };
+        PlaybackState {
+            status,
+            position: player.position().unwrap_or(0) as f64 / sample_rate,
+            rate: rate.speed() as f64,
+            volume: player.volume() as f64,
+            shuffle: queue.shuffle(),
+            repeat: queue.repeat(),
+            can_go_next: track.is_some() && queue.has_next(),
+            can_go_previous: track.is_some(),
+            track,
+        }
+    }
+}
+
+/// What the desktop asked the player to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MprisCommand {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    /// Move the position by an offset in seconds; negative offsets move back.
+    Seek(f64),
+    /// Jump to a position in the current song, in seconds.
+    SetPosition(f64),
+    SetRate(f64),
+    SetVolume(f64),
+    SetShuffle(bool),
+    SetRepeat(RepeatMode),
+    /// Play a file or folder.
+    Open(PathBuf),
+}
+
+impl MprisCommand {
+    /// Turns a command into the change it asks of the player, given what the player is doing.
+    ///
+    /// `Play`, `Pause` and `PlayPause` become a `PlayPause` toggle when the status should
+    /// change, `Seek` becomes a `SetPosition`, or `Next` when it goes past the end, and
+    /// `SetShuffle` is kept only when it changes the setting. A rate of 0.0 acts as `Pause`, as
+    /// the MPRIS specification asks. Commands that need a song are dropped while none is loaded,
+    /// and positions, offsets, rates and volumes that are not finite numbers are dropped always.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - What the player is doing now.
+    ///
+    /// # Returns
+    ///
+    /// The command to carry out, or `None` if there is nothing to do.
+    pub fn resolve(self, state: &PlaybackState) -> Option<MprisCommand> {
+        let length = state.track.as_ref().map(|track| track.length);
+        let playing = state.status == PlaybackStatus::Playing;
+        if let MprisCommand::Seek(value)
+        | MprisCommand::SetPosition(value)
+        | MprisCommand::SetRate(value)
+        | MprisCommand::SetVolume(value) = self
+            && !value.is_finite()
+        {
+            return None;
+        }
+        match self {
+            MprisCommand::Play | MprisCommand::Pause | MprisCommand::PlayPause => {
+                let toggle = match self {
+                    MprisCommand::Play => !playing,
+                    MprisCommand::Pause => playing,
+                    _ => true,
+                };
+                (length.is_some() && toggle).then_some(MprisCommand::PlayPause)
+            }
+            MprisCommand::Stop | MprisCommand::Next | MprisCommand::Previous => {
+                length.map(|_| self)
+            }
+            MprisCommand::Seek(offset) => {
+                let length = length?;
+                let position = state.position + offset;
+                // Seeking past the end skips to the next track.
+                Some(if position >= length {
+                    MprisCommand::Next
+                } else {
+                    MprisCommand::SetPosition(position.max(0.0))
+                })
+            }
+            MprisCommand::SetPosition(position) => {
+                Some(MprisCommand::SetPosition(position.clamp(0.0, length?)))
+            }
+            MprisCommand::SetShuffle(shuffle) => (shuffle != state.shuffle).then_some(self),
+            MprisCommand::SetRate(0.0) => MprisCommand::Pause.resolve(state),
+            MprisCommand::SetRate(_)
+            | MprisCommand::SetVolume(_)
+            | MprisCommand::SetRepeat(_)
+            | MprisCommand::Open(_) => Some(self),
+        }
+    }
+}
+
+/// Publishes the player over MPRIS for as long as it is kept.
+pub struct MprisServer {
+    /// Kept alive for as long as the player should stay on the bus.
+    _connection: Connection,
+    player: InterfaceRef<PlayerInterface>,
+    commands: Receiver<MprisCommand>,
+    /// The position in microseconds, shared with the interface so it is current when read.
+    position: Arc<AtomicI64>,
+    /// The state last published, and when it was.
+    published: PlaybackState,
+    published_at: Instant,
+    /// The file the current artwork was written to for its art URL.
+    art_file: Option<PathBuf>,
+    /// Number of artwork files written, so each gets a new name applets have not cached.
+    art_files_written: u32,
+}
+
+impl MprisServer {
+    /// Connects to the session bus and publishes the player.
+    ///
+    /// # Returns
+    ///
+    /// The server, or `None` (after reporting why) if there is no session bus.
+    pub fn start() -> Option<Self> {
+        match Self::serve(Builder::session()) {
+            Ok(server) => {
+                println!("🎛️ Media keys and desktop controls connected over MPRIS.");
+                Some(server)
+            }
+            Err(e) => {
+                eprintln!("⚠️ Could not publish the player over MPRIS: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Collects what the desktop asked for since the last call.
+    ///
+    /// # Returns
+    ///
+    /// The commands, in the order they were received.
+    pub fn poll(&mut self) -> Vec<MprisCommand> {
+        self.commands.try_iter().collect()
+    }
+
+    /// Publishes the playback state, signalling the properties that changed.
+    ///
+    /// A position that jumped rather than moved on with playback is signalled as a seek.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - What the player is doing now.
+    pub fn update(&mut self, state: PlaybackState) {
+        let now = Instant::now();
+        let seeked = self.has_seeked(&state, now);
+        self.position
+            .store(microseconds(state.position), Ordering::Relaxed);
+
+        let previous = &self.published;
+        let track_changed = match (&previous.track, &state.track) {
+            (Some(a), Some(b)) => a.differs_from(b),
+            (None, None) => false,
+            _ => true,
+        };
+        let status_changed = previous.status != state.status;
+        let rate_changed = previous.rate != state.rate;
+        let volume_changed = previous.volume != state.volume;
+        let shuffle_changed = previous.shuffle != state.shuffle;
+        let repeat_changed = previous.repeat != state.repeat;
+        let next_changed = previous.can_go_next != state.can_go_next;
+        let previous_changed = previous.can_go_previous != state.can_go_previous;
+        let loaded_changed = previous.track.is_some() != state.track.is_some();
+
+        let changed = track_changed
+            || status_changed
+            || rate_changed
+            || volume_changed
+            || shuffle_changed
+            || repeat_changed
+            || next_changed
+            || previous_changed;
+        if changed || seeked {
+            let art_url = track_changed.then(|| self.write_artwork(&state));
+            let mut player = self.player.get_mut();
+            player.state = state.clone();
+            if let Some(art_url) = art_url {
+                player.art_url = art_url;
+            }
+            let emitter = self.player.signal_emitter();
+            let result = block_on(async {
+                if track_changed {
+                    player.metadata_changed(emitter).await?;
+                }
+                if status_changed {
+                    player.playback_status_changed(emitter).await?;
+                }
+                if rate_changed {
+                    player.rate_changed(emitter).await?;
+                }
+                if volume_changed {
+                    player.volume_changed(emitter).await?;
+                }
+                if shuffle_changed {
+                    player.shuffle_changed(emitter).await?;
+                }
+                if repeat_changed {
+                    player.loop_status_changed(emitter).await?;
+                }
+                if next_changed {
+                    player.can_go_next_changed(emitter).await?;
+                }
+                if previous_changed {
+                    player.can_go_previous_changed(emitter).await?;
+                }
+                if loaded_changed {
+                    player.can_play_changed(emitter).await?;
+                    player.can_pause_changed(emitter).await?;
+                    player.can_seek_changed(emitter).await?;
+                }
+                if seeked {
+                    PlayerInterface::seeked(emitter, microseconds(state.position)).await?;
+                }
+                Ok::<_, zbus::Error>(())
+            });
+            if let Err(e) = result {
+                eprintln!("⚠️ Could not signal a change over MPRIS: {}", e);
+            }
+        }
+
+        self.published = state;
+        self.published_at = now;
+    }
+
+    // ============================================================================
+    // Private Helper Methods
+    // ============================================================================
+
+    /// Serves both interfaces on a bus and claims the player's bus name.
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - The connection to the bus, not yet made.
+    fn serve(builder: zbus::Result<Builder<'_>>) -> zbus::Result<Self> {
+        let (sender, commands) = mpsc::channel();
+        let position = Arc::new(AtomicI64::new(0));
+        let connection = builder?
+            .serve_at(OBJECT_PATH, RootInterface)?
+            .serve_at(
+                OBJECT_PATH,
+                PlayerInterface {
+                    commands: sender,
+                    state: PlaybackState::default(),
+                    art_url: None,
+                    position: Arc::clone(&position),
+                },
+            )?
+            .build()?;
+        // Another instance of the player may hold the name already.
+        if connection.request_name(BUS_NAME).is_err() {
+            connection.request_name(format!("{}.instance{}", BUS_NAME, process::id()))?;
+        }
+        let player = connection
+            .object_server()
+            .interface::<_, PlayerInterface>(OBJECT_PATH)?;
+        Ok(MprisServer {
+            _connection: connection,
+            player,
+            commands,
+            position,
+            published: PlaybackState::default(),
+            published_at: Instant::now(),
+            art_file: None,
+            art_files_written: 0,
+        })
+    }
+
+    /// Returns whether the position jumped since the last update rather than moving on with
+    /// playback. Changing songs is not a seek.
+    fn has_seeked(&self, state: &PlaybackState, now: Instant) -> bool {
+        let previous = &self.published;
+        let same_song = match (&previous.track, &state.track) {
+            (Some(a), Some(b)) => a.path == b.path,
+            _ => false,
+        };
+        if !same_song {
+            return false;
+        }
+        let played = match previous.status {
+            PlaybackStatus::Playing => {
+                now.duration_since(self.published_at).as_secs_f64() * previous.rate
+            }
+            _ => 0.0,
+        };
+        (state.position - (previous.position + played)).abs() > SEEK_TOLERANCE
+    }
+
+    /// Writes the artwork of the current song to a PNG file in the temporary folder, replacing
+    /// the previous song's.
+    ///
+    /// # Returns
+    ///
+    /// The `file://` URL of the artwork, or `None` if the song has none or it could not be
+    /// written.
+    fn write_artwork(&mut self, state: &PlaybackState) -> Option<String> {
+        if let Some(old) = self.art_file.take() {
+            fs::remove_file(old).ok();
+        }
+        let artwork = state.track.as_ref()?.artwork.as_ref()?;
+        self.art_files_written += 1;
+        let path = env::temp_dir().join(format!(
+            "music_visualizer-{}-{}.png",
+            process::id(),
+            self.art_files_written
+        ));
+        if let Err(e) = artwork.image.save(&path) {
+            eprintln!(
+                "⚠️ Could not write the artwork to '{}': {}",
+                path.display(),
+                e
+            );
+            return None;
+        }
+        let url = playlist::file_url(&path);
+        self.art_file = Some(path);
+        Some(url)
+    }
+}
+
+impl Drop for MprisServer {
+    fn drop(&mut self) {
+        if let Some(path) = self.art_file.take() {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+/// Converts seconds to the microseconds MPRIS counts time in.
+fn microseconds(seconds: f64) -> i64 {
+    (seconds * 1_000_000.0).round() as i64
+}
+
+/// Converts microseconds to seconds.
+fn seconds(microseconds: i64) -> f64 {
+    microseconds as f64 / 1_000_000.0
+}
+
+// ============================================================================
+// D-Bus Interfaces
+// ============================================================================
+
+/// The `org.mpris.MediaPlayer2` interface: the application itself.
+struct RootInterface;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl RootInterface {
+    /// Does nothing; the window cannot be brought to the front from here.
+    fn raise(&self) {}
+
+    /// Does nothing; the player is only quit from its window.
+    fn quit(&self) {}
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn identity(&self) -> &str {
+        IDENTITY
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["file".to_string()]
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec!["audio/wav".to_string(), "audio/x-wav".to_string()]
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface: the transport controls and the current song.
+struct PlayerInterface {
+    commands: Sender<MprisCommand>,
+    /// The state last published.
+    state: PlaybackState,
+    /// The `file://` URL of the current song's artwork.
+    art_url: Option<String>,
+    /// The position in microseconds, kept current every frame.
+    position: Arc<AtomicI64>,
+}
+
+impl PlayerInterface {
+    /// Passes a command on to the player; it is carried out on the next frame.
+    fn send(&self, command: MprisCommand) {
+        // The receiver only goes away as the player shuts down.
+        self.commands.send(command).ok();
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl PlayerInterface {
+    fn next(&self) {
+        self.send(MprisCommand::Next);
+    }
+
+    fn previous(&self) {
+        self.send(MprisCommand::Previous);
+    }
+
+    fn pause(&self) {
+        self.send(MprisCommand::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.send(MprisCommand::PlayPause);
+    }
+
+    fn stop(&self) {
+        self.send(MprisCommand::Stop);
+    }
+
+    fn play(&self) {
+        self.send(MprisCommand::Play);
+    }
+
+    /// Moves the position by an offset in microseconds.
+    fn seek(&self, offset: i64) {
+        self.send(MprisCommand::Seek(seconds(offset)));
+    }
+
+    /// Jumps to a position in microseconds, unless the track has changed since the caller
+    /// looked or the position is outside the track.
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+        let Some(track) = &self.state.track else {
+            return;
+        };
+        let position = seconds(position);
+        if track.track_id() == track_id && (0.0..=track.length).contains(&position) {
+            self.send(MprisCommand::SetPosition(position));
+        }
+    }
+
+    /// Plays a file given by its `file://` URL.
+    fn open_uri(&self, uri: &str) -> fdo::Result<()> {
+        let path = playlist::path_from_file_url(uri)
+            .ok_or_else(|| fdo::Error::NotSupported(format!("Cannot open '{}'", uri)))?;
+        self.send(MprisCommand::Open(path));
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        self.state.status.as_str()
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &str {
+        match self.state.repeat {
+            RepeatMode::Off => "None",
+            RepeatMode::One => "Track",
+            RepeatMode::All => "Playlist",
+        }
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&mut self, status: &str) -> fdo::Result<()> {
+        let repeat = match status {
+            "None" => RepeatMode::Off,
+            "Track" => RepeatMode::One,
+            "Playlist" => RepeatMode::All,
+            _ => {
+                return Err(fdo::Error::InvalidArgs(format!(
+                    "Unknown loop status '{}'",
+                    status
+                )));
+            }
+        };
+        self.send(MprisCommand::SetRepeat(repeat));
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        self.state.rate
+    }
+
+    #[zbus(property)]
+    fn set_rate(&mut self, rate: f64) {
+        self.send(MprisCommand::SetRate(rate));
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.state.shuffle
+    }
+
+    #[zbus(property)]
+    fn set_shuffle(&mut self, shuffle: bool) {
+        self.send(MprisCommand::SetShuffle(shuffle));
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<&'static str, Value<'static>> {
+        let mut metadata = HashMap::new();
+        let Some(track) = &self.state.track else {
+            metadata.insert(
+                "mpris:trackid",
+                Value::from(ObjectPath::from_static_str_unchecked(NO_TRACK)),
+            );
+            return metadata;
+        };
+        metadata.insert("mpris:trackid", Value::from(track.track_id()));
+        metadata.insert("mpris:length", Value::from(microseconds(track.length)));
+        metadata.insert("xesam:title", Value::from(track.title.clone()));
+        metadata.insert("xesam:url", Value::from(playlist::file_url(&track.path)));
+        if let Some(artist) = &track.artist {
+            metadata.insert("xesam:artist", Value::from(vec![artist.clone()]));
+        }
+        if let Some(album) = &track.album {
+            metadata.insert("xesam:album", Value::from(album.clone()));
+        }
+        if let Some(url) = &self.art_url {
+            metadata.insert("mpris:artUrl", Value::from(url.clone()));
+        }
+        metadata
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state.volume
+    }
+
+    #[zbus(property)]
+    fn set_volume(&mut self, volume: f64) {
+        self.send(MprisCommand::SetVolume(volume));
+    }
+
+    /// The position in microseconds. Changes are not signalled; jumps are, by `Seeked`.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.position.load(Ordering::Relaxed)
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn minimum_rate(&self) -> f64 {
+        MIN_SPEED as f64
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn maximum_rate(&self) -> f64 {
+        MAX_SPEED as f64
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.state.can_go_next
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.state.can_go_previous
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.state.track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.state.track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.state.track.is_some()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::RgbaImage;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::thread;
+    use std::time::Duration;
+    use zbus::MatchRule;
+    use zbus::blocking::{MessageIterator, Proxy};
+    use zbus::message::{self, Message};
+    use zbus::proxy::CacheProperties;
+    use zbus::zvariant::OwnedValue;
+
+    const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+
+    /// How long to wait for a signal that should arrive.
+    const SIGNAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A private session bus, run by `dbus-daemon` for as long as it is kept.
+    struct TestBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl TestBus {
+        /// Starts a bus, panicking if `dbus-daemon` cannot be started.
+        ///
+        /// The tests that need it are ignored by default; run them with `cargo test --
+        /// --ignored` where `dbus-daemon` is installed.
+        fn start() -> Self {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("could not start dbus-daemon");
+            let mut address = String::new();
+            let stdout = daemon.stdout.take().expect("dbus-daemon output is piped");
+            BufReader::new(stdout)
+                .read_line(&mut address)
+                .expect("dbus-daemon did not print its address");
+            assert!(!address.trim().is_empty(), "dbus-daemon printed no address");
+            TestBus {
+                daemon,
+                address: address.trim().to_string(),
+            }
+        }
+
+        /// Publishes a player on the bus.
+        fn server(&self) -> MprisServer {
+            MprisServer::serve(Builder::address(self.address.as_str())).unwrap()
+        }
+
+        /// Connects a client, standing in for the desktop.
+        fn client(&self) -> Connection {
+            Builder::address(self.address.as_str())
+                .unwrap()
+                .build()
+                .unwrap()
+        }
+    }
+
+    impl Drop for TestBus {
+        fn drop(&mut self) {
+            self.daemon.kill().ok();
+            self.daemon.wait().ok();
+        }
+    }
+
+    /// Returns a proxy for the player interface that reads properties afresh every time.
+    fn player_proxy(client: &Connection) -> Proxy<'_> {
+        zbus::blocking::proxy::Builder::new(client)
+            .destination(BUS_NAME)
+            .unwrap()
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface(PLAYER)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap()
+    }
+
+    /// Subscribes to a signal from the player, passing each one on to a channel so it can be
+    /// waited for with a timeout.
+    fn signals(
+        client: &Connection,
+        interface: &'static str,
+        member: &'static str,
+    ) -> Receiver<Message> {
+        let rule = MatchRule::builder()
+            .msg_type(message::Type::Signal)
+            .path(OBJECT_PATH)
+            .unwrap()
+            .interface(interface)
+            .unwrap()
+            .member(member)
+            .unwrap()
+            .build();
+        let messages = MessageIterator::for_match_rule(rule, client, None).unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for message in messages.flatten() {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Returns the state of a tagged song playing at a position, in seconds.
+    fn playing_state(position: f64) -> PlaybackState {
+        PlaybackState {
+            status: PlaybackStatus::Playing,
+            track: Some(NowPlaying {
+                path: PathBuf::from("/music/Blue Monday.wav"),
+                title: "Blue Monday".to_string(),
+                artist: Some("New Order".to_string()),
+                album: Some("Power, Corruption & Lies".to_string()),
+                length: 200.0,
+                artwork: None,
+            }),
+            position,
+            volume: 0.5,
+            repeat: RepeatMode::All,
+            can_go_next: true,
+            can_go_previous: true,
+            ..PlaybackState::default()
+        }
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn method_calls_become_commands() {
+        let bus = TestBus::start();
+        let mut server = bus.server();
+        server.update(playing_state(10.0));
+        let client = bus.client();
+        let player = player_proxy(&client);
+
+        for method in ["PlayPause", "Next", "Previous", "Stop"] {
+            player.call_method(method, &()).unwrap();
+        }
+        player.call_method("Seek", &(-2_500_000i64)).unwrap();
+        assert_eq!(
+            server.poll(),
+            [
+                MprisCommand::PlayPause,
+                MprisCommand::Next,
+                MprisCommand::Previous,
+                MprisCommand::Stop,
+                MprisCommand::Seek(-2.5),
+            ]
+        );
+
+        // Only positions in the current track are taken.
+        let track_id = playing_state(0.0).track.unwrap().track_id();
+        let other_id = ObjectPath::from_static_str_unchecked("/music_visualizer/track/0");
+        player
+            .call_method("SetPosition", &(&track_id, 30_000_000i64))
+            .unwrap();
+        player
+            .call_method("SetPosition", &(&other_id, 30_000_000i64))
+            .unwrap();
+        player
+            .call_method("SetPosition", &(&track_id, 300_000_000i64))
+            .unwrap();
+        assert_eq!(server.poll(), [MprisCommand::SetPosition(30.0)]);
+
+        player
+            .call_method("OpenUri", &("file:///music/New%20Order.wav"))
+            .unwrap();
+        assert!(
+            player
+                .call_method("OpenUri", &("https://example.com/song.wav"))
+                .is_err()
+        );
+        assert_eq!(
+            server.poll(),
+            [MprisCommand::Open(PathBuf::from("/music/New Order.wav"))]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn property_writes_become_commands() {
+        let bus = TestBus::start();
+        let mut server = bus.server();
+        let client = bus.client();
+        let player = player_proxy(&client);
+
+        player.set_property("Volume", 0.25).unwrap();
+        player.set_property("Shuffle", true).unwrap();
+        player.set_property("LoopStatus", "Track").unwrap();
+        player.set_property("Rate", 1.5).unwrap();
+        assert!(player.set_property("LoopStatus", "Forever").is_err());
+        assert_eq!(
+            server.poll(),
+            [
+                MprisCommand::SetVolume(0.25),
+                MprisCommand::SetShuffle(true),
+                MprisCommand::SetRepeat(RepeatMode::One),
+                MprisCommand::SetRate(1.5),
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn properties_follow_the_playback_state() {
+        let bus = TestBus::start();
+        let mut server = bus.server();
+        let client = bus.client();
+        let player = player_proxy(&client);
+
+        let metadata = || {
+            player
+                .get_property::<HashMap<String, OwnedValue>>("Metadata")
+                .unwrap()
+        };
+        assert_eq!(
+            player.get_property::<String>("PlaybackStatus").unwrap(),
+            "Stopped"
+        );
+        assert!(!player.get_property::<bool>("CanPlay").unwrap());
+        assert_eq!(
+            ObjectPath::try_from(metadata()["mpris:trackid"].clone()).unwrap(),
+            ObjectPath::from_static_str_unchecked(NO_TRACK)
+        );
+
+        let mut state = playing_state(12.5);
+        if let Some(track) = &mut state.track {
+            track.artwork = Some(Arc::new(Artwork {
+                image: RgbaImage::new(4, 4),
+                palette: Vec::new(),
+            }));
+        }
+        server.update(state);
+        assert_eq!(
+            player.get_property::<String>("PlaybackStatus").unwrap(),
+            "Playing"
+        );
+        assert_eq!(
+            player.get_property::<String>("LoopStatus").unwrap(),
+            "Playlist"
+        );
+        assert_eq!(player.get_property::<f64>("Volume").unwrap(), 0.5);
+        assert_eq!(player.get_property::<i64>("Position").unwrap(), 12_500_000);
+        assert!(player.get_property::<bool>("CanGoNext").unwrap());
+        assert!(player.get_property::<bool>("CanSeek").unwrap());
+
+        let metadata = metadata();
+        let text = |key: &str| String::try_from(metadata[key].clone()).unwrap();
+        assert_eq!(text("xesam:title"), "Blue Monday");
+        assert_eq!(text("xesam:album"), "Power, Corruption & Lies");
+        assert_eq!(
+            Vec::<String>::try_from(metadata["xesam:artist"].clone()).unwrap(),
+            ["New Order"]
+        );
+        assert_eq!(
+            i64::try_from(metadata["mpris:length"].clone()).unwrap(),
+            200_000_000
+        );
+        let art_url = text("mpris:artUrl");
+        let art_file = playlist::path_from_file_url(&art_url).unwrap();
+        assert!(art_file.is_file());
+
+        // The artwork file goes with the song.
+        server.update(PlaybackState::default());
+        assert!(!art_file.exists());
+        assert_eq!(
+            player.get_property::<String>("PlaybackStatus").unwrap(),
+            "Stopped"
+        );
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn changes_are_signalled() {
+        let bus = TestBus::start();
+        let mut server = bus.server();
+        let client = bus.client();
+        let changes = signals(
+            &client,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+        );
+        let seeks = signals(&client, PLAYER, "Seeked");
+        let changed_names = || {
+            let message = changes.recv_timeout(SIGNAL_TIMEOUT).unwrap();
+            let (interface, changed, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                message.body().deserialize().unwrap();
+            assert_eq!(interface, PLAYER);
+            let mut names: Vec<String> = changed.into_keys().collect();
+            names.sort();
+            names
+        };
+
+        let mut state = playing_state(10.0);
+        state.status = PlaybackStatus::Paused;
+        server.update(state.clone());
+        let mut names = Vec::new();
+        while let Ok(message) = changes.recv_timeout(Duration::from_millis(500)) {
+            let (_, changed, _): (String, HashMap<String, OwnedValue>, Vec<String>) =
+                message.body().deserialize().unwrap();
+            names.extend(changed.into_keys());
+        }
+        for name in [
+            "Metadata",
+            "PlaybackStatus",
+            "Volume",
+            "CanGoNext",
+            "CanPlay",
+        ] {
+            assert!(
+                names.iter().any(|n| n == name),
+                "{} was not signalled",
+                name
+            );
+        }
+
+        // Nothing changed, nothing is signalled.
+        server.update(state.clone());
+        state.status = PlaybackStatus::Playing;
+        server.update(state.clone());
+        assert_eq!(changed_names(), ["PlaybackStatus"]);
+        assert!(seeks.try_recv().is_err());
+
+        // A jump in the position is a seek; playing on is not.
+        state.position = 60.0;
+        server.update(state.clone());
+        let seeked = seeks.recv_timeout(SIGNAL_TIMEOUT).unwrap();
+        assert_eq!(seeked.body().deserialize::<i64>().unwrap(), 60_000_000);
+        state.position = 60.05;
+        server.update(state);
+        assert!(seeks.recv_timeout(Duration::from_millis(300)).is_err());
+        assert!(changes.try_recv().is_err());
+    }
+
+    #[test]
+    fn commands_resolve_against_the_state() {
+        let playing = playing_state(10.0);
+        let resolve = |command: MprisCommand| command.resolve(&playing);
+        assert_eq!(resolve(MprisCommand::Play), None);
+        assert_eq!(resolve(MprisCommand::Pause), Some(MprisCommand::PlayPause));
+        assert_eq!(
+            resolve(MprisCommand::Seek(-30.0)),
+            Some(MprisCommand::SetPosition(0.0))
+        );
+        assert_eq!(
+            resolve(MprisCommand::Seek(5.0)),
+            Some(MprisCommand::SetPosition(15.0))
+        );
+        assert_eq!(resolve(MprisCommand::Seek(190.0)), Some(MprisCommand::Next));
+        assert_eq!(
+            resolve(MprisCommand::SetPosition(500.0)),
+            Some(MprisCommand::SetPosition(200.0))
+        );
+        assert_eq!(resolve(MprisCommand::SetShuffle(false)), None);
+        assert_eq!(
+            resolve(MprisCommand::SetRate(0.0)),
+            Some(MprisCommand::PlayPause)
+        );
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(resolve(MprisCommand::SetVolume(value)), None);
+            assert_eq!(resolve(MprisCommand::SetRate(value)), None);
+            assert_eq!(resolve(MprisCommand::Seek(value)), None);
+            assert_eq!(resolve(MprisCommand::SetPosition(value)), None);
+        }
+
+        let stopped = PlaybackState::default();
+        assert_eq!(MprisCommand::PlayPause.resolve(&stopped), None);
+        assert_eq!(MprisCommand::Next.resolve(&stopped), None);
+        assert_eq!(
+            MprisCommand::SetVolume(0.2).resolve(&stopped),
+            Some(MprisCommand::SetVolume(0.2))
+        );
+    }
+}
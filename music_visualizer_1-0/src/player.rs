@@ -0,0 +1,516 @@
+//! Player engine module
+//!
+//! Owns the single, long-lived CPAL output stream. Songs are handed to the engine as shared,
+//! pre-decoded sample buffers:
+//! - The *current* source is played from its position
+//! - An optional *next* source is queued ahead of time
+//!
+//! The [`Mixer`] running in the callback switches to the next source within the same buffer
+//! (or crossfades into it), so consecutive tracks play without a gap. The mixed output then
+//! runs through the [`DspChain`] (graphic and parametric EQ, then volume) before reaching the
+//! device.
+//!
+//! The mixer is owned by the callback; the engine controls it through a [`MixerHandle`], so the
+//! output path never takes a lock.
+//!
+//! When the device accepts a song's native sample rate and channel count, the stream is reopened
+//! in that format so the song plays bit-perfect, without resampling. Other songs are prepared at
+//! the device's default format.
+
+use crate::dsp::eq::{EqParams, GraphicEq, ParametricEq};
+use crate::dsp::gain::Gain;
+use crate::dsp::{AtomicF32, DspChain};
+use crate::mixer::{Mixer, MixerCommand, MixerHandle, Source, TransitionMode};
+use crate::song::{PlaybackPath, Song};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use std::sync::{Arc, Mutex};
+
+/// Holds the mixer while no output stream owns it.
+type MixerSlot = Arc<Mutex<Option<Mixer>>>;
+
+/// The playback engine.
+///
+/// Opens one output stream at the device's default configuration and keeps it running for the
+/// lifetime of the application, reopening it only to change format. Pausing simply outputs
+/// silence.
+pub struct Player {
+    stream: Option<cpal::Stream>,
+    mixer: MixerHandle,
+    /// The mixer while no stream is running; it is moved into each stream's callback and handed
+    /// back when the stream is dropped.
+    parked: MixerSlot,
+    eq: Arc<EqParams>,
+    /// Linear output volume (0.0 to 1.0) read by the effect chain.
+    volume: Arc<AtomicF32>,
+    sample_rate: u32,
+    channels: usize,
+    /// The device's default sample rate and channel count, used for songs it cannot play natively.
+    default_format: (u32, usize),
+    /// The `f32` configurations the device accepts.
+    supported: Vec<cpal::SupportedStreamConfigRange>,
+    transition: TransitionMode,
+    /// Crossfade length in seconds, kept so the fade can be rescaled when the rate changes.
+    fade_seconds: f32,
+    playing: bool,
+    /// Whether a current source has been loaded.
+    loaded: bool,
+}
+
+impl Player {
+    // ============================================================================
+    // Public Methods
+    // ============================================================================
+
+    /// Creates the engine and opens the output stream.
+    ///
+    /// If no device is available, the engine is still created so the UI keeps working; songs
+    /// are then prepared at 44.1 kHz and nothing is heard.
+    pub fn new() -> Self {
+        let (mixer, handle) = Mixer::new();
+        let parked = Arc::new(Mutex::new(Some(mixer)));
+        let eq = Arc::new(EqParams::default());
+        let volume = Arc::new(AtomicF32::new(1.0));
+        let (stream, sample_rate, channels) =
+            match Self::open_stream(&parked, Arc::clone(&eq), Arc::clone(&volume), None) {
+                Some((stream, sample_rate, channels)) => (Some(stream), sample_rate, channels),
+                None => (None, 44100, 2),
+            };
+        Player {
+            stream,
+            mixer: handle,
+            parked,
+            eq,
+            volume,
+            sample_rate,
+            channels,
+            default_format: (sample_rate, channels),
+            supported: Self::supported_configs(),
+            transition: TransitionMode::default(),
+            fade_seconds: 0.0,
+            playing: false,
+            loaded: false,
+        }
+    }
+
+    /// Returns the sample rate of the output stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns whether the device can play audio in the given format without conversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - Sample rate in Hz.
+    /// * `channels` - Number of channels.
+    pub fn supports(&self, sample_rate: u32, channels: usize) -> bool {
+        self.supported.iter().any(|range| {
+            range.channels() as usize == channels
+                && (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&sample_rate)
+        })
+    }
+
+    /// Returns the stream format a song in the given format should be played in.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The song's native sample rate in Hz.
+    /// * `channels` - The song's number of channels.
+    ///
+    /// # Returns
+    ///
+    /// The song's own format if the device supports it (the bit-perfect path), otherwise the
+    /// device's default format, which the song must be resampled to.
+    pub fn format_for(&self, sample_rate: u32, channels: usize) -> (u32, usize) {
+        if self.supports(sample_rate, channels) {
+            (sample_rate, channels)
+        } else {
+            self.default_format
+        }
+    }
+
+    /// Returns whether `song` can be queued on the stream as it is currently open.
+    ///
+    /// Songs that need another stream format cannot follow the current one without a gap.
+    pub fn can_queue(&self, song: &Song) -> bool {
+        self.format_for(song.sample_rate(), song.channels()) == (self.sample_rate, self.channels)
+    }
+
+    /// Reopens the output stream in the format `song` should be played in, if it differs.
+    ///
+    /// The current and queued sources are kept. If the device refuses the format, the stream
+    /// is reopened in the default format instead.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the stream now runs at the song's sample rate.
+    pub fn match_format(&mut self, song: &Song) -> bool {
+        let (sample_rate, channels) = self.format_for(song.sample_rate(), song.channels());
+        if (sample_rate, channels) != (self.sample_rate, self.channels) {
+            // Release the device before opening it again in another format.
+            self.stream = None;
+            let opened = Self::open_stream(
+                &self.parked,
+                Arc::clone(&self.eq),
+                Arc::clone(&self.volume),
+                Some((sample_rate, channels)),
+            )
+            .or_else(|| {
+                eprintln!("⚠️ Falling back to the default output format.");
+                Self::open_stream(
+                    &self.parked,
+                    Arc::clone(&self.eq),
+                    Arc::clone(&self.volume),
+                    None,
+                )
+            });
+            if let Some((stream, sample_rate, channels)) = opened {
+                println!(
+                    "🔁 Output stream switched to {} Hz, {} channels.",
+                    sample_rate, channels
+                );
+                self.stream = Some(stream);
+                self.sample_rate = sample_rate;
+                self.channels = channels;
+            }
+            self.set_transition(self.transition, self.fade_seconds);
+        }
+        self.sample_rate == song.sample_rate()
+    }
+
+    /// Returns the equalizer settings applied in the output callback.
+    pub fn eq(&self) -> &EqParams {
+        &self.eq
+    }
+
+    /// Returns the output volume, from 0.0 (silent) to 1.0 (full).
+    pub fn volume(&self) -> f32 {
+        self.volume.load()
+    }
+
+    /// Sets the output volume, clamped to 0.0 (silent) to 1.0 (full).
+    pub fn set_volume(&self, volume: f32) {
+        self.volume.store(volume.clamp(0.0, 1.0));
+    }
+
+    /// Makes `song` the current source, starting from its beginning.
+    ///
+    /// Any queued next source and crossfade in progress are discarded.
+    pub fn load(&mut self, song: &Song) {
+        self.loaded = true;
+        self.send(MixerCommand::Load(Source::new(song)));
+    }
+
+    /// Sets how queued tracks follow each other.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - Gapless, crossfade or smart (silence-aware) crossfade.
+    /// * `seconds` - Crossfade length; ignored in gapless mode.
+    pub fn set_transition(&mut self, mode: TransitionMode, seconds: f32) {
+        self.transition = mode;
+        self.fade_seconds = seconds;
+        let fade_frames = (seconds.max(0.0) * self.sample_rate as f32) as usize;
+        self.send(MixerCommand::SetTransition(mode, fade_frames));
+    }
+
+    /// Queues `song` to start the moment the current source ends, or clears the queued source.
+    pub fn preload(&mut self, song: Option<&Song>) {
+        self.send(MixerCommand::Preload(song.map(Source::new)));
+    }
+
+    /// Removes the current and queued sources.
+    pub fn unload(&mut self) {
+        self.loaded = false;
+        self.send(MixerCommand::Unload);
+    }
+
+    /// Starts or pauses output.
+    pub fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+        self.send(MixerCommand::SetPlaying(playing));
+    }
+
+    /// Returns whether audio is currently being output.
+    pub fn is_playing(&self) -> bool {
+        self.playing && self.loaded && !self.mixer.is_finished() && self.stream.is_some()
+    }
+
+    /// Moves the current source back to its first frame.
+    pub fn rewind(&mut self) {
+        self.seek(0);
+    }
+
+    /// Moves the current source to the given frame.
+    pub fn seek(&mut self, frame: usize) {
+        self.send(MixerCommand::Seek(frame));
+    }
+
+    /// Returns the frame the current source will play next, if a source is loaded.
+    pub fn position(&self) -> Option<usize> {
+        self.mixer.position()
+    }
+
+    /// Sets or clears the A–B loop of the current source.
+    ///
+    /// # Arguments
+    ///
+    /// * `loop_range` - Start and end frame of the loop, or `None` to play through.
+    pub fn set_loop(&mut self, loop_range: Option<(usize, usize)>) {
+        self.send(MixerCommand::SetLoop(loop_range));
+    }
+
+    /// Returns the number of track switches made by the mixer since the last call, resetting the
+    /// count.
+    ///
+    /// Sources the mixer has finished with since the last call are freed here, rather than in
+    /// the output callback, and commands that did not fit in the mixer's queue are sent.
+    pub fn take_advanced(&mut self) -> usize {
+        self.mixer.free_retired();
+        self.mixer.flush();
+        self.update_parked();
+        self.mixer.take_advanced()
+    }
+
+    /// Returns whether the current source has ended with nothing queued after it.
+    pub fn is_finished(&self) -> bool {
+        self.mixer.is_finished()
+    }
+
+    /// Returns how `song` reaches the device with the output as it is currently set up.
+    ///
+    /// A song decoded bit-perfect is only heard bit-perfect while the stream runs in its exact
+    /// format and nothing on the way changes its samples: the EQ is flat, the volume is full,
+    /// speed and pitch are neutral, no A–B loop is set and tracks follow each other without a
+    /// crossfade. Otherwise it is reported as [`PlaybackPath::Native`].
+    ///
+    /// # Returns
+    ///
+    /// The song's playback path, or `None` for an empty song.
+    pub fn playback_path(&self, song: &Song) -> Option<PlaybackPath> {
+        match song.playback_path()? {
+            PlaybackPath::BitPerfect { bits } if !self.is_transparent_for(song) => {
+                Some(PlaybackPath::Native { bits })
+            }
+            path => Some(path),
+        }
+    }
+
+    /// Outputs debug information about the stream, the current song's playback path and the
+    /// device's supported configurations.
+    pub fn debug_info(&self, song: &Song) {
+        println!(
+            "🔊 Output stream: {} channels at {} Hz ({}); device default {} channels at {} Hz",
+            self.channels,
+            self.sample_rate,
+            if self.stream.is_some() {
+                "open"
+            } else {
+                "unavailable"
+            },
+            self.default_format.1,
+            self.default_format.0
+        );
+        if let Some(path) = self.playback_path(song) {
+            println!("🎚️ Playback: {}", path);
+        }
+
+        let host = cpal::default_host();
+        match host.default_output_device() {
+            Some(device) => Self::debug_supported_configs(&device),
+            None => eprintln!("No output device available."),
+        }
+    }
+
+    // ============================================================================
+    // Private Helper Methods
+    // ============================================================================
+
+    /// Returns whether the output plays `song`'s samples unchanged.
+    fn is_transparent_for(&self, song: &Song) -> bool {
+        self.stream.is_some()
+            && (song.sample_rate(), song.channels()) == (self.sample_rate, self.channels)
+            && self.eq.is_flat()
+            && self.volume() >= 1.0
+            && song.playback_rate().is_neutral()
+            && song.loop_range().is_none()
+            && self.transition == TransitionMode::Gapless
+    }
+
+    /// Sends a command to the mixer.
+    fn send(&mut self, command: MixerCommand) {
+        self.mixer.send(command);
+        self.update_parked();
+    }
+
+    /// Applies sent commands to the mixer directly while no stream is running to apply them.
+    fn update_parked(&mut self) {
+        if self.stream.is_some() {
+            return;
+        }
+        self.mixer.free_retired();
+        if let Some(mixer) = self.parked.lock().unwrap().as_mut() {
+            mixer.update();
+        }
+    }
+
+    /// Opens and starts the output stream on the default device.
+    ///
+    /// The effect chain is built here, once the stream's sample rate and channel count are known.
+    /// It is owned by the callback together with the mixer, so processing never waits on a lock.
+    ///
+    /// # Arguments
+    ///
+    /// * `parked` - Holds the mixer rendered in the callback. The mixer is taken from it for the
+    ///   stream's lifetime and put back when the stream is dropped.
+    /// * `eq` - The equalizer settings read by the effect chain.
+    /// * `volume` - The output volume read by the effect chain.
+    /// * `format` - Sample rate and channel count to open the stream with (in `f32`), or `None`
+    ///   for the device's default configuration.
+    ///
+    /// # Returns
+    ///
+    /// The running stream with its sample rate and channel count, or `None` on failure.
+    fn open_stream(
+        parked: &MixerSlot,
+        eq: Arc<EqParams>,
+        volume: Arc<AtomicF32>,
+        format: Option<(u32, usize)>,
+    ) -> Option<(cpal::Stream, u32, usize)> {
+        let host = cpal::default_host();
+        let device = match host.default_output_device() {
+            Some(d) => d,
+            None => {
+                eprintln!("❌ No output device available.");
+                return None;
+            }
+        };
+
+        let supported_config = match device.default_output_config() {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("❌ Failed to get default output config: {}", e);
+                return None;
+            }
+        };
+
+        let mut config = supported_config.config();
+        let mut sample_format = supported_config.sample_format();
+        if let Some((sample_rate, channels)) = format {
+            config.sample_rate = cpal::SampleRate(sample_rate);
+            config.channels = channels as u16;
+            // Only `f32` configurations are offered by `supports`.
+            sample_format = cpal::SampleFormat::F32;
+        }
+        let channels = config.channels as usize;
+        let sample_rate = config.sample_rate.0;
+
+        let mut chain = DspChain::default();
+        chain.push(Box::new(GraphicEq::new(
+            Arc::clone(&eq),
+            sample_rate,
+            channels,
+        )));
+        chain.push(Box::new(ParametricEq::new(eq, sample_rate, channels)));
+        chain.push(Box::new(Gain::new(volume)));
+
+        let stream_result = match sample_format {
+            cpal::SampleFormat::F32 => {
+                let Some(mixer) = parked.lock().unwrap().take() else {
+                    eprintln!("❌ The mixer is still owned by another stream.");
+                    return None;
+                };
+                let mut owned = OwnedMixer {
+                    mixer: Some(mixer),
+                    parked: Arc::clone(parked),
+                };
+                device.build_output_stream(
+                    &config,
+                    move |data: &mut [f32], _| {
+                        if let Some(mixer) = &mut owned.mixer {
+                            mixer.render(data, channels);
+                        }
+                        chain.process(data, channels);
+                    },
+                    move |err| eprintln!("⚠️ Stream error: {}", err),
+                    None,
+                )
+            }
+            _ => {
+                eprintln!("❌ Unsupported sample format: {:?}", sample_format);
+                return None;
+            }
+        };
+
+        match stream_result {
+            Ok(stream) => {
+                if let Err(e) = stream.play() {
+                    eprintln!("❌ Failed to start playback: {}", e);
+                    return None;
+                }
+                println!("✅ Output stream started.");
+                Some((stream, sample_rate, channels))
+            }
+            Err(e) => {
+                eprintln!("❌ Stream creation failed: {}", e);
+                Self::debug_supported_configs(&device);
+                None
+            }
+        }
+    }
+
+    /// Returns the `f32` configurations the default output device accepts.
+    fn supported_configs() -> Vec<cpal::SupportedStreamConfigRange> {
+        cpal::default_host()
+            .default_output_device()
+            .and_then(|device| device.supported_output_configs().ok())
+            .map(|configs| {
+                configs
+                    .filter(|range| range.sample_format() == cpal::SampleFormat::F32)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Outputs the supported configurations for the given output device.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - A reference to the output device.
+    fn debug_supported_configs(device: &cpal::Device) {
+        println!(
+            "🧪 Supported configs for device '{}':",
+            device.name().unwrap_or_default()
+        );
+        if let Ok(configs) = device.supported_output_configs() {
+            for cfg in configs {
+                println!(
+                    "  - {:?}, channels: {}, rate: {}-{}",
+                    cfg.sample_format(),
+                    cfg.channels(),
+                    cfg.min_sample_rate().0,
+                    cfg.max_sample_rate().0
+                );
+            }
+        } else {
+            eprintln!("⚠️ Could not retrieve supported output configs.");
+        }
+    }
+}
+
+/// The mixer as owned by a stream's callback.
+///
+/// Dropping the stream drops its callback, which hands the mixer back to the player so the
+/// current and queued sources survive a change of format.
+struct OwnedMixer {
+    mixer: Option<Mixer>,
+    parked: MixerSlot,
+}
+
+impl Drop for OwnedMixer {
+    fn drop(&mut self) {
+        if let Ok(mut parked) = self.parked.lock() {
+            *parked = self.mixer.take();
+        }
+    }
+}
@@ -0,0 +1,1038 @@
+//! Playlist module
+//!
+//! Imports and exports playlists shared with other players:
+//! - Extended M3U / M3U8 (`#EXTINF` durations and titles)
+//! - PLS (`[playlist]` INI files)
+//! - XSPF (XML Shareable Playlist Format)
+//!
+//! Relative entries are resolved against the playlist's own folder and matched to tracks in the
+//! [`Library`]. Entries that cannot be matched are reported back instead of being dropped.
+
+use crate::library::{self, Library, Track};
+use quick_xml::escape::{escape, unescape};
+use quick_xml::events::Event;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Folder (relative to the working directory) where playlists are kept.
+pub const PLAYLIST_DIR: &str = "playlists";
+
+/// Errors that can occur while reading or writing a playlist.
+#[derive(Debug, Error)]
+pub enum PlaylistError {
+    /// The playlist file could not be read or written.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file extension does not belong to a supported playlist format.
+    #[error("unsupported playlist format: '{0}'")]
+    UnsupportedFormat(String),
+    /// The playlist contents are malformed.
+    #[error("parse error on line {line}: {message}")]
+    Parse { line: usize, message: String },
+    /// The XSPF document is not valid XML.
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+}
+
+/// Supported playlist file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    /// Extended M3U (`.m3u`, `.m3u8`).
+    M3u,
+    /// PLS (`.pls`).
+    Pls,
+    /// XSPF (`.xspf`).
+    Xspf,
+}
+
+impl PlaylistFormat {
+    /// Detects the playlist format from a file's extension.
+    ///
+    /// # Returns
+    ///
+    /// The format, or `None` if the extension is not a supported playlist type.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFormat::M3u),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+}
+
+/// A single entry as written in a playlist file, before it is matched to the library.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// The raw location (path or URL) of the entry.
+    pub location: String,
+    /// Title given by the playlist, if any.
+    pub title: Option<String>,
+    /// Artist given by the playlist, if any.
+    pub artist: Option<String>,
+    /// Album given by the playlist, if any.
+    pub album: Option<String>,
+    /// Duration in seconds given by the playlist, if any.
+    pub duration: Option<f64>,
+}
+
+/// Why a playlist entry could not be matched to a library track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnresolvedReason {
+    /// The entry points to a network stream, which the player cannot play.
+    Remote,
+    /// The file exists but is not part of the music library.
+    NotInLibrary,
+    /// No file exists at the location and nothing in the library matches the entry.
+    Missing,
+}
+
+impl fmt::Display for UnresolvedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnresolvedReason::Remote => write!(f, "remote streams are not supported"),
+            UnresolvedReason::NotInLibrary => write!(f, "file is not in the music library"),
+            UnresolvedReason::Missing => write!(f, "file not found"),
+        }
+    }
+}
+
+/// A playlist entry that could not be resolved, along with the reason.
+#[derive(Debug, Clone)]
+pub struct UnresolvedEntry {
+    /// 1-based position of the entry within the playlist.
+    pub position: usize,
+    /// The entry as written in the playlist.
+    pub entry: PlaylistEntry,
+    /// Why the entry was not resolved.
+    pub reason: UnresolvedReason,
+}
+
+impl fmt::Display for UnresolvedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} '{}': {}",
+            self.position, self.entry.location, self.reason
+        )
+    }
+}
+
+/// The result of importing a playlist into the library.
+#[derive(Debug)]
+pub struct ImportedPlaylist {
+    /// Playlist name (taken from the playlist itself, or its file name).
+    pub name: String,
+    /// Library tracks in playlist order.
+    pub tracks: Vec<Track>,
+    /// Entries that could not be matched to the library.
+    pub unresolved: Vec<UnresolvedEntry>,
+}
+
+/// Parsed contents of a playlist file.
+#[derive(Debug, Default)]
+struct ParsedPlaylist {
+    title: Option<String>,
+    entries: Vec<PlaylistEntry>,
+}
+
+// ============================================================================
+// Import / Export
+// ============================================================================
+
+/// Imports a playlist file and matches its entries against the library.
+///
+/// # Arguments
+///
+/// * `path` - Path to the playlist file (`.m3u`, `.m3u8`, `.pls` or `.xspf`).
+/// * `library` - The library to resolve entries against.
+///
+/// # Returns
+///
+/// The imported playlist, or a [`PlaylistError`] if the file cannot be read or parsed.
+pub fn import(path: &Path, library: &Library) -> Result<ImportedPlaylist, PlaylistError> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or_else(|| PlaylistError::UnsupportedFormat(path.display().to_string()))?;
+    let contents = decode_text(&fs::read(path)?);
+
+    let parsed = match format {
+        PlaylistFormat::M3u => parse_m3u(&contents),
+        PlaylistFormat::Pls => parse_pls(&contents)?,
+        PlaylistFormat::Xspf => parse_xspf(&contents)?,
+    };
+
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let mut tracks = Vec::new();
+    let mut unresolved = Vec::new();
+    for (index, entry) in parsed.entries.into_iter().enumerate() {
+        match resolve_entry(&entry, base_dir, library) {
+            Ok(track) => tracks.push(track.clone()),
+            Err(reason) => unresolved.push(UnresolvedEntry {
+                position: index + 1,
+                entry,
+                reason,
+            }),
+        }
+    }
+
+    let name = parsed.title.unwrap_or_else(|| {
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    Ok(ImportedPlaylist {
+        name,
+        tracks,
+        unresolved,
+    })
+}
+
+/// Imports a playlist, reporting the entries that could not be matched to the library.
+///
+/// # Arguments
+///
+/// * `path` - Path to the playlist file.
+/// * `library` - The library to resolve entries against.
+///
+/// # Returns
+///
+/// The playlist's library tracks, or none if the playlist cannot be read.
+pub fn import_tracks(path: &Path, library: &Library) -> Vec<Track> {
+    match import(path, library) {
+        Ok(imported) => {
+            println!(
+                "📃 Imported playlist '{}' ({} tracks).",
+                imported.name,
+                imported.tracks.len()
+            );
+            for entry in &imported.unresolved {
+                eprintln!("⚠️ Unresolved playlist entry {}", entry);
+            }
+            imported.tracks
+        }
+        Err(e) => {
+            eprintln!("Failed to import playlist '{}': {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// Returns the tracks a file or folder opened from outside the library stands for.
+///
+/// An audio file is played from where it is, a folder stands for the audio files in it and its
+/// subfolders, in order, and a playlist for the tracks it imports.
+///
+/// # Arguments
+///
+/// * `path` - An audio file, a folder or a playlist.
+/// * `library` - The library to resolve playlist entries against.
+///
+/// # Returns
+///
+/// The tracks; files that cannot be read are reported and skipped.
+pub fn tracks_at(path: &Path, library: &Library) -> Vec<Track> {
+    let files = if path.is_dir() {
+        library::audio_files_in(path)
+    } else if PlaylistFormat::from_path(path).is_some() {
+        return import_tracks(path, library);
+    } else if library::is_audio_file(path) {
+        vec![path.to_path_buf()]
+    } else {
+        eprintln!(
+            "⚠️ '{}' is not an audio file, folder or playlist.",
+            path.display()
+        );
+        return Vec::new();
+    };
+
+    if files.is_empty() {
+        eprintln!("⚠️ No audio files in '{}'.", path.display());
+    }
+    files
+        .iter()
+        .filter_map(|file| match Track::from_path(file) {
+            Ok(track) => Some(track),
+            Err(e) => {
+                eprintln!("Skipping '{}': {}", file.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Writes tracks to a playlist file, choosing the format from the file extension.
+///
+/// Track paths are written relative to the playlist's folder where possible so that the
+/// playlist keeps working when the whole folder tree is moved.
+///
+/// # Arguments
+///
+/// * `path` - Destination path of the playlist.
+/// * `name` - Playlist name, stored where the format allows it.
+/// * `tracks` - The tracks to write, in order.
+///
+/// # Returns
+///
+/// `Ok(())` on success, or a [`PlaylistError`] on failure.
+pub fn export(path: &Path, name: &str, tracks: &[Track]) -> Result<(), PlaylistError> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or_else(|| PlaylistError::UnsupportedFormat(path.display().to_string()))?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let base_dir = path
+        .parent()
+        .and_then(|p| fs::canonicalize(p).ok())
+        .unwrap_or_default();
+
+    let contents = match format {
+        PlaylistFormat::M3u => write_m3u(tracks, &base_dir),
+        PlaylistFormat::Pls => write_pls(tracks, &base_dir),
+        PlaylistFormat::Xspf => write_xspf(name, tracks, &base_dir),
+    };
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Lists the playlist files found in a directory, sorted by file name.
+///
+/// # Arguments
+///
+/// * `dir_path` - The directory to scan.
+///
+/// # Returns
+///
+/// The paths of all supported playlist files. A missing directory yields an empty list.
+pub fn list_playlists(dir_path: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir_path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file() && PlaylistFormat::from_path(path).is_some())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths
+}
+
+/// Returns the `file://` URL of an absolute path.
+pub fn file_url(path: &Path) -> String {
+    let absolute = path.to_string_lossy().replace('\\', "/");
+    let absolute = if absolute.starts_with('/') {
+        absolute
+    } else {
+        format!("/{}", absolute)
+    };
+    format!("file://{}", percent_encode(&absolute))
+}
+
+/// Converts a `file://` URL into the path it names.
+///
+/// # Returns
+///
+/// The path, or `None` if the URL has another scheme.
+#[cfg(target_os = "linux")]
+pub fn path_from_file_url(url: &str) -> Option<PathBuf> {
+    url.starts_with("file://")
+        .then(|| location_to_path(url, Path::new("/")))
+}
+
+// ============================================================================
+// Resolution Helpers
+// ============================================================================
+
+/// Matches a playlist entry to a library track.
+///
+/// The entry's location is tried first (relative to `base_dir`), then its file name, and finally
+/// the title/artist given by the playlist.
+fn resolve_entry<'a>(
+    entry: &PlaylistEntry,
+    base_dir: &Path,
+    library: &'a Library,
+) -> Result<&'a Track, UnresolvedReason> {
+    let location = entry.location.trim();
+    let is_remote = location.contains("://") && !location.starts_with("file://");
+
+    if !is_remote {
+        let path = location_to_path(location, base_dir);
+        if let Some(track) = library.find_by_path(&path) {
+            return Ok(track);
+        }
+        if let Some(track) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| library.find_by_file_name(name))
+        {
+            return Ok(track);
+        }
+    }
+
+    if let Some(track) = entry
+        .title
+        .as_deref()
+        .and_then(|title| library.find_by_title(title, entry.artist.as_deref()))
+    {
+        return Ok(track);
+    }
+
+    if is_remote {
+        Err(UnresolvedReason::Remote)
+    } else if location_to_path(location, base_dir).is_file() {
+        Err(UnresolvedReason::NotInLibrary)
+    } else {
+        Err(UnresolvedReason::Missing)
+    }
+}
+
+/// Converts a playlist location (plain path or `file://` URL) into a filesystem path.
+fn location_to_path(location: &str, base_dir: &Path) -> PathBuf {
+    let raw = match location.strip_prefix("file://") {
+        // `file:///abs/path` on Unix, `file:///C:/path` on Windows.
+        Some(rest) => {
+            let decoded = percent_decode(rest.strip_prefix("localhost").unwrap_or(rest));
+            match decoded.as_bytes() {
+                [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => decoded[1..].to_string(),
+                _ => decoded,
+            }
+        }
+        None => location.to_string(),
+    };
+    // Playlists written on Windows use backslashes.
+    let raw = if cfg!(windows) {
+        raw
+    } else {
+        raw.replace('\\', "/")
+    };
+
+    let path = PathBuf::from(raw);
+    if path.is_absolute() {
+        path
+    } else {
+        base_dir.join(path)
+    }
+}
+
+/// Returns `path` relative to `base_dir` when it lives below it, otherwise the absolute path.
+fn relative_location(path: &Path, base_dir: &Path) -> String {
+    let relative = relative_path(path, base_dir).unwrap_or_else(|| path.to_path_buf());
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Computes a relative path from `base_dir` to `path`, walking up with `..` where needed.
+fn relative_path(path: &Path, base_dir: &Path) -> Option<PathBuf> {
+    if base_dir.as_os_str().is_empty() {
+        return None;
+    }
+    let path_parts: Vec<Component> = path.components().collect();
+    let base_parts: Vec<Component> = base_dir.components().collect();
+
+    let common = path_parts
+        .iter()
+        .zip(&base_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    // Paths that only share the root (or live on another drive) are kept absolute.
+    if path_parts[..common]
+        .iter()
+        .all(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+    {
+        return None;
+    }
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_parts.len() {
+        relative.push("..");
+    }
+    for part in &path_parts[common..] {
+        relative.push(part.as_os_str());
+    }
+    Some(relative)
+}
+
+/// Returns the XSPF `<location>` URI for a track: relative when possible, otherwise `file://`.
+fn xspf_location(path: &Path, base_dir: &Path) -> String {
+    match relative_path(path, base_dir) {
+        Some(relative) => percent_encode(&relative.to_string_lossy().replace('\\', "/")),
+        None => file_url(path),
+    }
+}
+
+// ============================================================================
+// Text Helpers
+// ============================================================================
+
+/// Decodes playlist bytes as UTF-8 (stripping a BOM), falling back to Latin-1 for legacy `.m3u`.
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+/// Decodes `%XX` escapes in a URL path.
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Some(value) = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            out.push(value);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Encodes a path for use in a URL, escaping everything except unreserved characters and `/`.
+fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(byte as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Splits an "Artist - Title" display string into its parts.
+fn split_artist_title(display: &str) -> (Option<String>, String) {
+    match display.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.trim().to_string()), title.trim().to_string()),
+        None => (None, display.trim().to_string()),
+    }
+}
+
+// ============================================================================
+// M3U
+// ============================================================================
+
+/// Parses an (extended) M3U playlist.
+///
+/// `#EXTINF:<seconds>[ attributes],<Artist - Title>` lines attach a duration and title to the
+/// next entry. `#PLAYLIST:` sets the playlist title. Other comments are ignored.
+fn parse_m3u(contents: &str) -> ParsedPlaylist {
+    let mut playlist = ParsedPlaylist::default();
+    let mut pending = PlaylistEntry::default();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (length, display) = info.split_once(',').unwrap_or((info, ""));
+            // The length may be followed by `key="value"` attributes.
+            let seconds = length.split_whitespace().next().unwrap_or("");
+            pending.duration = seconds.parse::<f64>().ok().filter(|d| *d >= 0.0);
+            if !display.trim().is_empty() {
+                let (artist, title) = split_artist_title(display);
+                pending.artist = artist;
+                pending.title = Some(title);
+            }
+        } else if let Some(title) = line.strip_prefix("#PLAYLIST:") {
+            playlist.title = Some(title.trim().to_string());
+        } else if !line.starts_with('#') {
+            pending.location = line.to_string();
+            playlist.entries.push(std::mem::take(&mut pending));
+        }
+    }
+    playlist
+}
+
+/// Writes tracks as an extended M3U playlist.
+fn write_m3u(tracks: &[Track], base_dir: &Path) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for track in tracks {
+        let seconds = track.duration.map(|d| d.round() as i64).unwrap_or(-1);
+        let display = match &track.artist {
+            Some(artist) => format!("{} - {}", artist, track.title),
+            None => track.title.clone(),
+        };
+        out.push_str(&format!("#EXTINF:{},{}\n", seconds, display));
+        out.push_str(&relative_location(&track.path, base_dir));
+        out.push('\n');
+    }
+    out
+}
+
+// ============================================================================
+// PLS
+// ============================================================================
+
+/// Parses a PLS playlist (`FileN=`, `TitleN=`, `LengthN=` keys in a `[playlist]` section).
+fn parse_pls(contents: &str) -> Result<ParsedPlaylist, PlaylistError> {
+    let mut entries: Vec<(usize, PlaylistEntry)> = Vec::new();
+    let mut seen_header = false;
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') {
+            seen_header = line.eq_ignore_ascii_case("[playlist]");
+            continue;
+        }
+        if !seen_header {
+            return Err(PlaylistError::Parse {
+                line: line_number + 1,
+                message: "expected a [playlist] section".to_string(),
+            });
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(PlaylistError::Parse {
+                line: line_number + 1,
+                message: format!("expected key=value, found '{}'", line),
+            });
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        let split = key.find(|c: char| c.is_ascii_digit()).unwrap_or(key.len());
+        let (field, index) = key.split_at(split);
+        let Ok(index) = index.parse::<usize>() else {
+            // `NumberOfEntries`, `Version` and similar keys carry no entry data.
+            continue;
+        };
+
+        let entry = match entries.iter_mut().find(|(i, _)| *i == index) {
+            Some((_, entry)) => entry,
+            None => {
+                entries.push((index, PlaylistEntry::default()));
+                &mut entries.last_mut().unwrap().1
+            }
+        };
+        match field {
+            "file" => entry.location = value.to_string(),
+            "title" => {
+                let (artist, title) = split_artist_title(value);
+                entry.artist = artist;
+                entry.title = Some(title);
+            }
+            "length" => entry.duration = value.parse::<f64>().ok().filter(|d| *d >= 0.0),
+            _ => {}
+        }
+    }
+
+    entries.sort_by_key(|(index, _)| *index);
+    Ok(ParsedPlaylist {
+        title: None,
+        entries: entries
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| !entry.location.is_empty())
+            .collect(),
+    })
+}
+
+/// Writes tracks as a version 2 PLS playlist.
+fn write_pls(tracks: &[Track], base_dir: &Path) -> String {
+    let mut out = String::from("[playlist]\n");
+    for (index, track) in tracks.iter().enumerate() {
+        let n = index + 1;
+        let display = match &track.artist {
+            Some(artist) => format!("{} - {}", artist, track.title),
+            None => track.title.clone(),
+        };
+        let seconds = track.duration.map(|d| d.round() as i64).unwrap_or(-1);
+        out.push_str(&format!(
+            "File{n}={}\nTitle{n}={}\nLength{n}={}\n",
+            relative_location(&track.path, base_dir),
+            display,
+            seconds
+        ));
+    }
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", tracks.len()));
+    out
+}
+
+// ============================================================================
+// XSPF
+// ============================================================================
+
+/// Parses an XSPF playlist.
+///
+/// Only the elements the player uses are read: the playlist `title` and each track's `location`,
+/// `title`, `creator`, `album` and `duration` (milliseconds).
+fn parse_xspf(contents: &str) -> Result<ParsedPlaylist, PlaylistError> {
+    let mut reader = quick_xml::Reader::from_str(contents);
+    reader.config_mut().trim_text(true);
+
+    let mut playlist = ParsedPlaylist::default();
+    let mut current: Option<PlaylistEntry> = None;
+    let mut path: Vec<String> = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_string();
+                if name == "track" {
+                    current = Some(PlaylistEntry::default());
+                }
+                path.push(name);
+            }
+            Event::End(element) => {
+                if element.local_name().as_ref() == b"track"
+                    && let Some(entry) = current.take()
+                    && !entry.location.is_empty()
+                {
+                    playlist.entries.push(entry);
+                }
+                path.pop();
+            }
+            Event::Text(text) => {
+                let raw = String::from_utf8_lossy(text.as_ref()).to_string();
+                let value = unescape(&raw)
+                    .map_err(quick_xml::Error::from)?
+                    .trim()
+                    .to_string();
+                let element = path.last().map(String::as_str).unwrap_or("");
+                let parent = path
+                    .len()
+                    .checked_sub(2)
+                    .map(|i| path[i].as_str())
+                    .unwrap_or("");
+
+                match (&mut current, parent, element) {
+                    // Only the first location of a track is used.
+                    (Some(entry), "track", "location") if entry.location.is_empty() => {
+                        // Relative locations are URI references and may be percent-encoded.
+                        entry.location = if value.contains("://") {
+                            value
+                        } else {
+                            percent_decode(&value)
+                        }
+                    }
+                    (Some(entry), "track", "title") => entry.title = Some(value),
+                    (Some(entry), "track", "creator") => entry.artist = Some(value),
+                    (Some(entry), "track", "album") => entry.album = Some(value),
+                    (Some(entry), "track", "duration") => {
+                        entry.duration = value.parse::<f64>().ok().map(|ms| ms / 1000.0)
+                    }
+                    (None, "playlist", "title") => playlist.title = Some(value),
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(playlist)
+}
+
+/// Writes tracks as an XSPF playlist.
+fn write_xspf(name: &str, tracks: &[Track], base_dir: &Path) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    out.push_str(&format!(
+        "  <title>{}</title>\n  <trackList>\n",
+        escape(name)
+    ));
+    for track in tracks {
+        out.push_str("    <track>\n");
+        out.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape(xspf_location(&track.path, base_dir))
+        ));
+        out.push_str(&format!("      <title>{}</title>\n", escape(&track.title)));
+        if let Some(artist) = &track.artist {
+            out.push_str(&format!("      <creator>{}</creator>\n", escape(artist)));
+        }
+        if let Some(album) = &track.album {
+            out.push_str(&format!("      <album>{}</album>\n", escape(album)));
+        }
+        if let Some(duration) = track.duration {
+            out.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                (duration * 1000.0).round() as u64
+            ));
+        }
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n</playlist>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A folder in the temporary directory that is removed when dropped.
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new() -> Self {
+            static CREATED: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "music_visualizer-playlist-{}-{}",
+                process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempFolder(fs::canonicalize(&path).unwrap())
+        }
+
+        /// Creates an empty file below the folder and returns its canonical path.
+        fn file(&self, relative: &str) -> PathBuf {
+            let path = self.0.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, b"").unwrap();
+            fs::canonicalize(path).unwrap()
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn track(path: PathBuf, title: &str, artist: Option<&str>) -> Track {
+        Track {
+            file_name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            path,
+            title: title.to_string(),
+            artist: artist.map(str::to_string),
+            album: Some("Night Drive".to_string()),
+            duration: Some(184.0),
+            ..Track::default()
+        }
+    }
+
+    /// Builds a library of three tracks, one in a sibling folder of `playlists` and one with
+    /// spaces in its name.
+    fn library(folder: &TempFolder) -> (Library, Vec<Track>) {
+        let tracks = vec![
+            track(folder.file("music/intro.wav"), "Intro", Some("Kiln")),
+            track(folder.file("music/city lights #2.wav"), "City Lights", None),
+            track(folder.file("music/deep/outro.flac"), "Outro", Some("Kiln")),
+        ];
+        let mut library = Library::default();
+        for track in &tracks {
+            library.insert(track.clone());
+        }
+        (library, tracks)
+    }
+
+    fn round_trip(file_name: &str) {
+        let folder = TempFolder::new();
+        let (library, tracks) = library(&folder);
+        let path = folder.0.join("playlists").join(file_name);
+
+        export(&path, "Evening", &tracks).unwrap();
+        let imported = import(&path, &library).unwrap();
+
+        assert_eq!(imported.tracks, tracks);
+        assert!(imported.unresolved.is_empty());
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("../music/"), "{}", contents);
+        assert!(
+            !contents.contains(&*folder.0.to_string_lossy()),
+            "{}",
+            contents
+        );
+    }
+
+    #[test]
+    fn m3u_round_trips() {
+        round_trip("evening.m3u");
+    }
+
+    #[test]
+    fn pls_round_trips() {
+        round_trip("evening.pls");
+    }
+
+    #[test]
+    fn xspf_round_trips_with_its_title() {
+        round_trip("evening.xspf");
+
+        let folder = TempFolder::new();
+        let (library, tracks) = library(&folder);
+        let path = folder.0.join("evening.xspf");
+        export(&path, "Songs & Sketches", &tracks).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(
+            contents.contains("music/city%20lights%20%232.wav"),
+            "{}",
+            contents
+        );
+        assert_eq!(import(&path, &library).unwrap().name, "Songs & Sketches");
+    }
+
+    #[test]
+    fn percent_encoded_file_urls_resolve() {
+        let folder = TempFolder::new();
+        let (library, tracks) = library(&folder);
+        let path = folder.0.join("urls.m3u");
+        let url = file_url(&tracks[1].path);
+        assert!(url.ends_with("/music/city%20lights%20%232.wav"), "{}", url);
+        let localhost = url.replacen("file://", "file://localhost", 1);
+        fs::write(&path, format!("{}\n{}\n", url, localhost)).unwrap();
+
+        let imported = import(&path, &library).unwrap();
+
+        assert_eq!(imported.tracks, vec![tracks[1].clone(), tracks[1].clone()]);
+    }
+
+    #[test]
+    fn relative_entries_resolve_against_the_playlist_folder() {
+        let folder = TempFolder::new();
+        let (library, tracks) = library(&folder);
+        let path = folder.0.join("music/deep/mix.m3u");
+        fs::write(
+            &path,
+            "outro.flac\n../intro.wav\n..\\city lights #2.wav\n./../deep/outro.flac\n",
+        )
+        .unwrap();
+
+        let imported = import(&path, &library).unwrap();
+
+        assert_eq!(
+            imported.tracks,
+            vec![
+                tracks[2].clone(),
+                tracks[0].clone(),
+                tracks[1].clone(),
+                tracks[2].clone()
+            ]
+        );
+        assert_eq!(imported.name, "mix");
+    }
+
+    #[test]
+    fn entries_fall_back_to_the_file_name_and_title() {
+        let folder = TempFolder::new();
+        let (library, tracks) = library(&folder);
+        let path = folder.0.join("moved.pls");
+        fs::write(
+            &path,
+            "[playlist]\nFile1=/elsewhere/INTRO.WAV\n\
+             File2=/elsewhere/renamed.wav\nTitle2=Kiln - Outro\n",
+        )
+        .unwrap();
+
+        let imported = import(&path, &library).unwrap();
+
+        assert_eq!(imported.tracks, vec![tracks[0].clone(), tracks[2].clone()]);
+    }
+
+    #[test]
+    fn unmatched_entries_report_why() {
+        let folder = TempFolder::new();
+        let (library, tracks) = library(&folder);
+        folder.file("music/unscanned.wav");
+        let path = folder.0.join("mixed.m3u");
+        fs::write(
+            &path,
+            "#EXTM3U\n\
+             #EXTINF:-1,Radio\n\
+             http://radio.example/stream\n\
+             music/intro.wav\n\
+             music/unscanned.wav\n\
+             #EXTINF:200,Nobody - Nothing\n\
+             music/gone.wav\n",
+        )
+        .unwrap();
+
+        let imported = import(&path, &library).unwrap();
+
+        assert_eq!(imported.tracks, vec![tracks[0].clone()]);
+        let unresolved: Vec<(usize, &str, UnresolvedReason)> = imported
+            .unresolved
+            .iter()
+            .map(|u| (u.position, u.entry.location.as_str(), u.reason.clone()))
+            .collect();
+        assert_eq!(
+            unresolved,
+            vec![
+                (1, "http://radio.example/stream", UnresolvedReason::Remote),
+                (3, "music/unscanned.wav", UnresolvedReason::NotInLibrary),
+                (4, "music/gone.wav", UnresolvedReason::Missing),
+            ]
+        );
+        assert_eq!(
+            imported.unresolved[2].entry.title.as_deref(),
+            Some("Nothing")
+        );
+        assert_eq!(imported.unresolved[2].entry.duration, Some(200.0));
+    }
+
+    #[test]
+    fn drive_letters_lose_the_leading_slash_of_file_urls() {
+        let base = Path::new("/playlists");
+        // Absolute on Windows, so joining keeps it as it is there.
+        assert_eq!(
+            location_to_path("file:///C:/Music/song.wav", base),
+            base.join("C:/Music/song.wav")
+        );
+        // Only a slash, an ASCII letter and a colon form a drive.
+        assert_eq!(
+            location_to_path("file://é:x", base),
+            PathBuf::from("/playlists/é:x")
+        );
+        assert_eq!(
+            location_to_path("file://ab:c.wav", base),
+            PathBuf::from("/playlists/ab:c.wav")
+        );
+        assert_eq!(
+            location_to_path("file:///1:/song.wav", base),
+            PathBuf::from("/1:/song.wav")
+        );
+    }
+
+    #[test]
+    fn percent_encoding_round_trips() {
+        let path = "/Music/Beyoncé/50% off & more.mp3";
+        let encoded = percent_encode(path);
+        assert_eq!(encoded, "/Music/Beyonc%C3%A9/50%25%20off%20%26%20more.mp3");
+        assert_eq!(percent_decode(&encoded), path);
+        // Stray percent signs are kept as they are.
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%2"), "%zz%2");
+    }
+
+    #[test]
+    fn relative_paths_walk_up_but_not_past_the_root() {
+        assert_eq!(
+            relative_path(Path::new("/music/a/song.wav"), Path::new("/music/b")),
+            Some(PathBuf::from("../a/song.wav"))
+        );
+        assert_eq!(
+            relative_path(Path::new("/music/song.wav"), Path::new("/music")),
+            Some(PathBuf::from("song.wav"))
+        );
+        assert_eq!(
+            relative_path(Path::new("/music/song.wav"), Path::new("/home/playlists")),
+            None
+        );
+        assert_eq!(
+            relative_path(Path::new("/music/song.wav"), Path::new("")),
+            None
+        );
+    }
+}
@@ -0,0 +1,433 @@
+//! Play queue module
+//!
+//! Keeps the ordered list of tracks to play and decides what comes next:
+//! - Next/previous navigation with a history of played tracks
+//! - Repeat-one and repeat-all modes
+//! - Non-repeating shuffle (Fisher–Yates) that still lets "previous" walk back
+
+use crate::library::Track;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+
+/// Number of played tracks remembered for "previous".
+const HISTORY_LIMIT: usize = 1000;
+
+/// How the queue behaves once a track (or the whole queue) finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RepeatMode {
+    /// Stop after the last track.
+    #[default]
+    Off,
+    /// Replay the current track when it ends.
+    One,
+    /// Start over from the beginning after the last track.
+    All,
+}
+
+impl RepeatMode {
+    /// Returns the next mode in the cycle Off → All → One → Off.
+    pub fn cycle(self) -> Self {
+        match self {
+            RepeatMode::Off => RepeatMode::All,
+            RepeatMode::All => RepeatMode::One,
+            RepeatMode::One => RepeatMode::Off,
+        }
+    }
+
+    /// Returns a short label for buttons.
+    pub fn label(self) -> &'static str {
+        match self {
+            RepeatMode::Off => "REPEAT: OFF",
+            RepeatMode::One => "REPEAT: ONE",
+            RepeatMode::All => "REPEAT: ALL",
+        }
+    }
+}
+
+/// An ordered queue of tracks with shuffle and repeat support.
+///
+/// The queue tracks three things: the index of the current track, the indices still to come
+/// (`upcoming`) and the last [`HISTORY_LIMIT`] indices played (`history`). Shuffling only reorders
+/// `upcoming`, so "previous" always returns to the track that was actually played before.
+#[derive(Debug, Default)]
+pub struct PlayQueue {
+    tracks: Vec<Track>,
+    current: Option<usize>,
+    upcoming: VecDeque<usize>,
+    history: VecDeque<usize>,
+    repeat: RepeatMode,
+    shuffle: bool,
+}
+
+impl PlayQueue {
+    // ============================================================================
+    // Public Methods
+    // ============================================================================
+
+    /// Replaces the queue contents and starts at `start_index`.
+    ///
+    /// Shuffle and repeat settings are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracks` - The tracks to queue, in order.
+    /// * `start_index` - Index of the first track to play.
+    ///
+    /// # Returns
+    ///
+    /// The track to play first, or `None` if the queue is empty.
+    pub fn set_tracks(&mut self, tracks: Vec<Track>, start_index: usize) -> Option<&Track> {
+        self.tracks = tracks;
+        self.history.clear();
+        self.current = if start_index < self.tracks.len() {
+            Some(start_index)
+        } else if self.tracks.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        self.rebuild_upcoming();
+        self.current_track()
+    }
+
+    /// Adds tracks to the end of the queue, or starts a new queue with them if it is empty.
+    ///
+    /// With shuffle on, the new tracks are shuffled in among those still to come.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracks` - The tracks to add, in order.
+    pub fn append(&mut self, tracks: Vec<Track>) {
+        if self.current.is_none() {
+            self.set_tracks(tracks, 0);
+            return;
+        }
+        let start = self.tracks.len();
+        self.tracks.extend(tracks);
+        if self.shuffle {
+            let mut rng = rand::thread_rng();
+            for index in start..self.tracks.len() {
+                let position = rng.gen_range(0..=self.upcoming.len());
+                self.upcoming.insert(position, index);
+            }
+        } else {
+            self.upcoming.extend(start..self.tracks.len());
+        }
+    }
+
+    /// Returns the queued tracks, in queue order.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Returns the index in [`tracks`](Self::tracks) of the track currently selected, if any.
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Returns the track currently selected, if any.
+    pub fn current_track(&self) -> Option<&Track> {
+        self.current.and_then(|index| self.tracks.get(index))
+    }
+
+    /// Refills the upcoming tracks if the queue has run out in repeat-all mode.
+    ///
+    /// The refill is reshuffled with shuffle on, so it is done once, ahead of time, for
+    /// [`peek_next`](Self::peek_next) to see the track [`next`](Self::next) will move to.
+    pub fn prepare_next(&mut self) {
+        if let Some(current) = self.current
+            && self.upcoming.is_empty()
+            && self.repeat == RepeatMode::All
+        {
+            self.refill_for_repeat(current);
+        }
+    }
+
+    /// Returns the track that will play after the current one finishes on its own.
+    ///
+    /// Repeat-one yields the current track again. In repeat-all mode, the answer only matches
+    /// what [`next`](Self::next) will return once [`prepare_next`](Self::prepare_next) has been
+    /// called.
+    pub fn peek_next(&self) -> Option<&Track> {
+        self.current?;
+        if self.repeat == RepeatMode::One {
+            return self.current_track();
+        }
+        self.upcoming
+            .front()
+            .and_then(|&index| self.tracks.get(index))
+    }
+
+    /// Returns whether skipping ahead leads to another track rather than the end of the queue.
+    #[cfg(target_os = "linux")]
+    pub fn has_next(&self) -> bool {
+        self.current.is_some() && (!self.upcoming.is_empty() || self.repeat != RepeatMode::Off)
+    }
+
+    /// Advances to the next track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track_finished` - `true` when called because the current track ended on its own. In
+    ///   repeat-one mode this replays the current track; a manual skip always moves on.
+    ///
+    /// # Returns
+    ///
+    /// The new current track, or `None` when the end of the queue is reached.
+    pub fn next(&mut self, track_finished: bool) -> Option<&Track> {
+        let current = self.current?;
+        if track_finished && self.repeat == RepeatMode::One {
+            return self.current_track();
+        }
+
+        self.prepare_next();
+        let next = self.upcoming.pop_front()?;
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(current);
+        self.current = Some(next);
+        self.current_track()
+    }
+
+    /// Steps back to the previously played track.
+    ///
+    /// The current track is put back at the front of the upcoming list so that "next" returns to
+    /// it. With no history, the current track is returned unchanged (i.e. restarted).
+    ///
+    /// # Returns
+    ///
+    /// The new current track, or `None` if the queue is empty.
+    pub fn previous(&mut self) -> Option<&Track> {
+        let current = self.current?;
+        if let Some(previous) = self.history.pop_back() {
+            self.upcoming.push_front(current);
+            self.current = Some(previous);
+        }
+        self.current_track()
+    }
+
+    /// Returns the active repeat mode.
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    /// Sets the repeat mode.
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    /// Returns whether shuffle is enabled.
+    pub fn shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Enables or disables shuffle.
+    ///
+    /// Turning shuffle on reshuffles the tracks that have not been played yet. Turning it off
+    /// continues in queue order after the current track. History is kept either way.
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        if self.shuffle != shuffle {
+            self.shuffle = shuffle;
+            self.rebuild_upcoming();
+        }
+    }
+
+    // ============================================================================
+    // Private Helper Methods
+    // ============================================================================
+
+    /// Recomputes the upcoming tracks from the current position and shuffle setting.
+    fn rebuild_upcoming(&mut self) {
+        self.upcoming.clear();
+        let Some(current) = self.current else {
+            return;
+        };
+
+        if self.shuffle {
+            // Everything not yet played (or playing) is eligible.
+            let played: HashSet<usize> = self.history.iter().copied().collect();
+            let mut remaining: Vec<usize> = (0..self.tracks.len())
+                .filter(|i| *i != current && !played.contains(i))
+                .collect();
+            fisher_yates(&mut remaining);
+            self.upcoming.extend(remaining);
+        } else {
+            self.upcoming.extend(current + 1..self.tracks.len());
+        }
+    }
+
+    /// Refills the upcoming list after the queue ran out in repeat-all mode.
+    fn refill_for_repeat(&mut self, current: usize) {
+        if self.shuffle {
+            let mut order: Vec<usize> = (0..self.tracks.len()).collect();
+            fisher_yates(&mut order);
+            // Avoid playing the same track twice in a row across the reshuffle.
+            if order.len() > 1 && order[0] == current {
+                order.swap(0, 1);
+            }
+            self.upcoming.extend(order);
+        } else {
+            self.upcoming.extend(0..self.tracks.len());
+        }
+    }
+}
+
+/// Shuffles a slice in place with the Fisher–Yates algorithm.
+fn fisher_yates(items: &mut [usize]) {
+    let mut rng = rand::thread_rng();
+    for i in (1..items.len()).rev() {
+        let j = rng.gen_range(0..=i);
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Builds a queue of `count` tracks, starting at the first.
+    fn queue(count: usize) -> PlayQueue {
+        let tracks = (0..count)
+            .map(|i| Track {
+                path: PathBuf::from(format!("/music/{}.wav", i)),
+                title: i.to_string(),
+                ..Track::default()
+            })
+            .collect();
+        let mut queue = PlayQueue::default();
+        queue.set_tracks(tracks, 0);
+        queue
+    }
+
+    /// Skips ahead and returns the index of the new current track.
+    fn skip(queue: &mut PlayQueue) -> Option<usize> {
+        queue.next(false)?;
+        queue.current_index()
+    }
+
+    #[test]
+    fn shuffle_plays_every_track_once() {
+        let mut queue = queue(20);
+        queue.set_shuffle(true);
+
+        let mut played = vec![0];
+        while let Some(index) = skip(&mut queue) {
+            played.push(index);
+        }
+
+        played.sort();
+        assert_eq!(played, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn previous_walks_back_through_the_shuffled_order() {
+        let mut queue = queue(10);
+        queue.set_shuffle(true);
+        let mut played = vec![0];
+        for _ in 0..5 {
+            played.push(skip(&mut queue).unwrap());
+        }
+
+        for &index in played.iter().rev().skip(1) {
+            queue.previous();
+            assert_eq!(queue.current_index(), Some(index));
+        }
+        // Out of history, the first track stays.
+        queue.previous();
+        assert_eq!(queue.current_index(), Some(0));
+        // "Next" returns along the same path.
+        for &index in &played[1..] {
+            assert_eq!(skip(&mut queue), Some(index));
+        }
+    }
+
+    #[test]
+    fn repeat_all_refills_without_repeating_the_current_track() {
+        for shuffle in [false, true] {
+            for _ in 0..20 {
+                let mut queue = queue(3);
+                queue.set_shuffle(shuffle);
+                queue.set_repeat(RepeatMode::All);
+                for _ in 0..2 {
+                    skip(&mut queue);
+                }
+                let last = queue.current_index();
+
+                queue.prepare_next();
+                let peeked = queue.peek_next().cloned();
+                let next = queue.next(true).cloned();
+
+                assert_eq!(peeked, next);
+                assert_ne!(queue.current_index(), last);
+            }
+        }
+    }
+
+    #[test]
+    fn peeking_leaves_the_queue_unchanged() {
+        let mut queue = queue(2);
+        queue.set_repeat(RepeatMode::All);
+        skip(&mut queue);
+
+        assert_eq!(queue.peek_next(), None);
+        queue.prepare_next();
+        assert_eq!(queue.peek_next(), Some(&queue.tracks()[0]));
+    }
+
+    #[test]
+    fn appended_tracks_join_the_shuffle() {
+        let mut queue = queue(5);
+        queue.set_shuffle(true);
+        let mut played = vec![0];
+        played.push(skip(&mut queue).unwrap());
+
+        let extra = (5..10)
+            .map(|i| Track {
+                title: i.to_string(),
+                ..Track::default()
+            })
+            .collect();
+        queue.append(extra);
+        while let Some(index) = skip(&mut queue) {
+            played.push(index);
+        }
+
+        played.sort();
+        assert_eq!(played, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn appending_to_an_empty_queue_starts_it() {
+        let mut queue = PlayQueue::default();
+        queue.append(vec![Track::default()]);
+        assert_eq!(queue.current_index(), Some(0));
+    }
+
+    #[test]
+    fn repeat_one_only_replays_when_the_track_finishes() {
+        let mut queue = queue(3);
+        queue.set_repeat(RepeatMode::One);
+
+        assert_eq!(queue.peek_next(), Some(&queue.tracks()[0]));
+        queue.next(true);
+        assert_eq!(queue.current_index(), Some(0));
+        queue.next(false);
+        assert_eq!(queue.current_index(), Some(1));
+        // A manual skip off the end stops, as repeat-one does not wrap the queue.
+        queue.next(false);
+        assert_eq!(queue.next(false), None);
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut queue = queue(2);
+        queue.set_repeat(RepeatMode::All);
+        for _ in 0..HISTORY_LIMIT + 10 {
+            skip(&mut queue);
+        }
+        assert_eq!(queue.history.len(), HISTORY_LIMIT);
+    }
+}
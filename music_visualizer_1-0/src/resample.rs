@@ -0,0 +1,563 @@
+//! Resampling module
+//!
+//! Converts interleaved audio between sample rates using `rubato`, with a choice of quality:
+//! - [`ResampleQuality::Fast`]: polynomial interpolation, cheap but with some aliasing
+//! - [`ResampleQuality::Sinc`]: windowed-sinc interpolation with a configurable filter length
+//! - [`ResampleQuality::Fft`]: FFT-based synchronous resampling
+//!
+//! [`StreamResampler`] accepts audio in pieces of any size, so long files never need to be
+//! converted in one go. The resampler's output delay is removed and the output is cut (or padded)
+//! to exactly `round(input_frames * to_rate / from_rate)` frames, so resampled audio stays aligned
+//! with the original (to the nearest sample) and has no added silence at either end.
+//!
+//! The delay `rubato` reports is only approximate for some resamplers, so it is measured instead
+//! by passing an impulse through an identical resampler.
+
+use rubato::{
+    FastFixedIn, FftFixedIn, PolynomialDegree, SincFixedIn, SincInterpolationParameters,
+    SincInterpolationType, VecResampler, WindowFunction, calculate_cutoff,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// Number of input frames handed to `rubato` at a time.
+const CHUNK_FRAMES: usize = 1024;
+
+/// Width (standard deviation, in input frames) of the pulse used to measure a resampler's delay.
+const PULSE_WIDTH_FRAMES: f64 = 4.0;
+
+/// Default sinc filter length, in taps.
+pub const DEFAULT_SINC_TAPS: usize = 256;
+
+/// How audio is converted between sample rates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ResampleQuality {
+    /// Septic polynomial interpolation.
+    Fast,
+    /// Windowed-sinc interpolation.
+    Sinc {
+        /// Length of the sinc filter; longer filters roll off less treble but cost more.
+        /// Zero selects [`DEFAULT_SINC_TAPS`].
+        taps: usize,
+    },
+    /// FFT-based synchronous resampling.
+    #[default]
+    Fft,
+}
+
+impl fmt::Display for ResampleQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResampleQuality::Fast => write!(f, "fast"),
+            ResampleQuality::Sinc { taps } => write!(f, "sinc{}", taps),
+            ResampleQuality::Fft => write!(f, "fft"),
+        }
+    }
+}
+
+/// Errors that can occur while resampling.
+#[derive(Debug, Error)]
+pub enum ResampleError {
+    /// The resampler could not be created for the requested rates.
+    #[error("cannot resample from {from} Hz to {to} Hz: {message}")]
+    Construction { from: u32, to: u32, message: String },
+    /// The resampler failed while processing.
+    #[error("resampling failed: {0}")]
+    Process(#[from] rubato::ResampleError),
+    /// The progress callback asked to stop.
+    #[error("resampling was cancelled")]
+    Cancelled,
+}
+
+/// Converts interleaved audio between two sample rates, piece by piece.
+pub struct StreamResampler {
+    /// `None` when both rates are equal and audio is passed through.
+    resampler: Option<Box<dyn VecResampler<f32>>>,
+    channels: usize,
+    from_rate: u32,
+    to_rate: u32,
+    /// Planar input waiting for a full chunk.
+    pending: Vec<Vec<f32>>,
+    /// One chunk of planar input handed to the resampler.
+    chunk: Vec<Vec<f32>>,
+    /// Planar output of one resampler call.
+    output: Vec<Vec<f32>>,
+    /// Interleaved output not yet handed out, because it may run past the exact length.
+    ready: Vec<f32>,
+    /// Output frames still to drop to compensate for the resampler's delay.
+    delay_remaining: usize,
+    frames_in: u64,
+    frames_out: u64,
+}
+
+impl StreamResampler {
+    // ============================================================================
+    // Public Methods
+    // ============================================================================
+
+    /// Creates a resampler.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_rate` - Sample rate of the input in Hz.
+    /// * `to_rate` - Sample rate of the output in Hz.
+    /// * `channels` - Number of interleaved channels.
+    /// * `quality` - Which resampling algorithm to use.
+    ///
+    /// # Returns
+    ///
+    /// The resampler, or a [`ResampleError`] if `rubato` rejects the rates.
+    pub fn new(
+        from_rate: u32,
+        to_rate: u32,
+        channels: usize,
+        quality: ResampleQuality,
+    ) -> Result<Self, ResampleError> {
+        let channels = channels.max(1);
+        let resampler = if from_rate == to_rate {
+            None
+        } else {
+            Some(Self::build(from_rate, to_rate, channels, quality)?)
+        };
+        let (chunk, output, delay) = match &resampler {
+            Some(resampler) => (
+                resampler.input_buffer_allocate(true),
+                resampler.output_buffer_allocate(true),
+                Self::measure_delay(from_rate, to_rate, quality)?,
+            ),
+            None => (Vec::new(), Vec::new(), 0),
+        };
+
+        Ok(StreamResampler {
+            resampler,
+            channels,
+            from_rate,
+            to_rate,
+            pending: vec![Vec::new(); channels],
+            chunk,
+            output,
+            ready: Vec::new(),
+            delay_remaining: delay,
+            frames_in: 0,
+            frames_out: 0,
+        })
+    }
+
+    /// Returns the number of output frames for `frames` input frames.
+    pub fn output_len(&self, frames: u64) -> u64 {
+        (frames * self.to_rate as u64 + self.from_rate as u64 / 2) / self.from_rate as u64
+    }
+
+    /// Resamples a piece of interleaved input, appending the finished output to `out`.
+    ///
+    /// Some output is held back until more input (or [`finish`](Self::finish)) arrives.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) -> Result<(), ResampleError> {
+        self.frames_in += (input.len() / self.channels) as u64;
+        let Some(resampler) = &mut self.resampler else {
+            out.extend_from_slice(&input[..input.len() - input.len() % self.channels]);
+            self.frames_out = self.frames_in;
+            return Ok(());
+        };
+
+        for frame in input.chunks_exact(self.channels) {
+            for (channel, sample) in self.pending.iter_mut().zip(frame) {
+                channel.push(*sample);
+            }
+        }
+
+        let mut start = 0;
+        while self.pending[0].len() - start >= resampler.input_frames_next() {
+            let needed = resampler.input_frames_next();
+            for (chunk, pending) in self.chunk.iter_mut().zip(&self.pending) {
+                chunk[..needed].copy_from_slice(&pending[start..start + needed]);
+            }
+            let (consumed, produced) =
+                resampler.process_into_buffer(&self.chunk, &mut self.output, None)?;
+            start += consumed;
+            Self::collect(
+                &self.output,
+                produced,
+                &mut self.delay_remaining,
+                &mut self.ready,
+            );
+        }
+        for pending in &mut self.pending {
+            pending.drain(..start);
+        }
+
+        // Hand out everything up to the exact length for the input seen so far.
+        let allowed = self.output_len(self.frames_in);
+        self.release(allowed, out);
+        Ok(())
+    }
+
+    /// Flushes the remaining input and the resampler's delay line into `out`.
+    ///
+    /// The total output is exactly [`output_len`](Self::output_len) of all input frames.
+    pub fn finish(&mut self, out: &mut Vec<f32>) -> Result<(), ResampleError> {
+        let expected = self.output_len(self.frames_in);
+        let Some(resampler) = &mut self.resampler else {
+            return Ok(());
+        };
+
+        let pending_frames = self.pending[0].len();
+        if pending_frames > 0 {
+            let (_, produced) = resampler.process_partial_into_buffer(
+                Some(&self.pending),
+                &mut self.output,
+                None,
+            )?;
+            Self::collect(
+                &self.output,
+                produced,
+                &mut self.delay_remaining,
+                &mut self.ready,
+            );
+            for pending in &mut self.pending {
+                pending.clear();
+            }
+        }
+
+        // Push silence through until the delayed tail has come out.
+        let mut attempts = 0;
+        let ready_frames = |ready: &Vec<f32>| (ready.len() / self.channels) as u64;
+        while self.frames_out + ready_frames(&self.ready) < expected && attempts < 64 {
+            let (_, produced) =
+                resampler.process_partial_into_buffer(None, &mut self.output, None)?;
+            Self::collect(
+                &self.output,
+                produced,
+                &mut self.delay_remaining,
+                &mut self.ready,
+            );
+            attempts += 1;
+        }
+
+        self.release(expected, out);
+        // Pad in the unlikely case the resampler produced too little.
+        while self.frames_out < expected {
+            out.extend(std::iter::repeat_n(0.0, self.channels));
+            self.frames_out += 1;
+        }
+        self.ready.clear();
+        Ok(())
+    }
+
+    // ============================================================================
+    // Private Helper Methods
+    // ============================================================================
+
+    /// Creates the `rubato` resampler for a quality mode.
+    fn build(
+        from_rate: u32,
+        to_rate: u32,
+        channels: usize,
+        quality: ResampleQuality,
+    ) -> Result<Box<dyn VecResampler<f32>>, ResampleError> {
+        let ratio = to_rate as f64 / from_rate as f64;
+        let error = |e: rubato::ResamplerConstructionError| ResampleError::Construction {
+            from: from_rate,
+            to: to_rate,
+            message: e.to_string(),
+        };
+
+        Ok(match quality {
+            ResampleQuality::Fast => Box::new(
+                FastFixedIn::<f32>::new(
+                    ratio,
+                    1.0,
+                    PolynomialDegree::Septic,
+                    CHUNK_FRAMES,
+                    channels,
+                )
+                .map_err(error)?,
+            ),
+            ResampleQuality::Sinc { taps } => {
+                let taps = match taps {
+                    0 => DEFAULT_SINC_TAPS,
+                    taps => taps.clamp(8, 2048),
+                };
+                let window = WindowFunction::BlackmanHarris2;
+                let parameters = SincInterpolationParameters {
+                    sinc_len: taps,
+                    f_cutoff: calculate_cutoff(taps, window),
+                    interpolation: SincInterpolationType::Cubic,
+                    oversampling_factor: 256,
+                    window,
+                };
+                Box::new(
+                    SincFixedIn::<f32>::new(ratio, 1.0, parameters, CHUNK_FRAMES, channels)
+                        .map_err(error)?,
+                )
+            }
+            ResampleQuality::Fft => Box::new(
+                FftFixedIn::<f32>::new(
+                    from_rate as usize,
+                    to_rate as usize,
+                    CHUNK_FRAMES,
+                    2,
+                    channels,
+                )
+                .map_err(error)?,
+            ),
+        })
+    }
+
+    /// Measures the output delay of a resampler, in output frames.
+    ///
+    /// A smooth Gaussian pulse is placed in the first chunk of a mono resampler built with the
+    /// same settings. The pulse is far below every cutoff, so each mode passes it unchanged apart
+    /// from the delay, which is the distance between the centre of the output pulse and where the
+    /// pulse belongs at the output rate.
+    fn measure_delay(
+        from_rate: u32,
+        to_rate: u32,
+        quality: ResampleQuality,
+    ) -> Result<usize, ResampleError> {
+        let mut resampler = Self::build(from_rate, to_rate, 1, quality)?;
+        let ratio = to_rate as f64 / from_rate as f64;
+        let mut input = resampler.input_buffer_allocate(true);
+        let mut output = resampler.output_buffer_allocate(true);
+        let centre = resampler.input_frames_next() / 2;
+        for (i, sample) in input[0].iter_mut().enumerate() {
+            let distance = (i as f64 - centre as f64) / PULSE_WIDTH_FRAMES;
+            *sample = (-0.5 * distance * distance).exp() as f32;
+        }
+
+        let mut weighted = 0.0;
+        let mut total = 0.0;
+        let mut produced_total = 0;
+        // Long enough for the pulse to come out even if the reported delay is badly off.
+        let horizon = (centre as f64 * ratio) as usize
+            + 2 * resampler.output_delay()
+            + resampler.output_frames_max();
+        while produced_total < horizon {
+            let (_, produced) = resampler.process_into_buffer(&input, &mut output, None)?;
+            for (i, &sample) in output[0][..produced].iter().enumerate() {
+                weighted += (produced_total + i) as f64 * sample as f64;
+                total += sample as f64;
+            }
+            produced_total += produced;
+            input[0].fill(0.0);
+        }
+        let delay = weighted / total.max(f64::EPSILON) - centre as f64 * ratio;
+        Ok(delay.round().max(0.0) as usize)
+    }
+
+    /// Interleaves `frames` frames of planar resampler output into `ready`, first dropping any
+    /// frames still owed to the resampler's delay.
+    fn collect(
+        output: &[Vec<f32>],
+        frames: usize,
+        delay_remaining: &mut usize,
+        ready: &mut Vec<f32>,
+    ) {
+        let skip = (*delay_remaining).min(frames);
+        *delay_remaining -= skip;
+        for i in skip..frames {
+            for channel in output {
+                ready.push(channel[i]);
+            }
+        }
+    }
+
+    /// Moves ready output into `out` until `limit` frames have been handed out in total.
+    fn release(&mut self, limit: u64, out: &mut Vec<f32>) {
+        let available = (self.ready.len() / self.channels) as u64;
+        let frames = available.min(limit.saturating_sub(self.frames_out)) as usize;
+        out.extend(self.ready.drain(..frames * self.channels));
+        self.frames_out += frames as u64;
+    }
+}
+
+/// Resamples a whole interleaved buffer, reporting progress after every second of input.
+///
+/// # Arguments
+///
+/// * `input` - Interleaved samples at `from_rate`.
+/// * `from_rate` - Sample rate of the input in Hz.
+/// * `to_rate` - Sample rate of the output in Hz.
+/// * `channels` - Number of interleaved channels.
+/// * `quality` - Which resampling algorithm to use.
+/// * `progress` - Called with the fraction of input processed so far; returning `false` stops
+///   resampling with [`ResampleError::Cancelled`].
+///
+/// # Returns
+///
+/// Exactly `round(frames * to_rate / from_rate)` frames of interleaved output, aligned with the
+/// input.
+pub fn resample(
+    input: &[f32],
+    from_rate: u32,
+    to_rate: u32,
+    channels: usize,
+    quality: ResampleQuality,
+    mut progress: impl FnMut(f32) -> bool,
+) -> Result<Vec<f32>, ResampleError> {
+    let channels = channels.max(1);
+    let mut resampler = StreamResampler::new(from_rate, to_rate, channels, quality)?;
+    let expected = resampler.output_len((input.len() / channels) as u64) as usize;
+    let mut out = Vec::with_capacity(expected * channels);
+
+    let piece = from_rate.max(1) as usize * channels;
+    let mut done = 0;
+    for samples in input.chunks(piece) {
+        resampler.process(samples, &mut out)?;
+        done += samples.len();
+        if !progress(done as f32 / input.len() as f32) {
+            return Err(ResampleError::Cancelled);
+        }
+    }
+    resampler.finish(&mut out)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const QUALITIES: [ResampleQuality; 3] = [
+        ResampleQuality::Fast,
+        ResampleQuality::Sinc {
+            taps: DEFAULT_SINC_TAPS,
+        },
+        ResampleQuality::Fft,
+    ];
+
+    const RATE_PAIRS: [(u32, u32); 6] = [
+        (44100, 48000),
+        (48000, 44100),
+        (48000, 96000),
+        (96000, 48000),
+        (44100, 96000),
+        (96000, 44100),
+    ];
+
+    /// Generates an interleaved stereo sine wave.
+    fn sine(frequency: f32, rate: u32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|i| {
+                let value = (2.0 * PI * frequency * i as f32 / rate as f32).sin() * 0.5;
+                [value, value]
+            })
+            .collect()
+    }
+
+    /// Estimates the frequency of one channel from its interpolated upward zero crossings.
+    fn measure_frequency(samples: &[f32], channels: usize, rate: u32) -> f32 {
+        let channel: Vec<f32> = samples.iter().step_by(channels).copied().collect();
+        let crossings: Vec<f32> = channel
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] <= 0.0 && w[1] > 0.0)
+            .map(|(i, w)| i as f32 + w[0] / (w[0] - w[1]))
+            .collect();
+        let periods = (crossings.len() - 1) as f32;
+        periods * rate as f32 / (crossings[crossings.len() - 1] - crossings[0])
+    }
+
+    #[test]
+    fn output_has_exact_length() {
+        for quality in QUALITIES {
+            for (from, to) in RATE_PAIRS {
+                for frames in [1, 1000, 44100, 48001] {
+                    let input = sine(440.0, from, frames);
+                    let output = resample(&input, from, to, 2, quality, |_| true).unwrap();
+                    let expected =
+                        ((frames as u64 * to as u64 + from as u64 / 2) / from as u64) as usize;
+                    assert_eq!(
+                        output.len(),
+                        expected * 2,
+                        "{} {}->{} Hz, {} frames",
+                        quality,
+                        from,
+                        to,
+                        frames
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sine_keeps_its_frequency() {
+        for quality in QUALITIES {
+            for (from, to) in RATE_PAIRS {
+                let input = sine(1000.0, from, from as usize);
+                let output = resample(&input, from, to, 2, quality, |_| true).unwrap();
+                let frequency = measure_frequency(&output, 2, to);
+                assert!(
+                    (frequency - 1000.0).abs() < 0.5,
+                    "{} {}->{} Hz measured {} Hz",
+                    quality,
+                    from,
+                    to,
+                    frequency
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn delay_is_compensated() {
+        for quality in QUALITIES {
+            for (from, to) in RATE_PAIRS {
+                let frames = from as usize / 2;
+                let output =
+                    resample(&sine(1000.0, from, frames), from, to, 2, quality, |_| true).unwrap();
+                let ideal = sine(1000.0, to, output.len() / 2);
+                // Compare away from the edges, where the filters see the start and end of input.
+                // Alignment to the nearest sample leaves at most half a sample of phase error.
+                let half_sample_error = 0.5 * 2.0 * PI * 1000.0 / to as f32 * 0.5;
+                let (start, end) = (output.len() / 4, output.len() * 3 / 4);
+                let error = output[start..end]
+                    .iter()
+                    .zip(&ideal[start..end])
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f32::max);
+                assert!(
+                    error < half_sample_error + 0.005,
+                    "{} {}->{} Hz differs from the ideal sine by {}",
+                    quality,
+                    from,
+                    to,
+                    error
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_matches_single_call() {
+        for quality in QUALITIES {
+            let input = sine(440.0, 44100, 20000);
+            let whole = resample(&input, 44100, 48000, 2, quality, |_| true).unwrap();
+
+            let mut resampler = StreamResampler::new(44100, 48000, 2, quality).unwrap();
+            let mut streamed = Vec::new();
+            for piece in input.chunks(2 * 777) {
+                resampler.process(piece, &mut streamed).unwrap();
+            }
+            resampler.finish(&mut streamed).unwrap();
+
+            assert_eq!(streamed.len(), whole.len(), "{}", quality);
+            assert!(
+                streamed
+                    .iter()
+                    .zip(&whole)
+                    .all(|(a, b)| (a - b).abs() < 1e-6),
+                "{}",
+                quality
+            );
+        }
+    }
+
+    #[test]
+    fn equal_rates_pass_through() {
+        let input = sine(440.0, 48000, 1234);
+        let output = resample(&input, 48000, 48000, 2, ResampleQuality::Fft, |_| true).unwrap();
+        assert_eq!(output, input);
+    }
+}
@@ -0,0 +1,285 @@
+//! Library search module
+//!
+//! Filters the song list as the user types. Matching is fuzzy: the characters of the query must
+//! appear in order in an entry's text, but not necessarily next to each other ("dfpk" finds
+//! "Daft Punk"). Matches are ranked so that runs of consecutive characters and characters at the
+//! start of words score higher.
+//!
+//! A [`SearchIndex`] keeps each entry's text folded to lower case together with a bit mask of the
+//! characters it contains. An entry whose mask lacks any character of the query is skipped
+//! without being scanned, and a query that extends the previous one only re-checks the previous
+//! matches, so typing stays fast on libraries of tens of thousands of tracks.
+
+/// Score for every matched character.
+const MATCH_SCORE: i32 = 16;
+
+/// Bonus for a matched character that directly follows the previous match.
+const CONSECUTIVE_BONUS: i32 = 12;
+
+/// Bonus for a matched character at the start of a word.
+const WORD_START_BONUS: i32 = 8;
+
+/// Penalty per unmatched character between two matches.
+const GAP_PENALTY: i32 = 1;
+
+/// Penalty per character before the first match, up to [`MAX_LEADING_PENALTY`].
+const LEADING_PENALTY: i32 = 1;
+
+/// Largest penalty for characters before the first match.
+const MAX_LEADING_PENALTY: i32 = 8;
+
+/// One searchable entry.
+struct IndexEntry {
+    /// The entry's text, folded to lower case one character at a time.
+    text: Vec<char>,
+    /// The characters present in `text` (see [`char_mask`]).
+    mask: u64,
+}
+
+/// A fuzzy search index over a fixed list of entries.
+pub struct SearchIndex {
+    entries: Vec<IndexEntry>,
+    /// The folded query of the last search and the entries it matched, in index order.
+    last: Option<(Vec<char>, Vec<usize>)>,
+}
+
+impl SearchIndex {
+    // ============================================================================
+    // Public Methods
+    // ============================================================================
+
+    /// Builds an index over the given texts.
+    ///
+    /// # Arguments
+    ///
+    /// * `texts` - The searchable text of each entry, such as a track's title, artist and album.
+    pub fn new<I, S>(texts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let entries = texts
+            .into_iter()
+            .map(|text| {
+                let text = fold(text.as_ref());
+                let mask = text.iter().fold(0, |mask, &c| mask | char_mask(c));
+                IndexEntry { text, mask }
+            })
+            .collect();
+        SearchIndex {
+            entries,
+            last: None,
+        }
+    }
+
+    /// Finds the entries matching a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The text typed by the user; case is ignored.
+    ///
+    /// # Returns
+    ///
+    /// The indices of the matching entries, best match first. An empty query matches every
+    /// entry, in index order.
+    pub fn search(&mut self, query: &str) -> Vec<usize> {
+        let query = fold(query.trim());
+        if query.is_empty() {
+            self.last = None;
+            return (0..self.entries.len()).collect();
+        }
+
+        // Every entry matching the query also matched any prefix of it.
+        let candidates = match self.last.take() {
+            Some((last_query, matches)) if query.starts_with(&last_query) => matches,
+            _ => (0..self.entries.len()).collect(),
+        };
+
+        let query_mask = query.iter().fold(0, |mask, &c| mask | char_mask(c));
+        let mut positions = Vec::with_capacity(query.len());
+        let mut scored: Vec<(i32, usize)> = candidates
+            .into_iter()
+            .filter_map(|index| {
+                let entry = &self.entries[index];
+                if entry.mask & query_mask != query_mask {
+                    return None;
+                }
+                fuzzy_match(&query, &entry.text, &mut positions).map(|score| (score, index))
+            })
+            .collect();
+
+        let matches: Vec<usize> = scored.iter().map(|&(_, index)| index).collect();
+        self.last = Some((query, matches));
+
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.into_iter().map(|(_, index)| index).collect()
+    }
+}
+
+/// Returns the characters of `text` that a query matches, for highlighting.
+///
+/// # Arguments
+///
+/// * `query` - The text typed by the user; case is ignored.
+/// * `text` - The displayed text.
+///
+/// # Returns
+///
+/// The indices (in characters) of the matched characters, or nothing if `text` does not match.
+pub fn highlights(query: &str, text: &str) -> Vec<usize> {
+    let query = fold(query.trim());
+    let mut positions = Vec::with_capacity(query.len());
+    if query.is_empty() || fuzzy_match(&query, &fold(text), &mut positions).is_none() {
+        return Vec::new();
+    }
+    positions
+}
+
+/// Matches a folded query against a folded text.
+///
+/// The first occurrence of the query's last character is found by a forward scan; the match is
+/// then tightened by scanning backwards from there, so "ab" in "a_xab" matches the closer "a".
+///
+/// # Arguments
+///
+/// * `query` - The folded query; must not be empty.
+/// * `text` - The folded text.
+/// * `positions` - Receives the indices of the matched characters.
+///
+/// # Returns
+///
+/// The match's score, or `None` if the query's characters do not all appear in order.
+fn fuzzy_match(query: &[char], text: &[char], positions: &mut Vec<usize>) -> Option<i32> {
+    positions.clear();
+
+    // Forward: find where the earliest complete match ends.
+    let mut next = 0;
+    let mut end = None;
+    for (i, &c) in text.iter().enumerate() {
+        if c == query[next] {
+            next += 1;
+            if next == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Backward: take the latest occurrence of each character before it.
+    let mut remaining = query.len();
+    for i in (0..=end).rev() {
+        if text[i] == query[remaining - 1] {
+            positions.push(i);
+            remaining -= 1;
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+    positions.reverse();
+
+    let mut score = -(positions[0] as i32 * LEADING_PENALTY).min(MAX_LEADING_PENALTY);
+    for (n, &i) in positions.iter().enumerate() {
+        score += MATCH_SCORE;
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+        if n > 0 {
+            let gap = i - positions[n - 1] - 1;
+            if gap == 0 {
+                score += CONSECUTIVE_BONUS;
+            } else {
+                score -= gap as i32 * GAP_PENALTY;
+            }
+        }
+    }
+    Some(score)
+}
+
+/// Folds text to lower case, keeping exactly one character per input character.
+///
+/// Keeping the length unchanged lets match positions index the original text.
+fn fold(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+/// Returns the bit of the index mask that stands for a folded character.
+///
+/// Letters and digits get a bit each; other characters share the remaining bits, and
+/// whitespace is ignored.
+fn char_mask(c: char) -> u64 {
+    match c {
+        'a'..='z' => 1 << (c as u32 - 'a' as u32),
+        '0'..='9' => 1 << (26 + c as u32 - '0' as u32),
+        c if c.is_whitespace() => 0,
+        c => 1 << (36 + c as u32 % 28),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TITLES: [&str; 6] = [
+        "Daft Punk - Digital Love",
+        "Deep Purple - Smoke on the Water",
+        "David Bowie - Heroes",
+        "Dua Lipa - Don't Start Now",
+        "Fleetwood Mac - Dreams",
+        "Beyoncé - Halo",
+    ];
+
+    #[test]
+    fn typing_ahead_matches_a_fresh_search() {
+        let mut typed = SearchIndex::new(TITLES);
+        for query in [
+            "d", "da", "daf", "daft", "daft p", "dafx", "d", "de", "dep", "Dë",
+        ] {
+            let fresh = SearchIndex::new(TITLES).search(query);
+            assert_eq!(typed.search(query), fresh, "query '{}'", query);
+        }
+    }
+
+    #[test]
+    fn an_empty_query_lists_every_entry_in_order() {
+        let mut index = SearchIndex::new(TITLES);
+        index.search("halo");
+        assert_eq!(index.search("  "), (0..TITLES.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn word_starts_rank_first() {
+        let mut index = SearchIndex::new(["xdxp", "Daft Punk"]);
+        assert_eq!(index.search("dp"), [1, 0]);
+    }
+
+    #[test]
+    fn consecutive_characters_rank_first() {
+        let mut index = SearchIndex::new(["fxuxn", "xfun"]);
+        assert_eq!(index.search("fun"), [1, 0]);
+    }
+
+    #[test]
+    fn equal_scores_keep_index_order() {
+        let mut index = SearchIndex::new(["Halo", "Hello", "Halo"]);
+        assert_eq!(index.search("halo"), [0, 2]);
+    }
+
+    #[test]
+    fn highlights_count_characters_not_bytes() {
+        assert_eq!(highlights("bé", "Beyoncé"), [0, 6]);
+        assert_eq!(highlights("üc", "Ünïcode"), [0, 3]);
+        // "İ" lower-cases to two characters; only the first is kept.
+        assert_eq!(highlights("ist", "İstanbul"), [0, 1, 2]);
+        assert!(highlights("xyz", "Beyoncé").is_empty());
+        assert!(highlights("", "Beyoncé").is_empty());
+    }
+
+    #[test]
+    fn matches_take_the_closest_occurrence() {
+        assert_eq!(highlights("ab", "a_xab"), [3, 4]);
+    }
+}
@@ -0,0 +1,271 @@
+//! Session module
+//!
+//! Remembers where the user left off, as JSON in `config/session.json`: the play queue, the
+//! track that was playing and how far into it, the volume, shuffle and repeat, the visualizer and
+//! where the window was. The session is saved periodically and on exit, and restored at startup.
+//!
+//! Unlike the [`Config`](crate::config::Config), which only changes when a setting does, the
+//! session changes all the time and is overwritten freely.
+
+use crate::atomic_file;
+use crate::library::Track;
+use crate::queue::{PlayQueue, RepeatMode};
+use crate::view::Visualizer;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Path (relative to the working directory) of the session file.
+pub const SESSION_PATH: &str = "config/session.json";
+
+/// Where the window was and how big it was.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    /// Position of the window's outer top-left corner on the desktop, in pixels.
+    pub x: i32,
+    pub y: i32,
+    /// Size of the window's contents, in points.
+    pub width: f32,
+    pub height: f32,
+}
+
+/// The state of the player when the session was saved.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Paths of the queued tracks, in queue order.
+    pub queue: Vec<PathBuf>,
+    /// Index in `queue` of the track that was playing, or `None` if none was.
+    pub current: Option<usize>,
+    /// Position in the current track, in seconds.
+    pub position: f64,
+    /// Whether the current track was playing rather than paused.
+    pub playing: bool,
+    /// Output volume, from 0.0 (silent) to 1.0 (full).
+    pub volume: f32,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    pub visualizer: Visualizer,
+    /// The window, if its position could be read.
+    pub window: Option<WindowGeometry>,
+}
+
+/// Where playback picks up in a restored queue.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resume {
+    /// The last song is still there: continue it at this position, in seconds.
+    At(f64),
+    /// The last song is gone: start the next surviving track from its beginning.
+    FromStart,
+    /// Nothing was playing, or no track is left after the last song: stay stopped.
+    Stopped,
+}
+
+/// The queue of a session, read back from the files that are still there.
+pub struct RestoredQueue {
+    pub tracks: Vec<Track>,
+    /// Index in `tracks` of the track to load.
+    pub start: usize,
+    pub resume: Resume,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session {
+            queue: Vec::new(),
+            current: None,
+            position: 0.0,
+            playing: false,
+            volume: 1.0,
+            shuffle: false,
+            repeat: RepeatMode::Off,
+            visualizer: Visualizer::Status,
+            window: None,
+        }
+    }
+}
+
+impl Session {
+    /// Captures the playback part of a session: the queue, the song and position, and the
+    /// volume, shuffle and repeat settings.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The play queue.
+    /// * `position` - Position in the queue's current song, in seconds, or `None` if no song
+    ///   is loaded or loading.
+    /// * `playing` - Whether the song is playing rather than paused.
+    /// * `volume` - The output volume.
+    pub fn capture(queue: &PlayQueue, position: Option<f64>, playing: bool, volume: f32) -> Self {
+        Session {
+            queue: queue
+                .tracks()
+                .iter()
+                .map(|track| track.path.clone())
+                .collect(),
+            current: queue.current_index().filter(|_| position.is_some()),
+            position: position.unwrap_or_default(),
+            playing,
+            volume,
+            shuffle: queue.shuffle(),
+            repeat: queue.repeat(),
+            ..Session::default()
+        }
+    }
+
+    /// Reads the queued tracks back, leaving out those whose files are gone.
+    ///
+    /// If the song that was playing is gone, playback picks up at the next surviving track, or,
+    /// with none after it, stays stopped at the first one.
+    ///
+    /// # Returns
+    ///
+    /// The tracks and where to pick up; no tracks if none are left.
+    pub fn restore_queue(&self) -> RestoredQueue {
+        let mut tracks = Vec::new();
+        let mut start = None;
+        for (index, path) in self.queue.iter().enumerate() {
+            match Track::from_path(path) {
+                Ok(track) => {
+                    if start.is_none() && self.current.is_some_and(|current| index >= current) {
+                        let resume = if self.current == Some(index) {
+                            Resume::At(self.position)
+                        } else {
+                            Resume::FromStart
+                        };
+                        start = Some((tracks.len(), resume));
+                    }
+                    tracks.push(track);
+                }
+                Err(e) => eprintln!(
+                    "⚠️ Skipping '{}' from the last session: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+        let (start, resume) = start.unwrap_or((0, Resume::Stopped));
+        RestoredQueue {
+            tracks,
+            start,
+            resume,
+        }
+    }
+
+    /// Loads the last session.
+    ///
+    /// # Returns
+    ///
+    /// The session, or `None` if there is none or the file is invalid.
+    pub fn load() -> Option<Self> {
+        let contents = fs::read_to_string(SESSION_PATH).ok()?;
+        serde_json::from_str(&contents)
+            .map_err(|e| eprintln!("⚠️ Invalid session '{}': {}", SESSION_PATH, e))
+            .ok()
+    }
+
+    /// Writes the session file, creating the config folder if needed.
+    ///
+    /// The file is replaced atomically, so being killed mid-save keeps the previous session.
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                atomic_file::write(Path::new(SESSION_PATH), json.as_bytes())
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            eprintln!("⚠️ Could not save session to '{}': {}", SESSION_PATH, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A folder in the temporary directory that is removed when dropped.
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new() -> Self {
+            static CREATED: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "music_visualizer-session-{}-{}",
+                process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempFolder(path)
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    /// Builds a session whose queue holds `one.wav`, `two.wav` and `three.wav`, all but `gone`
+    /// still on disk, with the track at `current` playing 42 seconds in.
+    fn session(folder: &TempFolder, current: Option<usize>, gone: &[&str]) -> Session {
+        let queue: Vec<PathBuf> = ["one.wav", "two.wav", "three.wav"]
+            .iter()
+            .map(|name| {
+                let path = folder.0.join(name);
+                if !gone.contains(name) {
+                    fs::write(&path, b"RIFF").unwrap();
+                }
+                path
+            })
+            .collect();
+        Session {
+            queue,
+            current,
+            position: 42.0,
+            playing: true,
+            ..Session::default()
+        }
+    }
+
+    fn file_names(restored: &RestoredQueue) -> Vec<&str> {
+        restored
+            .tracks
+            .iter()
+            .map(|track| track.file_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn the_last_song_continues_where_it_was() {
+        let folder = TempFolder::new();
+        let restored = session(&folder, Some(1), &["one.wav"]).restore_queue();
+        assert_eq!(file_names(&restored), ["two.wav", "three.wav"]);
+        assert_eq!(restored.start, 0);
+        assert_eq!(restored.resume, Resume::At(42.0));
+    }
+
+    #[test]
+    fn a_missing_song_gives_way_to_the_next_survivor() {
+        let folder = TempFolder::new();
+        let restored = session(&folder, Some(0), &["one.wav", "two.wav"]).restore_queue();
+        assert_eq!(file_names(&restored), ["three.wav"]);
+        assert_eq!(restored.start, 0);
+        assert_eq!(restored.resume, Resume::FromStart);
+    }
+
+    #[test]
+    fn nothing_after_a_missing_song_stays_stopped() {
+        let folder = TempFolder::new();
+        let restored = session(&folder, Some(2), &["three.wav"]).restore_queue();
+        assert_eq!(file_names(&restored), ["one.wav", "two.wav"]);
+        assert_eq!(restored.start, 0);
+        assert_eq!(restored.resume, Resume::Stopped);
+
+        let restored = session(&folder, None, &[]).restore_queue();
+        assert_eq!(restored.tracks.len(), 3);
+        assert_eq!((restored.start, restored.resume), (0, Resume::Stopped));
+    }
+}
@@ -0,0 +1,601 @@
+//! Smart playlist module
+//!
+//! A smart playlist is a [`Rule`] rather than a list of files: it holds whichever library tracks
+//! match the rule when it is evaluated, so it follows the library as tracks are added, played
+//! and rated. Rules combine conditions on a track's tags and on its stats from the
+//! [`LibraryStore`] in `all` (AND) and `any` (OR) groups, which can be nested. The matches can be
+//! sorted and limited to a number of tracks.
+//!
+//! Smart playlists are JSON files kept with the regular playlists, for example
+//! `playlists/Upbeat.json`:
+//!
+//! ```json
+//! {
+//!   "name": "Upbeat",
+//!   "rule": { "all": [{ "rating": { "min": 4 } }, { "bpm": { "min": 120, "max": 130 } }] },
+//!   "sort": { "by": "play_count", "descending": true },
+//!   "limit": 50
+//! }
+//! ```
+
+use crate::library::Track;
+use crate::store::{self, LibraryStore, TrackStats};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::Path;
+
+/// Seconds in a day, for rules counting days.
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// An inclusive range of numbers; a missing bound leaves that side open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Range {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+impl Range {
+    /// Returns whether a value lies within the range. A missing value never does.
+    fn contains(&self, value: Option<f64>) -> bool {
+        value.is_some_and(|value| {
+            self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+        })
+    }
+}
+
+/// A condition a track has to meet to be part of a smart playlist.
+///
+/// Text is compared ignoring case.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// Every rule of the group matches.
+    All(Vec<Rule>),
+    /// At least one rule of the group matches.
+    Any(Vec<Rule>),
+    /// Stars, where 0 means unrated.
+    Rating(Range),
+    /// Tempo in beats per minute, as tagged.
+    Bpm(Range),
+    /// Number of times the track was played to the end.
+    PlayCount(Range),
+    /// Length in seconds.
+    Duration(Range),
+    /// The track was never played to the end.
+    NeverPlayed,
+    /// The track finished playing within this many days.
+    PlayedWithinDays(u32),
+    /// The track joined the library within this many days.
+    AddedWithinDays(u32),
+    /// The genre tag is this text.
+    Genre(String),
+    /// The artist tag is this text.
+    Artist(String),
+    /// The album tag is this text.
+    Album(String),
+}
+
+impl Rule {
+    /// Returns whether a track meets the rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track.
+    /// * `stats` - The track's stats.
+    /// * `now` - The current time, in seconds since the Unix epoch.
+    fn matches(&self, track: &Track, stats: &TrackStats, now: i64) -> bool {
+        let within_days = |time: Option<i64>, days: u32| {
+            time.is_some_and(|time| now - time <= days as i64 * SECONDS_PER_DAY)
+        };
+        let text_is = |text: &Option<String>, wanted: &str| {
+            text.as_deref()
+                .is_some_and(|text| text.trim().eq_ignore_ascii_case(wanted.trim()))
+        };
+        match self {
+            Rule::All(rules) => rules.iter().all(|rule| rule.matches(track, stats, now)),
+            Rule::Any(rules) => rules.iter().any(|rule| rule.matches(track, stats, now)),
+            Rule::Rating(range) => range.contains(Some(stats.rating as f64)),
+            Rule::Bpm(range) => range.contains(track.bpm.map(f64::from)),
+            Rule::PlayCount(range) => range.contains(Some(stats.play_count as f64)),
+            Rule::Duration(range) => range.contains(track.duration),
+            Rule::NeverPlayed => stats.play_count == 0,
+            Rule::PlayedWithinDays(days) => within_days(stats.last_played, *days),
+            Rule::AddedWithinDays(days) => within_days(stats.added_at, *days),
+            Rule::Genre(genre) => text_is(&track.genre, genre),
+            Rule::Artist(artist) => text_is(&track.artist, artist),
+            Rule::Album(album) => text_is(&track.album, album),
+        }
+    }
+}
+
+/// What the tracks of a smart playlist are ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    Title,
+    Artist,
+    Album,
+    Rating,
+    PlayCount,
+    LastPlayed,
+    Added,
+    Bpm,
+    Duration,
+    /// A new shuffle every time the playlist is evaluated.
+    Random,
+}
+
+/// The order of a smart playlist's tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sort {
+    pub by: SortKey,
+    /// Largest (or last in the alphabet) first.
+    #[serde(default)]
+    pub descending: bool,
+}
+
+/// A playlist of the library tracks matching a rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartPlaylist {
+    pub name: String,
+    pub rule: Rule,
+    /// Order of the tracks; without one, they keep the library's order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Sort>,
+    /// Largest number of tracks, taken after sorting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl SmartPlaylist {
+    /// Returns the smart playlists that ship with the player.
+    pub fn builtin() -> Vec<SmartPlaylist> {
+        vec![
+            SmartPlaylist {
+                name: "Top Rated".to_string(),
+                rule: Rule::Rating(Range {
+                    min: Some(4.0),
+                    max: None,
+                }),
+                sort: Some(Sort {
+                    by: SortKey::Rating,
+                    descending: true,
+                }),
+                limit: None,
+            },
+            SmartPlaylist {
+                name: "Most Played".to_string(),
+                rule: Rule::PlayCount(Range {
+                    min: Some(1.0),
+                    max: None,
+                }),
+                sort: Some(Sort {
+                    by: SortKey::PlayCount,
+                    descending: true,
+                }),
+                limit: Some(25),
+            },
+            SmartPlaylist {
+                name: "Never Played".to_string(),
+                rule: Rule::NeverPlayed,
+                sort: None,
+                limit: None,
+            },
+            SmartPlaylist {
+                name: "Recently Added".to_string(),
+                rule: Rule::AddedWithinDays(30),
+                sort: Some(Sort {
+                    by: SortKey::Added,
+                    descending: true,
+                }),
+                limit: None,
+            },
+        ]
+    }
+
+    /// Loads the built-in smart playlists followed by those saved in a folder.
+    ///
+    /// Only `.json` files are read; a saved smart playlist replaces a built-in one with the same
+    /// name. Files that cannot be read are reported and skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir_path` - The playlists folder.
+    pub fn load_all(dir_path: &str) -> Vec<SmartPlaylist> {
+        let mut playlists = Self::builtin();
+        let Ok(entries) = fs::read_dir(dir_path) else {
+            return playlists;
+        };
+        let mut paths: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| Self::is_smart_playlist(path))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let playlist = match fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|json| {
+                    serde_json::from_str::<SmartPlaylist>(&json).map_err(|e| e.to_string())
+                }) {
+                Ok(playlist) => playlist,
+                Err(e) => {
+                    eprintln!("⚠️ Invalid smart playlist '{}': {}", path.display(), e);
+                    continue;
+                }
+            };
+            match playlists.iter_mut().find(|p| p.name == playlist.name) {
+                Some(existing) => *existing = playlist,
+                None => playlists.push(playlist),
+            }
+        }
+        playlists
+    }
+
+    /// Finds the library tracks that belong to the playlist right now.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracks` - The library's tracks.
+    /// * `store` - The stats of the tracks.
+    ///
+    /// # Returns
+    ///
+    /// The matching tracks, sorted and limited as the playlist asks.
+    pub fn evaluate(&self, tracks: &[Track], store: &LibraryStore) -> Vec<Track> {
+        let tracks = tracks.iter().map(|track| (track, store.stats(&track.path)));
+        self.select(tracks, store::now())
+    }
+
+    /// Picks the tracks that match the rule, sorted and limited as the playlist asks.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracks` - The library's tracks with their stats.
+    /// * `now` - The current time, in seconds since the Unix epoch.
+    fn select<'a>(
+        &self,
+        tracks: impl Iterator<Item = (&'a Track, TrackStats)>,
+        now: i64,
+    ) -> Vec<Track> {
+        let mut matches: Vec<(&Track, TrackStats)> = tracks
+            .filter(|(track, stats)| self.rule.matches(track, stats, now))
+            .collect();
+
+        match self.sort {
+            Some(Sort {
+                by: SortKey::Random,
+                ..
+            }) => matches.shuffle(&mut rand::thread_rng()),
+            Some(sort) => matches.sort_by(|a, b| compare(sort, a, b)),
+            None => {}
+        }
+        if let Some(limit) = self.limit {
+            matches.truncate(limit);
+        }
+        matches
+            .into_iter()
+            .map(|(track, _)| track.clone())
+            .collect()
+    }
+
+    /// Returns whether a file in the playlists folder is a smart playlist.
+    fn is_smart_playlist(path: &Path) -> bool {
+        path.is_file()
+            && path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+    }
+}
+
+/// The smart playlists, each with the tracks it matched when it was last evaluated.
+#[derive(Default)]
+pub struct SmartPlaylists {
+    playlists: Vec<SmartPlaylist>,
+    /// The tracks each playlist matched, in the order of the playlists.
+    matches: Vec<Vec<Track>>,
+}
+
+impl SmartPlaylists {
+    /// Loads the smart playlists (see [`SmartPlaylist::load_all`]) and evaluates them.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir_path` - The playlists folder.
+    /// * `tracks` - The library's tracks.
+    /// * `store` - The stats of the tracks.
+    pub fn load(dir_path: &str, tracks: &[Track], store: &LibraryStore) -> Self {
+        let mut playlists = SmartPlaylists {
+            playlists: SmartPlaylist::load_all(dir_path),
+            matches: Vec::new(),
+        };
+        playlists.refresh(tracks, store);
+        playlists
+    }
+
+    /// Evaluates every playlist again, after the library, a play count or a rating changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracks` - The library's tracks.
+    /// * `store` - The stats of the tracks.
+    pub fn refresh(&mut self, tracks: &[Track], store: &LibraryStore) {
+        self.matches = self
+            .playlists
+            .iter()
+            .map(|playlist| playlist.evaluate(tracks, store))
+            .collect();
+    }
+
+    /// Returns the number of smart playlists.
+    pub fn count(&self) -> usize {
+        self.playlists.len()
+    }
+
+    /// Returns a smart playlist and the tracks it matched, if there is one at `index`.
+    pub fn get(&self, index: usize) -> Option<(&SmartPlaylist, &[Track])> {
+        Some((self.playlists.get(index)?, self.matches.get(index)?))
+    }
+
+    /// Returns the smart playlists, in order.
+    pub fn playlists(&self) -> &[SmartPlaylist] {
+        &self.playlists
+    }
+
+    /// Returns the tracks to queue to play a smart playlist, reporting what is played.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the smart playlist.
+    ///
+    /// # Returns
+    ///
+    /// The tracks the playlist matched when it was last evaluated, or `None` if it matched
+    /// none.
+    pub fn tracks_to_play(&self, index: usize) -> Option<Vec<Track>> {
+        let (playlist, tracks) = self.get(index)?;
+        if tracks.is_empty() {
+            println!("📃 No tracks match smart playlist '{}'.", playlist.name);
+            return None;
+        }
+        println!(
+            "📃 Playing smart playlist '{}' ({} tracks).",
+            playlist.name,
+            tracks.len()
+        );
+        Some(tracks.to_vec())
+    }
+}
+
+/// A value tracks are sorted by.
+enum SortValue {
+    /// Text, in lower case so the order ignores case.
+    Text(String),
+    Number(f64),
+}
+
+/// Orders two tracks by a sort key. Tracks without a value for the key come last either way.
+fn compare(sort: Sort, a: &(&Track, TrackStats), b: &(&Track, TrackStats)) -> Ordering {
+    match (sort_value(sort.by, a), sort_value(sort.by, b)) {
+        (Some(a), Some(b)) => {
+            let ordering = match (a, b) {
+                (SortValue::Text(a), SortValue::Text(b)) => a.cmp(&b),
+                (SortValue::Number(a), SortValue::Number(b)) => a.total_cmp(&b),
+                _ => Ordering::Equal,
+            };
+            if sort.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Returns the value a track is sorted by, if it has one.
+fn sort_value(key: SortKey, (track, stats): &(&Track, TrackStats)) -> Option<SortValue> {
+    let text = |text: &Option<String>| {
+        text.as_ref()
+            .map(|text| SortValue::Text(text.to_lowercase()))
+    };
+    let number = |number: Option<f64>| number.map(SortValue::Number);
+    match key {
+        SortKey::Title => Some(SortValue::Text(track.title.to_lowercase())),
+        SortKey::Artist => text(&track.artist),
+        SortKey::Album => text(&track.album),
+        SortKey::Rating => number(Some(stats.rating as f64)),
+        SortKey::PlayCount => number(Some(stats.play_count as f64)),
+        SortKey::LastPlayed => number(stats.last_played.map(|time| time as f64)),
+        SortKey::Added => number(stats.added_at.map(|time| time as f64)),
+        SortKey::Bpm => number(track.bpm.map(f64::from)),
+        SortKey::Duration => number(track.duration),
+        SortKey::Random => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds since the Unix epoch the tests treat as now.
+    const NOW: i64 = 1_700_000_000;
+
+    fn track(title: &str, artist: Option<&str>, bpm: Option<f32>) -> Track {
+        Track {
+            title: title.to_string(),
+            artist: artist.map(str::to_string),
+            genre: Some("Electronic".to_string()),
+            bpm,
+            ..Track::default()
+        }
+    }
+
+    fn stats(rating: u8, play_count: u32) -> TrackStats {
+        TrackStats {
+            rating,
+            play_count,
+            ..TrackStats::default()
+        }
+    }
+
+    fn titles(tracks: &[Track]) -> Vec<&str> {
+        tracks.iter().map(|track| track.title.as_str()).collect()
+    }
+
+    #[test]
+    fn ranges_can_be_open_on_either_side() {
+        let at_least = Range {
+            min: Some(120.0),
+            max: None,
+        };
+        assert!(at_least.contains(Some(120.0)));
+        assert!(at_least.contains(Some(1000.0)));
+        assert!(!at_least.contains(Some(119.9)));
+
+        let at_most = Range {
+            min: None,
+            max: Some(3.0),
+        };
+        assert!(at_most.contains(Some(0.0)));
+        assert!(!at_most.contains(Some(3.5)));
+
+        assert!(Range::default().contains(Some(-5.0)));
+        assert!(!Range::default().contains(None));
+    }
+
+    #[test]
+    fn groups_nest() {
+        // Highly rated, and either fast or by New Order.
+        let rule = Rule::All(vec![
+            Rule::Rating(Range {
+                min: Some(4.0),
+                max: None,
+            }),
+            Rule::Any(vec![
+                Rule::Bpm(Range {
+                    min: Some(120.0),
+                    max: None,
+                }),
+                Rule::Artist(" new order ".to_string()),
+            ]),
+        ]);
+        let fast = track("Fast", None, Some(128.0));
+        let slow = track("Slow", Some("New Order"), Some(90.0));
+        let untagged = track("Untagged", None, None);
+
+        assert!(rule.matches(&fast, &stats(5, 0), NOW));
+        assert!(rule.matches(&slow, &stats(4, 0), NOW));
+        assert!(!rule.matches(&untagged, &stats(5, 0), NOW));
+        assert!(!rule.matches(&fast, &stats(3, 0), NOW));
+        assert!(Rule::Genre("electronic".to_string()).matches(&fast, &stats(0, 0), NOW));
+    }
+
+    #[test]
+    fn days_count_back_from_now() {
+        let played = TrackStats {
+            play_count: 1,
+            last_played: Some(NOW - 2 * SECONDS_PER_DAY),
+            ..TrackStats::default()
+        };
+        let track = track("Song", None, None);
+        assert!(Rule::PlayedWithinDays(2).matches(&track, &played, NOW));
+        assert!(!Rule::PlayedWithinDays(1).matches(&track, &played, NOW));
+        assert!(!Rule::NeverPlayed.matches(&track, &played, NOW));
+        assert!(!Rule::AddedWithinDays(30).matches(&track, &played, NOW));
+    }
+
+    #[test]
+    fn missing_values_sort_last_and_the_limit_applies_after_sorting() {
+        let tracks = [
+            track("No tempo", None, None),
+            track("Slow", None, Some(80.0)),
+            track("Fast", None, Some(140.0)),
+            track("Medium", None, Some(110.0)),
+        ];
+        let candidates = || tracks.iter().map(|track| (track, stats(0, 0)));
+        let mut playlist = SmartPlaylist {
+            name: "Tempo".to_string(),
+            rule: Rule::All(Vec::new()),
+            sort: Some(Sort {
+                by: SortKey::Bpm,
+                descending: false,
+            }),
+            limit: None,
+        };
+        assert_eq!(
+            titles(&playlist.select(candidates(), NOW)),
+            ["Slow", "Medium", "Fast", "No tempo"]
+        );
+
+        playlist.sort = Some(Sort {
+            by: SortKey::Bpm,
+            descending: true,
+        });
+        assert_eq!(
+            titles(&playlist.select(candidates(), NOW)),
+            ["Fast", "Medium", "Slow", "No tempo"]
+        );
+
+        playlist.limit = Some(2);
+        assert_eq!(
+            titles(&playlist.select(candidates(), NOW)),
+            ["Fast", "Medium"]
+        );
+    }
+
+    #[test]
+    fn text_sorts_ignore_case() {
+        let tracks = [
+            track("b", None, None),
+            track("C", None, None),
+            track("A", None, None),
+        ];
+        let playlist = SmartPlaylist {
+            name: "Titles".to_string(),
+            rule: Rule::All(Vec::new()),
+            sort: Some(Sort {
+                by: SortKey::Title,
+                descending: false,
+            }),
+            limit: None,
+        };
+        let selected = playlist.select(tracks.iter().map(|track| (track, stats(0, 0))), NOW);
+        assert_eq!(titles(&selected), ["A", "b", "C"]);
+    }
+
+    #[test]
+    fn rules_read_from_json() {
+        let playlist: SmartPlaylist = serde_json::from_str(
+            r#"{
+                "name": "Upbeat",
+                "rule": { "all": [{ "rating": { "min": 4 } }, { "bpm": { "min": 120, "max": 130 } }] },
+                "sort": { "by": "play_count", "descending": true },
+                "limit": 50
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            playlist.rule,
+            Rule::All(vec![
+                Rule::Rating(Range {
+                    min: Some(4.0),
+                    max: None
+                }),
+                Rule::Bpm(Range {
+                    min: Some(120.0),
+                    max: Some(130.0)
+                }),
+            ])
+        );
+        assert_eq!(playlist.limit, Some(50));
+    }
+}
@@ -1,32 +1,113 @@
 //! Song module
 //!
-//! Handles loading, playing, and (if necessary) resampling of song audio data.
-//! It supports dynamic sample rate selection based on the output device's capabilities,
-//! caching a resampled file so that the expensive processing is only done once.
-
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use rubato::{FftFixedInOut, Resampler};
+//! Handles loading and (if necessary) resampling of song audio data.
+//! Songs are decoded up front. A song the device can play at its own rate is used exactly as
+//! decoded; any other is resampled to the output stream's rate, storing the result in the
+//! [`AudioCache`] so that the expensive processing is only done once.
+//! Playback itself is handled by the [`Player`](crate::player::Player) engine, which applies the
+//! song's speed and pitch settings while it plays.
+
+use crate::artwork::Artwork;
+use crate::cache::{AudioCache, CacheKey};
+use crate::dsp::stretch::{PlaybackRate, SpeedMode};
+use crate::library::Track;
+use crate::loader::LoadStage;
+use crate::lyrics::Lyrics;
+use crate::resample::{self, ResampleError, ResampleQuality};
 use std::convert::TryInto;
+use std::fmt;
 use std::fs;
-use std::io;
-use std::path::Path;
+use std::io::Cursor;
 use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// Level below which audio is treated as silence when finding where the music starts and ends
+/// (about -60 dBFS).
+const SILENCE_THRESHOLD: f32 = 0.001;
+
+/// Errors that can occur when loading a song.
+#[derive(Debug, Error)]
+pub enum SongError {
+    /// The audio file could not be read.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The audio file is not valid WAV data.
+    #[error("decode error: {0}")]
+    Decode(#[from] hound::Error),
+    /// The progress callback asked to stop.
+    #[error("loading was cancelled")]
+    Cancelled,
+}
+
+/// How a song's audio was brought to the format it plays in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackPath {
+    /// Decoded exactly, in the file's own sample rate and channel count. The song plays
+    /// bit-perfect as long as the output leaves its samples unchanged.
+    BitPerfect { bits: u16 },
+    /// Played at the file's own rate, but not exactly as decoded: its samples (such as 32-bit
+    /// integers) cannot all be represented by the `f32` output, its channels are mapped onto the
+    /// output's, or the effects change them.
+    Native { bits: u16 },
+    /// Resampled from the file's rate while loading.
+    Resampled { from: u32, quality: ResampleQuality },
+    /// Resampled earlier and loaded from the cache.
+    Cached { quality: ResampleQuality },
+}
+
+impl fmt::Display for PlaybackPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlaybackPath::BitPerfect { bits } => {
+                write!(
+                    f,
+                    "bit-perfect ({}-bit, native format, no resampling)",
+                    bits
+                )
+            }
+            PlaybackPath::Native { bits } => {
+                write!(
+                    f,
+                    "native rate, no resampling ({}-bit, not bit-exact)",
+                    bits
+                )
+            }
+            PlaybackPath::Resampled { from, quality } => {
+                write!(f, "resampled from {} Hz ({})", from, quality)
+            }
+            PlaybackPath::Cached { quality } => {
+                write!(f, "resampled ({}), loaded from cache", quality)
+            }
+        }
+    }
+}
 
-/// Represents a song that can be played.
+/// Represents a decoded song that can be handed to the player.
 ///
-/// This struct holds the audio data, playback state, and related metadata (such as title and file name).
-/// It also maintains a reference to an output stream to play the song.
+/// This struct holds the interleaved audio data and related metadata (such as the title).
+/// The audio data is shared, so passing a song to the player does not copy it.
 pub struct Song {
-    is_playing: bool,
-    audio_stream: Option<cpal::Stream>,
-    audio_data: Arc<Mutex<Vec<f32>>>,
-    current_frame: usize,
+    audio_data: Arc<Vec<f32>>,
+    /// The number of interleaved channels in `audio_data`.
+    channels: usize,
+    /// First and one-past-last frames that are louder than [`SILENCE_THRESHOLD`].
+    content_bounds: (usize, usize),
     /// The title of the song.
     pub title: String,
-    /// The file name of the song.
-    pub filename: String,
     /// The sample rate at which the audio data will be played.
     final_sample_rate: u32,
+    /// How the audio was brought to `final_sample_rate`, or `None` for an empty song.
+    playback_path: Option<PlaybackPath>,
+    /// Speed and pitch settings, read by the player while the song plays.
+    playback_rate: Arc<PlaybackRate>,
+    /// A–B loop start point, in frames.
+    loop_start: Option<usize>,
+    /// A–B loop end point, in frames.
+    loop_end: Option<usize>,
+    /// The track's cover art and its palette, if it has any.
+    pub artwork: Option<Arc<Artwork>>,
+    /// The track's lyrics, if it has any.
+    pub lyrics: Option<Arc<Lyrics>>,
 }
 
 impl Song {
@@ -34,44 +115,105 @@ impl Song {
     // Public Methods
     // ============================================================================
 
-    /// Creates a `Song` from a file.
+    /// Creates a `Song` from a library track.
     ///
-    /// This method loads a WAV file from the music library and always attempts to load the resampled
-    /// version from cache in the `"music_cache"` folder. If the cached version does not exist, it loads
-    /// the original file, resamples (if necessary), saves it to cache, then returns the processed audio.
+    /// A file already in the target format is decoded and played as is (the bit-perfect path),
+    /// without going through the cache. Otherwise the prepared audio is taken from the cache if an intact
+    /// copy exists for this version of the file, output rate and resampling quality; failing that,
+    /// the track's WAV file is loaded, any encoder delay/padding is trimmed, the audio is resampled
+    /// and the result is stored in the cache. The track's cover art and lyrics are looked up as
+    /// well.
     ///
     /// # Arguments
     ///
-    /// * `song_file_name` - The name of the song file (assumed to be located in the "music_library" directory).
+    /// * `track` - The track to load.
+    /// * `target` - The sample rate and channel count of the output stream the song will be
+    ///   played on, as chosen by [`Player::format_for`](crate::player::Player::format_for).
+    /// * `quality` - The resampling algorithm to use if the file's rate differs.
+    /// * `cache` - The cache of prepared audio.
+    /// * `progress` - Called as each [`LoadStage`] advances; returning `false` cancels the load.
     ///
     /// # Returns
     ///
-    /// A new `Song` instance with the appropriate audio data, title, and final sample rate.
-    pub fn from_file(song_file_name: &str) -> Self {
-        let song_path = format!("music_library/{}", song_file_name);
-
-        // Load the file's native audio data and sample rate.
-        let (raw_samples, file_sample_rate) = match Self::load_wav(&song_path) {
-            Ok((data, rate)) => (data, rate),
-            Err(e) => {
-                eprintln!("Failed to load audio file '{}': {}", song_file_name, e);
-                (Vec::new(), 44100)
+    /// A new `Song` instance with the appropriate audio data, title, artwork, lyrics and final
+    /// sample rate, [`SongError::Cancelled`] if the load was cancelled, or the error that stopped
+    /// the file from being read.
+    pub fn from_track(
+        track: &Track,
+        target: (u32, usize),
+        quality: ResampleQuality,
+        cache: &Mutex<AudioCache>,
+        progress: &dyn Fn(LoadStage, f32) -> bool,
+    ) -> Result<Self, SongError> {
+        if !progress(LoadStage::Checking, 0.0) {
+            return Err(SongError::Cancelled);
+        }
+        let (target_rate, target_channels) = target;
+        // The resampling quality is part of the format, so changing it does not reuse audio made
+        // with another one.
+        let format = format!("s16-{}", quality);
+        let native =
+            track.sample_rate == Some(target_rate) && track.channels == Some(target_channels);
+        let key = if native {
+            None
+        } else {
+            match AudioCache::key(cache, &track.path, target_rate, &format) {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    eprintln!("⚠️ Could not fingerprint '{}': {}", track.file_name, e);
+                    None
+                }
             }
         };
 
-        // Process the audio data from cache if available or create the cached version if needed.
-        let (audio_data, final_rate) =
-            Self::prepare_audio_data(song_file_name, raw_samples, file_sample_rate);
+        let cached = key
+            .as_ref()
+            .and_then(|key| AudioCache::get(cache, key))
+            .and_then(|bytes| match Self::decode_wav(&bytes) {
+                Ok((samples, spec)) => Some((
+                    samples,
+                    spec.channels as usize,
+                    target_rate,
+                    PlaybackPath::Cached { quality },
+                )),
+                Err(e) => {
+                    eprintln!(
+                        "Failed to decode cached audio of '{}': {}",
+                        track.file_name, e
+                    );
+                    None
+                }
+            });
 
-        Song {
-            is_playing: false,
-            audio_stream: None,
-            audio_data,
-            current_frame: 0,
-            title: Self::get_title_from_file(song_file_name),
-            filename: song_file_name.to_string(),
-            final_sample_rate: final_rate,
-        }
+        let (audio_data, channels, sample_rate, playback_path) = match cached {
+            Some(cached) => cached,
+            None => {
+                let prepared = Self::prepare_audio_data(track, target, quality, progress)?;
+                let (audio_data, channels, sample_rate, playback_path) = &prepared;
+                // Only resampled audio is worth caching; native audio is decoded directly.
+                if let (Some(key), PlaybackPath::Resampled { .. }) = (&key, playback_path) {
+                    if !progress(LoadStage::Caching, 0.0) {
+                        return Err(SongError::Cancelled);
+                    }
+                    Self::store_in_cache(cache, key, audio_data, *sample_rate, *channels);
+                }
+                prepared
+            }
+        };
+
+        Ok(Song {
+            content_bounds: Self::detect_content_bounds(&audio_data, channels),
+            audio_data: Arc::new(audio_data),
+            channels,
+            title: track.title.clone(),
+            final_sample_rate: sample_rate,
+            playback_path: Some(playback_path),
+            playback_rate: Arc::default(),
+            loop_start: None,
+            loop_end: None,
+            artwork: Artwork::find(&track.path).map(Arc::new),
+            lyrics: Lyrics::find(&track.path).map(Arc::new),
+        })
     }
 
     /// Returns an empty `Song` instance.
@@ -79,13 +221,17 @@ impl Song {
     /// Useful as a default when no song is selected.
     pub fn empty() -> Self {
         Song {
-            is_playing: false,
-            audio_stream: None,
-            audio_data: Arc::new(Mutex::new(Vec::new())),
-            current_frame: 0,
+            audio_data: Arc::new(Vec::new()),
+            channels: 2,
+            content_bounds: (0, 0),
             title: "".to_string(),
-            filename: "".to_string(),
             final_sample_rate: 44100,
+            playback_path: None,
+            playback_rate: Arc::default(),
+            loop_start: None,
+            loop_end: None,
+            artwork: None,
+            lyrics: None,
         }
     }
 
@@ -116,450 +262,370 @@ impl Song {
             .join(" ")
     }
 
-    /// Converts a song title into its corresponding file name.
+    /// Returns whether the song has no audio data.
+    pub fn is_empty(&self) -> bool {
+        self.audio_data.is_empty()
+    }
+
+    /// Returns the shared, interleaved audio samples.
+    pub fn audio_data(&self) -> Arc<Vec<f32>> {
+        Arc::clone(&self.audio_data)
+    }
+
+    /// Returns the number of interleaved channels.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Returns the sample rate of the audio data.
+    pub fn sample_rate(&self) -> u32 {
+        self.final_sample_rate
+    }
+
+    /// Returns how the audio was prepared, or `None` for an empty song.
+    ///
+    /// Whether a bit-perfect song is heard bit-perfect depends on the output; see
+    /// [`Player::playback_path`](crate::player::Player::playback_path).
+    pub fn playback_path(&self) -> Option<PlaybackPath> {
+        self.playback_path
+    }
+
+    /// Returns the song's speed and pitch settings.
+    pub fn playback_rate(&self) -> Arc<PlaybackRate> {
+        Arc::clone(&self.playback_rate)
+    }
+
+    /// Replaces the song's speed and pitch settings with shared ones.
+    ///
+    /// Songs sharing settings all follow the same speed and pitch controls, so a change carries
+    /// over when the queue moves on.
     ///
     /// # Arguments
     ///
-    /// * `title` - The song title.
+    /// * `playback_rate` - The settings to share.
+    pub fn set_playback_rate(&mut self, playback_rate: Arc<PlaybackRate>) {
+        self.playback_rate = playback_rate;
+    }
+
+    /// Returns the playback speed (1.0 is normal speed).
+    pub fn speed(&self) -> f32 {
+        self.playback_rate.speed()
+    }
+
+    /// Sets the playback speed, from 0.5× to 2×.
     ///
-    /// # Returns
+    /// In [`SpeedMode::Stretch`] the pitch is kept; in [`SpeedMode::Varispeed`] it follows the
+    /// speed.
+    pub fn set_speed(&self, speed: f32) {
+        self.playback_rate.set_speed(speed);
+    }
+
+    /// Returns the pitch shift in semitones.
+    pub fn pitch_semitones(&self) -> f32 {
+        self.playback_rate.semitones()
+    }
+
+    /// Shifts the pitch by up to an octave in either direction without changing the speed.
+    pub fn set_pitch_semitones(&self, semitones: f32) {
+        self.playback_rate.set_semitones(semitones);
+    }
+
+    /// Returns how speed changes affect pitch.
+    pub fn speed_mode(&self) -> SpeedMode {
+        self.playback_rate.mode()
+    }
+
+    /// Sets how speed changes affect pitch.
+    pub fn set_speed_mode(&self, mode: SpeedMode) {
+        self.playback_rate.set_mode(mode);
+    }
+
+    /// Returns the number of frames of audio.
+    pub fn frames(&self) -> usize {
+        self.audio_data.len() / self.channels.max(1)
+    }
+
+    /// Returns the A–B loop's start and end frame once both points are set.
+    pub fn loop_range(&self) -> Option<(usize, usize)> {
+        match (self.loop_start, self.loop_end) {
+            (Some(start), Some(end)) if end > start => Some((start, end)),
+            _ => None,
+        }
+    }
+
+    /// Returns the loop points set so far, which may not form a complete loop yet.
+    pub fn loop_points(&self) -> (Option<usize>, Option<usize>) {
+        (self.loop_start, self.loop_end)
+    }
+
+    /// Sets the loop start point (A).
+    ///
+    /// If it falls after the end point, the two are swapped.
     ///
-    /// The file name (e.g. "example-song.wav").
-    pub fn get_file_from_title(title: &str) -> String {
-        format!(
-            "{}.wav",
-            title
-                .split_whitespace()
-                .map(|word| word.to_lowercase())
-                .collect::<Vec<String>>()
-                .join("-")
-        )
+    /// # Arguments
+    ///
+    /// * `frame` - Loop start, in frames.
+    pub fn set_loop_start(&mut self, frame: usize) {
+        self.loop_start = Some(frame.min(self.frames()));
+        self.order_loop_points();
     }
 
-    /// Updates the playing state of the song.
+    /// Sets the loop end point (B).
     ///
-    /// If `should_play` is true and the song is not already playing, playback starts.
-    /// If false, playback is paused.
+    /// If it falls before the start point, the two are swapped.
     ///
     /// # Arguments
     ///
-    /// * `should_play` - Boolean flag indicating desired playing state.
-    pub fn update(&mut self, should_play: bool) {
-        if should_play && !self.is_playing {
-            self.play();
-        } else if !should_play && self.is_playing {
-            self.pause();
-        }
-        self.is_playing = should_play;
+    /// * `frame` - Loop end, in frames.
+    pub fn set_loop_end(&mut self, frame: usize) {
+        self.loop_end = Some(frame.min(self.frames()));
+        self.order_loop_points();
     }
 
-    /// Returns whether the song is currently playing.
-    pub fn is_playing(&self) -> bool {
-        self.is_playing
+    /// Removes both loop points.
+    pub fn clear_loop(&mut self) {
+        self.loop_start = None;
+        self.loop_end = None;
     }
 
-    /// Returns whether the song has no audio data.
-    pub fn is_empty(&self) -> bool {
-        self.audio_data.lock().unwrap().is_empty()
+    /// Returns the first and one-past-last frames containing audible content.
+    ///
+    /// Used by the mixer to place smart crossfades where the music actually ends.
+    pub fn content_bounds(&self) -> (usize, usize) {
+        self.content_bounds
     }
 
-    /// Outputs debug information regarding the output device's supported configurations.
+    /// Outputs debug information about the loaded song.
     pub fn debug_info(&self) {
-        let host = cpal::default_host();
-        let device = match host.default_output_device() {
-            Some(d) => d,
-            None => {
-                eprintln!("No output device available.");
-                return;
-            }
-        };
-        self.debug_supported_configs(&device);
+        println!(
+            "🎵 Song: '{}' — {} channels at {} Hz, {} samples",
+            self.title,
+            self.channels,
+            self.final_sample_rate,
+            self.audio_data.len()
+        );
     }
 
     // ============================================================================
     // Private Helper Methods
     // ============================================================================
 
-    /// Prepares the audio data by attempting to load the cached file first.
-    ///
-    /// If the cached resampled file (located in "music_cache") exists, it is loaded.
-    /// Otherwise, this function processes the original audio data (resampling when necessary),
-    /// saves it to cache, and returns the processed data.
-    ///
-    /// # Arguments
-    ///
-    /// * `song_file_name` - The original song file name.
-    /// * `raw_samples` - The raw audio samples loaded from the original file.
-    /// * `file_sample_rate` - The native sample rate of the file.
-    ///
-    /// # Returns
-    ///
-    /// A tuple containing:
-    /// - `Arc<Mutex<Vec<f32>>>` wrapping the processed (cached) audio samples.
-    /// - The final sample rate to use.
-    fn prepare_audio_data(
-        song_file_name: &str,
-        raw_samples: Vec<f32>,
-        file_sample_rate: u32,
-    ) -> (Arc<Mutex<Vec<f32>>>, u32) {
-        let (supports_native_rate, final_rate) =
-            Self::determine_final_sample_rate(file_sample_rate);
-        // Construct the cache path, naming it with the song title and the final sample rate.
-        let cache_path = format!(
-            "music_cache/{}-{}Hz.wav",
-            Self::get_title_from_file(song_file_name),
-            final_rate
-        );
-        // If a cached file exists, always prefer loading it.
-        if Path::new(&cache_path).exists() {
-            match Self::load_wav(&cache_path) {
-                Ok((cached_samples, _)) => {
-                    return (Arc::new(Mutex::new(cached_samples)), final_rate);
-                }
-                Err(e) => {
-                    eprintln!(
-                        "Failed to load cached file '{}': {}. Will process original file...",
-                        cache_path, e
-                    );
-                }
-            }
+    /// Swaps the loop points if the end was placed before the start.
+    fn order_loop_points(&mut self) {
+        if let (Some(start), Some(end)) = (self.loop_start, self.loop_end)
+            && end < start
+        {
+            self.loop_start = Some(end);
+            self.loop_end = Some(start);
         }
-        // No cache exists; process the original file.
-        // Even if the device supports the native rate, we choose to use the cache version.
-        let channels = 2; // assuming stereo
-        let processed = if file_sample_rate != final_rate {
-            Self::resample_and_cache(
-                raw_samples,
-                file_sample_rate,
-                final_rate,
-                channels,
-                &cache_path,
-            )
-        } else {
-            // If no resampling is needed, copy the file into cache.
-            if let Err(e) = Self::save_wav(
-                &cache_path,
-                &raw_samples,
-                final_rate,
-                channels.try_into().unwrap(),
-            ) {
-                eprintln!("Warning: Could not save cache to '{}': {}", cache_path, e);
-            }
-            raw_samples
-        };
-        (Arc::new(Mutex::new(processed)), final_rate)
     }
 
-    /// Determines the final sample rate for playing a song.
-    ///
-    /// If the output device supports the native sample rate, it is used.
-    /// Otherwise, the device's default sample rate is used as a fallback.
+    /// Finds the leading and trailing silence of interleaved audio.
     ///
     /// # Arguments
     ///
-    /// * `file_sample_rate` - Native sample rate of the file.
+    /// * `samples` - Interleaved audio samples.
+    /// * `channels` - Number of interleaved channels.
     ///
     /// # Returns
     ///
-    /// A tuple `(bool, u32)` where the boolean indicates if the native rate is supported,
-    /// and the `u32` is the final sample rate to use.
-    fn determine_final_sample_rate(file_sample_rate: u32) -> (bool, u32) {
-        let host = cpal::default_host();
-        let device = host.default_output_device();
-        let mut supports_native_rate = false;
-        let mut fallback_rate = file_sample_rate;
-
-        if let Some(device) = &device {
-            match device.supported_output_configs() {
-                Ok(configs) => {
-                    for config in configs {
-                        if file_sample_rate >= config.min_sample_rate().0
-                            && file_sample_rate <= config.max_sample_rate().0
-                        {
-                            supports_native_rate = true;
-                            break;
-                        }
-                    }
-                    if !supports_native_rate {
-                        if let Ok(default_config) = device.default_output_config() {
-                            fallback_rate = default_config.sample_rate().0;
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!(
-                        "Error querying supported configs: {}. Assuming the song rate is unsupported.",
-                        e
-                    );
-                }
-            }
-        }
-        (supports_native_rate, fallback_rate)
+    /// The first frame above [`SILENCE_THRESHOLD`] and one past the last such frame. Silent
+    /// audio yields `(0, 0)`.
+    fn detect_content_bounds(samples: &[f32], channels: usize) -> (usize, usize) {
+        let channels = channels.max(1);
+        let is_loud = |frame: &[f32]| frame.iter().any(|s| s.abs() > SILENCE_THRESHOLD);
+        let mut frames = samples.chunks_exact(channels);
+
+        let Some(start) = frames.position(is_loud) else {
+            return (0, 0);
+        };
+        let end = samples
+            .chunks_exact(channels)
+            .rposition(is_loud)
+            .unwrap_or(start)
+            + 1;
+        (start, end)
     }
 
-    /// Resamples the input audio data to the target sample rate and saves the result to cache.
+    /// Loads a track's WAV file and prepares it for the output stream.
+    ///
+    /// Encoder priming and padding are removed so consecutive tracks join seamlessly, and the
+    /// audio is resampled if the file's rate differs from the output's.
     ///
     /// # Arguments
     ///
-    /// * `input` - The original audio samples.
-    /// * `from_rate` - The native sample rate.
-    /// * `to_rate` - The target sample rate.
-    /// * `channels` - Number of audio channels.
-    /// * `cache_path` - The file path where the resampled data should be saved.
+    /// * `track` - The track to load.
+    /// * `target` - The sample rate and channel count of the output stream.
+    /// * `quality` - The resampling algorithm to use.
+    /// * `progress` - Called as each [`LoadStage`] advances; returning `false` cancels the load.
     ///
     /// # Returns
     ///
-    /// A `Vec<f32>` containing the resampled and interleaved audio data.
-    fn resample_and_cache(
-        input: Vec<f32>,
-        from_rate: u32,
-        to_rate: u32,
-        channels: usize,
-        cache_path: &str,
-    ) -> Vec<f32> {
-        println!("⚠️ Resampling from {} Hz to {} Hz...", from_rate, to_rate);
-        let resampled = Self::resample_to_device_rate(input, from_rate, to_rate, channels);
-        println!("✅ Resampled to {} samples.", resampled.len());
-        if let Err(e) = Self::save_wav(
-            cache_path,
-            &resampled,
-            to_rate,
-            channels.try_into().unwrap(),
-        ) {
-            eprintln!("Warning: Could not save cache to '{}': {}", cache_path, e);
-        }
-        resampled
-    }
-
-    /// Starts playback by creating and starting an output stream.
-    ///
-    /// The stream is configured to use `final_sample_rate`. If a stream is already active, it does nothing.
-    fn play(&mut self) {
-        if self.audio_stream.is_some() {
-            return;
+    /// The processed audio samples, their channel count, their sample rate and how they were
+    /// prepared, or the error that stopped the file from being read. If resampling fails, the
+    /// audio is returned unchanged at the file's own rate.
+    fn prepare_audio_data(
+        track: &Track,
+        target: (u32, usize),
+        quality: ResampleQuality,
+        progress: &dyn Fn(LoadStage, f32) -> bool,
+    ) -> Result<(Vec<f32>, usize, u32, PlaybackPath), SongError> {
+        if !progress(LoadStage::Decoding, 0.0) {
+            return Err(SongError::Cancelled);
         }
-
-        let host = cpal::default_host();
-        let device = match host.default_output_device() {
-            Some(d) => d,
-            None => {
-                eprintln!("❌ No output device available.");
-                return;
+        let (final_rate, final_channels) = target;
+        // Load the file's native audio data, sample rate and channel count.
+        let (mut raw_samples, spec) = Self::decode_wav(&fs::read(&track.path)?)?;
+        let (file_sample_rate, channels) = (spec.sample_rate, spec.channels as usize);
+        // What the audio is played as if it is not resampled. Channels the output does not have
+        // are mapped onto it by the mixer, so only a matching layout is heard as decoded.
+        let native_path = if Self::is_exact_in_f32(&spec) && channels == final_channels {
+            PlaybackPath::BitPerfect {
+                bits: spec.bits_per_sample,
             }
-        };
-
-        let supported_config = match device.default_output_config() {
-            Ok(c) => c,
-            Err(e) => {
-                eprintln!("❌ Failed to get default output config: {}", e);
-                return;
+        } else {
+            PlaybackPath::Native {
+                bits: spec.bits_per_sample,
             }
         };
 
-        let mut config = supported_config.config();
-        config.sample_rate = cpal::SampleRate(self.final_sample_rate);
-
-        let audio_data = self.audio_data.clone();
-        let frame_count = Arc::new(Mutex::new(self.current_frame));
-
-        let stream_result = match supported_config.sample_format() {
-            cpal::SampleFormat::F32 => device.build_output_stream(
-                &config,
-                {
-                    let frame_count = Arc::clone(&frame_count);
-                    move |data: &mut [f32], _| {
-                        let audio_data = audio_data.lock().unwrap();
-                        let mut count = frame_count.lock().unwrap();
-                        for sample in data.iter_mut() {
-                            *sample = if *count < audio_data.len() {
-                                audio_data[*count]
-                            } else {
-                                0.0
-                            };
-                            *count += 1;
-                        }
-                    }
-                },
-                move |err| eprintln!("⚠️ Stream error: {}", err),
-                None,
-            ),
-            _ => {
-                eprintln!(
-                    "❌ Unsupported sample format: {:?}",
-                    supported_config.sample_format()
-                );
-                return;
-            }
-        };
+        if let Some(gapless) = &track.gapless {
+            let start = (gapless.delay * channels).min(raw_samples.len());
+            let end = raw_samples
+                .len()
+                .saturating_sub(gapless.padding * channels)
+                .max(start);
+            raw_samples.truncate(end);
+            raw_samples.drain(..start);
+        }
 
-        match stream_result {
-            Ok(stream) => {
-                if let Err(e) = stream.play() {
-                    eprintln!("❌ Failed to start playback: {}", e);
-                } else {
-                    println!("✅ Playback started.");
-                }
-                self.audio_stream = Some(stream);
+        if file_sample_rate == final_rate {
+            return Ok((raw_samples, channels, final_rate, native_path));
+        }
+        println!(
+            "⚠️ Resampling from {} Hz to {} Hz ({})...",
+            file_sample_rate, final_rate, quality
+        );
+        match resample::resample(
+            &raw_samples,
+            file_sample_rate,
+            final_rate,
+            channels,
+            quality,
+            |fraction| progress(LoadStage::Resampling, fraction),
+        ) {
+            Ok(resampled) => {
+                println!("✅ Resampled to {} samples.", resampled.len());
+                let path = PlaybackPath::Resampled {
+                    from: file_sample_rate,
+                    quality,
+                };
+                Ok((resampled, channels, final_rate, path))
             }
+            Err(ResampleError::Cancelled) => Err(SongError::Cancelled),
             Err(e) => {
-                eprintln!("❌ Stream creation failed: {}", e);
-                self.debug_supported_configs(&device);
+                eprintln!("Failed to resample: {}. Playing at the original rate.", e);
+                Ok((raw_samples, channels, file_sample_rate, native_path))
             }
         }
     }
 
-    /// Pauses playback by dropping the current output stream.
-    fn pause(&mut self) {
-        if let Some(stream) = self.audio_stream.take() {
-            drop(stream);
-        }
-    }
-
-    /// Loads WAV audio data using the hound crate.
+    /// Encodes prepared audio as WAV and stores it in the cache.
     ///
     /// # Arguments
     ///
-    /// * `path` - The file system path to the WAV file.
-    ///
-    /// # Returns
-    ///
-    /// A `Result` with a tuple of the audio samples and the sample rate on success, or a `hound::Error`.
-    fn load_wav(path: &str) -> Result<(Vec<f32>, u32), hound::Error> {
-        let reader = hound::WavReader::open(Path::new(path))?;
-        let spec = reader.spec();
-        let samples: Vec<f32> = reader
-            .into_samples::<i16>()
-            .map(|s| s.unwrap_or(0) as f32 / i16::MAX as f32)
-            .collect();
-        Ok((samples, spec.sample_rate))
-    }
-
-    /// Outputs the supported configurations for the given output device.
-    ///
-    /// # Arguments
-    ///
-    /// * `device` - A reference to the output device.
-    fn debug_supported_configs(&self, device: &cpal::Device) {
-        println!(
-            "🧪 Supported configs for device '{}':",
-            device.name().unwrap_or_default()
-        );
-        if let Ok(configs) = device.supported_output_configs() {
-            for cfg in configs {
-                println!(
-                    "  - {:?}, channels: {}, rate: {}-{}",
-                    cfg.sample_format(),
-                    cfg.channels(),
-                    cfg.min_sample_rate().0,
-                    cfg.max_sample_rate().0
-                );
-            }
-        } else {
-            eprintln!("⚠️ Could not retrieve supported output configs.");
+    /// * `cache` - The cache of prepared audio.
+    /// * `key` - The key to store the audio under.
+    /// * `samples` - The prepared audio samples.
+    /// * `sample_rate` - The sample rate of the audio data.
+    /// * `channels` - The number of audio channels.
+    fn store_in_cache(
+        cache: &Mutex<AudioCache>,
+        key: &CacheKey,
+        samples: &[f32],
+        sample_rate: u32,
+        channels: usize,
+    ) {
+        let result = Self::encode_wav(samples, sample_rate, channels.try_into().unwrap())
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| AudioCache::put(cache, key, &bytes).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            eprintln!("⚠️ Could not save audio to the cache: {}", e);
         }
     }
 
-    /// Resamples audio data from one sample rate to another using Rubato.
+    /// Decodes WAV audio data using the hound crate.
     ///
-    /// This method de-interleaves the input samples, processes them in chunks, and then re-interleaves the
-    /// resampled data.
+    /// Integer samples of any width are scaled by 2^(bits-1), so full scale maps to -1.0..1.0
+    /// and 24-bit or narrower samples keep their exact values; float samples are taken as they
+    /// are.
     ///
     /// # Arguments
     ///
-    /// * `input` - The original audio samples.
-    /// * `from_rate` - The native sample rate.
-    /// * `to_rate` - The target sample rate.
-    /// * `channels` - The number of audio channels.
+    /// * `bytes` - The contents of a WAV file.
     ///
     /// # Returns
     ///
-    /// A vector of interleaved, resampled audio samples.
-    fn resample_to_device_rate(
-        input: Vec<f32>,
-        from_rate: u32,
-        to_rate: u32,
-        channels: usize,
-    ) -> Vec<f32> {
-        let chunk_size = 1024;
-        let mut resampler =
-            FftFixedInOut::<f32>::new(from_rate as usize, to_rate as usize, chunk_size, channels)
-                .expect("Failed to create Rubato resampler");
-
-        // De-interleave samples.
-        let mut input_per_channel = vec![Vec::new(); channels];
-        for (i, sample) in input.iter().enumerate() {
-            input_per_channel[i % channels].push(*sample);
-        }
-
-        let input_frames = resampler.input_frames_next();
-        let mut all_resampled = vec![Vec::new(); channels];
-
-        // Process in chunks.
-        for chunk_start in (0..input_per_channel[0].len()).step_by(input_frames) {
-            let chunk: Vec<Vec<f32>> = input_per_channel
-                .iter()
-                .map(|channel| {
-                    let mut slice = vec![0.0; input_frames];
-                    for i in 0..input_frames {
-                        if let Some(&s) = channel.get(chunk_start + i) {
-                            slice[i] = s;
-                        }
-                    }
-                    slice
-                })
-                .collect();
-
-            let resampled = resampler.process(&chunk, None).expect("Resampling failed");
-            for (i, chan) in resampled.into_iter().enumerate() {
-                all_resampled[i].extend(chan);
+    /// A `Result` with the audio samples and the file's format on success, or a `hound::Error`
+    /// if the file or any of its samples cannot be read.
+    fn decode_wav(bytes: &[u8]) -> Result<(Vec<f32>, hound::WavSpec), hound::Error> {
+        let reader = hound::WavReader::new(Cursor::new(bytes))?;
+        let spec = reader.spec();
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample.clamp(1, 32) - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect::<Result<_, _>>()?
             }
-        }
+        };
+        Ok((samples, spec))
+    }
 
-        // Re-interleave samples.
-        let mut interleaved = Vec::with_capacity(all_resampled[0].len() * channels);
-        for i in 0..all_resampled[0].len() {
-            for ch in 0..channels {
-                interleaved.push(all_resampled[ch][i]);
-            }
+    /// Returns whether every sample of a WAV format has an exact `f32` equivalent, so it reaches
+    /// the output unchanged: integers of up to 24 bits (the width of an `f32` mantissa) and
+    /// 32-bit floats.
+    fn is_exact_in_f32(spec: &hound::WavSpec) -> bool {
+        match spec.sample_format {
+            hound::SampleFormat::Int => spec.bits_per_sample <= 24,
+            hound::SampleFormat::Float => spec.bits_per_sample == 32,
         }
-        interleaved
     }
 
-    /// Saves the provided audio samples as a WAV file using the hound crate.
+    /// Encodes audio samples as a 16-bit WAV file using the hound crate.
     ///
     /// # Arguments
     ///
-    /// * `path` - The file path where the WAV file should be saved.
-    /// * `samples` - The audio samples to save.
+    /// * `samples` - The audio samples to encode.
     /// * `sample_rate` - The sample rate of the audio data.
     /// * `channels` - The number of audio channels.
     ///
     /// # Returns
     ///
-    /// A `Result` which is `Ok(())` on success or a `hound::Error` on failure.
-    fn save_wav(
-        path: &str,
+    /// A `Result` with the contents of the WAV file on success, or a `hound::Error`.
+    fn encode_wav(
         samples: &[f32],
         sample_rate: u32,
         channels: u16,
-    ) -> Result<(), hound::Error> {
-        // Ensure the cache directory exists.
-        if let Some(parent) = Path::new(path).parent() {
-            fs::create_dir_all(parent).ok();
-        }
-
+    ) -> Result<Vec<u8>, hound::Error> {
         let spec = hound::WavSpec {
             channels,
             sample_rate,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
-        let mut writer = hound::WavWriter::create(path, spec)?;
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec)?;
         for sample in samples {
-            let scaled = (sample * i16::MAX as f32) as i16;
+            // The inverse of `decode_wav`'s scaling, so cached audio reads back as written.
+            let scaled = (sample * 32768.0)
+                .round()
+                .clamp(i16::MIN as f32, i16::MAX as f32) as i16;
             writer.write_sample(scaled)?;
         }
         writer.finalize()?;
-        Ok(())
+        Ok(bytes.into_inner())
     }
 }
@@ -0,0 +1,790 @@
+//! Library store module
+//!
+//! Keeps what should survive between sessions about each track in an SQLite database,
+//! `config/library.db`: its metadata, when it joined the library, bookmarks, play count, when it
+//! was last played, a 0–5 star rating, and a history of every time it was played to the end.
+//! Tracks are keyed by their canonical path, so the data follows a file for as long as it stays
+//! in the library.
+//!
+//! The schema is versioned with SQLite's `user_version`: opening an older database applies the
+//! missing [`MIGRATIONS`] in order. Bookmarks from the JSON file used before the database are
+//! imported when the database is first created.
+
+use crate::library::Track;
+use crate::song::Song;
+use rusqlite::{Connection, params};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Path (relative to the working directory) of the database.
+pub const STORE_PATH: &str = "config/library.db";
+
+/// Name of the JSON store used before the database, kept next to it, whose bookmarks are
+/// imported once.
+const LEGACY_STORE_NAME: &str = "library_store.json";
+
+/// Highest star rating.
+pub const MAX_RATING: u8 = 5;
+
+/// Schema changes, in order. The database's `user_version` is the number already applied.
+const MIGRATIONS: [&str; 2] = [
+    r#"
+    CREATE TABLE tracks (
+        id          INTEGER PRIMARY KEY,
+        path        TEXT NOT NULL UNIQUE,
+        title       TEXT NOT NULL,
+        artist      TEXT,
+        album       TEXT,
+        duration    REAL,
+        play_count  INTEGER NOT NULL DEFAULT 0,
+        last_played INTEGER,
+        rating      INTEGER NOT NULL DEFAULT 0 CHECK (rating BETWEEN 0 AND 5)
+    );
+    CREATE TABLE bookmarks (
+        id       INTEGER PRIMARY KEY,
+        track_id INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+        name     TEXT NOT NULL,
+        seconds  REAL NOT NULL
+    );
+    CREATE INDEX bookmarks_by_track ON bookmarks(track_id, seconds);
+    CREATE TABLE history (
+        id        INTEGER PRIMARY KEY,
+        track_id  INTEGER NOT NULL REFERENCES tracks(id) ON DELETE CASCADE,
+        played_at INTEGER NOT NULL
+    );
+    CREATE INDEX history_by_time ON history(played_at);
+"#,
+    // Tags and the date added, for smart playlists. Tracks already known count as added now.
+    r#"
+    ALTER TABLE tracks ADD COLUMN genre TEXT;
+    ALTER TABLE tracks ADD COLUMN bpm REAL;
+    ALTER TABLE tracks ADD COLUMN added_at INTEGER;
+    UPDATE tracks SET added_at = CAST(strftime('%s', 'now') AS INTEGER);
+"#,
+];
+
+/// A named position within a track.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Bookmark {
+    /// Name shown next to the marker.
+    pub name: String,
+    /// Position from the start of the track, in seconds.
+    pub seconds: f64,
+}
+
+/// How often and how recently a track was played, how the user rated it, and when it was added.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrackStats {
+    /// Number of times the track was played to the end.
+    pub play_count: u32,
+    /// When the track last finished playing, in seconds since the Unix epoch.
+    pub last_played: Option<i64>,
+    /// Stars from 0 (unrated) to [`MAX_RATING`].
+    pub rating: u8,
+    /// When the track was first found in the library, in seconds since the Unix epoch.
+    pub added_at: Option<i64>,
+}
+
+/// One entry of the listening history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayRecord {
+    pub title: String,
+    pub artist: Option<String>,
+    /// When the track finished playing, in seconds since the Unix epoch.
+    pub played_at: i64,
+}
+
+/// The bookmarks of one track in the JSON store.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct LegacyRecord {
+    bookmarks: Vec<Bookmark>,
+}
+
+/// The JSON store used before the database.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct LegacyStore {
+    tracks: HashMap<String, LegacyRecord>,
+}
+
+/// Persistent per-track data for the library.
+///
+/// Bookmarks and stats are also kept in memory, since they are drawn every frame; every change
+/// is written to the database straight away.
+pub struct LibraryStore {
+    connection: Connection,
+    /// Path of the database file.
+    path: PathBuf,
+    /// Bookmarks per track, sorted by position.
+    bookmarks: HashMap<PathBuf, Vec<Bookmark>>,
+    stats: HashMap<PathBuf, TrackStats>,
+}
+
+impl LibraryStore {
+    // ============================================================================
+    // Public Methods
+    // ============================================================================
+
+    /// Opens the database at [`STORE_PATH`], creating or migrating it as needed.
+    ///
+    /// If the database cannot be opened, an empty in-memory one is used for the session.
+    pub fn open() -> Self {
+        Self::open_at(Path::new(STORE_PATH))
+    }
+
+    /// Opens the database at a given path, creating or migrating it as needed.
+    ///
+    /// A newly created database imports the bookmarks of the JSON store in the same folder.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the database file.
+    pub fn open_at(path: &Path) -> Self {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).ok();
+        }
+        let connection = Connection::open(path)
+            .or_else(|e| {
+                eprintln!(
+                    "⚠️ Could not open library database '{}': {}. Changes will not be saved.",
+                    path.display(),
+                    e
+                );
+                Connection::open_in_memory()
+            })
+            .expect("an in-memory database can always be opened");
+
+        let mut store = LibraryStore {
+            connection,
+            path: path.to_path_buf(),
+            bookmarks: HashMap::new(),
+            stats: HashMap::new(),
+        };
+        if let Err(e) = store.migrate() {
+            eprintln!("⚠️ Could not prepare library database: {}", e);
+        }
+        if let Err(e) = store.load_cache() {
+            eprintln!("⚠️ Could not read library database: {}", e);
+        }
+        store
+    }
+
+    /// Records the metadata of the library's tracks, adding tracks the database has not seen.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracks` - The tracks found in the library.
+    pub fn sync_tracks(&mut self, tracks: &[Track]) {
+        let result = (|| {
+            let transaction = self.connection.transaction()?;
+            for track in tracks {
+                Self::upsert_track(&transaction, track)?;
+            }
+            transaction.commit()
+        })();
+        self.report(result, "track metadata");
+
+        for track in tracks {
+            self.stats_mut(&track.path);
+        }
+    }
+
+    /// Moves everything known about a track to its new path after the file was renamed.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - Canonical path the track had.
+    /// * `track` - The track at its new path.
+    pub fn rename_track(&mut self, from: &Path, track: &Track) {
+        let result = (|| {
+            let transaction = self.connection.transaction()?;
+            // A record left at the new path by an earlier file of that name gives way.
+            transaction.execute(
+                "DELETE FROM tracks WHERE path = ?1",
+                [Self::key(&track.path)],
+            )?;
+            transaction.execute(
+                "UPDATE tracks SET path = ?1 WHERE path = ?2",
+                [Self::key(&track.path), Self::key(from)],
+            )?;
+            Self::upsert_track(&transaction, track)?;
+            transaction.commit()
+        })();
+        self.report(result, "renamed track");
+
+        if let Some(bookmarks) = self.bookmarks.remove(from) {
+            self.bookmarks.insert(track.path.clone(), bookmarks);
+        } else {
+            self.bookmarks.remove(&track.path);
+        }
+        let stats = self.stats.remove(from).unwrap_or(TrackStats {
+            added_at: Some(now()),
+            ..TrackStats::default()
+        });
+        self.stats.insert(track.path.clone(), stats);
+    }
+
+    /// Returns the bookmarks of a track, sorted by position.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - Canonical path of the track.
+    pub fn bookmarks(&self, track: &Path) -> &[Bookmark] {
+        self.bookmarks
+            .get(track)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Adds a bookmark to a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track.
+    /// * `bookmark` - The bookmark to add.
+    pub fn add_bookmark(&mut self, track: &Track, bookmark: Bookmark) {
+        let result = Self::upsert_track(&self.connection, track).and_then(|track_id| {
+            self.connection.execute(
+                "INSERT INTO bookmarks (track_id, name, seconds) VALUES (?1, ?2, ?3)",
+                params![track_id, bookmark.name, bookmark.seconds],
+            )
+        });
+        self.report(result, "bookmark");
+
+        self.stats_mut(&track.path);
+        let bookmarks = self.bookmarks.entry(track.path.clone()).or_default();
+        let index = bookmarks.partition_point(|b| b.seconds <= bookmark.seconds);
+        bookmarks.insert(index, bookmark);
+    }
+
+    /// Adds a bookmark named after the number of bookmarks the track then has.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track.
+    /// * `seconds` - Position of the bookmark from the start of the track.
+    ///
+    /// # Returns
+    ///
+    /// The name of the bookmark, such as "Bookmark 3".
+    pub fn add_numbered_bookmark(&mut self, track: &Track, seconds: f64) -> String {
+        let name = format!("Bookmark {}", self.bookmarks(&track.path).len() + 1);
+        self.add_bookmark(
+            track,
+            Bookmark {
+                name: name.clone(),
+                seconds,
+            },
+        );
+        name
+    }
+
+    /// Removes a bookmark from a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - Canonical path of the track.
+    /// * `index` - Index of the bookmark in [`bookmarks`](Self::bookmarks).
+    pub fn remove_bookmark(&mut self, track: &Path, index: usize) {
+        let Some(bookmarks) = self.bookmarks.get_mut(track) else {
+            return;
+        };
+        if index >= bookmarks.len() {
+            return;
+        }
+        let bookmark = bookmarks.remove(index);
+        if bookmarks.is_empty() {
+            self.bookmarks.remove(track);
+        }
+
+        let result = self.connection.execute(
+            "DELETE FROM bookmarks WHERE id = (
+                SELECT bookmarks.id FROM bookmarks JOIN tracks ON tracks.id = track_id
+                WHERE path = ?1 AND name = ?2 AND seconds = ?3 LIMIT 1
+            )",
+            params![Self::key(track), bookmark.name, bookmark.seconds],
+        );
+        self.report(result, "bookmark removal");
+    }
+
+    /// Removes the bookmark of a track closest to a position, if it has any.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - Canonical path of the track.
+    /// * `seconds` - The position, from the start of the track.
+    pub fn remove_nearest_bookmark(&mut self, track: &Path, seconds: f64) {
+        let nearest = self
+            .bookmarks(track)
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                (a.seconds - seconds)
+                    .abs()
+                    .total_cmp(&(b.seconds - seconds).abs())
+            })
+            .map(|(index, _)| index);
+        if let Some(index) = nearest {
+            self.remove_bookmark(track, index);
+        }
+    }
+
+    /// Returns the play count, last play, rating and date added of a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - Canonical path of the track.
+    pub fn stats(&self, track: &Path) -> TrackStats {
+        self.stats.get(track).copied().unwrap_or_default()
+    }
+
+    /// Rates a track.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track.
+    /// * `rating` - Stars from 0 (unrated) to [`MAX_RATING`]; higher values are capped.
+    pub fn set_rating(&mut self, track: &Track, rating: u8) {
+        let rating = rating.min(MAX_RATING);
+        let result = Self::upsert_track(&self.connection, track).and_then(|track_id| {
+            self.connection.execute(
+                "UPDATE tracks SET rating = ?1 WHERE id = ?2",
+                params![rating, track_id],
+            )
+        });
+        self.report(result, "rating");
+        self.stats_mut(&track.path).rating = rating;
+    }
+
+    /// Records that a track was played to the end: counts the play, notes the time and adds it
+    /// to the history.
+    ///
+    /// # Arguments
+    ///
+    /// * `track` - The track that finished.
+    pub fn record_play(&mut self, track: &Track) {
+        let now = now();
+        let result = (|| {
+            let transaction = self.connection.transaction()?;
+            let track_id = Self::upsert_track(&transaction, track)?;
+            transaction.execute(
+                "UPDATE tracks SET play_count = play_count + 1, last_played = ?1 WHERE id = ?2",
+                params![now, track_id],
+            )?;
+            transaction.execute(
+                "INSERT INTO history (track_id, played_at) VALUES (?1, ?2)",
+                params![track_id, now],
+            )?;
+            transaction.commit()
+        })();
+        self.report(result, "play history");
+
+        let stats = self.stats_mut(&track.path);
+        stats.play_count += 1;
+        stats.last_played = Some(now);
+    }
+
+    /// Returns the most recent entries of the listening history, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Largest number of entries to return.
+    pub fn recent_plays(&self, limit: usize) -> Vec<PlayRecord> {
+        let query = || {
+            let mut statement = self.connection.prepare(
+                "SELECT title, artist, played_at FROM history
+                 JOIN tracks ON tracks.id = history.track_id
+                 ORDER BY played_at DESC, history.id DESC LIMIT ?1",
+            )?;
+            let rows = statement.query_map([limit as i64], |row| {
+                Ok(PlayRecord {
+                    title: row.get(0)?,
+                    artist: row.get(1)?,
+                    played_at: row.get(2)?,
+                })
+            })?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        };
+        query().unwrap_or_else(|e| {
+            eprintln!("⚠️ Could not read play history: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Prints the most recent entries of the listening history, newest first, with how long
+    /// ago each was played.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Largest number of entries to print.
+    pub fn print_recent_plays(&self, limit: usize) {
+        let plays = self.recent_plays(limit);
+        if plays.is_empty() {
+            println!("\n🕘 No plays recorded yet.");
+            return;
+        }
+        println!("\n🕘 Recently played:");
+        let now = now();
+        for play in plays {
+            let minutes = (now - play.played_at).max(0) / 60;
+            let age = match minutes {
+                0 => "just now".to_string(),
+                1..60 => format!("{} min ago", minutes),
+                60..1440 => format!("{} h ago", minutes / 60),
+                _ => format!("{} days ago", minutes / 1440),
+            };
+            match play.artist {
+                Some(artist) => println!("   {} – {} ({})", artist, play.title, age),
+                None => println!("   {} ({})", play.title, age),
+            }
+        }
+    }
+
+    // ============================================================================
+    // Private Helper Methods
+    // ============================================================================
+
+    /// Returns the key a track is stored under.
+    fn key(track: &Path) -> String {
+        track.to_string_lossy().into_owned()
+    }
+
+    /// Returns the in-memory stats of a track, adding a track the database has just added.
+    fn stats_mut(&mut self, track: &Path) -> &mut TrackStats {
+        let stats = self.stats.entry(track.to_path_buf()).or_default();
+        stats.added_at.get_or_insert_with(now);
+        stats
+    }
+
+    /// Brings the schema up to date, importing the JSON store into a newly created database.
+    fn migrate(&mut self) -> rusqlite::Result<()> {
+        self.connection.pragma_update(None, "foreign_keys", true)?;
+        let version: u32 = self
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let transaction = self.connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", (index + 1) as i64)?;
+            transaction.commit()?;
+            println!("🗄️ Library database updated to version {}.", index + 1);
+        }
+
+        if version == 0 {
+            self.import_legacy_store()?;
+        }
+        Ok(())
+    }
+
+    /// Copies the bookmarks of the JSON store used before the database into it.
+    fn import_legacy_store(&mut self) -> rusqlite::Result<()> {
+        let legacy_path = self.path.with_file_name(LEGACY_STORE_NAME);
+        let Ok(contents) = fs::read_to_string(&legacy_path) else {
+            return Ok(());
+        };
+        let legacy: LegacyStore = match serde_json::from_str(&contents) {
+            Ok(legacy) => legacy,
+            Err(e) => {
+                eprintln!(
+                    "⚠️ Invalid library store '{}': {}",
+                    legacy_path.display(),
+                    e
+                );
+                return Ok(());
+            }
+        };
+
+        let transaction = self.connection.transaction()?;
+        for (path, record) in &legacy.tracks {
+            let path = PathBuf::from(path);
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default();
+            let track = Track {
+                title: Song::get_title_from_file(file_name),
+                file_name: file_name.to_string(),
+                path: path.clone(),
+                ..Track::default()
+            };
+            let track_id = Self::upsert_track(&transaction, &track)?;
+            for bookmark in &record.bookmarks {
+                transaction.execute(
+                    "INSERT INTO bookmarks (track_id, name, seconds) VALUES (?1, ?2, ?3)",
+                    params![track_id, bookmark.name, bookmark.seconds],
+                )?;
+            }
+        }
+        transaction.commit()?;
+        println!(
+            "📥 Imported the bookmarks of {} tracks from '{}'.",
+            legacy.tracks.len(),
+            legacy_path.display()
+        );
+        Ok(())
+    }
+
+    /// Reads the bookmarks and stats of every track into memory.
+    fn load_cache(&mut self) -> rusqlite::Result<()> {
+        let mut statement = self.connection.prepare(
+            "SELECT path, name, seconds FROM bookmarks
+             JOIN tracks ON tracks.id = bookmarks.track_id ORDER BY path, seconds",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let path: String = row.get(0)?;
+            let bookmark = Bookmark {
+                name: row.get(1)?,
+                seconds: row.get(2)?,
+            };
+            Ok((PathBuf::from(path), bookmark))
+        })?;
+        for row in rows {
+            let (path, bookmark) = row?;
+            self.bookmarks.entry(path).or_default().push(bookmark);
+        }
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT path, play_count, last_played, rating, added_at FROM tracks")?;
+        let rows = statement.query_map([], |row| {
+            let path: String = row.get(0)?;
+            let stats = TrackStats {
+                play_count: row.get(1)?,
+                last_played: row.get(2)?,
+                rating: row.get(3)?,
+                added_at: row.get(4)?,
+            };
+            Ok((PathBuf::from(path), stats))
+        })?;
+        for row in rows {
+            let (path, stats) = row?;
+            self.stats.insert(path, stats);
+        }
+        Ok(())
+    }
+
+    /// Adds a track or updates its metadata. A new track is noted as added now.
+    ///
+    /// # Returns
+    ///
+    /// The track's row ID.
+    fn upsert_track(connection: &Connection, track: &Track) -> rusqlite::Result<i64> {
+        connection.query_row(
+            "INSERT INTO tracks (path, title, artist, album, genre, bpm, duration, added_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (path) DO UPDATE SET
+                 title = excluded.title,
+                 artist = excluded.artist,
+                 album = excluded.album,
+                 genre = excluded.genre,
+                 bpm = excluded.bpm,
+                 duration = coalesce(excluded.duration, duration),
+                 added_at = coalesce(added_at, excluded.added_at)
+             RETURNING id",
+            params![
+                Self::key(&track.path),
+                track.title,
+                track.artist,
+                track.album,
+                track.genre,
+                track.bpm,
+                track.duration,
+                now()
+            ],
+            |row| row.get(0),
+        )
+    }
+
+    /// Reports a failed write on stderr.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - Outcome of the write.
+    /// * `what` - What was being saved.
+    fn report<T>(&self, result: rusqlite::Result<T>, what: &str) {
+        if let Err(e) = result {
+            eprintln!(
+                "⚠️ Could not save {} to '{}': {}",
+                what,
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Returns the current time in seconds since the Unix epoch, as the store records times.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A folder in the temporary directory that is removed when dropped.
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new() -> Self {
+            static CREATED: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "music_visualizer-store-{}-{}",
+                process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempFolder(path)
+        }
+
+        fn database(&self) -> PathBuf {
+            self.0.join("library.db")
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn track(path: &str) -> Track {
+        Track {
+            path: PathBuf::from(path),
+            file_name: path.rsplit('/').next().unwrap().to_string(),
+            title: Song::get_title_from_file(path.rsplit('/').next().unwrap()),
+            artist: Some("Artist".to_string()),
+            ..Track::default()
+        }
+    }
+
+    fn user_version(store: &LibraryStore) -> u32 {
+        store
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn version_1_databases_gain_tags_and_dates_added() {
+        let folder = TempFolder::new();
+        let connection = Connection::open(folder.database()).unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        connection
+            .execute(
+                "INSERT INTO tracks (path, title, play_count) VALUES ('/music/old.wav', 'Old', 4)",
+                [],
+            )
+            .unwrap();
+        drop(connection);
+
+        let before = now();
+        let store = LibraryStore::open_at(&folder.database());
+        assert_eq!(user_version(&store), MIGRATIONS.len() as u32);
+        let stats = store.stats(Path::new("/music/old.wav"));
+        assert_eq!(stats.play_count, 4);
+        assert!(
+            stats
+                .added_at
+                .is_some_and(|added| added >= before && added <= now())
+        );
+    }
+
+    #[test]
+    fn new_databases_import_legacy_bookmarks_once() {
+        let folder = TempFolder::new();
+        fs::write(
+            folder.0.join(LEGACY_STORE_NAME),
+            r#"{ "tracks": { "/music/song.wav": { "bookmarks": [
+                { "name": "Chorus", "seconds": 60.0 },
+                { "name": "Intro", "seconds": 5.0 }
+            ] } } }"#,
+        )
+        .unwrap();
+
+        let store = LibraryStore::open_at(&folder.database());
+        let names: Vec<&str> = store
+            .bookmarks(Path::new("/music/song.wav"))
+            .iter()
+            .map(|bookmark| bookmark.name.as_str())
+            .collect();
+        assert_eq!(names, ["Intro", "Chorus"]);
+        drop(store);
+
+        let store = LibraryStore::open_at(&folder.database());
+        assert_eq!(store.bookmarks(Path::new("/music/song.wav")).len(), 2);
+    }
+
+    #[test]
+    fn plays_are_counted_and_kept_in_the_history() {
+        let folder = TempFolder::new();
+        let mut store = LibraryStore::open_at(&folder.database());
+        let (song, other) = (track("/music/song.wav"), track("/music/other.wav"));
+        let before = now();
+        store.record_play(&song);
+        store.record_play(&other);
+        store.record_play(&song);
+
+        let stats = store.stats(&song.path);
+        assert_eq!(stats.play_count, 2);
+        assert!(stats.last_played.is_some_and(|played| played >= before));
+        let titles: Vec<String> = store
+            .recent_plays(2)
+            .into_iter()
+            .map(|play| play.title)
+            .collect();
+        assert_eq!(titles, [song.title.clone(), other.title.clone()]);
+        drop(store);
+
+        let store = LibraryStore::open_at(&folder.database());
+        assert_eq!(store.stats(&song.path), stats);
+        assert_eq!(store.recent_plays(10).len(), 3);
+    }
+
+    #[test]
+    fn ratings_are_capped() {
+        let folder = TempFolder::new();
+        let mut store = LibraryStore::open_at(&folder.database());
+        let song = track("/music/song.wav");
+        store.set_rating(&song, 9);
+        assert_eq!(store.stats(&song.path).rating, MAX_RATING);
+        drop(store);
+
+        let store = LibraryStore::open_at(&folder.database());
+        assert_eq!(store.stats(&song.path).rating, MAX_RATING);
+    }
+
+    #[test]
+    fn renaming_moves_bookmarks_and_stats() {
+        let folder = TempFolder::new();
+        let mut store = LibraryStore::open_at(&folder.database());
+        let (old, new) = (track("/music/old.wav"), track("/music/new.wav"));
+        store.add_numbered_bookmark(&old, 12.0);
+        store.record_play(&old);
+        store.set_rating(&old, 3);
+        // A stale record at the new path gives way.
+        store.set_rating(&new, 1);
+
+        store.rename_track(&old.path, &new);
+        let check = |store: &LibraryStore| {
+            assert_eq!(store.stats(&old.path), TrackStats::default());
+            assert!(store.bookmarks(&old.path).is_empty());
+            let stats = store.stats(&new.path);
+            assert_eq!((stats.play_count, stats.rating), (1, 3));
+            assert_eq!(store.bookmarks(&new.path)[0].name, "Bookmark 1");
+        };
+        check(&store);
+        drop(store);
+        check(&LibraryStore::open_at(&folder.database()));
+    }
+}
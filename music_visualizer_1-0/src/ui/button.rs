@@ -1,65 +1,155 @@
+use crate::ui::theme::{self, Color, TextSize, Theme};
+use crate::ui::widget::{Event, Widget, WidgetState};
 use nannou::prelude::*;
 
-/// A reusable UI button
-pub struct Button {
+/// Which of the theme's button colours a button is drawn with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ButtonStyle {
+    /// An ordinary button, or a setting that is on
+    #[default]
+    Normal,
+    /// A less important button, or a setting that is off
+    Subdued,
+    /// A button that leaves a screen, such as back
+    Exit,
+    /// The play button while playing
+    Playing,
+    /// The play button while paused
+    Paused,
+}
+
+impl ButtonStyle {
+    /// Returns the background, text and border colours of the style in a theme
+    fn colors(self, theme: &Theme) -> (Color, Color, Option<Color>) {
+        let button = &theme.button;
+        match self {
+            ButtonStyle::Normal => (button.normal, theme.text, Some(theme.accent)),
+            ButtonStyle::Subdued => (button.subdued, theme.text, Some(theme.accent)),
+            ButtonStyle::Exit => (button.exit, theme.contrast_text, None),
+            ButtonStyle::Playing => (button.playing, theme.contrast_text, None),
+            ButtonStyle::Paused => (button.paused, theme.contrast_text, None),
+        }
+    }
+}
+
+/// A reusable UI button that sends a message when clicked
+///
+/// A click is a press and release inside the button, so a press can be abandoned by moving off
+/// before letting go.
+pub struct Button<M> {
     pub label: String,
-    pub tag: String,
-    pub rect: Rect,
-    pub is_visible: bool,
+    pub style: ButtonStyle,
+    /// Whether the button is outlined in the highlight colour, such as a row chosen with the
+    /// keyboard
+    pub selected: bool,
+    /// Sent when the button is clicked
+    message: M,
+    state: WidgetState,
 }
 
-impl Button {
-    /// Creates a new Button
-    pub fn new(label: &str, tag: &str, rect: Rect) -> Self {
+impl<M> Button<M> {
+    /// Creates a new Button with the default style
+    ///
+    /// # Arguments
+    /// * `label` - Text shown on the button
+    /// * `message` - Sent when the button is clicked
+    /// * `rect` - Area of the button
+    pub fn new(label: &str, message: M, rect: Rect) -> Self {
         Self {
             label: label.to_string(),
-            tag: tag.to_string(),
-            rect,
-            is_visible: true,
+            style: ButtonStyle::default(),
+            selected: false,
+            message,
+            state: WidgetState::new(rect),
         }
     }
 
-    /// Returns true if the mouse is inside the button and the button is visible
-    pub fn contains(&self, point: Point2) -> bool {
-        self.is_visible && self.rect.contains(point)
+    /// Returns the button with other colours
+    pub fn with_style(mut self, style: ButtonStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        if self.label != label {
+            self.label = label.to_string();
+        }
     }
 
-    /// Draws the button if visible
-    pub fn draw(
-        &self,
-        draw: &Draw,
-        background: Rgb<f32>,
-        text_color: Rgb<f32>,
-        border: Option<Rgb<f32>>,
-    ) {
-        if !self.is_visible {
+    /// Draws the button if visible, with some characters of the label in the highlight colour
+    ///
+    /// # Arguments
+    /// * `highlights` - Indices (in characters) of the label's characters to highlight
+    pub fn draw_highlighted(&self, draw: &Draw, highlights: &[usize]) {
+        if !self.state.visible {
             return;
         }
+        let theme = theme::current();
+        let rect = self.state.rect;
 
-        draw.rect()
-            .xy(self.rect.xy())
-            .wh(self.rect.wh())
-            .color(background);
+        let (background, text_color, border) = self.style.colors(&theme);
+        let (background, text_color) = if !self.state.enabled {
+            (theme.button.disabled, theme.muted_text)
+        } else if self.state.pressed {
+            (background.shade(theme.button.pressed_shade), text_color)
+        } else if self.state.hovered {
+            (background.shade(theme.button.hover_shade), text_color)
+        } else {
+            (background, text_color)
+        };
+        draw.rect().xy(rect.xy()).wh(rect.wh()).color(background);
 
+        let border = if self.selected {
+            Some(theme.highlight)
+        } else {
+            border
+        };
         if let Some(border_color) = border {
             draw.rect()
-                .xy(self.rect.xy())
-                .wh(self.rect.wh())
+                .xy(rect.xy())
+                .wh(rect.wh())
                 .no_fill()
                 .stroke(border_color)
                 .stroke_weight(2.0);
         }
 
+        let glyph_colors = (0..self.label.chars().count()).map(|index| {
+            if highlights.contains(&index) {
+                theme.highlight
+            } else {
+                text_color
+            }
+        });
         draw.text(&self.label)
-            .xy(self.rect.xy())
+            .xy(rect.xy())
             .color(text_color)
-            .font_size(20)
+            .glyph_colors(glyph_colors)
+            .font_size(theme.font_size(TextSize::Title))
             .align_text_middle_y()
             .center_justify()
-            .width(self.rect.w() - 20.0);
+            .width(rect.w() - 20.0);
     }
+}
 
-    pub fn set_label(&mut self, label: &str) {
-        self.label = label.to_string();
+impl<M: Clone> Widget for Button<M> {
+    type Message = M;
+
+    fn state(&self) -> &WidgetState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        &mut self.state
+    }
+
+    fn on_event(&mut self, event: Event) -> Option<M> {
+        match event {
+            Event::Release { inside: true, .. } => Some(self.message.clone()),
+            _ => None,
+        }
+    }
+
+    fn draw(&self, draw: &Draw) {
+        self.draw_highlighted(draw, &[]);
     }
 }
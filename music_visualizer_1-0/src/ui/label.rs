@@ -0,0 +1,65 @@
+use crate::ui::theme::{self, TextSize};
+use crate::ui::widget::{Event, Widget, WidgetState};
+use nannou::prelude::*;
+
+/// A line of text centred in an area, in the theme's text colour
+///
+/// Labels ignore the pointer; they are widgets so they can be laid out and shown or hidden
+/// along with the controls around them.
+pub struct Label {
+    pub text: String,
+    pub size: TextSize,
+    state: WidgetState,
+}
+
+impl Label {
+    /// Creates a label
+    ///
+    /// # Arguments
+    /// * `text` - Text shown
+    /// * `size` - Which of the theme's text sizes to use
+    /// * `rect` - Area the text is centred in; longer text wraps to its width
+    pub fn new(text: &str, size: TextSize, rect: Rect) -> Self {
+        Label {
+            text: text.to_string(),
+            size,
+            state: WidgetState::new(rect),
+        }
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        if self.text != text {
+            self.text = text.to_string();
+        }
+    }
+}
+
+impl Widget for Label {
+    type Message = ();
+
+    fn state(&self) -> &WidgetState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        &mut self.state
+    }
+
+    fn on_event(&mut self, _event: Event) -> Option<()> {
+        None
+    }
+
+    fn draw(&self, draw: &Draw) {
+        if !self.state.visible {
+            return;
+        }
+        let theme = theme::current();
+        draw.text(&self.text)
+            .xy(self.state.rect.xy())
+            .wh(self.state.rect.wh())
+            .color(theme.text)
+            .font_size(theme.font_size(self.size))
+            .center_justify()
+            .align_text_middle_y();
+    }
+}
@@ -0,0 +1,241 @@
+use nannou::prelude::*;
+
+/// Width of the sidebar when the application starts, in points
+pub const DEFAULT_SIDEBAR_WIDTH: f32 = 200.0;
+
+/// Narrowest the sidebar can be dragged to, in points
+const MIN_SIDEBAR_WIDTH: f32 = 160.0;
+
+/// Largest share of the window the sidebar can take
+const MAX_SIDEBAR_FRACTION: f32 = 0.6;
+
+/// How far from the divider (in points) a press still grabs it
+const DIVIDER_GRAB_DISTANCE: f32 = 5.0;
+
+/// Size of the square handle on the divider that collapses and expands the sidebar, in points
+const TOGGLE_SIZE: f32 = 18.0;
+
+/// Splits the window into the visualization view and the menu sidebar
+///
+/// All sizes are in nannou's logical points. Edges are snapped to whole physical pixels using
+/// the window's scale factor, so the divider stays crisp on HiDPI displays.
+pub struct Layout {
+    /// The window's rectangle
+    window: Rect,
+    /// Physical pixels per point
+    scale_factor: f32,
+    /// Width of the sidebar when expanded
+    sidebar_width: f32,
+    /// Whether the sidebar is hidden
+    collapsed: bool,
+}
+
+impl Layout {
+    /// Creates a layout for a window
+    ///
+    /// # Arguments
+    /// * `window` - The window's rectangle
+    /// * `scale_factor` - Physical pixels per point
+    pub fn new(window: Rect, scale_factor: f32) -> Self {
+        let mut layout = Layout {
+            window,
+            scale_factor,
+            sidebar_width: DEFAULT_SIDEBAR_WIDTH,
+            collapsed: false,
+        };
+        layout.set_sidebar_width(DEFAULT_SIDEBAR_WIDTH);
+        layout
+    }
+
+    /// Adapts the layout to a resized window or a new scale factor
+    ///
+    /// # Arguments
+    /// * `window` - The window's new rectangle
+    /// * `scale_factor` - Physical pixels per point
+    ///
+    /// # Returns
+    /// `true` if anything changed, so the rects must be handed out again
+    pub fn resize(&mut self, window: Rect, scale_factor: f32) -> bool {
+        if window == self.window && scale_factor == self.scale_factor {
+            return false;
+        }
+        self.window = window;
+        self.scale_factor = scale_factor;
+        // Keep the sidebar within bounds for the new window size.
+        self.set_sidebar_width(self.sidebar_width);
+        true
+    }
+
+    /// Returns the x position of the divider between the view and the sidebar
+    pub fn divider_x(&self) -> f32 {
+        let width = if self.collapsed {
+            0.0
+        } else {
+            self.sidebar_width
+        };
+        self.snap(self.window.right() - width)
+    }
+
+    /// Returns the rectangle of the menu sidebar (zero wide while collapsed)
+    pub fn menu_rect(&self) -> Rect {
+        let left = self.divider_x();
+        Rect::from_x_y_w_h(
+            (left + self.window.right()) / 2.0,
+            self.window.y(),
+            self.window.right() - left,
+            self.window.h(),
+        )
+    }
+
+    /// Returns the rectangle of the visualization view
+    pub fn view_rect(&self) -> Rect {
+        let right = self.divider_x();
+        Rect::from_x_y_w_h(
+            (self.window.left() + right) / 2.0,
+            self.window.y(),
+            right - self.window.left(),
+            self.window.h(),
+        )
+    }
+
+    /// Returns the handle that collapses and expands the sidebar, centred on the divider
+    pub fn toggle_rect(&self) -> Rect {
+        let x = (self.divider_x() - TOGGLE_SIZE / 2.0).min(self.window.right() - TOGGLE_SIZE / 2.0);
+        Rect::from_x_y_w_h(x, self.window.y(), TOGGLE_SIZE, TOGGLE_SIZE * 2.0)
+    }
+
+    /// Returns the width of one physical pixel in points
+    pub fn pixel(&self) -> f32 {
+        1.0 / self.scale_factor.max(1.0)
+    }
+
+    /// Returns whether the sidebar is hidden
+    pub fn is_collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    /// Hides or shows the sidebar
+    pub fn toggle_sidebar(&mut self) {
+        self.collapsed = !self.collapsed;
+    }
+
+    /// Returns whether a point is close enough to the divider to start dragging it
+    ///
+    /// The divider cannot be dragged while the sidebar is collapsed.
+    pub fn is_near_divider(&self, point: Point2) -> bool {
+        !self.collapsed
+            && (point.x - self.divider_x()).abs() <= DIVIDER_GRAB_DISTANCE
+            && !self.toggle_rect().contains(point)
+    }
+
+    /// Moves the divider to follow a drag
+    ///
+    /// # Arguments
+    /// * `x` - The x position the divider is dragged to
+    pub fn drag_divider_to(&mut self, x: f32) {
+        self.set_sidebar_width(self.window.right() - x);
+    }
+
+    /// Sets the expanded sidebar width, clamped to the allowed range for the window
+    fn set_sidebar_width(&mut self, width: f32) {
+        let max = (self.window.w() * MAX_SIDEBAR_FRACTION).max(MIN_SIDEBAR_WIDTH);
+        self.sidebar_width = width.clamp(MIN_SIDEBAR_WIDTH, max);
+    }
+
+    /// Rounds a position to the nearest physical pixel
+    fn snap(&self, value: f32) -> f32 {
+        (value * self.scale_factor).round() / self.scale_factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1000 by 600 point window centred on the origin, so its right edge is at x = 500
+    fn window() -> Rect {
+        Rect::from_w_h(1000.0, 600.0)
+    }
+
+    /// Returns whether a position lies on the physical pixel grid for a scale factor
+    fn on_pixel_grid(value: f32, scale_factor: f32) -> bool {
+        let pixels = value * scale_factor;
+        (pixels - pixels.round()).abs() < 1e-3
+    }
+
+    #[test]
+    fn splits_the_window_at_the_divider() {
+        let layout = Layout::new(window(), 1.0);
+        assert_eq!(layout.divider_x(), 300.0);
+        assert_eq!(
+            layout.menu_rect(),
+            Rect::from_x_y_w_h(400.0, 0.0, 200.0, 600.0)
+        );
+        assert_eq!(
+            layout.view_rect(),
+            Rect::from_x_y_w_h(-100.0, 0.0, 800.0, 600.0)
+        );
+    }
+
+    #[test]
+    fn sidebar_width_is_clamped_to_the_minimum_and_share_of_the_window() {
+        let mut layout = Layout::new(window(), 1.0);
+        layout.drag_divider_to(490.0);
+        assert_eq!(layout.divider_x(), 500.0 - MIN_SIDEBAR_WIDTH);
+        layout.drag_divider_to(-500.0);
+        assert_eq!(layout.divider_x(), -100.0);
+
+        // Shrinking the window pulls the sidebar back within 60% of it.
+        assert!(layout.resize(Rect::from_w_h(500.0, 600.0), 1.0));
+        assert_eq!(layout.menu_rect().w(), 300.0);
+        assert!(!layout.resize(Rect::from_w_h(500.0, 600.0), 1.0));
+
+        // In a window too narrow for both limits, the minimum wins.
+        layout.resize(Rect::from_w_h(200.0, 600.0), 1.0);
+        assert_eq!(layout.menu_rect().w(), MIN_SIDEBAR_WIDTH);
+    }
+
+    #[test]
+    fn collapsing_gives_the_whole_window_to_the_view() {
+        let mut layout = Layout::new(window(), 1.0);
+        layout.toggle_sidebar();
+        assert!(layout.is_collapsed());
+        assert_eq!(layout.divider_x(), 500.0);
+        assert_eq!(layout.menu_rect().w(), 0.0);
+        assert_eq!(layout.view_rect(), window());
+        assert!(!layout.is_near_divider(pt2(499.0, 100.0)));
+        // The handle stays inside the window so the sidebar can be brought back.
+        assert!(layout.toggle_rect().right() <= 500.0);
+
+        layout.toggle_sidebar();
+        assert!(!layout.is_collapsed());
+        assert_eq!(layout.divider_x(), 300.0);
+    }
+
+    #[test]
+    fn presses_on_the_toggle_handle_do_not_grab_the_divider() {
+        let layout = Layout::new(window(), 1.0);
+        let toggle = layout.toggle_rect();
+        assert!(toggle.contains(pt2(298.0, 0.0)));
+        assert!(!layout.is_near_divider(pt2(298.0, 0.0)));
+
+        assert!(layout.is_near_divider(pt2(298.0, 100.0)));
+        assert!(layout.is_near_divider(pt2(305.0, -100.0)));
+        assert!(!layout.is_near_divider(pt2(306.0, 100.0)));
+        assert!(!layout.is_near_divider(pt2(290.0, 100.0)));
+    }
+
+    #[test]
+    fn the_divider_snaps_to_physical_pixels() {
+        for (scale_factor, divider_x) in [(1.5, 299.0 + 1.0 / 3.0), (2.0, 299.5)] {
+            let mut layout = Layout::new(window(), scale_factor);
+            layout.drag_divider_to(299.6);
+            assert!((layout.divider_x() - divider_x).abs() < 1e-3);
+            assert!(on_pixel_grid(layout.divider_x(), scale_factor));
+            assert!(on_pixel_grid(layout.menu_rect().w(), scale_factor));
+            assert_eq!(layout.pixel(), 1.0 / scale_factor);
+        }
+        // Below one pixel per point a pixel is still drawn a point wide.
+        assert_eq!(Layout::new(window(), 0.5).pixel(), 1.0);
+    }
+}
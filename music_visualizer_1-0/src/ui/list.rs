@@ -0,0 +1,332 @@
+use crate::ui::button::{Button, ButtonStyle};
+use crate::ui::theme;
+use crate::ui::widget::{Event, Widget, WidgetState};
+use nannou::prelude::*;
+use std::ops::Range;
+
+/// Width of the scrollbar, in points
+const SCROLLBAR_WIDTH: f32 = 8.0;
+
+/// Gap between the rows and the scrollbar, in points
+const SCROLLBAR_GAP: f32 = 6.0;
+
+/// Shortest the scrollbar thumb can get, in points
+const MIN_THUMB_HEIGHT: f32 = 24.0;
+
+/// What a row of a [`ScrollList`] shows
+pub struct ListRow {
+    /// Text of the row
+    pub label: String,
+    /// Colours of the row
+    pub style: ButtonStyle,
+    /// Indices (in characters) of the label's characters to highlight, such as search matches
+    pub highlights: Vec<usize>,
+}
+
+/// A vertically scrolling list of equally tall rows
+///
+/// Only the rows inside the visible area are laid out and drawn, so a list of tens of thousands
+/// of entries costs no more per frame than a handful. Clicking a row sends its index; pressing
+/// the scrollbar drags it.
+pub struct ScrollList {
+    /// Area the list (including its scrollbar) occupies and its interaction state
+    state: WidgetState,
+    /// Height of a row's box
+    row_height: f32,
+    /// Distance between the tops of consecutive rows
+    row_pitch: f32,
+    /// Number of rows
+    len: usize,
+    /// How far the list is scrolled, in points from the top
+    offset: f32,
+    /// Distance from the mouse to the top of the thumb while the scrollbar is dragged
+    thumb_grab: Option<f32>,
+    /// Row chosen with the keyboard
+    selected: Option<usize>,
+    /// Row under the pointer
+    hovered_row: Option<usize>,
+    /// Row a press started on, which is clicked if the press is released over it
+    pressed_row: Option<usize>,
+}
+
+impl ScrollList {
+    /// Creates an empty list
+    ///
+    /// # Arguments
+    /// * `rect` - Area of the list, including its scrollbar
+    /// * `row_height` - Height of a row's box
+    /// * `row_pitch` - Distance between the tops of consecutive rows (height plus spacing)
+    pub fn new(rect: Rect, row_height: f32, row_pitch: f32) -> Self {
+        ScrollList {
+            state: WidgetState::new(rect),
+            row_height,
+            row_pitch,
+            len: 0,
+            offset: 0.0,
+            thumb_grab: None,
+            selected: None,
+            hovered_row: None,
+            pressed_row: None,
+        }
+    }
+
+    /// Sets the number of rows, dropping the selection if its row is gone
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len;
+        self.selected = self.selected.filter(|&index| index < len);
+        self.clamp_offset();
+    }
+
+    /// Scrolls by a distance in points; positive values move towards the end of the list
+    pub fn scroll_by(&mut self, distance: f32) {
+        self.offset += distance;
+        self.clamp_offset();
+    }
+
+    /// Scrolls back to the first row
+    pub fn scroll_to_top(&mut self) {
+        self.offset = 0.0;
+    }
+
+    /// Returns the row chosen with the keyboard, if any
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Chooses a row and scrolls it into view
+    ///
+    /// # Arguments
+    /// * `index` - The row, clamped to the list; `None` clears the selection
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index
+            .filter(|_| self.len > 0)
+            .map(|index| index.min(self.len - 1));
+        if let Some(index) = self.selected {
+            let top = index as f32 * self.row_pitch;
+            let bottom = top + self.row_height;
+            if top < self.offset {
+                self.offset = top;
+            } else if bottom > self.offset + self.state.rect.h() {
+                self.offset = bottom - self.state.rect.h();
+            }
+            self.clamp_offset();
+        }
+    }
+
+    /// Moves the selection by a number of rows, starting from the first row if none is selected
+    ///
+    /// # Arguments
+    /// * `rows` - Rows to move; positive values move towards the end of the list
+    pub fn move_selection(&mut self, rows: isize) {
+        let index = match self.selected {
+            Some(index) => index.saturating_add_signed(rows),
+            None => 0,
+        };
+        self.select(Some(index));
+    }
+
+    /// Returns the number of whole rows that fit in the list's area
+    pub fn page_rows(&self) -> usize {
+        ((self.state.rect.h() / self.row_pitch).floor() as usize).max(1)
+    }
+
+    /// Returns the indices of the rows at least partly visible
+    pub fn visible_rows(&self) -> Range<usize> {
+        let first = (self.offset / self.row_pitch).floor().max(0.0) as usize;
+        let last = ((self.offset + self.state.rect.h()) / self.row_pitch)
+            .ceil()
+            .max(0.0) as usize;
+        first.min(self.len)..last.min(self.len)
+    }
+
+    /// Returns the box of a row at the current scroll position
+    pub fn row_rect(&self, index: usize) -> Rect {
+        let content = self.content_rect();
+        let top = self.state.rect.top() + self.offset - index as f32 * self.row_pitch;
+        Rect::from_x_y_w_h(
+            content.x(),
+            top - self.row_height / 2.0,
+            content.w(),
+            self.row_height,
+        )
+    }
+
+    /// Returns the row under a point, if any
+    pub fn row_at(&self, point: Point2) -> Option<usize> {
+        if !self.content_rect().contains(point) {
+            return None;
+        }
+        self.visible_rows()
+            .find(|&index| self.row_rect(index).contains(point))
+    }
+
+    /// Draws the visible rows and, if the list overflows, the scrollbar
+    ///
+    /// # Arguments
+    /// * `draw` - Nannou Draw context for rendering
+    /// * `row` - Returns what a row shows; only called for visible rows
+    pub fn draw_rows(&self, draw: &Draw, row: impl Fn(usize) -> ListRow) {
+        if !self.state.visible {
+            return;
+        }
+        // Rows scrolled partly out of the area are cut off at its edges.
+        let clipped = draw.scissor(self.state.rect);
+        for index in self.visible_rows() {
+            let row = row(index);
+            let mut button =
+                Button::new(&row.label, (), self.row_rect(index)).with_style(row.style);
+            button.selected = self.selected == Some(index);
+            let state = button.state_mut();
+            state.hovered = self.hovered_row == Some(index);
+            state.pressed = self.pressed_row == Some(index) && state.hovered;
+            button.draw_highlighted(&clipped, &row.highlights);
+        }
+        self.draw(draw);
+    }
+
+    /// Points the hovered row at the row under the pointer
+    fn hover(&mut self, position: Point2) {
+        self.hovered_row = self.row_at(position);
+    }
+
+    /// Scrolls so the thumb's top sits at the pointer's height less its grab distance
+    fn drag_thumb(&mut self, position: Point2, grab: f32) {
+        let track = self.scrollbar_rect();
+        let travel = track.h() - self.thumb_rect().h();
+        if travel > 0.0 {
+            let fraction = ((track.top() - (position.y + grab)) / travel).clamp(0.0, 1.0);
+            self.offset = fraction * self.max_offset();
+        }
+    }
+
+    /// Returns the area the rows are drawn in, leaving room for the scrollbar
+    fn content_rect(&self) -> Rect {
+        let width = self.state.rect.w() - SCROLLBAR_WIDTH - SCROLLBAR_GAP;
+        Rect::from_x_y_w_h(
+            self.state.rect.left() + width / 2.0,
+            self.state.rect.y(),
+            width,
+            self.state.rect.h(),
+        )
+    }
+
+    /// Returns the scrollbar's track along the right edge
+    fn scrollbar_rect(&self) -> Rect {
+        Rect::from_x_y_w_h(
+            self.state.rect.right() - SCROLLBAR_WIDTH / 2.0,
+            self.state.rect.y(),
+            SCROLLBAR_WIDTH,
+            self.state.rect.h(),
+        )
+    }
+
+    /// Returns the scrollbar's thumb, sized by the visible share of the list
+    fn thumb_rect(&self) -> Rect {
+        let track = self.scrollbar_rect();
+        let content_height = self.content_height().max(track.h());
+        let height = (track.h() * track.h() / content_height)
+            .max(MIN_THUMB_HEIGHT)
+            .min(track.h());
+        let fraction = if self.max_offset() > 0.0 {
+            self.offset / self.max_offset()
+        } else {
+            0.0
+        };
+        let top = track.top() - fraction * (track.h() - height);
+        Rect::from_x_y_w_h(track.x(), top - height / 2.0, track.w(), height)
+    }
+
+    /// Returns the height of all rows together
+    fn content_height(&self) -> f32 {
+        if self.len == 0 {
+            return 0.0;
+        }
+        (self.len - 1) as f32 * self.row_pitch + self.row_height
+    }
+
+    /// Returns how far the list can scroll
+    fn max_offset(&self) -> f32 {
+        (self.content_height() - self.state.rect.h()).max(0.0)
+    }
+
+    /// Keeps the scroll position within the list
+    fn clamp_offset(&mut self) {
+        self.offset = self.offset.clamp(0.0, self.max_offset());
+    }
+}
+
+impl Widget for ScrollList {
+    /// The index of a clicked row
+    type Message = usize;
+
+    fn state(&self) -> &WidgetState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        &mut self.state
+    }
+
+    fn set_rect(&mut self, rect: Rect) {
+        // Keep the scroll position where possible.
+        self.state.rect = rect;
+        self.clamp_offset();
+    }
+
+    fn on_event(&mut self, event: Event) -> Option<usize> {
+        match event {
+            Event::Hover(position) => self.hover(position),
+            Event::Leave => self.hovered_row = None,
+            Event::Press { position, .. } => {
+                // A press on the scrollbar grabs the thumb, jumping it to the pointer if the
+                // press missed it.
+                if self.max_offset() > 0.0 && self.scrollbar_rect().contains(position) {
+                    let thumb = self.thumb_rect();
+                    let grab = if thumb.contains(position) {
+                        thumb.top() - position.y
+                    } else {
+                        thumb.h() / 2.0
+                    };
+                    self.thumb_grab = Some(grab);
+                    self.drag_thumb(position, grab);
+                } else {
+                    self.pressed_row = self.row_at(position);
+                }
+            }
+            Event::Drag(position) => {
+                if let Some(grab) = self.thumb_grab {
+                    self.drag_thumb(position, grab);
+                }
+                self.hover(position);
+            }
+            Event::Release { position, inside } => {
+                self.thumb_grab = None;
+                let pressed = self.pressed_row.take();
+                self.hover(position);
+                if inside && pressed.is_some() && pressed == self.row_at(position) {
+                    return pressed;
+                }
+            }
+            Event::Enter | Event::Focus(_) => {}
+        }
+        None
+    }
+
+    /// Draws the scrollbar if the list overflows; the rows are drawn by
+    /// [`ScrollList::draw_rows`], which asks the owner what each row shows
+    fn draw(&self, draw: &Draw) {
+        if !self.state.visible || self.max_offset() <= 0.0 {
+            return;
+        }
+        let theme = theme::current();
+        let track = self.scrollbar_rect();
+        draw.rect().xy(track.xy()).wh(track.wh()).color(theme.field);
+        let thumb = self.thumb_rect();
+        let thumb_color = if self.thumb_grab.is_some() {
+            theme.text
+        } else {
+            theme.accent
+        };
+        draw.rect().xy(thumb.xy()).wh(thumb.wh()).color(thumb_color);
+    }
+}
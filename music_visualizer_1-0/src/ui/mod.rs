@@ -1,2 +1,11 @@
 pub mod button;
-pub mod color;
+pub mod label;
+pub mod layout;
+pub mod list;
+pub mod picture;
+pub mod progress;
+pub mod slider;
+pub mod text_input;
+pub mod theme;
+pub mod toggle;
+pub mod widget;
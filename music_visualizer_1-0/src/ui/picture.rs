@@ -0,0 +1,67 @@
+use crate::ui::theme;
+use crate::ui::widget::{Event, Widget, WidgetState};
+use nannou::prelude::*;
+
+/// An image scaled to fit an area without stretching, such as a song's cover art
+///
+/// Like labels, pictures ignore the pointer. A picture without an image draws nothing.
+pub struct Picture {
+    texture: Option<wgpu::Texture>,
+    state: WidgetState,
+}
+
+impl Picture {
+    /// Creates a picture without an image
+    ///
+    /// # Arguments
+    /// * `rect` - Area the image is fitted into
+    pub fn new(rect: Rect) -> Self {
+        Picture {
+            texture: None,
+            state: WidgetState::new(rect),
+        }
+    }
+
+    /// Shows an image, or nothing
+    pub fn set_texture(&mut self, texture: Option<wgpu::Texture>) {
+        self.texture = texture;
+    }
+
+    pub fn has_texture(&self) -> bool {
+        self.texture.is_some()
+    }
+}
+
+impl Widget for Picture {
+    type Message = ();
+
+    fn state(&self) -> &WidgetState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        &mut self.state
+    }
+
+    fn on_event(&mut self, _event: Event) -> Option<()> {
+        None
+    }
+
+    fn draw(&self, draw: &Draw) {
+        let Some(texture) = self.texture.as_ref().filter(|_| self.state.visible) else {
+            return;
+        };
+        let rect = self.state.rect;
+        let [width, height] = texture.size();
+        let scale = (rect.w() / width as f32).min(rect.h() / height as f32);
+        let size = vec2(width as f32, height as f32) * scale;
+
+        draw.texture(texture).xy(rect.xy()).wh(size);
+        draw.rect()
+            .xy(rect.xy())
+            .wh(size)
+            .no_fill()
+            .stroke(theme::current().accent)
+            .stroke_weight(1.0);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::ui::theme::{self, Color};
+use crate::ui::widget::{Event, Widget, WidgetState};
+use nannou::prelude::*;
+
+/// A vertical line across a [`ProgressBar`] at some point along it
+pub struct Marker {
+    /// Position along the bar (0.0 to 1.0)
+    pub fraction: f32,
+    pub color: Color,
+}
+
+/// A horizontal bar filled up to a fraction, such as the playing position or a load's progress
+///
+/// A bar with a seek callback can be clicked or dragged along to pick a position; a bar with a
+/// mark callback reports right clicks too. Without callbacks it only displays.
+pub struct ProgressBar<M> {
+    /// How far the bar is filled (0.0 to 1.0)
+    value: f32,
+    /// Part of the bar shaded behind the fill, such as a loop
+    span: Option<(f32, f32)>,
+    markers: Vec<Marker>,
+    /// Turns a position picked with the primary button into the message sent to the owner
+    on_seek: Option<Box<dyn Fn(f32) -> M>>,
+    /// Turns a position right-clicked into the message sent to the owner
+    on_mark: Option<Box<dyn Fn(f32) -> M>>,
+    /// Where the pointer last picked a position during a primary press, so holding still does
+    /// not keep picking the same position; `None` outside primary presses
+    drag_x: Option<f32>,
+    state: WidgetState,
+}
+
+impl<M> ProgressBar<M> {
+    /// Creates an empty bar that only displays
+    ///
+    /// # Arguments
+    /// * `rect` - Area of the bar
+    pub fn new(rect: Rect) -> Self {
+        ProgressBar {
+            value: 0.0,
+            span: None,
+            markers: Vec::new(),
+            on_seek: None,
+            on_mark: None,
+            drag_x: None,
+            state: WidgetState::new(rect),
+        }
+    }
+
+    /// Returns the bar reporting positions picked with the primary button
+    pub fn on_seek(mut self, on_seek: impl Fn(f32) -> M + 'static) -> Self {
+        self.on_seek = Some(Box::new(on_seek));
+        self
+    }
+
+    /// Returns the bar reporting positions picked with the secondary button
+    pub fn on_mark(mut self, on_mark: impl Fn(f32) -> M + 'static) -> Self {
+        self.on_mark = Some(Box::new(on_mark));
+        self
+    }
+
+    /// Fills the bar up to a fraction (0.0 to 1.0)
+    pub fn set_value(&mut self, value: f32) {
+        self.value = value.clamp(0.0, 1.0);
+    }
+
+    /// Shades a part of the bar, given as fractions of its length, or removes the shading
+    pub fn set_span(&mut self, span: Option<(f32, f32)>) {
+        self.span = span;
+    }
+
+    /// Replaces the marker lines drawn across the bar
+    pub fn set_markers(&mut self, markers: Vec<Marker>) {
+        self.markers = markers;
+    }
+
+    /// Returns the x position of a fraction of the bar
+    pub fn x_at(&self, fraction: f32) -> f32 {
+        let rect = self.state.rect;
+        rect.left() + rect.w() * fraction.clamp(0.0, 1.0)
+    }
+
+    /// Returns the fraction of the bar at an x position
+    fn fraction_at(&self, x: f32) -> f32 {
+        let rect = self.state.rect;
+        ((x - rect.left()) / rect.w()).clamp(0.0, 1.0)
+    }
+}
+
+impl<M> Widget for ProgressBar<M> {
+    type Message = M;
+
+    fn state(&self) -> &WidgetState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        &mut self.state
+    }
+
+    fn on_event(&mut self, event: Event) -> Option<M> {
+        match event {
+            Event::Press {
+                position,
+                secondary: true,
+            } => {
+                self.drag_x = None;
+                let fraction = self.fraction_at(position.x);
+                self.on_mark.as_ref().map(|on_mark| on_mark(fraction))
+            }
+            Event::Drag(position) if self.drag_x.is_none_or(|x| x == position.x) => None,
+            Event::Press { position, .. } | Event::Drag(position) => {
+                self.drag_x = Some(position.x);
+                let fraction = self.fraction_at(position.x);
+                self.on_seek.as_ref().map(|on_seek| on_seek(fraction))
+            }
+            _ => None,
+        }
+    }
+
+    fn draw(&self, draw: &Draw) {
+        if !self.state.visible {
+            return;
+        }
+        let theme = theme::current();
+        let rect = self.state.rect;
+        let interactive = self.on_seek.is_some() || self.on_mark.is_some();
+        let background = if interactive && self.state.hovered {
+            theme.field.shade(theme.button.hover_shade)
+        } else if interactive {
+            theme.field
+        } else {
+            theme.button.normal
+        };
+        draw.rect().xy(rect.xy()).wh(rect.wh()).color(background);
+
+        if let Some((start, end)) = self.span {
+            let (left, right) = (self.x_at(start), self.x_at(end));
+            draw.rect()
+                .x_y((left + right) / 2.0, rect.y())
+                .w_h(right - left, rect.h())
+                .color(theme.loop_span);
+        }
+
+        // Interactive bars show the fill as a thinner line so the shading stays visible.
+        let filled = self.x_at(self.value) - rect.left();
+        let fill_height = if interactive {
+            rect.h() * 0.5
+        } else {
+            rect.h()
+        };
+        draw.rect()
+            .x_y(rect.left() + filled / 2.0, rect.y())
+            .w_h(filled, fill_height)
+            .color(theme.accent);
+
+        for marker in &self.markers {
+            let x = self.x_at(marker.fraction);
+            draw.line()
+                .start(pt2(x, rect.bottom()))
+                .end(pt2(x, rect.top()))
+                .color(marker.color)
+                .weight(2.0);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::ui::theme::{self, TextSize};
+use crate::ui::widget::{Event, Widget, WidgetState};
+use nannou::prelude::*;
+use std::ops::RangeInclusive;
+
+/// Thickness of the slider's handle along its track, in points
+const HANDLE_THICKNESS: f32 = 8.0;
+
+/// Direction a slider's track runs in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    /// Minimum on the left, maximum on the right
+    Horizontal,
+    /// Minimum at the bottom, maximum at the top
+    Vertical,
+}
+
+/// A handle dragged along a track to pick a value from a range
+///
+/// Pressing anywhere on the slider jumps the handle to the pointer; it then follows the pointer
+/// until released, even outside the slider. Every change is reported through the slider's
+/// callback.
+pub struct Slider<M> {
+    orientation: Orientation,
+    range: RangeInclusive<f32>,
+    value: f32,
+    /// Small text below the track, such as the band a slider controls
+    caption: String,
+    /// Turns a newly picked value into the message sent to the owner
+    on_change: Box<dyn Fn(f32) -> M>,
+    state: WidgetState,
+}
+
+impl<M> Slider<M> {
+    /// Creates a slider set to the start of its range
+    ///
+    /// # Arguments
+    /// * `orientation` - Direction the track runs in
+    /// * `range` - Values at the two ends of the track
+    /// * `rect` - Area of the slider; the track runs along its middle
+    /// * `on_change` - Turns a newly picked value into a message
+    pub fn new(
+        orientation: Orientation,
+        range: RangeInclusive<f32>,
+        rect: Rect,
+        on_change: impl Fn(f32) -> M + 'static,
+    ) -> Self {
+        Slider {
+            orientation,
+            value: *range.start(),
+            range,
+            caption: String::new(),
+            on_change: Box::new(on_change),
+            state: WidgetState::new(rect),
+        }
+    }
+
+    /// Returns the slider with a caption below its track
+    pub fn with_caption(mut self, caption: &str) -> Self {
+        self.caption = caption.to_string();
+        self
+    }
+
+    /// Moves the handle without sending a message, for values changed elsewhere
+    pub fn set_value(&mut self, value: f32) {
+        self.value = value.clamp(*self.range.start(), *self.range.end());
+    }
+
+    /// Returns how far along the track the handle is (0.0 to 1.0)
+    fn fraction(&self) -> f32 {
+        let span = self.range.end() - self.range.start();
+        if span > 0.0 {
+            (self.value - self.range.start()) / span
+        } else {
+            0.0
+        }
+    }
+
+    /// Moves the handle to the point on the track nearest the pointer
+    fn pick(&mut self, position: Point2) -> Option<M> {
+        let rect = self.state.rect;
+        let fraction = match self.orientation {
+            Orientation::Horizontal => (position.x - rect.left()) / rect.w(),
+            Orientation::Vertical => (position.y - rect.bottom()) / rect.h(),
+        };
+        let span = self.range.end() - self.range.start();
+        self.set_value(self.range.start() + fraction.clamp(0.0, 1.0) * span);
+        Some((self.on_change)(self.value))
+    }
+}
+
+impl<M> Widget for Slider<M> {
+    type Message = M;
+
+    fn state(&self) -> &WidgetState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        &mut self.state
+    }
+
+    fn on_event(&mut self, event: Event) -> Option<M> {
+        match event {
+            Event::Press { position, .. } | Event::Drag(position) => self.pick(position),
+            _ => None,
+        }
+    }
+
+    fn draw(&self, draw: &Draw) {
+        if !self.state.visible {
+            return;
+        }
+        let theme = theme::current();
+        let rect = self.state.rect;
+        let fraction = self.fraction();
+        let (start, end, handle) = match self.orientation {
+            Orientation::Horizontal => (
+                pt2(rect.left(), rect.y()),
+                pt2(rect.right(), rect.y()),
+                Rect::from_x_y_w_h(
+                    rect.left() + rect.w() * fraction,
+                    rect.y(),
+                    HANDLE_THICKNESS,
+                    rect.h() * 0.8,
+                ),
+            ),
+            Orientation::Vertical => (
+                pt2(rect.x(), rect.bottom()),
+                pt2(rect.x(), rect.top()),
+                Rect::from_x_y_w_h(
+                    rect.x(),
+                    rect.bottom() + rect.h() * fraction,
+                    rect.w() * 0.8,
+                    HANDLE_THICKNESS,
+                ),
+            ),
+        };
+
+        let track_color = if self.state.enabled {
+            theme.accent
+        } else {
+            theme.muted_text
+        };
+        draw.line()
+            .start(start)
+            .end(end)
+            .color(track_color)
+            .weight(2.0);
+
+        let handle_color = if self.state.pressed {
+            theme.marker
+        } else if self.state.hovered {
+            theme.marker.shade(0.6)
+        } else {
+            theme.text
+        };
+        draw.rect()
+            .xy(handle.xy())
+            .wh(handle.wh())
+            .color(handle_color);
+
+        if !self.caption.is_empty() {
+            draw.text(&self.caption)
+                .xy(pt2(rect.x(), rect.bottom() - 12.0))
+                .color(theme.text)
+                .font_size(theme.font_size(TextSize::Caption));
+        }
+    }
+}
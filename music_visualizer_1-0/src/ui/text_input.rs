@@ -0,0 +1,350 @@
+use crate::ui::theme::{self, TextSize};
+use crate::ui::widget::{Event, Widget, WidgetState};
+use nannou::event::ModifiersState;
+use nannou::prelude::*;
+use std::ops::Range;
+
+/// Space between the border and the text, in points
+const PADDING: f32 = 8.0;
+
+/// A single-line text field with a caret, selection and clipboard support
+///
+/// Characters arrive through [`TextInput::insert_char`] and editing keys through
+/// [`TextInput::key_pressed`]; both are ignored unless the field has focus, which it gains by
+/// being clicked. Dragging across the text selects it.
+pub struct TextInput {
+    /// Area of the field, focus and whether a press is extending the selection
+    state: WidgetState,
+    /// Text shown while the field is empty
+    placeholder: String,
+    /// The entered text
+    text: String,
+    /// Position of the caret, in characters
+    caret: usize,
+    /// The other end of the selection, in characters; equal to `caret` when nothing is selected
+    anchor: usize,
+}
+
+impl TextInput {
+    /// Creates an empty, unfocused field
+    ///
+    /// # Arguments
+    /// * `placeholder` - Text shown while the field is empty
+    /// * `rect` - Area of the field
+    pub fn new(placeholder: &str, rect: Rect) -> Self {
+        TextInput {
+            state: WidgetState::new(rect),
+            placeholder: placeholder.to_string(),
+            text: String::new(),
+            caret: 0,
+            anchor: 0,
+        }
+    }
+
+    /// Returns the entered text
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Types a character, replacing the selection
+    ///
+    /// # Returns
+    /// `true` if the text changed
+    pub fn insert_char(&mut self, c: char) -> bool {
+        // Editing keys also arrive as control characters; they are handled in `key_pressed`.
+        if !self.state.focused || c.is_control() {
+            return false;
+        }
+        self.insert_str(&c.to_string());
+        true
+    }
+
+    /// Applies an editing key
+    ///
+    /// Handles Backspace, Delete, the arrow keys, Home and End (extending the selection with
+    /// Shift), Escape to clear the text (or drop focus once empty), and Ctrl+A, Ctrl+C, Ctrl+X
+    /// and Ctrl+V.
+    ///
+    /// # Arguments
+    /// * `key` - The pressed key
+    /// * `mods` - The modifier keys held at the time
+    ///
+    /// # Returns
+    /// `true` if the text changed
+    pub fn key_pressed(&mut self, key: Key, mods: ModifiersState) -> bool {
+        if !self.state.focused {
+            return false;
+        }
+        let len = self.char_count();
+        let shortcut = mods.ctrl() || mods.logo();
+        match key {
+            Key::Back | Key::Delete => {
+                if self.caret == self.anchor {
+                    if key == Key::Back && self.caret > 0 {
+                        self.anchor = self.caret - 1;
+                    } else if key == Key::Delete && self.caret < len {
+                        self.anchor = self.caret + 1;
+                    } else {
+                        return false;
+                    }
+                }
+                self.insert_str("");
+                return true;
+            }
+            Key::Left if self.caret != self.anchor && !mods.shift() => {
+                self.move_caret(self.selection().start, false)
+            }
+            Key::Right if self.caret != self.anchor && !mods.shift() => {
+                self.move_caret(self.selection().end, false)
+            }
+            Key::Left => self.move_caret(self.caret.saturating_sub(1), mods.shift()),
+            Key::Right => self.move_caret((self.caret + 1).min(len), mods.shift()),
+            Key::Home => self.move_caret(0, mods.shift()),
+            Key::End => self.move_caret(len, mods.shift()),
+            Key::Escape if !self.text.is_empty() => {
+                self.clear();
+                return true;
+            }
+            Key::Escape => {
+                self.set_focus(false);
+            }
+            Key::A if shortcut => {
+                self.anchor = 0;
+                self.caret = len;
+            }
+            Key::C if shortcut => self.copy(),
+            Key::X if shortcut && self.caret != self.anchor => {
+                self.copy();
+                self.insert_str("");
+                return true;
+            }
+            Key::V if shortcut => return self.paste(),
+            _ => {}
+        }
+        false
+    }
+
+    /// Draws the field with its text, selection and (when focused) caret
+    fn draw_field(&self, draw: &Draw) {
+        let theme = theme::current();
+        let font_size = theme.font_size(TextSize::Input);
+        let border = if self.state.focused {
+            theme.accent
+        } else if self.state.hovered {
+            theme.button.normal.shade(theme.button.hover_shade)
+        } else {
+            theme.button.normal
+        };
+        draw.rect()
+            .xy(self.state.rect.xy())
+            .wh(self.state.rect.wh())
+            .color(theme.field)
+            .stroke(border)
+            .stroke_weight(2.0);
+
+        let inner = self.inner_rect();
+        let clipped = draw.scissor(inner);
+        if self.text.is_empty() {
+            clipped
+                .text(&self.placeholder)
+                .xy(inner.xy())
+                .wh(inner.wh())
+                .color(theme.muted_text)
+                .font_size(font_size)
+                .no_line_wrap()
+                .left_justify()
+                .align_text_middle_y();
+        }
+
+        // Scroll long text sideways so the caret stays in view.
+        let boundaries = self.boundaries();
+        let shift = (boundaries[self.caret] - inner.right()).max(0.0);
+        let x = |index: usize| boundaries[index] - shift;
+
+        let selection = self.selection();
+        if !selection.is_empty() {
+            let (left, right) = (x(selection.start), x(selection.end));
+            clipped
+                .rect()
+                .x_y((left + right) / 2.0, inner.y())
+                .w_h(right - left, inner.h())
+                .color(theme.button.normal);
+        }
+
+        clipped
+            .text(&self.text)
+            .x_y(inner.x() - shift, inner.y())
+            .wh(inner.wh())
+            .color(theme.text)
+            .font_size(font_size)
+            .no_line_wrap()
+            .left_justify()
+            .align_text_middle_y();
+
+        if self.state.focused {
+            clipped
+                .line()
+                .start(pt2(x(self.caret), inner.top()))
+                .end(pt2(x(self.caret), inner.bottom()))
+                .color(theme.text)
+                .weight(1.5);
+        }
+    }
+
+    /// Returns the area the text is laid out in
+    fn inner_rect(&self) -> Rect {
+        self.state.rect.pad_left(PADDING).pad_right(PADDING)
+    }
+
+    /// Returns the number of characters entered
+    fn char_count(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// Returns the selected characters
+    fn selection(&self) -> Range<usize> {
+        self.caret.min(self.anchor)..self.caret.max(self.anchor)
+    }
+
+    /// Converts a position in characters into a byte offset into the text
+    fn byte_index(&self, index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(index)
+            .map_or(self.text.len(), |(byte, _)| byte)
+    }
+
+    /// Removes all text
+    fn clear(&mut self) {
+        self.text.clear();
+        self.caret = 0;
+        self.anchor = 0;
+    }
+
+    /// Moves the caret, keeping the anchor in place when extending the selection
+    fn move_caret(&mut self, index: usize, extend: bool) {
+        self.caret = index;
+        if !extend {
+            self.anchor = index;
+        }
+    }
+
+    /// Replaces the selection with a string and places the caret after it
+    fn insert_str(&mut self, s: &str) {
+        let selection = self.selection();
+        let range = self.byte_index(selection.start)..self.byte_index(selection.end);
+        self.text.replace_range(range, s);
+        self.caret = selection.start + s.chars().count();
+        self.anchor = self.caret;
+    }
+
+    /// Puts the selected text on the clipboard
+    fn copy(&self) {
+        let selection = self.selection();
+        if selection.is_empty() {
+            return;
+        }
+        let selected: String = self
+            .text
+            .chars()
+            .skip(selection.start)
+            .take(selection.len())
+            .collect();
+        let result =
+            arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(selected));
+        if let Err(e) = result {
+            eprintln!("⚠️ Could not copy to the clipboard: {}", e);
+        }
+    }
+
+    /// Replaces the selection with the clipboard's text (as a single line)
+    ///
+    /// # Returns
+    /// `true` if the text changed
+    fn paste(&mut self) -> bool {
+        match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(pasted) => {
+                let line: String = pasted
+                    .chars()
+                    .map(|c| if c.is_whitespace() { ' ' } else { c })
+                    .filter(|c| !c.is_control())
+                    .collect();
+                self.insert_str(&line);
+                true
+            }
+            Err(e) => {
+                eprintln!("⚠️ Could not paste from the clipboard: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Returns the x position of every caret position, from before the first character to
+    /// after the last, with the text unscrolled
+    fn boundaries(&self) -> Vec<f32> {
+        let inner = self.inner_rect();
+        let layout = nannou::text::text(&self.text)
+            .font_size(theme::current().font_size(TextSize::Input))
+            .no_line_wrap()
+            .left_justify()
+            .build(inner);
+        let mut boundaries = vec![inner.left()];
+        for (glyph, rect) in layout.glyphs() {
+            *boundaries.last_mut().unwrap() = rect.left();
+            boundaries.push(rect.left() + glyph.h_metrics().advance_width);
+        }
+        // Pad in case the font merged characters into fewer glyphs.
+        let last = *boundaries.last().unwrap();
+        boundaries.resize(self.char_count() + 1, last);
+        boundaries
+    }
+
+    /// Returns the caret position closest to an x position
+    fn index_at(&self, x: f32) -> usize {
+        let inner = self.inner_rect();
+        let boundaries = self.boundaries();
+        let shift = (boundaries[self.caret] - inner.right()).max(0.0);
+        let x = x + shift;
+        (0..boundaries.len())
+            .min_by(|&a, &b| {
+                (boundaries[a] - x)
+                    .abs()
+                    .total_cmp(&(boundaries[b] - x).abs())
+            })
+            .unwrap_or(0)
+    }
+}
+
+impl Widget for TextInput {
+    type Message = ();
+
+    fn state(&self) -> &WidgetState {
+        &self.state
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        &mut self.state
+    }
+
+    fn is_focusable(&self) -> bool {
+        true
+    }
+
+    fn on_event(&mut self, event: Event) -> Option<()> {
+        match event {
+            Event::Press { position, .. } => {
+                self.caret = self.index_at(position.x);
+                self.anchor = self.caret;
+            }
+            Event::Drag(position) => self.caret = self.index_at(position.x),
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&self, draw: &Draw) {
+        if self.state.visible {
+            self.draw_field(draw);
+        }
+    }
+}
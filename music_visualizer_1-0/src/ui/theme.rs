@@ -0,0 +1,441 @@
+use nannou::color::{IntoLinSrgba, LinSrgba};
+use nannou::prelude::*;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Folder (relative to the working directory) searched for user themes
+pub const THEMES_DIR: &str = "config/themes";
+
+/// The theme everything is drawn with
+static CURRENT: Lazy<RwLock<Arc<Theme>>> = Lazy::new(|| RwLock::new(Arc::new(Theme::dark())));
+
+/// Returns the theme everything is drawn with
+pub fn current() -> Arc<Theme> {
+    Arc::clone(&CURRENT.read().unwrap())
+}
+
+/// Switches the theme; the next frame is drawn with it
+pub fn set_current(theme: Theme) {
+    *CURRENT.write().unwrap() = Arc::new(theme);
+}
+
+/// An sRGB colour with alpha, written in theme files as `"#rrggbb"` or `"#rrggbbaa"`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub Rgba<f32>);
+
+impl Color {
+    /// Creates an opaque colour
+    pub fn rgb(red: f32, green: f32, blue: f32) -> Self {
+        Color(rgba(red, green, blue, 1.0))
+    }
+
+    /// Creates a colour with alpha
+    pub fn rgba(red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        Color(rgba(red, green, blue, alpha))
+    }
+
+    /// Returns the colour with another alpha
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        Color::rgba(self.0.red, self.0.green, self.0.blue, alpha)
+    }
+
+    /// Returns how bright the colour looks, from 0.0 (black) to 1.0 (white)
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.0.red + 0.7152 * self.0.green + 0.0722 * self.0.blue
+    }
+
+    /// Lightens the colour towards white (positive amounts) or darkens it towards black
+    /// (negative), keeping its alpha
+    ///
+    /// # Arguments
+    /// * `amount` - How far to move, from -1.0 (black) to 1.0 (white)
+    pub fn shade(self, amount: f32) -> Self {
+        let target = if amount < 0.0 { 0.0 } else { 1.0 };
+        let t = amount.abs().min(1.0);
+        let mix = |channel: f32| channel + (target - channel) * t;
+        Color::rgba(
+            mix(self.0.red),
+            mix(self.0.green),
+            mix(self.0.blue),
+            self.0.alpha,
+        )
+    }
+}
+
+impl IntoLinSrgba<f32> for Color {
+    fn into_lin_srgba(self) -> LinSrgba<f32> {
+        self.0.into_lin_srgba()
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(hex: String) -> Result<Self, String> {
+        let digits = hex.strip_prefix('#').unwrap_or(&hex);
+        if !matches!(digits.len(), 6 | 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "invalid colour '{}', expected #rrggbb or #rrggbbaa",
+                hex
+            ));
+        }
+        let channel = |index: usize| {
+            digits.get(index * 2..index * 2 + 2).map_or(1.0, |pair| {
+                u8::from_str_radix(pair, 16).unwrap() as f32 / 255.0
+            })
+        };
+        Ok(Color::rgba(channel(0), channel(1), channel(2), channel(3)))
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> String {
+        let byte = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let Color(c) = color;
+        let mut hex = format!(
+            "#{:02x}{:02x}{:02x}",
+            byte(c.red),
+            byte(c.green),
+            byte(c.blue)
+        );
+        if c.alpha < 1.0 {
+            hex.push_str(&format!("{:02x}", byte(c.alpha)));
+        }
+        hex
+    }
+}
+
+/// Colours of the buttons
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ButtonColors {
+    /// Background of ordinary buttons and of settings that are on
+    pub normal: Color,
+    /// Background of less important buttons and of settings that are off
+    pub subdued: Color,
+    /// Background of disabled buttons
+    pub disabled: Color,
+    /// Background of buttons that leave a screen, such as back
+    pub exit: Color,
+    /// Background of the play button while playing
+    pub playing: Color,
+    /// Background of the play button while paused
+    pub paused: Color,
+    /// How far hovering shades a background; positive lightens, negative darkens
+    pub hover_shade: f32,
+    /// How far pressing shades a background
+    pub pressed_shade: f32,
+}
+
+/// Colours of the visualization view
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VisualizerPalette {
+    /// Background while playing
+    pub playing: Color,
+    /// Background while paused
+    pub paused: Color,
+    /// Status and loading text
+    pub text: Color,
+    /// Empty part of the loading bar
+    pub track: Color,
+    /// Filled part of the loading bar
+    pub fill: Color,
+}
+
+/// Text sizes, in points
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontSizes {
+    /// Screen titles
+    pub heading: u32,
+    /// Buttons and the playing song's title
+    pub title: u32,
+    /// Text fields
+    pub input: u32,
+    /// The title of a loading song
+    pub body: u32,
+    /// Load steps and the sidebar handle
+    pub small: u32,
+    /// Times and loading notices
+    pub detail: u32,
+    /// Slider captions and bookmark numbers
+    pub caption: u32,
+    /// The playback status over the visualization
+    pub status: u32,
+}
+
+/// Which of a theme's [`FontSizes`] some text uses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextSize {
+    Heading,
+    Title,
+    Input,
+    Body,
+    Small,
+    Detail,
+    Caption,
+    Status,
+}
+
+/// The colours and text sizes everything is drawn with
+///
+/// Themes are read from JSON or TOML files in [`THEMES_DIR`]; settings missing from a file are
+/// taken from the dark theme, so a theme can change just a few colours.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Name the theme is chosen by; defaults to the file name
+    #[serde(default)]
+    pub name: String,
+    /// Window background, behind the view and along the divider
+    pub background: Color,
+    /// Menu background
+    pub panel: Color,
+    /// Inset areas: text fields, progress bars and scrollbar tracks
+    pub field: Color,
+    /// Borders, progress, focus, scrollbar thumbs and slider tracks
+    pub accent: Color,
+    /// Search matches and the row chosen with the keyboard
+    pub highlight: Color,
+    pub text: Color,
+    /// Placeholders and disabled text
+    pub muted_text: Color,
+    /// Text on the exit and play buttons
+    pub contrast_text: Color,
+    /// Loop points and dragged slider handles
+    pub marker: Color,
+    /// The A–B loop on the progress bar
+    pub loop_span: Color,
+    pub button: ButtonColors,
+    pub visualizer: VisualizerPalette,
+    pub font_sizes: FontSizes,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    /// Returns the default theme: light text on dark grey and slate
+    pub fn dark() -> Self {
+        Theme {
+            name: "Dark".to_string(),
+            background: Color::rgb(0.0, 0.0, 0.0),
+            panel: Color::rgb(0.1, 0.1, 0.1),
+            field: Color::rgb(0.0, 0.0, 0.0),
+            accent: Color::rgb(0.8, 0.8, 1.0),
+            highlight: Color::rgb(1.0, 0.8, 0.3),
+            text: Color::rgb(1.0, 1.0, 1.0),
+            muted_text: Color::rgb(0.5, 0.5, 0.5),
+            contrast_text: Color::rgb(0.0, 0.0, 0.0),
+            marker: Color::rgb(0.0, 1.0, 0.0),
+            loop_span: Color::rgb(0.0, 0.0, 1.0),
+            button: ButtonColors {
+                normal: Color::rgb(0.3, 0.3, 0.5),
+                subdued: Color::rgb(0.1, 0.1, 0.1),
+                disabled: Color::rgb(0.1, 0.1, 0.1),
+                exit: Color::rgb(0.0, 0.0, 1.0),
+                playing: Color::rgb(0.0, 1.0, 0.0),
+                paused: Color::rgb(1.0, 0.0, 0.0),
+                hover_shade: 0.15,
+                pressed_shade: -0.3,
+            },
+            visualizer: VisualizerPalette {
+                playing: Color::rgba(0.0, 0.5, 0.0, 0.8),
+                paused: Color::rgba(0.5, 0.0, 0.0, 0.8),
+                text: Color::rgb(1.0, 1.0, 1.0),
+                track: Color::rgba(0.0, 0.0, 0.0, 0.5),
+                fill: Color::rgb(1.0, 1.0, 1.0),
+            },
+            font_sizes: FontSizes::default(),
+        }
+    }
+
+    /// Returns a theme with dark text on light greys
+    pub fn light() -> Self {
+        Theme {
+            name: "Light".to_string(),
+            background: Color::rgb(0.85, 0.85, 0.88),
+            panel: Color::rgb(0.95, 0.95, 0.96),
+            field: Color::rgb(1.0, 1.0, 1.0),
+            accent: Color::rgb(0.23, 0.37, 0.8),
+            highlight: Color::rgb(0.8, 0.45, 0.0),
+            text: Color::rgb(0.1, 0.1, 0.1),
+            muted_text: Color::rgb(0.5, 0.5, 0.5),
+            contrast_text: Color::rgb(1.0, 1.0, 1.0),
+            marker: Color::rgb(0.1, 0.6, 0.2),
+            loop_span: Color::rgb(0.62, 0.7, 1.0),
+            button: ButtonColors {
+                normal: Color::rgb(0.77, 0.8, 0.88),
+                subdued: Color::rgb(0.9, 0.9, 0.92),
+                disabled: Color::rgb(0.88, 0.88, 0.88),
+                exit: Color::rgb(0.23, 0.37, 0.8),
+                playing: Color::rgb(0.18, 0.6, 0.27),
+                paused: Color::rgb(0.78, 0.2, 0.2),
+                hover_shade: -0.08,
+                pressed_shade: -0.2,
+            },
+            visualizer: VisualizerPalette {
+                playing: Color::rgba(0.55, 0.85, 0.6, 0.9),
+                paused: Color::rgba(0.9, 0.6, 0.6, 0.9),
+                text: Color::rgb(0.1, 0.1, 0.1),
+                track: Color::rgba(1.0, 1.0, 1.0, 0.5),
+                fill: Color::rgb(0.1, 0.1, 0.1),
+            },
+            font_sizes: FontSizes::default(),
+        }
+    }
+
+    /// Returns a theme of pure colours on black with larger text, for low vision
+    pub fn high_contrast() -> Self {
+        Theme {
+            name: "High Contrast".to_string(),
+            background: Color::rgb(0.0, 0.0, 0.0),
+            panel: Color::rgb(0.0, 0.0, 0.0),
+            field: Color::rgb(0.0, 0.0, 0.0),
+            accent: Color::rgb(1.0, 1.0, 0.0),
+            highlight: Color::rgb(0.0, 1.0, 1.0),
+            text: Color::rgb(1.0, 1.0, 1.0),
+            muted_text: Color::rgb(0.75, 0.75, 0.75),
+            contrast_text: Color::rgb(0.0, 0.0, 0.0),
+            marker: Color::rgb(0.0, 1.0, 0.0),
+            loop_span: Color::rgb(0.0, 0.3, 1.0),
+            button: ButtonColors {
+                normal: Color::rgb(0.15, 0.15, 0.15),
+                subdued: Color::rgb(0.0, 0.0, 0.0),
+                disabled: Color::rgb(0.0, 0.0, 0.0),
+                exit: Color::rgb(1.0, 1.0, 0.0),
+                playing: Color::rgb(0.0, 1.0, 0.0),
+                paused: Color::rgb(1.0, 0.0, 0.0),
+                hover_shade: 0.3,
+                pressed_shade: 0.5,
+            },
+            visualizer: VisualizerPalette {
+                playing: Color::rgb(0.0, 0.4, 0.0),
+                paused: Color::rgb(0.4, 0.0, 0.0),
+                text: Color::rgb(1.0, 1.0, 1.0),
+                track: Color::rgb(0.0, 0.0, 0.0),
+                fill: Color::rgb(1.0, 1.0, 0.0),
+            },
+            font_sizes: FontSizes {
+                heading: 28,
+                title: 22,
+                input: 20,
+                body: 18,
+                small: 16,
+                detail: 14,
+                caption: 10,
+                status: 56,
+            },
+        }
+    }
+
+    /// Returns the themes that ship with the player
+    pub fn builtin() -> Vec<Theme> {
+        vec![Theme::dark(), Theme::light(), Theme::high_contrast()]
+    }
+
+    /// Loads the built-in themes followed by the user's themes from [`THEMES_DIR`]
+    ///
+    /// A user theme replaces a built-in one with the same name. Files that cannot be read are
+    /// reported and skipped.
+    pub fn load_all() -> Vec<Theme> {
+        let mut themes = Self::builtin();
+        let Ok(entries) = fs::read_dir(THEMES_DIR) else {
+            return themes;
+        };
+        let mut paths: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+        paths.sort();
+        for path in paths {
+            let theme = match Self::load(&path) {
+                Some(Ok(theme)) => theme,
+                Some(Err(e)) => {
+                    eprintln!("⚠️ Invalid theme '{}': {}", path.display(), e);
+                    continue;
+                }
+                None => continue,
+            };
+            match themes.iter_mut().find(|t| t.name == theme.name) {
+                Some(existing) => *existing = theme,
+                None => themes.push(theme),
+            }
+        }
+        themes
+    }
+
+    /// Returns the size of some text in points
+    pub fn font_size(&self, size: TextSize) -> u32 {
+        let sizes = &self.font_sizes;
+        match size {
+            TextSize::Heading => sizes.heading,
+            TextSize::Title => sizes.title,
+            TextSize::Input => sizes.input,
+            TextSize::Body => sizes.body,
+            TextSize::Small => sizes.small,
+            TextSize::Detail => sizes.detail,
+            TextSize::Caption => sizes.caption,
+            TextSize::Status => sizes.status,
+        }
+    }
+
+    /// Reads a theme file
+    ///
+    /// # Returns
+    /// `None` if the file is neither JSON nor TOML, and the error message if it cannot be read
+    fn load(path: &Path) -> Option<Result<Theme, String>> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        if extension != "json" && extension != "toml" {
+            return None;
+        }
+        let parsed = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                if extension == "json" {
+                    serde_json::from_str::<Theme>(&contents).map_err(|e| e.to_string())
+                } else {
+                    toml::from_str::<Theme>(&contents).map_err(|e| e.to_string())
+                }
+            });
+        Some(parsed.map(|mut theme| {
+            if theme.name.is_empty() {
+                let stem = path.file_stem().and_then(|stem| stem.to_str());
+                theme.name = stem.unwrap_or_default().to_string();
+            }
+            theme
+        }))
+    }
+}
+
+impl Default for ButtonColors {
+    fn default() -> Self {
+        Theme::dark().button
+    }
+}
+
+impl Default for VisualizerPalette {
+    fn default() -> Self {
+        Theme::dark().visualizer
+    }
+}
+
+impl Default for FontSizes {
+    fn default() -> Self {
+        FontSizes {
+            heading: 24,
+            title: 20,
+            input: 18,
+            body: 16,
+            small: 14,
+            detail: 12,
+            caption: 8,
+            status: 48,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use crate::ui::button::{Button, ButtonStyle};
+use crate::ui::widget::{Event, Widget, WidgetState};
+use nannou::prelude::*;
+
+/// A button showing an on/off setting, such as `SHUFFLE: ON`
+///
+/// Clicking sends the toggle's message; the owner flips the setting and reports it back
+/// through [`Toggle::set_on`], so the toggle never disagrees with what it controls.
+pub struct Toggle<M> {
+    /// The button drawing the toggle and detecting clicks
+    button: Button<M>,
+    /// Name of the setting, shown before its state
+    name: String,
+    /// Whether the setting is on
+    on: bool,
+}
+
+impl<M> Toggle<M> {
+    /// Creates a toggle that is off
+    ///
+    /// # Arguments
+    /// * `name` - Name of the setting, shown before its state
+    /// * `message` - Sent when the toggle is clicked
+    /// * `rect` - Area of the toggle
+    pub fn new(name: &str, message: M, rect: Rect) -> Self {
+        let mut toggle = Toggle {
+            button: Button::new("", message, rect),
+            name: name.to_string(),
+            on: true,
+        };
+        toggle.set_on(false);
+        toggle
+    }
+
+    /// Shows the setting as on or off
+    pub fn set_on(&mut self, on: bool) {
+        if self.on == on {
+            return;
+        }
+        self.on = on;
+        let state = if on { "ON" } else { "OFF" };
+        self.button.set_label(&format!("{}: {}", self.name, state));
+        self.button.style = if on {
+            ButtonStyle::Normal
+        } else {
+            ButtonStyle::Subdued
+        };
+    }
+}
+
+impl<M: Clone> Widget for Toggle<M> {
+    type Message = M;
+
+    fn state(&self) -> &WidgetState {
+        self.button.state()
+    }
+
+    fn state_mut(&mut self) -> &mut WidgetState {
+        self.button.state_mut()
+    }
+
+    fn on_event(&mut self, event: Event) -> Option<M> {
+        self.button.on_event(event)
+    }
+
+    fn draw(&self, draw: &Draw) {
+        self.button.draw(draw);
+    }
+}
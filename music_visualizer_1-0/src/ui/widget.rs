@@ -0,0 +1,210 @@
+use nannou::prelude::*;
+
+/// The mouse as widgets see it during one update
+#[derive(Clone, Copy, Debug)]
+pub struct Pointer {
+    /// The current mouse position
+    pub position: Point2,
+    /// Whether a mouse button is down
+    pub is_down: bool,
+    /// Whether a mouse button was down at the previous update
+    pub was_down: bool,
+    /// Whether the button down is the secondary (right) button
+    pub is_secondary: bool,
+}
+
+impl Pointer {
+    /// Returns whether a mouse button went down since the previous update
+    pub fn just_pressed(&self) -> bool {
+        self.is_down && !self.was_down
+    }
+}
+
+/// Something that happened to a widget
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    /// The pointer moved onto the widget
+    Enter,
+    /// The pointer moved off the widget, or the widget was hidden or disabled under it
+    Leave,
+    /// The pointer is over the widget with no button down
+    Hover(Point2),
+    /// A mouse button went down over the widget
+    Press { position: Point2, secondary: bool },
+    /// The pointer moved with the button held since pressing the widget, possibly outside it
+    Drag(Point2),
+    /// The button that pressed the widget went up; `inside` is false when the press was
+    /// abandoned by releasing elsewhere or by the widget being hidden
+    Release { position: Point2, inside: bool },
+    /// The widget gained or lost keyboard focus
+    Focus(bool),
+}
+
+/// Where a widget is and how it is interacting with the pointer
+#[derive(Clone, Copy, Debug)]
+pub struct WidgetState {
+    /// Area of the widget
+    pub rect: Rect,
+    /// Whether the widget is drawn and receives events
+    pub visible: bool,
+    /// Whether the widget receives events; disabled widgets are drawn greyed out
+    pub enabled: bool,
+    /// Whether the pointer is over the widget
+    pub hovered: bool,
+    /// Whether a press that started on the widget is still held
+    pub pressed: bool,
+    /// Whether the widget has keyboard focus
+    pub focused: bool,
+}
+
+impl WidgetState {
+    /// Creates the state of a visible, enabled widget the pointer is not interacting with
+    pub fn new(rect: Rect) -> Self {
+        WidgetState {
+            rect,
+            visible: true,
+            enabled: true,
+            hovered: false,
+            pressed: false,
+            focused: false,
+        }
+    }
+
+    /// Returns whether the widget takes part in events
+    pub fn is_active(&self) -> bool {
+        self.visible && self.enabled
+    }
+}
+
+/// A retained UI element that turns pointer input into typed messages
+///
+/// Owners call [`Widget::update`] once per frame; it works out which [`Event`]s happened from
+/// the pointer and passes them to [`Widget::on_event`]. A widget that was pressed keeps receiving
+/// drags and the release even once the pointer leaves it, so sliders can be dragged past their
+/// ends.
+pub trait Widget {
+    /// What the widget reports to its owner, such as the action of a button
+    type Message;
+
+    /// Returns the widget's area and interaction state
+    fn state(&self) -> &WidgetState;
+
+    /// Returns the widget's area and interaction state for changing
+    fn state_mut(&mut self) -> &mut WidgetState;
+
+    /// Reacts to an event
+    ///
+    /// # Returns
+    /// A message for the owner if the event completed an interaction, such as a click
+    fn on_event(&mut self, event: Event) -> Option<Self::Message>;
+
+    /// Draws the widget if visible
+    ///
+    /// # Arguments
+    /// * `draw` - Nannou Draw context for rendering
+    fn draw(&self, draw: &Draw);
+
+    /// Returns whether pressing the widget gives it keyboard focus
+    fn is_focusable(&self) -> bool {
+        false
+    }
+
+    /// Moves the widget to a new area
+    fn set_rect(&mut self, rect: Rect) {
+        self.state_mut().rect = rect;
+    }
+
+    /// Shows or hides the widget
+    fn set_visible(&mut self, visible: bool) {
+        self.state_mut().visible = visible;
+    }
+
+    /// Enables or disables the widget
+    fn set_enabled(&mut self, enabled: bool) {
+        self.state_mut().enabled = enabled;
+    }
+
+    /// Returns whether the widget has keyboard focus
+    fn is_focused(&self) -> bool {
+        self.state().focused
+    }
+
+    /// Gives or takes away keyboard focus, sending [`Event::Focus`] if it changed
+    fn set_focus(&mut self, focused: bool) -> Option<Self::Message> {
+        if self.state().focused == focused {
+            return None;
+        }
+        self.state_mut().focused = focused;
+        self.on_event(Event::Focus(focused))
+    }
+
+    /// Passes the events caused by the pointer since the previous update to the widget
+    ///
+    /// A press outside the widget takes its focus away. Hiding or disabling the widget ends its
+    /// hover and abandons its press.
+    ///
+    /// # Arguments
+    /// * `pointer` - The mouse as of this update
+    ///
+    /// # Returns
+    /// The first message produced by the events, if any
+    fn update(&mut self, pointer: &Pointer) -> Option<Self::Message> {
+        let state = *self.state();
+        let position = pointer.position;
+        let inside = state.is_active() && state.rect.contains(position);
+        let mut message = None;
+
+        if state.pressed && !state.is_active() {
+            self.state_mut().pressed = false;
+            let release = Event::Release {
+                position,
+                inside: false,
+            };
+            message = message.or(self.on_event(release));
+        }
+        if inside != state.hovered {
+            self.state_mut().hovered = inside;
+            let event = if inside { Event::Enter } else { Event::Leave };
+            message = message.or(self.on_event(event));
+        }
+        if !state.is_active() {
+            return message;
+        }
+
+        if pointer.just_pressed() {
+            let focused = inside && self.is_focusable();
+            message = message.or(self.set_focus(focused));
+            if inside {
+                self.state_mut().pressed = true;
+                let press = Event::Press {
+                    position,
+                    secondary: pointer.is_secondary,
+                };
+                message = message.or(self.on_event(press));
+            }
+        } else if state.pressed && pointer.is_down {
+            message = message.or(self.on_event(Event::Drag(position)));
+        } else if state.pressed {
+            self.state_mut().pressed = false;
+            message = message.or(self.on_event(Event::Release { position, inside }));
+        } else if inside && !pointer.is_down {
+            message = message.or(self.on_event(Event::Hover(position)));
+        }
+        message
+    }
+}
+
+/// Updates several widgets with the same pointer
+///
+/// Every widget is updated, so all of their hover states stay current.
+///
+/// # Returns
+/// The first message produced, if any
+pub fn update_all<'a, M: 'a>(
+    widgets: impl IntoIterator<Item = &'a mut dyn Widget<Message = M>>,
+    pointer: &Pointer,
+) -> Option<M> {
+    widgets
+        .into_iter()
+        .fold(None, |message, widget| message.or(widget.update(pointer)))
+}
@@ -1,11 +1,60 @@
 //! Visualization module
 //!
 //! Handles the main display area that shows playback status through:
-//! - Color changes (green for playing, red for paused)
+//! - Color changes (green for playing, red for paused, in the default theme)
+//! - Colors taken from the album art's palette, when the song has artwork
+//! - The song's lyrics, scrolling along with playback and with the current line highlighted
 //! - Text status indicators
+//! - A progress bar while a song loads
+//! - A choice of visualizers, switched from the keyboard
 //! - Responsive layout based on assigned rectangle
 
+use crate::artwork::Artwork;
+use crate::lyrics::Lyrics;
+use crate::ui::theme::{self, Color, TextSize};
 use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Smallest difference in brightness between an album color and the background for the color
+/// to be used for text
+const MIN_TEXT_CONTRAST: f32 = 0.4;
+
+/// Number of lyric lines shown above and below the current one
+const VISIBLE_LYRIC_LINES: isize = 3;
+
+/// Vertical distance between lyric lines, in points
+const LYRIC_LINE_HEIGHT: f32 = 28.0;
+
+/// Fraction of the remaining distance the lyrics scroll each frame, so they glide between lines
+const LYRICS_EASING: f32 = 0.15;
+
+/// The ways the view can show playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Visualizer {
+    /// Coloured background with the playback status written over it
+    Status,
+    /// Only the coloured background
+    Backdrop,
+}
+
+impl Visualizer {
+    /// Returns the visualizer that follows this one
+    pub fn next(self) -> Self {
+        match self {
+            Visualizer::Status => Visualizer::Backdrop,
+            Visualizer::Backdrop => Visualizer::Status,
+        }
+    }
+
+    /// Returns the name shown when switching to this visualizer
+    pub fn label(self) -> &'static str {
+        match self {
+            Visualizer::Status => "Status",
+            Visualizer::Backdrop => "Backdrop",
+        }
+    }
+}
 
 /// Represents the main visualization view
 ///
@@ -18,6 +67,18 @@ pub struct View {
     view_rect: Rect,
     /// Current playback state (true when audio is playing)
     is_playing: bool,
+    /// Description and progress (0.0 to 1.0) of the song being loaded, if any
+    loading: Option<(String, f32)>,
+    /// How playback is shown
+    visualizer: Visualizer,
+    /// Cover art of the playing song, whose palette colors the view
+    artwork: Option<Arc<Artwork>>,
+    /// Lyrics of the playing song
+    lyrics: Option<Arc<Lyrics>>,
+    /// Index of the lyric line being sung, if the lyrics are synced and it has started
+    current_line: Option<usize>,
+    /// Lyric line (with fractions while scrolling) shown in the middle of the lyrics
+    lyrics_scroll: f32,
 }
 
 impl View {
@@ -31,38 +92,147 @@ impl View {
         View {
             view_rect,
             is_playing: false,
+            loading: None,
+            visualizer: Visualizer::Status,
+            artwork: None,
+            lyrics: None,
+            current_line: None,
+            lyrics_scroll: 0.0,
         }
     }
 
-    /// Updates the playback state
+    /// Moves the view to a new area, for example after the window was resized
+    ///
+    /// # Arguments
+    /// * `view_rect` - The new bounding rectangle for the view area
+    pub fn set_rect(&mut self, view_rect: Rect) {
+        self.view_rect = view_rect;
+    }
+
+    /// Updates the playback and loading state
     ///
     /// # Arguments
     /// * `is_playing` - New playback state (true for playing, false for paused)
+    /// * `loading` - Description and progress of the song being loaded, if any
+    /// * `artwork` - Cover art of the playing song, if it has any
+    /// * `lyrics` - Lyrics of the playing song with the playback position and song length in
+    ///   seconds, if it has lyrics
     ///
-    /// This affects both the background color and status text
-    pub fn update(&mut self, is_playing: bool) {
+    /// This affects the background color, status text, lyrics and loading bar
+    pub fn update(
+        &mut self,
+        is_playing: bool,
+        loading: Option<(String, f32)>,
+        artwork: Option<Arc<Artwork>>,
+        lyrics: Option<(Arc<Lyrics>, f64, f64)>,
+    ) {
         self.is_playing = is_playing;
+        self.loading = loading;
+        self.artwork = artwork;
+
+        let Some((lyrics, position, length)) = lyrics else {
+            self.lyrics = None;
+            return;
+        };
+        // Synced lyrics follow the line being sung; plain lyrics scroll evenly through the song.
+        self.current_line = lyrics.line_at(position);
+        let target = if lyrics.synced {
+            self.current_line.map_or(-1.0, |line| line as f32)
+        } else if length > 0.0 {
+            (position / length).clamp(0.0, 1.0) as f32 * (lyrics.lines.len() - 1) as f32
+        } else {
+            0.0
+        };
+        // Jump straight to a new song's lyrics, then glide from line to line.
+        let is_new = !self
+            .lyrics
+            .as_ref()
+            .is_some_and(|shown| Arc::ptr_eq(shown, &lyrics));
+        self.lyrics_scroll = if is_new {
+            target
+        } else {
+            self.lyrics_scroll + (target - self.lyrics_scroll) * LYRICS_EASING
+        };
+        self.lyrics = Some(lyrics);
+    }
+
+    /// Returns the background, text and loading bar colors
+    ///
+    /// With album art, the background is the art's most common color (darkened while paused)
+    /// and the text is whichever palette color stands out most against it. Otherwise the
+    /// theme's visualizer palette is used.
+    fn colors(&self) -> (Color, Color, Color) {
+        let theme = theme::current();
+        let palette = &theme.visualizer;
+        let album = self
+            .artwork
+            .as_ref()
+            .map(|artwork| &artwork.palette)
+            .filter(|colors| !colors.is_empty());
+        let Some(colors) = album else {
+            let background = if self.is_playing {
+                palette.playing
+            } else {
+                palette.paused
+            };
+            return (background, palette.text, palette.fill);
+        };
+
+        let background = if self.is_playing {
+            colors[0].with_alpha(palette.playing.0.alpha)
+        } else {
+            colors[0].shade(-0.5).with_alpha(palette.paused.0.alpha)
+        };
+        let contrast = |color: &Color| (color.luminance() - background.luminance()).abs();
+        let text = colors
+            .iter()
+            .copied()
+            .max_by(|a, b| contrast(a).total_cmp(&contrast(b)))
+            .filter(|color| contrast(color) >= MIN_TEXT_CONTRAST)
+            .unwrap_or(if background.luminance() > 0.5 {
+                Color::rgb(0.0, 0.0, 0.0)
+            } else {
+                Color::rgb(1.0, 1.0, 1.0)
+            });
+        (background, text, text)
+    }
+
+    /// Returns how playback is shown
+    pub fn visualizer(&self) -> Visualizer {
+        self.visualizer
+    }
+
+    /// Chooses how playback is shown
+    pub fn set_visualizer(&mut self, visualizer: Visualizer) {
+        self.visualizer = visualizer;
+    }
+
+    /// Switches to the next visualizer
+    pub fn next_visualizer(&mut self) {
+        self.visualizer = self.visualizer.next();
+        println!("🎨 Visualizer: {}", self.visualizer.label());
     }
 
     /// Renders the visualization
     ///
     /// Draws:
-    /// - Background rectangle with state-appropriate color
-    ///   - Green with 80% opacity when playing
+    /// - Background rectangle with state-appropriate color from the theme's visualizer palette
+    ///   - Green with 80% opacity when playing, in the default theme
     ///   - Red with 80% opacity when paused
-    /// - Centered status text
+    /// - Centered status text (with the status visualizer)
     ///   - "PLAYING" when active
     ///   - "PAUSED" when inactive
+    /// - The lyrics in the lower part of the view, fading out away from the current line
+    /// - A loading bar below the status while a song loads
     ///
     /// # Arguments
     /// * `draw` - Nannou Draw context for rendering
     pub fn draw(&self, draw: &Draw) {
-        // Set color based on playback state
-        let bg_color = if self.is_playing {
-            rgba(0.0, 0.5, 0.0, 0.8) // Green when playing
-        } else {
-            rgba(0.5, 0.0, 0.0, 0.8) // Red when paused
-        };
+        let theme = theme::current();
+        let palette = &theme.visualizer;
+
+        // Set color based on playback state and album art
+        let (bg_color, text_color, fill_color) = self.colors();
 
         // Draw background
         draw.rect()
@@ -71,11 +241,66 @@ impl View {
             .color(bg_color);
 
         // Add status text overlay
-        let status_text = if self.is_playing { "PLAYING" } else { "PAUSED" };
+        if self.visualizer == Visualizer::Status {
+            let status_text = if self.is_playing { "PLAYING" } else { "PAUSED" };
 
-        draw.text(status_text)
-            .xy(self.view_rect.xy())
-            .color(WHITE)
-            .font_size(48);
+            draw.text(status_text)
+                .xy(self.view_rect.xy())
+                .color(text_color)
+                .font_size(theme.font_size(TextSize::Status));
+        }
+
+        self.draw_lyrics(draw, text_color);
+
+        if let Some((description, fraction)) = &self.loading {
+            let y = self.view_rect.y() - 60.0;
+            let width = self.view_rect.w() * 0.5;
+            draw.text(description)
+                .x_y(self.view_rect.x(), y + 20.0)
+                .w(width)
+                .color(text_color)
+                .font_size(theme.font_size(TextSize::Body));
+            draw.rect()
+                .x_y(self.view_rect.x(), y)
+                .w_h(width, 8.0)
+                .color(palette.track);
+            draw.rect()
+                .x_y(self.view_rect.x() - width / 2.0 + width * fraction / 2.0, y)
+                .w_h(width * fraction, 8.0)
+                .color(fill_color);
+        }
+    }
+
+    /// Draws the lyric lines around the scroll position, highlighting the line being sung
+    ///
+    /// # Arguments
+    /// * `draw` - Nannou Draw context for rendering
+    /// * `text_color` - Color of the other lines, which fade out with distance
+    fn draw_lyrics(&self, draw: &Draw, text_color: Color) {
+        let Some(lyrics) = &self.lyrics else {
+            return;
+        };
+        let theme = theme::current();
+        let center = self.view_rect.bottom() + self.view_rect.h() * 0.25;
+        let first = (self.lyrics_scroll.floor() as isize - VISIBLE_LYRIC_LINES).max(0) as usize;
+        let last = (self.lyrics_scroll.ceil() as isize + VISIBLE_LYRIC_LINES + 1).max(0) as usize;
+
+        for (index, line) in lyrics.lines.iter().enumerate().take(last).skip(first) {
+            let distance = index as f32 - self.lyrics_scroll;
+            let fade = 1.0 - distance.abs() / (VISIBLE_LYRIC_LINES + 1) as f32;
+            if fade <= 0.0 {
+                continue;
+            }
+            let (color, size) = if self.current_line == Some(index) {
+                (theme.highlight, TextSize::Title)
+            } else {
+                (text_color.with_alpha(fade), TextSize::Body)
+            };
+            draw.text(&line.text)
+                .x_y(self.view_rect.x(), center - distance * LYRIC_LINE_HEIGHT)
+                .w(self.view_rect.w() * 0.9)
+                .color(color)
+                .font_size(theme.font_size(size));
+        }
     }
 }
@@ -0,0 +1,466 @@
+//! Library watcher module
+//!
+//! Watches the music library folder (with inotify on Linux) and reports audio files that were
+//! added, removed, renamed or modified, so the library can be updated without a rescan.
+//! [`LibraryWatcher::update_library`] applies the changes to the library and the store.
+//!
+//! Copying a file in produces a burst of events while it is written. Changes are therefore held
+//! back until a file has been quiet for [`SETTLE_TIME`], and only then reported, once.
+
+use crate::cache::AudioCache;
+use crate::library::{Library, Track, is_audio_file};
+use crate::store::LibraryStore;
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+/// How long a file must go without events before its change is reported.
+const SETTLE_TIME: Duration = Duration::from_millis(750);
+
+/// A change to an audio file in the library folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LibraryChange {
+    /// The file was created, modified or moved into the folder.
+    Changed(PathBuf),
+    /// The file was deleted or moved out of the folder.
+    Removed(PathBuf),
+    /// The file was renamed within the folder.
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+/// A change waiting for its file to settle.
+struct PendingChange {
+    change: LibraryChange,
+    last_event: Instant,
+}
+
+/// Changes not yet reported, by the path of the file as it is now.
+#[derive(Default)]
+struct PendingChanges(HashMap<PathBuf, PendingChange>);
+
+/// Watches a folder for changes to audio files.
+pub struct LibraryWatcher {
+    /// Kept alive for as long as events should arrive.
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    pending: PendingChanges,
+}
+
+impl LibraryWatcher {
+    /// Starts watching a folder. Subfolders are not watched, as the library does not scan them.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir_path` - The folder to watch.
+    ///
+    /// # Returns
+    ///
+    /// The watcher, or `None` (after reporting why) if the folder cannot be watched.
+    pub fn start(dir_path: &str) -> Option<Self> {
+        let start = || {
+            // Events name files by the path that was watched; the library uses canonical paths.
+            let dir = fs::canonicalize(dir_path).map_err(notify::Error::io)?;
+            let (sender, events) = mpsc::channel();
+            let mut watcher = notify::recommended_watcher(sender)?;
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            Ok::<_, notify::Error>(LibraryWatcher {
+                _watcher: watcher,
+                events,
+                pending: PendingChanges::default(),
+            })
+        };
+        match start() {
+            Ok(watcher) => {
+                println!("👀 Watching '{}' for changes.", dir_path);
+                Some(watcher)
+            }
+            Err(e) => {
+                eprintln!(
+                    "⚠️ Could not watch '{}': {}. Press BACK to rescan the library.",
+                    dir_path, e
+                );
+                None
+            }
+        }
+    }
+
+    /// Collects the events received since the last call.
+    ///
+    /// # Returns
+    ///
+    /// The changes to files that have since settled, at most one per file.
+    pub fn poll(&mut self) -> Vec<LibraryChange> {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Ok(event) => self.pending.record(event, Instant::now()),
+                Err(e) => eprintln!("⚠️ Library watcher error: {}", e),
+            }
+        }
+        self.pending.take_settled(Instant::now())
+    }
+
+    /// Applies the changes that settled since the last call to the library and the store.
+    ///
+    /// Prepared audio of changed files is dropped from the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `library` - The library to update.
+    /// * `store` - The store to keep in step with the library.
+    /// * `cache` - The audio cache.
+    ///
+    /// # Returns
+    ///
+    /// Whether anything changed.
+    pub fn update_library(
+        &mut self,
+        library: &mut Library,
+        store: &mut LibraryStore,
+        cache: &Mutex<AudioCache>,
+    ) -> bool {
+        let changes = self.poll();
+        let changed = !changes.is_empty();
+        for change in changes {
+            match change {
+                LibraryChange::Changed(path) => {
+                    cache.lock().unwrap().invalidate(&path);
+                    update_track(library, store, &path, None);
+                }
+                LibraryChange::Removed(path) => {
+                    cache.lock().unwrap().invalidate(&path);
+                    if let Some(track) = library.remove(&path) {
+                        println!("➖ Removed '{}' from the library.", track.title);
+                    }
+                }
+                LibraryChange::Renamed { from, to } => {
+                    cache.lock().unwrap().invalidate(&from);
+                    let renamed = library.remove(&from).is_some();
+                    update_track(library, store, &to, renamed.then_some(from.as_path()));
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl PendingChanges {
+    /// Notes the change an event stands for, replacing any pending change to the same file.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event.
+    /// * `now` - When the event arrived.
+    fn record(&mut self, event: Event, now: Instant) {
+        let paths = &event.paths;
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+                let (from, to) = (&paths[0], &paths[1]);
+                // The rename was also reported as `from` leaving and `to` arriving.
+                match (is_audio_file(from), is_audio_file(to)) {
+                    (true, true) => {
+                        self.0.remove(from);
+                        self.note(
+                            to,
+                            LibraryChange::Renamed {
+                                from: from.clone(),
+                                to: to.clone(),
+                            },
+                            now,
+                        );
+                    }
+                    // A file written under a temporary name and renamed once complete.
+                    (false, true) => self.note(to, LibraryChange::Changed(to.clone()), now),
+                    _ => {}
+                }
+            }
+            EventKind::Create(_)
+            | EventKind::Modify(_)
+            | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                for path in paths.iter().filter(|path| is_audio_file(path)) {
+                    let change = if path.exists() {
+                        LibraryChange::Changed(path.clone())
+                    } else {
+                        LibraryChange::Removed(path.clone())
+                    };
+                    self.note(path, change, now);
+                }
+            }
+            EventKind::Remove(_) => {
+                for path in paths.iter().filter(|path| is_audio_file(path)) {
+                    self.note(path, LibraryChange::Removed(path.clone()), now);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets the pending change to a file and restarts its wait to settle.
+    ///
+    /// A rename stays a rename when the renamed file is then written to.
+    fn note(&mut self, path: &Path, change: LibraryChange, now: Instant) {
+        let change = match (self.0.remove(path), change) {
+            (
+                Some(PendingChange {
+                    change: renamed @ LibraryChange::Renamed { .. },
+                    ..
+                }),
+                LibraryChange::Changed(_),
+            ) => renamed,
+            (_, change) => change,
+        };
+        self.0.insert(
+            path.to_path_buf(),
+            PendingChange {
+                change,
+                last_event: now,
+            },
+        );
+    }
+
+    /// Removes and returns the changes to files that have had no events for [`SETTLE_TIME`].
+    fn take_settled(&mut self, now: Instant) -> Vec<LibraryChange> {
+        let settled: Vec<PathBuf> = self
+            .0
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.last_event) >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        settled
+            .into_iter()
+            .filter_map(|path| self.0.remove(&path))
+            .map(|pending| pending.change)
+            .collect()
+    }
+}
+
+/// Reads a file in the music library folder and adds or replaces its track.
+///
+/// # Arguments
+///
+/// * `library` - The library.
+/// * `store` - The store, told about the new or renamed track.
+/// * `path` - Path of the audio file.
+/// * `renamed_from` - The path of the library track the file was renamed from, if any.
+fn update_track(
+    library: &mut Library,
+    store: &mut LibraryStore,
+    path: &Path,
+    renamed_from: Option<&Path>,
+) {
+    let track = match Track::from_path(path) {
+        Ok(track) => track,
+        Err(e) => {
+            eprintln!("Skipping '{}': {}", path.display(), e);
+            return;
+        }
+    };
+    match renamed_from {
+        Some(from) => {
+            store.rename_track(from, &track);
+            println!("🔀 Renamed '{}' to '{}'.", from.display(), track.file_name);
+            library.insert(track);
+        }
+        None => {
+            store.sync_tracks(slice::from_ref(&track));
+            let title = track.title.clone();
+            if library.insert(track) {
+                println!("🔄 Updated '{}' in the library.", title);
+            } else {
+                println!("➕ Added '{}' to the library.", title);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A folder in the temporary directory that is removed when dropped.
+    ///
+    /// Writes are told apart from deletions by whether the file exists, so the files events
+    /// name as present have to be there.
+    struct TempFolder(PathBuf);
+
+    impl TempFolder {
+        fn new() -> Self {
+            static CREATED: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "music_visualizer-watcher-{}-{}",
+                process::id(),
+                CREATED.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempFolder(path)
+        }
+
+        /// Returns the path of a file in the folder, creating the file.
+        fn file(&self, name: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, b"RIFF").unwrap();
+            path
+        }
+
+        /// Returns the path of a file in the folder that does not exist.
+        fn missing(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempFolder {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn event(kind: EventKind, paths: &[&PathBuf]) -> Event {
+        paths.iter().fold(Event::new(kind), |event, path| {
+            event.add_path(path.to_path_buf())
+        })
+    }
+
+    fn rename(mode: RenameMode) -> EventKind {
+        EventKind::Modify(ModifyKind::Name(mode))
+    }
+
+    fn write() -> EventKind {
+        EventKind::Modify(ModifyKind::Data(DataChange::Content))
+    }
+
+    /// Returns the changes that have settled by `SETTLE_TIME` after `start`.
+    fn settled(pending: &mut PendingChanges, start: Instant) -> Vec<LibraryChange> {
+        pending.take_settled(start + SETTLE_TIME)
+    }
+
+    #[test]
+    fn renames_within_the_folder_are_reported_once() {
+        let folder = TempFolder::new();
+        let (from, to) = (folder.missing("old.wav"), folder.file("new.wav"));
+        let mut pending = PendingChanges::default();
+        let now = Instant::now();
+
+        pending.record(event(rename(RenameMode::From), &[&from]), now);
+        pending.record(event(rename(RenameMode::To), &[&to]), now);
+        pending.record(event(rename(RenameMode::Both), &[&from, &to]), now);
+        assert_eq!(
+            settled(&mut pending, now),
+            [LibraryChange::Renamed {
+                from: from.clone(),
+                to: to.clone()
+            }]
+        );
+    }
+
+    #[test]
+    fn moves_in_and_out_are_changes_and_removals() {
+        let folder = TempFolder::new();
+        let (gone, arrived) = (folder.missing("gone.wav"), folder.file("arrived.wav"));
+        let mut pending = PendingChanges::default();
+        let now = Instant::now();
+
+        pending.record(event(rename(RenameMode::From), &[&gone]), now);
+        assert_eq!(
+            settled(&mut pending, now),
+            [LibraryChange::Removed(gone.clone())]
+        );
+        pending.record(event(rename(RenameMode::To), &[&arrived]), now);
+        assert_eq!(
+            settled(&mut pending, now),
+            [LibraryChange::Changed(arrived.clone())]
+        );
+    }
+
+    #[test]
+    fn a_temporary_file_renamed_into_place_is_a_change() {
+        let folder = TempFolder::new();
+        let (temp, done) = (folder.missing("song.wav.part"), folder.file("song.wav"));
+        let mut pending = PendingChanges::default();
+        let now = Instant::now();
+
+        pending.record(event(EventKind::Create(CreateKind::File), &[&temp]), now);
+        pending.record(event(write(), &[&temp]), now);
+        pending.record(event(rename(RenameMode::Both), &[&temp, &done]), now);
+        assert_eq!(
+            settled(&mut pending, now),
+            [LibraryChange::Changed(done.clone())]
+        );
+
+        // Renaming an audio file to something else is not a rename within the library.
+        let mut pending = PendingChanges::default();
+        pending.record(event(rename(RenameMode::Both), &[&done, &temp]), now);
+        assert!(settled(&mut pending, now).is_empty());
+    }
+
+    #[test]
+    fn a_renamed_file_written_to_stays_renamed() {
+        let folder = TempFolder::new();
+        let (from, to) = (folder.missing("old.wav"), folder.file("new.wav"));
+        let mut pending = PendingChanges::default();
+        let now = Instant::now();
+
+        pending.record(event(rename(RenameMode::Both), &[&from, &to]), now);
+        pending.record(event(write(), &[&to]), now);
+        pending.record(
+            event(
+                EventKind::Access(AccessKind::Close(AccessMode::Write)),
+                &[&to],
+            ),
+            now,
+        );
+        assert_eq!(
+            settled(&mut pending, now),
+            [LibraryChange::Renamed {
+                from: from.clone(),
+                to: to.clone()
+            }]
+        );
+
+        // Deleting it afterwards still removes it.
+        let mut pending = PendingChanges::default();
+        pending.record(event(rename(RenameMode::Both), &[&from, &to]), now);
+        pending.record(event(EventKind::Remove(RemoveKind::File), &[&to]), now);
+        assert_eq!(settled(&mut pending, now), [LibraryChange::Removed(to)]);
+    }
+
+    #[test]
+    fn changes_wait_until_the_file_settles() {
+        let folder = TempFolder::new();
+        let song = folder.file("song.wav");
+        let mut pending = PendingChanges::default();
+        let start = Instant::now();
+
+        pending.record(event(EventKind::Create(CreateKind::File), &[&song]), start);
+        let later = start + SETTLE_TIME / 2;
+        assert!(pending.take_settled(later).is_empty());
+
+        // Another write restarts the wait.
+        pending.record(event(write(), &[&song]), later);
+        assert!(pending.take_settled(start + SETTLE_TIME).is_empty());
+        assert_eq!(
+            pending.take_settled(later + SETTLE_TIME),
+            [LibraryChange::Changed(song)]
+        );
+        assert!(pending.take_settled(later + SETTLE_TIME * 2).is_empty());
+    }
+
+    #[test]
+    fn other_files_are_ignored() {
+        let folder = TempFolder::new();
+        let cover = folder.file("cover.jpg");
+        let mut pending = PendingChanges::default();
+        let now = Instant::now();
+
+        pending.record(event(EventKind::Create(CreateKind::File), &[&cover]), now);
+        pending.record(event(write(), &[&cover]), now);
+        assert!(settled(&mut pending, now).is_empty());
+    }
+}